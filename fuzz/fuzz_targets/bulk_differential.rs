@@ -0,0 +1,123 @@
+#![no_main]
+
+//! Runs the same bulk operation through every implementation the crate has
+//! of it and checks that they agree byte for byte: stored against on-the-fly
+//! key schedules, one-shot CTR against the streaming, buffered and background
+//! keystreams cut at arbitrary points, and parallel against sequential CBC
+//! decryption. Lengths are arbitrary, so partial final blocks are covered.
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rc5_cipher::algo::{BlockCipherAlgo, KeySchedule, Rc5Algo};
+use rc5_cipher::cbc::decrypt_cbc_parallel;
+use rc5_cipher::ctr::{apply_keystream_at, KeystreamBuffer, Rc5Ctr};
+use rc5_cipher::expand_key;
+use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+use rc5_cipher::prefetch::BackgroundKeystream;
+use rc5_cipher::word::Word;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    word: u8,
+    rounds: u8,
+    key: Vec<u8>,
+    iv: [u8; 32],
+    offset: u16,
+    data: Vec<u8>,
+    cuts: Vec<u16>,
+    storage_blocks: u8,
+}
+
+fuzz_target!(|input: Input| {
+    match input.word % 5 {
+        0 => check::<u8>(&input),
+        1 => check::<u16>(&input),
+        2 => check::<u32>(&input),
+        3 => check::<u64>(&input),
+        _ => check::<u128>(&input),
+    }
+});
+
+fn check<W: Word + Send + Sync + 'static>(input: &Input) {
+    let bs = 2 * W::BYTES;
+    let rounds = usize::from(input.rounds % 33);
+    let key = &input.key[..input.key.len().min(255)];
+    let key_exp = expand_key::<W>(key, rounds);
+    let iv = &input.iv[..bs];
+    let offset = u64::from(input.offset);
+
+    // key schedules
+    let stored = Rc5Algo::<W>::with_schedule(key, rounds, KeySchedule::Stored);
+    let on_the_fly = Rc5Algo::<W>::with_schedule(key, rounds, KeySchedule::OnTheFly);
+    for block in input.data.chunks_exact(bs) {
+        let (mut a, mut b) = (block.to_vec(), block.to_vec());
+        stored.encrypt_block(&mut a);
+        on_the_fly.encrypt_block(&mut b);
+        assert_eq!(a, b);
+        stored.decrypt_block(&mut a);
+        on_the_fly.decrypt_block(&mut b);
+        assert_eq!(a, block);
+        assert_eq!(b, block);
+    }
+
+    // CTR
+    let mut expected = input.data.clone();
+    apply_keystream_at(&key_exp, iv, offset, &mut expected).unwrap();
+
+    let mut streamed = input.data.clone();
+    let mut ctr = Rc5Ctr::new(&key_exp[..], iv, 0).unwrap();
+    ctr.seek(offset);
+    for part in split(&mut streamed, &input.cuts) {
+        ctr.apply_keystream(part);
+    }
+    assert_eq!(streamed, expected);
+
+    let mut storage = vec![0u8; bs * (1 + usize::from(input.storage_blocks % 8))];
+    let mut buffered = input.data.clone();
+    let mut keystream = KeystreamBuffer::new(&key_exp, iv, offset, &mut storage).unwrap();
+    for (i, part) in split(&mut buffered, &input.cuts).into_iter().enumerate() {
+        keystream.prefill(i % 3);
+        keystream.apply(part);
+    }
+    assert_eq!(buffered, expected);
+
+    let chunk_blocks = 1 + usize::from(input.storage_blocks % 8);
+    let mut background = input.data.clone();
+    let mut keystream =
+        BackgroundKeystream::spawn(key_exp.clone(), iv, offset, chunk_blocks).unwrap();
+    for part in split(&mut background, &input.cuts) {
+        keystream.apply(part);
+    }
+    assert_eq!(background, expected);
+
+    // CBC
+    let whole = input.data.len() / bs * bs;
+    let plain = &input.data[..whole];
+    let profile = LegacyProfile {
+        rounds,
+        endian: Endian::Little,
+        key: KeyHandling::Raw,
+        padding: LegacyPadding::None,
+        chaining: Chaining::CbcFixedIv(iv.to_vec()),
+    };
+    let mut ct = profile.encrypt::<W>(key, plain).unwrap();
+    assert_eq!(profile.decrypt::<W>(key, &ct).unwrap(), plain);
+    decrypt_cbc_parallel(&key_exp, iv, &mut ct).unwrap();
+    assert_eq!(ct, plain);
+}
+
+// Cuts `buf` at the offsets in `cuts`, taken modulo the remaining length.
+fn split<'a>(mut buf: &'a mut [u8], cuts: &[u16]) -> Vec<&'a mut [u8]> {
+    let mut parts = Vec::new();
+    for &cut in cuts {
+        if buf.is_empty() {
+            break;
+        }
+        let at = usize::from(cut) % (buf.len() + 1);
+        let (head, tail) = buf.split_at_mut(at);
+        parts.push(head);
+        buf = tail;
+    }
+    parts.push(buf);
+    parts
+}
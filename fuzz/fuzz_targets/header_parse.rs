@@ -0,0 +1,43 @@
+#![no_main]
+
+//! Parses raw bytes as a header and as envelope metadata, checking that
+//! nothing panics, that `parse` reports what `parse_with_limits` does, that
+//! tighter limits never admit a header the defaults reject, and that accepted
+//! headers re-encode to the bytes they came from.
+//!
+//! `seeds/header_parse` holds valid headers of each shape to start from:
+//!
+//! ```text
+//! cargo fuzz run header_parse corpus/header_parse seeds/header_parse
+//! ```
+
+use libfuzzer_sys::fuzz_target;
+use rc5_cipher::envelope::read_metadata;
+use rc5_cipher::header::{Header, HeaderLimits};
+use rc5_cipher::Error;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = Header::parse_with_limits(data, &HeaderLimits::default());
+    assert_eq!(Header::parse(data), parsed.clone().map_err(Error::from));
+
+    let limits = HeaderLimits {
+        max_len: 512,
+        max_extensions: 8,
+    };
+    if let Ok((header, len)) = Header::parse_with_limits(data, &limits) {
+        assert!(len <= limits.max_len);
+        assert!(header.extensions.len() <= 8);
+        assert_eq!(parsed, Ok((header, len)));
+    }
+
+    if let Ok((header, len)) = parsed {
+        assert!(len <= data.len() && len <= Header::MAX_LEN);
+        if data[3] == 0 {
+            assert_eq!(header.to_compact_bytes().unwrap(), data[..len]);
+        } else {
+            assert_eq!(header.to_bytes(), data[..len]);
+        }
+    }
+
+    let _ = read_metadata(data);
+});
@@ -0,0 +1,75 @@
+#![no_main]
+
+//! Builds valid headers from structured input, mutates their encoding and
+//! checks that parsing never panics and only accepts canonical encodings.
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rc5_cipher::header::{Extension, Header, MacAlg, Mode, CRITICAL, EXT_METADATA};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    word: u8,
+    rounds: u8,
+    mode: u8,
+    mac: u8,
+    extensions: Vec<(u8, Vec<u8>)>,
+    mutations: Vec<Mutation>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Mutation {
+    Flip { pos: u16, mask: u8 },
+    Truncate(u16),
+    Append(Vec<u8>),
+}
+
+fuzz_target!(|input: Input| {
+    let mut seen = [false; 256];
+    let header = Header {
+        version: Header::VERSION,
+        word_bytes: [1, 2, 4, 8, 16][input.word as usize % 5],
+        rounds: input.rounds,
+        mode: Mode::from_u8(1 + input.mode % 4).unwrap(),
+        mac: MacAlg::from_u8(input.mac % 4).unwrap(),
+        extensions: input
+            .extensions
+            .into_iter()
+            .filter(|(kind, _)| {
+                let kind = kind & !CRITICAL;
+                // only metadata may repeat
+                let fresh = !seen[kind as usize] || kind == EXT_METADATA;
+                seen[kind as usize] = true;
+                fresh
+            })
+            .take(u8::MAX as usize)
+            .map(|(kind, mut value)| {
+                value.truncate(u8::MAX as usize);
+                Extension {
+                    kind: kind & !CRITICAL,
+                    value,
+                }
+            })
+            .collect(),
+    };
+
+    let mut bytes = header.to_bytes();
+    assert_eq!(Header::parse(&bytes), Ok((header, bytes.len())));
+
+    for mutation in input.mutations {
+        match mutation {
+            Mutation::Flip { pos, mask } => {
+                if let Some(b) = bytes.get_mut(pos as usize) {
+                    *b ^= mask;
+                }
+            }
+            Mutation::Truncate(len) => bytes.truncate(len as usize),
+            Mutation::Append(tail) => bytes.extend(tail),
+        }
+    }
+
+    if let Ok((parsed, len)) = Header::parse(&bytes) {
+        assert!(len <= bytes.len());
+        assert_eq!(parsed.to_bytes(), bytes[..len]);
+    }
+});
@@ -0,0 +1,79 @@
+#![no_main]
+
+//! Seals structured input with the chunked migrate format, mutates the result
+//! and checks that `open` never accepts modified ciphertext and that neither
+//! `open` nor `recover` panic.
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use rc5_cipher::algo::AeadAlgorithm;
+use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+use rc5_cipher::migrate::{migrate, open, recover, NoncePrefix};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    data: Vec<u8>,
+    chacha: bool,
+    key: [u8; 32],
+    prefix: [u8; 7],
+    mutations: Vec<Mutation>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Mutation {
+    Flip { pos: u32, mask: u8 },
+    Truncate(u32),
+    Append(Vec<u8>),
+}
+
+fuzz_target!(|input: Input| {
+    let profile = LegacyProfile {
+        rounds: 12,
+        endian: Endian::Little,
+        key: KeyHandling::Raw,
+        padding: LegacyPadding::Pkcs7,
+        chaining: Chaining::Ecb,
+    };
+    let algo = if input.chacha {
+        AeadAlgorithm::ChaCha20Poly1305
+    } else {
+        AeadAlgorithm::Aes256Gcm
+    };
+
+    let old = profile.encrypt::<u32>(b"fuzz", &input.data).unwrap();
+    let mut sealed = Vec::new();
+    migrate::<u32, _, _>(
+        &profile,
+        b"fuzz",
+        algo,
+        &input.key,
+        NoncePrefix::assume_unique(input.prefix),
+        &old[..],
+        &mut sealed,
+    )
+    .unwrap();
+
+    let mut mutated = sealed.clone();
+    for mutation in input.mutations {
+        match mutation {
+            Mutation::Flip { pos, mask } => {
+                if let Some(b) = mutated.get_mut(pos as usize) {
+                    *b ^= mask;
+                }
+            }
+            Mutation::Truncate(len) => mutated.truncate(len as usize),
+            Mutation::Append(tail) => mutated.extend(tail),
+        }
+    }
+
+    let mut out = Vec::new();
+    let opened = open(algo, &input.key, &mutated[..], &mut out);
+    if mutated == sealed {
+        assert_eq!(opened, Ok(input.data.len() as u64));
+        assert_eq!(out, input.data);
+    } else {
+        assert!(opened.is_err());
+    }
+
+    let _ = recover(algo, &input.key, &mutated[..], Vec::new());
+});
@@ -0,0 +1,733 @@
+//!
+//! Algorithm-agnostic cipher interfaces.
+//!
+//! Code that frames, chunks or stores ciphertext should depend on
+//! [`BlockCipherAlgo`] or [`AeadAlgo`] rather than on RC5 directly, and pick the
+//! implementation from a [`BlockAlgorithm`] or [`AeadAlgorithm`] value, e.g. read
+//! from a header or a configuration file. Moving data to another algorithm is
+//! then a matter of changing that value.
+//!
+//! [`BlockCipherAlgo`] is object safe, and [`BlockAlgorithm::instantiate`]
+//! returns a `Box<dyn BlockCipherAlgo + Send + Sync>` that plugins can keep
+//! in shared state. A [`BlockAlgorithm`] parses from the usual `RC5-w/r`
+//! notation, so the cipher can come straight from a configuration string.
+//!
+//! [`AeadAlgo::seal`] takes a [`Nonce`], which only a generator or a
+//! [`NonceCounter`] can make and which sealing consumes, so a constant or
+//! reused nonce doesn't compile by accident. Sealing under given nonce bytes
+//! is spelled [`AeadAlgo::seal_with_raw_nonce`].
+//!
+
+use std::fmt;
+use std::str::FromStr;
+
+use zeroize::Zeroizing;
+
+use crate::audit::{self, Event};
+use crate::error::Error;
+use crate::header::Header;
+use crate::params::{ParameterFloor, Rc5Params};
+use crate::rc5::{
+    decrypt_kernel, encrypt_kernel, expand_key, expand_key_into, wipe, KeyExp, MAX_KEY_BYTES,
+};
+use crate::rng::{CryptoRng, RngCore};
+use crate::word::Word;
+
+///
+/// A block cipher operating in place on byte blocks.
+///
+pub trait BlockCipherAlgo {
+    fn block_size(&self) -> usize;
+
+    /// Encrypts one block of exactly `block_size()` bytes.
+    fn encrypt_block(&self, block: &mut [u8]);
+
+    /// Decrypts one block of exactly `block_size()` bytes.
+    fn decrypt_block(&self, block: &mut [u8]);
+}
+
+///
+/// An authenticated cipher with associated data, the tag appended to the
+/// ciphertext.
+///
+pub trait AeadAlgo {
+    fn nonce_len(&self) -> usize;
+
+    fn tag_len(&self) -> usize;
+
+    /// Seals `plaintext` under `nonce`, which is used up.
+    fn seal(&self, nonce: Nonce, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        self.seal_with_raw_nonce(nonce.as_bytes(), aad, plaintext)
+    }
+
+    /// Seals `plaintext` under `nonce` bytes the caller vouches for, e.g. to
+    /// reproduce a test vector or in a format that derives its own unique
+    /// nonces. Nothing stops such a nonce from being used twice.
+    fn seal_with_raw_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error>;
+
+    fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error>;
+}
+
+///
+/// A nonce for [`AeadAlgo::seal`], drawn from a generator or taken from a
+/// [`NonceCounter`].
+///
+/// There's deliberately no `Clone`, `Copy` or conversion from bytes, and
+/// sealing consumes it, so the same nonce can't seal two messages by
+/// accident. Read [`Nonce::as_bytes`] before sealing to send it along.
+///
+/// Example:
+///
+/// ```rust
+/// use rand_core::OsRng;
+/// use rc5_cipher::algo::{AeadAlgo, Nonce};
+/// use rc5_cipher::ccm::Ccm;
+///
+/// let ccm = Ccm::<u64>::new(b"sensor key 00001", 20, 13, 8).unwrap();
+/// let nonce = Nonce::generate(&mut OsRng, ccm.nonce_len());
+/// let sent = nonce.as_bytes().to_vec();
+///
+/// let sealed = ccm.seal(nonce, b"node 4", b"temp=21.5").unwrap();
+/// assert_eq!(ccm.open(&sent, b"node 4", &sealed).unwrap(), b"temp=21.5");
+/// ```
+///
+pub struct Nonce(Vec<u8>);
+
+impl Nonce {
+    ///
+    /// Draws a fresh nonce of `len` bytes from `rng`.
+    ///
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R, len: usize) -> Self {
+        let mut nonce = vec![0u8; len];
+        rng.fill_bytes(&mut nonce);
+        Nonce(nonce)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+///
+/// Nonces made of a random prefix and a big-endian 32-bit counter, for
+/// sealing many messages under one key without a generator call each.
+///
+/// Like [`Nonce`] it isn't `Clone`: two copies would hand out the same
+/// nonces.
+///
+/// Example:
+///
+/// ```rust
+/// use rand_core::OsRng;
+/// use rc5_cipher::algo::NonceCounter;
+///
+/// let mut nonces = NonceCounter::generate(&mut OsRng, 12).unwrap();
+/// let a = nonces.next_nonce().unwrap();
+/// let b = nonces.next_nonce().unwrap();
+/// assert_eq!(a.as_bytes()[..8], b.as_bytes()[..8]);
+/// assert_eq!(a.as_bytes()[8..], [0, 0, 0, 0]);
+/// assert_eq!(b.as_bytes()[8..], [0, 0, 0, 1]);
+/// ```
+///
+pub struct NonceCounter {
+    prefix: Vec<u8>,
+    counter: Option<u32>,
+}
+
+impl NonceCounter {
+    ///
+    /// Counts nonces of `len` bytes, the first `len - 4` drawn from `rng`.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `len` is below 4.
+    ///
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R, len: usize) -> Result<Self, Error> {
+        let prefix_len = len.checked_sub(4).ok_or(Error::InvalidLength)?;
+        let mut prefix = vec![0u8; prefix_len];
+        rng.fill_bytes(&mut prefix);
+        Ok(NonceCounter {
+            prefix,
+            counter: Some(0),
+        })
+    }
+
+    ///
+    /// The next nonce. Fails with [`Error::InvalidLength`] once all 2^32 have
+    /// been handed out.
+    ///
+    pub fn next_nonce(&mut self) -> Result<Nonce, Error> {
+        let counter = self.counter.ok_or(Error::InvalidLength)?;
+        self.counter = counter.checked_add(1);
+        let mut nonce = self.prefix.clone();
+        nonce.extend_from_slice(&counter.to_be_bytes());
+        Ok(Nonce(nonce))
+    }
+}
+
+///
+/// RC5 with `W` words as a [`BlockCipherAlgo`]. Blocks are loaded as two
+/// little-endian words, as in the reference implementation.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::{BlockCipherAlgo, Rc5Algo};
+///
+/// let cipher = Rc5Algo::<u8>::new(&[0x00, 0x01, 0x02, 0x03], 12);
+/// let mut block = [0x00, 0x01];
+///
+/// cipher.encrypt_block(&mut block);
+/// assert_eq!(block, [0x21, 0x2A]);
+///
+/// cipher.decrypt_block(&mut block);
+/// assert_eq!(block, [0x00, 0x01]);
+/// ```
+///
+pub struct Rc5Algo<W: Word> {
+    schedule: Schedule<W>,
+}
+
+///
+/// Where [`Rc5Algo`] keeps its key schedule.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySchedule {
+    /// The `2 * (r + 1)` expanded words are held for the cipher's lifetime.
+    Stored,
+    /// Only the key is held and the schedule is rebuilt on the stack for
+    /// every block. RC5 mixes the whole table at once, so single `S[i]` can't
+    /// be derived on their own; this trades one key expansion per block for
+    /// the table's RAM between calls, e.g. on 8- and 16-bit MCUs.
+    ///
+    /// The stack buffer is sized for the rounds in use: RC5-8/12 takes 26
+    /// bytes, and only unusually many rounds pay for the 255-round table.
+    OnTheFly,
+}
+
+enum Schedule<W: Word> {
+    Stored(Zeroizing<KeyExp<W>>),
+    OnTheFly {
+        key: Zeroizing<Vec<u8>>,
+        rounds: usize,
+    },
+}
+
+// Most rounds supported by `KeySchedule::OnTheFly`.
+const MAX_ROUNDS: usize = 255;
+
+// Builds the schedule in a `[W; N]` on the stack, so each size gets its own
+// stack frame and common round counts don't reserve room for `MAX_ROUNDS`.
+fn with_stack_schedule<W: Word, T, const N: usize>(
+    key: &[u8],
+    rounds: usize,
+    f: impl FnOnce(&[W]) -> T,
+) -> T {
+    let mut buf = [W::ZERO; N];
+    let key_exp = &mut buf[..2 * (rounds + 1)];
+    expand_key_into(key, key_exp);
+    let out = f(key_exp);
+    wipe(key_exp);
+    out
+}
+
+impl<W: Word> Rc5Algo<W> {
+    ///
+    /// Keys the cipher with any parameters, including the toy ones of test
+    /// vectors. Application code should prefer [`Rc5Algo::checked`].
+    ///
+    pub fn new(key: &[u8], rounds: usize) -> Self {
+        Self::with_schedule(key, rounds, KeySchedule::Stored)
+    }
+
+    ///
+    /// Keys the cipher, failing with [`Error::WeakParameters`] if the rounds or
+    /// the key length are below `floor`.
+    ///
+    pub fn checked(key: &[u8], rounds: usize, floor: &ParameterFloor) -> Result<Self, Error> {
+        floor.check(&Rc5Params {
+            word_bytes: W::BYTES,
+            rounds,
+            key_bytes: key.len(),
+        })?;
+        Ok(Self::new(key, rounds))
+    }
+
+    ///
+    /// Keys the cipher with the given key schedule mode.
+    ///
+    /// [`KeySchedule::OnTheFly`] supports at most 255 rounds and keys of at
+    /// most [`MAX_KEY_BYTES`] bytes, and panics otherwise.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::algo::{BlockCipherAlgo, KeySchedule, Rc5Algo};
+    ///
+    /// let key = [0x00, 0x01, 0x02, 0x03];
+    /// let cipher = Rc5Algo::<u8>::with_schedule(&key, 12, KeySchedule::OnTheFly);
+    /// let mut block = [0x00, 0x01];
+    ///
+    /// cipher.encrypt_block(&mut block);
+    /// assert_eq!(block, [0x21, 0x2A]);
+    /// ```
+    ///
+    pub fn with_schedule(key: &[u8], rounds: usize, schedule: KeySchedule) -> Self {
+        let schedule = match schedule {
+            KeySchedule::Stored => {
+                Schedule::Stored(Zeroizing::new(KeyExp(expand_key(key, rounds))))
+            }
+            KeySchedule::OnTheFly => {
+                assert!(rounds <= MAX_ROUNDS, "too many rounds for OnTheFly");
+                assert!(key.len() <= MAX_KEY_BYTES, "key too long for OnTheFly");
+                Schedule::OnTheFly {
+                    key: Zeroizing::new(key.to_vec()),
+                    rounds,
+                }
+            }
+        };
+        let params = Rc5Params {
+            word_bytes: W::BYTES,
+            rounds,
+            key_bytes: key.len(),
+        };
+        audit::emit(Event::KeyLoaded, None, Some(params), 0);
+        Rc5Algo { schedule }
+    }
+
+    fn with_key_exp<T>(&self, f: impl FnOnce(&[W]) -> T) -> T {
+        match &self.schedule {
+            Schedule::Stored(key_exp) => f(&key_exp.0),
+            Schedule::OnTheFly { key, rounds } => match rounds {
+                0..=12 => with_stack_schedule::<W, T, 26>(key, *rounds, f),
+                13..=20 => with_stack_schedule::<W, T, 42>(key, *rounds, f),
+                21..=32 => with_stack_schedule::<W, T, 66>(key, *rounds, f),
+                _ => with_stack_schedule::<W, T, { 2 * (MAX_ROUNDS + 1) }>(key, *rounds, f),
+            },
+        }
+    }
+}
+
+impl<W: Word> BlockCipherAlgo for Rc5Algo<W> {
+    fn block_size(&self) -> usize {
+        2 * W::BYTES
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        debug_assert_eq!(block.len(), 2 * W::BYTES, "block size mismatch");
+        let pt = [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ];
+        let ct = self.with_key_exp(|key_exp| encrypt_kernel(pt, key_exp));
+        ct[0].write_le(block);
+        ct[1].write_le(&mut block[W::BYTES..]);
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        debug_assert_eq!(block.len(), 2 * W::BYTES, "block size mismatch");
+        let ct = [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ];
+        let pt = self.with_key_exp(|key_exp| decrypt_kernel(ct, key_exp));
+        pt[0].write_le(block);
+        pt[1].write_le(&mut block[W::BYTES..]);
+    }
+}
+
+///
+/// Selector for a [`BlockCipherAlgo`] implementation.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockAlgorithm {
+    Rc5 { word_bytes: usize, rounds: usize },
+}
+
+impl BlockAlgorithm {
+    ///
+    /// Reads the algorithm parameters recorded in a wire header.
+    ///
+    pub fn from_header(header: &Header) -> Self {
+        BlockAlgorithm::Rc5 {
+            word_bytes: header.word_bytes as usize,
+            rounds: header.rounds as usize,
+        }
+    }
+
+    ///
+    /// Keys the selected algorithm, rejecting parameters below the default
+    /// [`ParameterFloor`] with [`Error::WeakParameters`].
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::algo::BlockAlgorithm;
+    ///
+    /// let algo = BlockAlgorithm::Rc5 { word_bytes: 4, rounds: 12 };
+    /// let cipher = algo.instantiate(&[0u8; 16]).unwrap();
+    ///
+    /// let mut block = [0u8; 8];
+    /// cipher.encrypt_block(&mut block);
+    /// assert_eq!(block, [0x21, 0xA5, 0xDB, 0xEE, 0x15, 0x4B, 0x8F, 0x6D]);
+    /// ```
+    ///
+    pub fn instantiate(&self, key: &[u8]) -> Result<Box<dyn BlockCipherAlgo + Send + Sync>, Error> {
+        self.instantiate_with(key, &ParameterFloor::default())
+    }
+
+    ///
+    /// Keys the selected algorithm, checking the parameters against `floor`.
+    ///
+    pub fn instantiate_with(
+        &self,
+        key: &[u8],
+        floor: &ParameterFloor,
+    ) -> Result<Box<dyn BlockCipherAlgo + Send + Sync>, Error> {
+        match *self {
+            BlockAlgorithm::Rc5 { word_bytes, rounds } => {
+                floor.check(&Rc5Params {
+                    word_bytes,
+                    rounds,
+                    key_bytes: key.len(),
+                })?;
+                Ok(match word_bytes {
+                    1 => Box::new(Rc5Algo::<u8>::new(key, rounds)),
+                    2 => Box::new(Rc5Algo::<u16>::new(key, rounds)),
+                    4 => Box::new(Rc5Algo::<u32>::new(key, rounds)),
+                    8 => Box::new(Rc5Algo::<u64>::new(key, rounds)),
+                    16 => Box::new(Rc5Algo::<u128>::new(key, rounds)),
+                    _ => return Err(Error::InvalidLength),
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for BlockAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockAlgorithm::Rc5 { word_bytes, rounds } => {
+                write!(f, "RC5-{}/{}", 8 * word_bytes, rounds)
+            }
+        }
+    }
+}
+
+///
+/// Parses `RC5-w/r`, with the word size `w` in bits, in any case.
+///
+/// Fails with [`Error::UnsupportedParameters`] for anything else, including a
+/// word size other than 8, 16, 32, 64 or 128.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::BlockAlgorithm;
+///
+/// // e.g. from a configuration file
+/// let algo: BlockAlgorithm = "rc5-64/24".parse().unwrap();
+/// assert_eq!(algo, BlockAlgorithm::Rc5 { word_bytes: 8, rounds: 24 });
+///
+/// let cipher = algo.instantiate(&[0u8; 16]).unwrap();
+/// assert_eq!(cipher.block_size(), 16);
+/// ```
+///
+impl FromStr for BlockAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let params = s
+            .get(..4)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("rc5-"))
+            .map(|_| &s[4..])
+            .ok_or(Error::UnsupportedParameters)?;
+        let (bits, rounds) = params.split_once('/').ok_or(Error::UnsupportedParameters)?;
+        let parse = |n: &str| {
+            n.parse::<usize>()
+                .ok()
+                .filter(|_| n.bytes().all(|b| b.is_ascii_digit()))
+                .ok_or(Error::UnsupportedParameters)
+        };
+        let word_bytes = match parse(bits)? {
+            bits @ (8 | 16 | 32 | 64 | 128) => bits / 8,
+            _ => return Err(Error::UnsupportedParameters),
+        };
+        Ok(BlockAlgorithm::Rc5 {
+            word_bytes,
+            rounds: parse(rounds)?,
+        })
+    }
+}
+
+///
+/// Selector for an [`AeadAlgo`] implementation. Only available with the
+/// `migrate` feature.
+///
+#[cfg(feature = "migrate")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AeadAlgorithm {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+#[cfg(feature = "migrate")]
+impl AeadAlgorithm {
+    ///
+    /// Keys the selected algorithm with a 32-byte key.
+    ///
+    pub fn instantiate(&self, key: &[u8]) -> Result<Box<dyn AeadAlgo>, Error> {
+        use aes_gcm::aead::KeyInit;
+
+        if key.len() != 32 {
+            return Err(Error::InvalidLength);
+        }
+        Ok(match self {
+            AeadAlgorithm::Aes256Gcm => Box::new(aes_gcm::Aes256Gcm::new(key.into())),
+            AeadAlgorithm::ChaCha20Poly1305 => {
+                Box::new(chacha20poly1305::ChaCha20Poly1305::new(key.into()))
+            }
+        })
+    }
+}
+
+#[cfg(feature = "migrate")]
+macro_rules! impl_aead_algo {
+    ($t:ty) => {
+        impl AeadAlgo for $t {
+            fn nonce_len(&self) -> usize {
+                12
+            }
+
+            fn tag_len(&self) -> usize {
+                16
+            }
+
+            fn seal_with_raw_nonce(
+                &self,
+                nonce: &[u8],
+                aad: &[u8],
+                plaintext: &[u8],
+            ) -> Result<Vec<u8>, Error> {
+                use aes_gcm::aead::{Aead, Payload};
+
+                if nonce.len() != 12 {
+                    return Err(Error::InvalidLength);
+                }
+                let payload = Payload {
+                    msg: plaintext,
+                    aad,
+                };
+                self.encrypt(nonce.into(), payload)
+                    .map_err(|_| Error::InvalidLength)
+            }
+
+            fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+                use aes_gcm::aead::{Aead, Payload};
+
+                if nonce.len() != 12 {
+                    return Err(Error::InvalidLength);
+                }
+                let payload = Payload {
+                    msg: ciphertext,
+                    aad,
+                };
+                self.decrypt(nonce.into(), payload)
+                    .map_err(|_| Error::AuthenticationFailed)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "migrate")]
+impl_aead_algo!(aes_gcm::Aes256Gcm);
+#[cfg(feature = "migrate")]
+impl_aead_algo!(chacha20poly1305::ChaCha20Poly1305);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{MacAlg, Mode};
+    use crate::rng::CounterRng;
+
+    #[test]
+    fn rc5_algo_matches_block_api() {
+        let key = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+        ];
+        let header = Header::new::<u64>(24, Mode::Ecb, MacAlg::None);
+        let cipher = BlockAlgorithm::from_header(&header)
+            .instantiate(&key)
+            .unwrap();
+        assert_eq!(cipher.block_size(), 16);
+
+        let mut block: Vec<u8> = (0..16).collect();
+        cipher.encrypt_block(&mut block);
+        let ct = [
+            u64::from_le_bytes(block[..8].try_into().unwrap()),
+            u64::from_le_bytes(block[8..].try_into().unwrap()),
+        ];
+        assert_eq!(ct, [0x02CEDB0E827267A4u64, 0xDA7871AE32EAAB35]);
+
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block, (0..16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn algorithm_from_config_string() {
+        for (text, word_bytes, rounds) in [("RC5-32/12", 4, 12), ("rc5-128/28", 16, 28)] {
+            let algo: BlockAlgorithm = text.parse().unwrap();
+            assert_eq!(algo, BlockAlgorithm::Rc5 { word_bytes, rounds });
+            assert!(algo.to_string().eq_ignore_ascii_case(text));
+        }
+        for bad in [
+            "",
+            "RC5",
+            "RC5-32",
+            "RC5-24/12",
+            "RC6-32/20",
+            "RC5-32/+12",
+            "RC5-32/12/16",
+        ] {
+            assert_eq!(
+                bad.parse::<BlockAlgorithm>(),
+                Err(Error::UnsupportedParameters),
+                "{}",
+                bad
+            );
+        }
+
+        // a cipher picked at runtime, shared across threads
+        let cipher: std::sync::Arc<dyn BlockCipherAlgo + Send + Sync> = "RC5-32/16"
+            .parse::<BlockAlgorithm>()
+            .unwrap()
+            .instantiate(&[1; 16])
+            .unwrap()
+            .into();
+        let worker = std::thread::spawn({
+            let cipher = cipher.clone();
+            move || {
+                let mut block = [0u8; 8];
+                cipher.encrypt_block(&mut block);
+                block
+            }
+        });
+        let mut block = worker.join().unwrap();
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block, [0; 8]);
+    }
+
+    #[test]
+    fn on_the_fly_schedule_matches_stored() {
+        let key: Vec<u8> = (0..16).collect();
+        // Both sides of every stack buffer size.
+        for rounds in [0, 12, 13, 20, 21, 32, 33, 255] {
+            let stored = Rc5Algo::<u16>::new(&key, rounds);
+            let on_the_fly = Rc5Algo::<u16>::with_schedule(&key, rounds, KeySchedule::OnTheFly);
+
+            for i in 0..=255u8 {
+                let mut a = [i, 1, 2, i];
+                let mut b = a;
+                stored.encrypt_block(&mut a);
+                on_the_fly.encrypt_block(&mut b);
+                assert_eq!(a, b);
+                on_the_fly.decrypt_block(&mut b);
+                assert_eq!(b, [i, 1, 2, i]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "too many rounds")]
+    fn on_the_fly_rejects_huge_schedules() {
+        Rc5Algo::<u8>::with_schedule(&[0; 4], 256, KeySchedule::OnTheFly);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "block size mismatch")]
+    fn debug_rejects_wrong_block_size() {
+        Rc5Algo::<u64>::new(b"key", 12).encrypt_block(&mut [0u8; 8]);
+    }
+
+    #[test]
+    fn checked_constructors_enforce_the_floor() {
+        let floor = ParameterFloor::default();
+        assert!(Rc5Algo::<u32>::checked(&[0; 16], 12, &floor).is_ok());
+        assert!(matches!(
+            Rc5Algo::<u32>::checked(&[0; 4], 12, &floor),
+            Err(Error::WeakParameters)
+        ));
+
+        let toy = BlockAlgorithm::Rc5 {
+            word_bytes: 1,
+            rounds: 12,
+        };
+        assert_eq!(
+            toy.instantiate(&[0, 1, 2, 3]).err(),
+            Some(Error::WeakParameters)
+        );
+        let cipher = toy
+            .instantiate_with(&[0, 1, 2, 3], &floor.allow_weak_parameters())
+            .unwrap();
+        let mut block = [0x00, 0x01];
+        cipher.encrypt_block(&mut block);
+        assert_eq!(block, [0x21, 0x2A]);
+    }
+
+    #[test]
+    fn unsupported_word_size() {
+        let algo = BlockAlgorithm::Rc5 {
+            word_bytes: 3,
+            rounds: 12,
+        };
+        assert!(algo.instantiate(&[0; 16]).is_err());
+    }
+
+    #[test]
+    fn nonce_counter_never_repeats() {
+        let mut nonces = NonceCounter::generate(&mut CounterRng(9), 12).unwrap();
+        assert_eq!(
+            nonces.next_nonce().unwrap().as_bytes(),
+            [9, 10, 11, 12, 13, 14, 15, 16, 0, 0, 0, 0]
+        );
+        nonces.counter = Some(u32::MAX);
+        assert_eq!(nonces.next_nonce().unwrap().as_bytes()[8..], [0xff; 4]);
+        assert!(nonces.next_nonce().is_err());
+
+        assert!(NonceCounter::generate(&mut CounterRng(0), 3).is_err());
+        assert_eq!(
+            NonceCounter::generate(&mut CounterRng(0), 4)
+                .unwrap()
+                .prefix,
+            []
+        );
+    }
+
+    #[cfg(feature = "migrate")]
+    #[test]
+    fn aead_algorithms_roundtrip() {
+        for algo in [AeadAlgorithm::Aes256Gcm, AeadAlgorithm::ChaCha20Poly1305] {
+            let aead = algo.instantiate(&[3; 32]).unwrap();
+            let nonce = Nonce::generate(&mut CounterRng(5), 12);
+            let sent = nonce.as_bytes().to_vec();
+
+            let ct = aead.seal(nonce, b"aad", b"plaintext").unwrap();
+            assert_eq!(ct.len(), 9 + aead.tag_len());
+            assert_eq!(aead.open(&sent, b"aad", &ct).unwrap(), b"plaintext");
+            assert_eq!(
+                aead.open(&sent, b"other", &ct),
+                Err(Error::AuthenticationFailed)
+            );
+            assert_eq!(
+                aead.seal_with_raw_nonce(&[0; 8], b"", b""),
+                Err(Error::InvalidLength)
+            );
+        }
+        assert!(AeadAlgorithm::Aes256Gcm.instantiate(&[0; 16]).is_err());
+    }
+}
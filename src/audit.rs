@@ -0,0 +1,225 @@
+//!
+//! Audit trail of cryptographic operations.
+//!
+//! An application installs one [`AuditHook`] with [`set_hook`]; the crate then
+//! reports key loads, rekeys and the start and end of every envelope, legacy
+//! and migration operation to it. Records carry only non-secret metadata: the
+//! operation, the RC5 parameters (including the key length), byte counts, the
+//! outcome and, for operations that find their key by ID, e.g. in a keyring,
+//! that ID.
+//!
+//! Without a hook the reporting costs one atomic load per operation.
+//!
+
+use std::sync::OnceLock;
+
+use crate::error::Error;
+use crate::params::Rc5Params;
+
+///
+/// What a [`Record`] reports.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A key schedule was expanded.
+    KeyLoaded,
+    /// A usage tracker was reset for a new key.
+    Rekeyed,
+    Started(Operation),
+    /// The operation ended, successfully or with the given error.
+    Finished(Operation, Result<(), Error>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Encrypt,
+    Decrypt,
+    Migrate,
+}
+
+///
+/// One entry of the audit trail.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub event: Event,
+    /// The key ID in hex, passed by the operation if it knows it. Records are
+    /// made on the thread doing the work, so an ID travels with the
+    /// operation, not with the thread that started it.
+    pub key_id: Option<&'a str>,
+    pub params: Option<Rc5Params>,
+    /// Plaintext bytes processed, for [`Event::Finished`].
+    pub bytes: u64,
+}
+
+///
+/// Receiver of audit records, e.g. writing them to a tamper-evident log.
+///
+/// Hooks are called synchronously on the thread doing the work and must not
+/// call back into the operation that reported.
+///
+pub trait AuditHook: Sync {
+    fn record(&self, record: &Record<'_>);
+}
+
+static HOOK: OnceLock<&'static dyn AuditHook> = OnceLock::new();
+
+///
+/// Installs the process-wide audit hook. It can only be set once; later calls
+/// return the rejected hook.
+///
+/// Example:
+///
+/// ```rust
+/// use std::sync::Mutex;
+/// use rc5_cipher::audit::{self, AuditHook, Event, Record};
+/// use rc5_cipher::envelope::encrypt_with_header;
+/// use rc5_cipher::header::Mode;
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// struct Trail(Mutex<Vec<String>>);
+///
+/// impl AuditHook for Trail {
+///     fn record(&self, record: &Record<'_>) {
+///         let line = format!("{:?} key={:?} {:?}", record.event, record.key_id, record.params);
+///         self.0.lock().unwrap().push(line);
+///     }
+/// }
+///
+/// static TRAIL: Trail = Trail(Mutex::new(Vec::new()));
+/// audit::set_hook(&TRAIL).ok().unwrap();
+///
+/// encrypt_with_header::<u32, _>(&mut rng, &[0; 16], 20, Mode::Ctr, b"hi").unwrap();
+///
+/// let trail = TRAIL.0.lock().unwrap();
+/// assert!(trail[0].starts_with("Started(Encrypt) key=None"));
+/// assert!(trail[1].starts_with("KeyLoaded"));
+/// ```
+///
+pub fn set_hook(hook: &'static dyn AuditHook) -> Result<(), &'static dyn AuditHook> {
+    HOOK.set(hook)
+}
+
+pub(crate) fn emit(event: Event, key_id: Option<&str>, params: Option<Rc5Params>, bytes: u64) {
+    let Some(hook) = HOOK.get() else { return };
+    hook.record(&Record {
+        event,
+        key_id,
+        params,
+        bytes,
+    });
+}
+
+///
+/// Reports `f` as `operation` on the key `key_id`, with the byte count taken
+/// from its result.
+///
+pub(crate) fn operation<T>(
+    operation: Operation,
+    key_id: Option<&str>,
+    params: Option<Rc5Params>,
+    bytes: impl Fn(&T) -> u64,
+    f: impl FnOnce() -> Result<T, Error>,
+) -> Result<T, Error> {
+    emit(Event::Started(operation), key_id, params, 0);
+    let result = f();
+    let (outcome, count) = match &result {
+        Ok(val) => (Ok(()), bytes(val)),
+        Err(err) => (Err(*err), 0),
+    };
+    emit(Event::Finished(operation, outcome), key_id, params, count);
+    result
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread::{self, ThreadId};
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) struct Owned {
+        pub event: Event,
+        pub key_id: Option<String>,
+        pub params: Option<Rc5Params>,
+        pub bytes: u64,
+    }
+
+    struct Collect(Mutex<Vec<(ThreadId, Owned)>>);
+
+    impl AuditHook for Collect {
+        fn record(&self, record: &Record<'_>) {
+            let owned = Owned {
+                event: record.event,
+                key_id: record.key_id.map(str::to_owned),
+                params: record.params,
+                bytes: record.bytes,
+            };
+            self.0.lock().unwrap().push((thread::current().id(), owned));
+        }
+    }
+
+    static COLLECT: Collect = Collect(Mutex::new(Vec::new()));
+
+    ///
+    /// Runs `f` and returns the records it produced. Other tests run
+    /// concurrently, each on its own thread, so records are told apart by the
+    /// thread that made them.
+    ///
+    pub(crate) fn capture(f: impl FnOnce()) -> Vec<Owned> {
+        let _ = set_hook(&COLLECT);
+        let start = COLLECT.0.lock().unwrap().len();
+        f();
+        let this = thread::current().id();
+        COLLECT.0.lock().unwrap()[start..]
+            .iter()
+            .filter(|(thread, _)| *thread == this)
+            .map(|(_, record)| record.clone())
+            .collect()
+    }
+
+    #[test]
+    fn operation_reports_start_and_outcome() {
+        let params = Some(Rc5Params {
+            word_bytes: 4,
+            rounds: 12,
+            key_bytes: 16,
+        });
+        let records = capture(|| {
+            operation(Operation::Encrypt, None, params, |n: &u64| *n, || Ok(42)).unwrap();
+            let _ = operation(
+                Operation::Decrypt,
+                None,
+                params,
+                |n: &u64| *n,
+                || Err(Error::InvalidPadding),
+            );
+        });
+
+        let events: Vec<_> = records.iter().map(|r| (r.event, r.bytes)).collect();
+        assert_eq!(
+            events,
+            [
+                (Event::Started(Operation::Encrypt), 0),
+                (Event::Finished(Operation::Encrypt, Ok(())), 42),
+                (Event::Started(Operation::Decrypt), 0),
+                (
+                    Event::Finished(Operation::Decrypt, Err(Error::InvalidPadding)),
+                    0
+                ),
+            ]
+        );
+        assert!(records.iter().all(|r| r.params == params));
+    }
+
+    #[test]
+    fn key_id_belongs_to_the_operation() {
+        let records = capture(|| {
+            operation(Operation::Encrypt, Some("k1"), None, |_| 0, || Ok(())).unwrap();
+            emit(Event::Rekeyed, None, None, 0);
+        });
+        let ids: Vec<_> = records.iter().map(|r| r.key_id.as_deref()).collect();
+        assert_eq!(ids, [Some("k1"), Some("k1"), None]);
+    }
+}
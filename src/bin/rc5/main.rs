@@ -0,0 +1,453 @@
+//!
+//! Command line front end. Only built with the `cli` feature.
+//!
+//! ```text
+//! rc5 inspect FILE          print the header of FILE, no key needed
+//! rc5 encrypt [--compat openssl] (--passphrase | --pass PASS) [IN [OUT]]
+//! rc5 decrypt [--compat openssl] (--passphrase | --pass PASS) [IN [OUT]]
+//! rc5 verify --aead (aes-256-gcm | chacha20-poly1305) --key-file KEY FILE
+//! ```
+//!
+//! `IN` and `OUT` default to standard input and output. `--passphrase` reads
+//! the passphrase from the terminal without echo, which keeps it out of the
+//! shell history and the process list.
+//!
+//! By default the key is derived from the passphrase with Argon2id, with the
+//! parameters stored in the header. With `--compat openssl` the data is read
+//! and written in the format of `openssl enc -rc5-cbc -pass pass:PASS`.
+//!
+//! `verify` authenticates every segment of a stream written by
+//! [`rc5_cipher::migrate`] and reports the damaged ones, discarding the
+//! plaintext. `KEY` holds the 32-byte key, raw or as hex or base64 text.
+//!
+
+mod native;
+mod openssl;
+
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::process::ExitCode;
+
+use rc5_cipher::algo::AeadAlgorithm;
+use rc5_cipher::encoding::hex_encode;
+use rc5_cipher::header::{Header, CRITICAL, EXT_ARGON2ID, EXT_KDF_SALT};
+use rc5_cipher::key::{KeyFormat, Rc5Key};
+use rc5_cipher::migrate::recover;
+use rc5_cipher::passphrase::header_params;
+use rc5_cipher::Error;
+use zeroize::Zeroizing;
+
+const USAGE: &str = "\
+usage: rc5 inspect FILE
+       rc5 encrypt [--compat openssl] (--passphrase | --pass PASS) [IN [OUT]]
+       rc5 decrypt [--compat openssl] (--passphrase | --pass PASS) [IN [OUT]]
+       rc5 verify --aead (aes-256-gcm | chacha20-poly1305) --key-file KEY FILE";
+
+///
+/// Parsed command line.
+///
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Inspect(String),
+    Encrypt(Options),
+    Decrypt(Options),
+    Verify {
+        aead: AeadAlgorithm,
+        key_file: String,
+        path: String,
+    },
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Options {
+    compat: Option<String>,
+    pass: Option<String>,
+    prompt: bool,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(command) = parse_args(&args) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let result = match command {
+        Command::Inspect(path) => inspect(&path),
+        Command::Encrypt(opts) => transform(&opts, true),
+        Command::Decrypt(opts) => transform(&opts, false),
+        Command::Verify {
+            aead,
+            key_file,
+            path,
+        } => match verify(aead, &key_file, &path) {
+            Ok(true) => Ok(()),
+            Ok(false) => return ExitCode::FAILURE,
+            Err(err) => Err(err),
+        },
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("rc5: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Option<Command> {
+    let (name, rest) = args.split_first()?;
+    if name == "inspect" {
+        return match rest {
+            [path] => Some(Command::Inspect(path.clone())),
+            _ => None,
+        };
+    }
+    if name == "verify" {
+        return parse_verify(rest);
+    }
+
+    let mut opts = Options::default();
+    let mut positional = Vec::new();
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--compat" => opts.compat = Some(rest.next()?.clone()),
+            "--pass" => opts.pass = Some(rest.next()?.clone()),
+            "--passphrase" => opts.prompt = true,
+            flag if flag.starts_with("--") => return None,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    if positional.len() > 2 || opts.pass.is_some() == opts.prompt {
+        return None;
+    }
+    let mut positional = positional.into_iter();
+    opts.input = positional.next();
+    opts.output = positional.next();
+
+    match name.as_str() {
+        "encrypt" => Some(Command::Encrypt(opts)),
+        "decrypt" => Some(Command::Decrypt(opts)),
+        _ => None,
+    }
+}
+
+fn parse_verify(args: &[String]) -> Option<Command> {
+    let mut aead = None;
+    let mut key_file = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--aead" => {
+                aead = Some(match args.next()?.as_str() {
+                    "aes-256-gcm" => AeadAlgorithm::Aes256Gcm,
+                    "chacha20-poly1305" => AeadAlgorithm::ChaCha20Poly1305,
+                    _ => return None,
+                })
+            }
+            "--key-file" => key_file = Some(args.next()?.clone()),
+            flag if flag.starts_with("--") => return None,
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return None,
+        }
+    }
+    Some(Command::Verify {
+        aead: aead?,
+        key_file: key_file?,
+        path: path?,
+    })
+}
+
+fn transform(opts: &Options, encrypt: bool) -> Result<(), Error> {
+    let openssl = match opts.compat.as_deref() {
+        None => false,
+        Some("openssl") => true,
+        Some(_) => return Err(Error::UnsupportedParameters),
+    };
+    let pass = match &opts.pass {
+        Some(pass) => Zeroizing::new(pass.clone()),
+        None => read_passphrase(encrypt)?,
+    };
+
+    let data = match opts.input.as_deref() {
+        Some(path) => std::fs::read(path)?,
+        None => {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data)?;
+            data
+        }
+    };
+    let out = match (openssl, encrypt) {
+        (true, true) => openssl::encrypt(pass.as_bytes(), &data)?,
+        (true, false) => openssl::decrypt(pass.as_bytes(), &data)?,
+        (false, true) => native::encrypt(pass.as_bytes(), &data)?,
+        (false, false) => native::decrypt(pass.as_bytes(), &data)?,
+    };
+    match opts.output.as_deref() {
+        Some(path) => std::fs::write(path, out)?,
+        None => std::io::stdout().write_all(&out)?,
+    }
+    Ok(())
+}
+
+///
+/// Prompts for a passphrase on the terminal, twice when encrypting.
+///
+fn read_passphrase(confirm: bool) -> Result<Zeroizing<String>, Error> {
+    let pass = Zeroizing::new(rpassword::prompt_password("Passphrase: ")?);
+    if confirm {
+        let again = Zeroizing::new(rpassword::prompt_password("Confirm passphrase: ")?);
+        if *again != *pass {
+            eprintln!("rc5: passphrases don't match");
+            std::process::exit(1);
+        }
+    }
+    Ok(pass)
+}
+
+///
+/// Prints one line per damaged segment and a summary. Returns whether every
+/// segment authenticated.
+///
+fn verify(aead: AeadAlgorithm, key_file: &str, path: &str) -> Result<bool, Error> {
+    let key = read_key(key_file)?;
+    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let recovery = recover(aead, key.as_bytes(), file, std::io::sink())?;
+
+    for range in &recovery.damaged {
+        println!("damaged: bytes {}..{}", range.start, range.end);
+    }
+    let intact = recovery.damaged.is_empty();
+    println!(
+        "{}: {} plaintext bytes, {} damaged segments",
+        if intact { "ok" } else { "FAILED" },
+        recovery.bytes,
+        recovery.damaged.len()
+    );
+    Ok(intact)
+}
+
+///
+/// Reads a 32-byte key stored raw or as hex or base64 text.
+///
+fn read_key(path: &str) -> Result<Rc5Key, Error> {
+    let key = Rc5Key::from_reader(std::fs::File::open(path)?, KeyFormat::Auto)?;
+    if key.len() != 32 {
+        return Err(Error::InvalidLength);
+    }
+    Ok(key)
+}
+
+fn inspect(path: &str) -> Result<(), Error> {
+    let bytes = std::fs::read(path)?;
+    print!("{}", describe(&bytes)?);
+    Ok(())
+}
+
+///
+/// Renders the header at the start of `bytes` one field per line.
+///
+fn describe(bytes: &[u8]) -> Result<String, Error> {
+    let (header, len) = Header::parse(bytes)?;
+    let mut out = String::new();
+    let _ = writeln!(out, "version:    {}", header.version);
+    let _ = writeln!(
+        out,
+        "parameters: RC5-{}/{}",
+        8 * header.word_bytes as usize,
+        header.rounds
+    );
+    let _ = writeln!(out, "mode:       {:?}", header.mode);
+    let _ = writeln!(out, "mac:        {:?}", header.mac);
+    if let Some(salt) = header.extension(EXT_KDF_SALT) {
+        let _ = writeln!(out, "kdf:        HKDF-SHA256, salt {}", hex_encode(salt));
+    } else if header.extension(EXT_ARGON2ID).is_some() {
+        let (params, salt) = header_params(&header)?;
+        let _ = writeln!(
+            out,
+            "kdf:        Argon2id, {} KiB, {} iterations, {} lanes, salt {}",
+            params.memory_kib,
+            params.iterations,
+            params.lanes,
+            hex_encode(salt)
+        );
+    } else {
+        let _ = writeln!(out, "kdf:        none");
+    }
+    let known = [EXT_KDF_SALT, EXT_ARGON2ID];
+    for ext in header
+        .extensions
+        .iter()
+        .filter(|ext| !known.contains(&ext.kind))
+    {
+        let critical = if ext.kind & CRITICAL != 0 {
+            ", critical"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "extension:  {:#04x} ({} bytes{})",
+            ext.kind,
+            ext.value.len(),
+            critical
+        );
+    }
+    let _ = writeln!(out, "header:     {} bytes", len);
+    let _ = writeln!(out, "payload:    {} bytes", bytes.len() - len);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc5_cipher::header::{Extension, MacAlg, Mode};
+    use rc5_cipher::passphrase::Argon2Params;
+    use rc5_cipher::rng::OsRng;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            parse_args(&args("inspect a.enc")),
+            Some(Command::Inspect("a.enc".into()))
+        );
+        assert_eq!(
+            parse_args(&args("encrypt --compat openssl --pass pw in.txt")),
+            Some(Command::Encrypt(Options {
+                compat: Some("openssl".into()),
+                pass: Some("pw".into()),
+                input: Some("in.txt".into()),
+                ..Options::default()
+            }))
+        );
+        assert_eq!(
+            parse_args(&args("decrypt a b --passphrase")),
+            Some(Command::Decrypt(Options {
+                prompt: true,
+                input: Some("a".into()),
+                output: Some("b".into()),
+                ..Options::default()
+            }))
+        );
+
+        assert_eq!(parse_args(&args("")), None);
+        assert_eq!(parse_args(&args("inspect")), None);
+        assert_eq!(parse_args(&args("encrypt --pass")), None);
+        assert_eq!(parse_args(&args("encrypt --compat openssl")), None);
+        assert_eq!(parse_args(&args("encrypt --passphrase --pass pw")), None);
+        assert_eq!(parse_args(&args("encrypt --bogus x")), None);
+        assert_eq!(parse_args(&args("encrypt a b c")), None);
+        assert_eq!(parse_args(&args("frobnicate")), None);
+
+        assert_eq!(
+            parse_args(&args(
+                "verify --aead chacha20-poly1305 --key-file k.hex x.enc"
+            )),
+            Some(Command::Verify {
+                aead: AeadAlgorithm::ChaCha20Poly1305,
+                key_file: "k.hex".into(),
+                path: "x.enc".into(),
+            })
+        );
+        assert_eq!(parse_args(&args("verify --aead des --key-file k x")), None);
+        assert_eq!(parse_args(&args("verify --key-file k x")), None);
+        assert_eq!(parse_args(&args("verify --aead aes-256-gcm x")), None);
+    }
+
+    #[test]
+    fn describes_header_fields() {
+        let mut header = Header::new::<u64>(24, Mode::Ctr, MacAlg::Cmac);
+        header.extensions.push(Extension {
+            kind: 0x10,
+            value: vec![0; 3],
+        });
+        let mut bytes = header.to_bytes();
+        bytes.extend_from_slice(&[0; 40]);
+
+        assert_eq!(
+            describe(&bytes).unwrap(),
+            "version:    1\n\
+             parameters: RC5-64/24\n\
+             mode:       Ctr\n\
+             mac:        Cmac\n\
+             kdf:        none\n\
+             extension:  0x10 (3 bytes)\n\
+             header:     13 bytes\n\
+             payload:    40 bytes\n"
+        );
+        assert_eq!(describe(b"not a header"), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn describes_argon2_settings() {
+        let kdf = Argon2Params {
+            memory_kib: 32,
+            iterations: 1,
+            lanes: 1,
+        };
+        let ct = native::encrypt_with(&mut OsRng, kdf, b"pw", b"data").unwrap();
+        let text = describe(&ct).unwrap();
+        assert!(text.contains("parameters: RC5-32/20\n"));
+        assert!(text.contains("kdf:        Argon2id, 32 KiB, 1 iterations, 1 lanes, salt "));
+        assert!(text.ends_with("payload:    20 bytes\n"));
+    }
+
+    #[test]
+    fn verify_checks_every_segment() {
+        use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+        use rc5_cipher::migrate::{migrate, NoncePrefix, CHUNK_LEN, HEADER_LEN};
+
+        let profile = LegacyProfile {
+            rounds: 12,
+            endian: Endian::Little,
+            key: KeyHandling::Raw,
+            padding: LegacyPadding::Pkcs7,
+            chaining: Chaining::Ecb,
+        };
+        let old = profile
+            .encrypt::<u32>(b"old", &vec![7u8; 2 * CHUNK_LEN])
+            .unwrap();
+        let mut sealed = Vec::new();
+        let aead = AeadAlgorithm::Aes256Gcm;
+        let prefix = NoncePrefix::assume_unique([0; 7]);
+        migrate::<u32, _, _>(
+            &profile,
+            b"old",
+            aead,
+            &[3; 32],
+            prefix,
+            &old[..],
+            &mut sealed,
+        )
+        .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("rc5-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("key.hex");
+        let data_file = dir.join("data.enc");
+        let path = |p: &std::path::Path| p.to_str().unwrap().to_string();
+        std::fs::write(&key_file, format!("{}\n", hex_encode(&[3; 32]))).unwrap();
+
+        std::fs::write(&data_file, &sealed).unwrap();
+        assert_eq!(verify(aead, &path(&key_file), &path(&data_file)), Ok(true));
+
+        sealed[HEADER_LEN + 10] ^= 1;
+        std::fs::write(&data_file, &sealed).unwrap();
+        assert_eq!(verify(aead, &path(&key_file), &path(&data_file)), Ok(false));
+
+        std::fs::write(&key_file, "AwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwMDAwM=\n").unwrap();
+        assert_eq!(verify(aead, &path(&key_file), &path(&data_file)), Ok(false));
+
+        std::fs::write(&key_file, [3; 31]).unwrap();
+        assert!(verify(aead, &path(&key_file), &path(&data_file)).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
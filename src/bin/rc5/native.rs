@@ -0,0 +1,125 @@
+//!
+//! The CLI's own file format for passphrase-protected data.
+//!
+//! ```text
+//! header (RC5-32/20, CTR, CMAC, Argon2id extension) || nonce (8 bytes) || SIV(plaintext)
+//! ```
+//!
+//! The layout is that of [`encrypt_authenticated`](rc5_cipher::envelope::encrypt_authenticated):
+//! the passphrase yields an encryption and a MAC key, and the header, Argon2id
+//! costs included, is authenticated along with the data.
+//!
+
+use rc5_cipher::envelope::decrypt_authenticated;
+use rc5_cipher::header::{Header, MacAlg, Mode};
+use rc5_cipher::params::{ParameterFloor, Rc5Params, SecurityLevel};
+use rc5_cipher::passphrase::{new_passphrase_key, passphrase_key, Argon2Params};
+use rc5_cipher::rng::{CryptoRng, OsRng, RngCore};
+use rc5_cipher::siv::DeterministicAead;
+use rc5_cipher::Error;
+use zeroize::Zeroizing;
+
+pub fn encrypt(pass: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    encrypt_with(&mut OsRng, Argon2Params::default(), pass, data)
+}
+
+pub fn encrypt_with<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    kdf: Argon2Params,
+    pass: &[u8],
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let params = SecurityLevel::Standard.params();
+    let mut header = Header::new::<u32>(params.rounds as u8, Mode::Ctr, MacAlg::Cmac);
+    let keys = Zeroizing::new(new_passphrase_key(
+        rng,
+        pass,
+        kdf,
+        &mut header,
+        2 * params.key_bytes,
+    )?);
+    let (enc_key, mac_key) = keys.split_at(params.key_bytes);
+
+    let mut out = header.to_bytes();
+    let header_len = out.len();
+    let mut nonce = [0u8; 8];
+    rng.fill_bytes(&mut nonce);
+    out.extend_from_slice(&nonce);
+
+    let siv = DeterministicAead::<u32>::new(mac_key, enc_key, params.rounds)?;
+    let sealed = siv.seal(&[&out[..header_len], &nonce], data);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+pub fn decrypt(pass: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (header, _) = Header::parse(data)?;
+    if header.word_bytes != 4 || header.mode != Mode::Ctr || header.mac != MacAlg::Cmac {
+        return Err(Error::UnsupportedParameters);
+    }
+    let key_bytes = SecurityLevel::Standard.params().key_bytes;
+    // Checked before the costly key derivation, not only by the envelope.
+    ParameterFloor::default().check(&Rc5Params {
+        word_bytes: 4,
+        rounds: header.rounds as usize,
+        key_bytes,
+    })?;
+    let keys = Zeroizing::new(passphrase_key(pass, &header, 2 * key_bytes)?);
+    let (enc_key, mac_key) = keys.split_at(key_bytes);
+    decrypt_authenticated(enc_key, mac_key, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHEAP: Argon2Params = Argon2Params {
+        memory_kib: 32,
+        iterations: 1,
+        lanes: 1,
+    };
+
+    #[test]
+    fn roundtrip_with_header() {
+        let ct = encrypt_with(&mut OsRng, CHEAP, b"pw", b"backup").unwrap();
+        let (header, len) = Header::parse(&ct).unwrap();
+        assert_eq!((header.word_bytes, header.rounds), (4, 20));
+        assert_eq!((header.mode, header.mac), (Mode::Ctr, MacAlg::Cmac));
+        assert_eq!(ct.len(), len + 8 + 8 + b"backup".len());
+
+        assert_eq!(decrypt(b"pw", &ct).unwrap(), b"backup");
+        assert_eq!(decrypt(b"wrong", &ct), Err(Error::AuthenticationFailed));
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let ct = encrypt_with(&mut OsRng, CHEAP, b"pw", b"backup").unwrap();
+        let (_, len) = Header::parse(&ct).unwrap();
+
+        let mut body = ct.clone();
+        *body.last_mut().unwrap() ^= 1;
+        assert_eq!(decrypt(b"pw", &body), Err(Error::AuthenticationFailed));
+
+        // The last salt byte ends the header.
+        let mut salt = ct.clone();
+        salt[len - 1] ^= 1;
+        assert_eq!(decrypt(b"pw", &salt), Err(Error::AuthenticationFailed));
+    }
+
+    #[test]
+    fn rejects_weak_or_costly_headers() {
+        let ct = encrypt_with(&mut OsRng, CHEAP, b"pw", b"backup").unwrap();
+        let (mut header, len) = Header::parse(&ct).unwrap();
+
+        header.rounds = 4;
+        let mut weak = header.to_bytes();
+        weak.extend_from_slice(&ct[len..]);
+        assert_eq!(decrypt(b"pw", &weak), Err(Error::WeakParameters));
+
+        header.rounds = 20;
+        header.extensions[0].value[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut costly = header.to_bytes();
+        costly.extend_from_slice(&ct[len..]);
+        assert_eq!(decrypt(b"pw", &costly), Err(Error::UnsupportedParameters));
+    }
+}
@@ -0,0 +1,132 @@
+//!
+//! The `openssl enc -rc5-cbc` file format.
+//!
+//! ```text
+//! "Salted__" || salt (8 bytes) || RC5-32/12/16-CBC(PKCS#7(plaintext))
+//! ```
+//!
+//! Key and IV come from `EVP_BytesToKey` with SHA-256 and a single iteration,
+//! the defaults of OpenSSL 1.1.0 and later.
+//!
+
+use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+use rc5_cipher::rng::{OsRng, RngCore};
+use rc5_cipher::Error;
+use sha2::{Digest, Sha256};
+
+const MAGIC: &[u8; 8] = b"Salted__";
+const KEY_LEN: usize = 16;
+const IV_LEN: usize = 8;
+
+pub fn encrypt(pass: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut salt = [0u8; 8];
+    OsRng.fill_bytes(&mut salt);
+    encrypt_with_salt(pass, &salt, data)
+}
+
+pub fn encrypt_with_salt(pass: &[u8], salt: &[u8; 8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (key, iv) = bytes_to_key(pass, salt);
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(salt);
+    out.extend(profile(iv).encrypt::<u32>(&key, data)?);
+    Ok(out)
+}
+
+pub fn decrypt(pass: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 16 || &data[..8] != MAGIC {
+        return Err(Error::InvalidHeader);
+    }
+    let (key, iv) = bytes_to_key(pass, &data[8..16]);
+    profile(iv).decrypt::<u32>(&key, &data[16..])
+}
+
+fn profile(iv: Vec<u8>) -> LegacyProfile {
+    LegacyProfile {
+        rounds: 12,
+        endian: Endian::Little,
+        key: KeyHandling::Raw,
+        padding: LegacyPadding::Pkcs7,
+        chaining: Chaining::CbcFixedIv(iv),
+    }
+}
+
+// EVP_BytesToKey: D_i = H(D_{i-1} || pass || salt), key || iv = D_1 || D_2 ...
+fn bytes_to_key(pass: &[u8], salt: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut material = Vec::with_capacity(KEY_LEN + IV_LEN);
+    let mut prev = Vec::new();
+    while material.len() < KEY_LEN + IV_LEN {
+        prev = Sha256::new()
+            .chain_update(&prev)
+            .chain_update(pass)
+            .chain_update(salt)
+            .finalize()
+            .to_vec();
+        material.extend_from_slice(&prev);
+    }
+    let iv = material[KEY_LEN..KEY_LEN + IV_LEN].to_vec();
+    material.truncate(KEY_LEN);
+    (material, iv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rc5_cipher::encoding::hex_decode;
+
+    #[test]
+    fn key_derivation_matches_openssl() {
+        // openssl enc -aes-128-cbc -pass pass:password -S 0102030405060708 -P
+        let (key, iv) = bytes_to_key(b"password", &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(key, hex_decode("2435177f1410536baad2acc155c0f947").unwrap());
+        assert_eq!(iv, hex_decode("83d58384573cb0f7").unwrap());
+    }
+
+    #[test]
+    fn salted_format_roundtrip() {
+        let ct = encrypt_with_salt(b"pw", &[9; 8], b"pipeline data").unwrap();
+        assert_eq!(&ct[..8], b"Salted__");
+        assert_eq!(ct[8..16], [9; 8]);
+        assert_eq!(ct.len(), 16 + 16);
+        assert_eq!(decrypt(b"pw", &ct).unwrap(), b"pipeline data");
+
+        assert_ne!(encrypt(b"pw", b"x").unwrap(), encrypt(b"pw", b"x").unwrap());
+        assert_eq!(decrypt(b"pw", b"Salted__"), Err(Error::InvalidHeader));
+    }
+
+    // Most distribution and conda builds of OpenSSL leave RC5 out, so this
+    // only runs on request: `cargo test --features cli -- --ignored`.
+    #[test]
+    #[ignore = "needs an `openssl` binary built with RC5"]
+    fn matches_system_openssl() {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let openssl = |args: &[&str], input: &[u8]| {
+            let mut child = Command::new("openssl")
+                .args([
+                    "enc",
+                    "-rc5-cbc",
+                    "-provider",
+                    "legacy",
+                    "-provider",
+                    "default",
+                ])
+                .args(["-md", "sha256", "-pass", "pass:pw"])
+                .args(args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("openssl not found");
+            child.stdin.take().unwrap().write_all(input).unwrap();
+            let out = child.wait_with_output().unwrap();
+            assert!(out.status.success(), "openssl lacks RC5");
+            out.stdout
+        };
+
+        let data = b"pipeline data, long enough for several blocks";
+        let theirs = openssl(&["-S", "0909090909090909"], data);
+        assert_eq!(theirs, encrypt_with_salt(b"pw", &[9; 8], data).unwrap());
+        assert_eq!(decrypt(b"pw", &theirs).unwrap(), data);
+        assert_eq!(openssl(&["-d"], &encrypt(b"pw", data).unwrap()), data);
+    }
+}
@@ -0,0 +1,63 @@
+//!
+//! Cooperative cancellation of long-running operations.
+//!
+//! A [`CancellationToken`] is cloned into the code that may want to stop an
+//! operation, e.g. a UI thread or a shutdown handler, and passed by reference
+//! to the bulk APIs. Those check it at every chunk boundary and return
+//! [`Error::Cancelled`](crate::Error::Cancelled) once it is set, so no chunk is
+//! ever half written.
+//!
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::Error;
+
+///
+/// A shared flag requesting that an operation stops.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::cancel::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+/// assert!(!token.is_cancelled());
+///
+/// std::thread::spawn(move || handle.cancel()).join().unwrap();
+/// assert!(token.is_cancelled());
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Requests cancellation. Every clone of this token observes it.
+    ///
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    ///
+    /// Returns [`Error::Cancelled`] if cancellation was requested.
+    ///
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//!
+//! Multi-threaded CBC decryption.
+//!
+//! Each CBC plaintext block depends only on two ciphertext blocks,
+//! `P_i = D(C_i) ^ C_(i-1)`, so unlike encryption, decryption of a long buffer
+//! can be split into runs decrypted on separate threads. Reading a large
+//! CBC-encrypted archive then scales with the available cores.
+//!
+//! Padding is not removed; strip it afterwards with the scheme the data was
+//! encrypted with.
+//!
+
+use std::thread;
+
+use crate::error::Error;
+use crate::rc5::decrypt_kernel;
+use crate::word::Word;
+
+// Runs shorter than this aren't worth a thread.
+const MIN_BYTES_PER_THREAD: usize = 16 * 1024;
+
+///
+/// Decrypts the CBC ciphertext in `buf` in place, on as many threads as the
+/// machine offers and the length warrants.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long or `buf`
+/// isn't a whole number of blocks.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::cbc::decrypt_cbc_parallel;
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+/// use rc5_cipher::expand_key;
+///
+/// let iv = [9u8; 16];
+/// let profile = LegacyProfile {
+///     rounds: 16,
+///     endian: Endian::Little,
+///     key: KeyHandling::Raw,
+///     padding: LegacyPadding::None,
+///     chaining: Chaining::CbcFixedIv(iv.to_vec()),
+/// };
+/// let archive = vec![0x5a; 1 << 20];
+/// let mut buf = profile.encrypt::<u64>(b"archive key 0001", &archive).unwrap();
+///
+/// let key_exp = expand_key::<u64>(b"archive key 0001", 16);
+/// decrypt_cbc_parallel(&key_exp, &iv, &mut buf).unwrap();
+/// assert_eq!(buf, archive);
+/// ```
+///
+pub fn decrypt_cbc_parallel<W: Word + Sync>(
+    key_exp: &[W],
+    iv: &[u8],
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let bs = 2 * W::BYTES;
+    if iv.len() != bs || !buf.len().is_multiple_of(bs) {
+        return Err(Error::InvalidLength);
+    }
+
+    let cores = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = cores.min(buf.len() / MIN_BYTES_PER_THREAD).max(1);
+    decrypt_on_threads(key_exp, iv, buf, threads);
+    Ok(())
+}
+
+fn decrypt_on_threads<W: Word + Sync>(key_exp: &[W], iv: &[u8], buf: &mut [u8], threads: usize) {
+    let bs = 2 * W::BYTES;
+    let run = (buf.len() / bs).div_ceil(threads).max(1) * bs;
+    if threads == 1 {
+        return decrypt_run(key_exp, iv, buf);
+    }
+
+    // each run chains from the last ciphertext block before it, which the
+    // previous run overwrites, so take copies first
+    let prevs: Vec<Vec<u8>> = (0..buf.len())
+        .step_by(run)
+        .map(|start| match start {
+            0 => iv.to_vec(),
+            _ => buf[start - bs..start].to_vec(),
+        })
+        .collect();
+
+    thread::scope(|scope| {
+        for (chunk, prev) in buf.chunks_mut(run).zip(&prevs) {
+            scope.spawn(move || decrypt_run(key_exp, prev, chunk));
+        }
+    });
+}
+
+// Sequential CBC decryption of `buf`, chained from `prev`.
+fn decrypt_run<W: Word>(key_exp: &[W], prev: &[u8], buf: &mut [u8]) {
+    let bs = 2 * W::BYTES;
+    let mut prev = [W::from_le_slice(prev), W::from_le_slice(&prev[W::BYTES..])];
+    for block in buf.chunks_exact_mut(bs) {
+        let ct = [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ];
+        let pt = decrypt_kernel(ct, key_exp);
+        (pt[0] ^ prev[0]).write_le(block);
+        (pt[1] ^ prev[1]).write_le(&mut block[W::BYTES..]);
+        prev = ct;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::{encrypt_kernel, expand_key};
+
+    fn encrypt_cbc<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8]) {
+        let mut prev = [W::from_le_slice(iv), W::from_le_slice(&iv[W::BYTES..])];
+        for block in buf.chunks_exact_mut(2 * W::BYTES) {
+            let pt = [
+                W::from_le_slice(block) ^ prev[0],
+                W::from_le_slice(&block[W::BYTES..]) ^ prev[1],
+            ];
+            prev = encrypt_kernel(pt, key_exp);
+            prev[0].write_le(block);
+            prev[1].write_le(&mut block[W::BYTES..]);
+        }
+    }
+
+    #[test]
+    fn matches_sequential_decryption() {
+        let key_exp = expand_key::<u32>(b"cbc parallel key", 12);
+        let iv = [0xa5u8; 8];
+        // an odd number of blocks, so runs split unevenly
+        for len in [0, 8, 16, 8 * 1001, MIN_BYTES_PER_THREAD * 7 + 8 * 3] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31) as u8).collect();
+            let mut buf = data.clone();
+            encrypt_cbc(&key_exp, &iv, &mut buf);
+
+            let mut sequential = buf.clone();
+            decrypt_run(&key_exp, &iv, &mut sequential);
+            assert_eq!(sequential, data);
+
+            for threads in [3, 8] {
+                let mut parallel = buf.clone();
+                decrypt_on_threads(&key_exp, &iv, &mut parallel, threads);
+                assert_eq!(parallel, data, "len {} on {} threads", len, threads);
+            }
+
+            decrypt_cbc_parallel(&key_exp, &iv, &mut buf).unwrap();
+            assert_eq!(buf, data, "len {}", len);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_lengths() {
+        let key_exp = expand_key::<u16>(b"key", 12);
+        assert_eq!(
+            decrypt_cbc_parallel(&key_exp, &[0; 4], &mut [0; 6]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            decrypt_cbc_parallel(&key_exp, &[0; 8], &mut [0; 8]),
+            Err(Error::InvalidLength)
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//!
+//! CCM authenticated encryption (counter mode with CBC-MAC).
+//!
+//! The construction of NIST SP 800-38C and RFC 3610, generalized from
+//! 128-bit blocks to the `2w`-bit RC5 block: a CBC-MAC over a formatted first
+//! block `B0`, the length-prefixed associated data and the plaintext, then
+//! counter mode over the plaintext and the tag. `B0` and the counter blocks
+//! hold a flags byte, the nonce and a `q`-byte big-endian field with the
+//! message length or the block counter, so the nonce length fixes `q` as
+//! `block size - 1 - nonce length` and with it the longest message, below
+//! `2^(8q)` bytes.
+//!
+//! The format needs room for a nonce and a length field of 2 to 8 bytes, so
+//! CCM is only available for `u32` words and wider. Tags are 4 to 16 bytes
+//! long, even and at most a block. Every block of data costs two block
+//! encryptions, one for the MAC and one for the keystream. A nonce must never
+//! repeat under one key: that reveals the XOR of the two plaintexts.
+//!
+
+use crate::algo::AeadAlgo;
+use crate::error::Error;
+use crate::rc5::{encrypt_kernel, expand_key};
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+///
+/// CCM over RC5 with a fixed nonce and tag length. The tag is appended to
+/// the ciphertext.
+///
+/// Example:
+///
+/// ```rust
+/// use rand_core::OsRng;
+/// use rc5_cipher::algo::{AeadAlgo, Nonce};
+/// use rc5_cipher::ccm::Ccm;
+///
+/// // 13-byte nonces leave 2 length bytes: messages up to 64 KiB
+/// let ccm = Ccm::<u64>::new(b"sensor key 00001", 20, 13, 8).unwrap();
+/// let nonce = Nonce::generate(&mut OsRng, 13);
+/// let sent = nonce.as_bytes().to_vec();
+///
+/// let sealed = ccm.seal(nonce, b"node 4", b"temp=21.5").unwrap();
+/// assert_eq!(sealed.len(), 9 + 8);
+///
+/// assert_eq!(ccm.open(&sent, b"node 4", &sealed).unwrap(), b"temp=21.5");
+/// assert!(ccm.open(&sent, b"node 5", &sealed).is_err());
+/// ```
+///
+pub struct Ccm<W: Word> {
+    key_exp: Vec<W>,
+    nonce_len: usize,
+    tag_len: usize,
+}
+
+impl<W: Word> Ccm<W> {
+    ///
+    /// Expands `key` for `rounds` rounds, for nonces of `nonce_len` bytes and
+    /// tags of `tag_len` bytes.
+    ///
+    /// Fails with [`Error::UnsupportedParameters`] for words narrower than
+    /// `u32`, an empty nonce or one leaving a length field outside 2 to 8
+    /// bytes, or a tag length that is odd, below 4 or above 16 or the block
+    /// size.
+    ///
+    pub fn new(key: &[u8], rounds: usize, nonce_len: usize, tag_len: usize) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        let q = bs.saturating_sub(1 + nonce_len);
+        if bs < 8
+            || nonce_len == 0
+            || !(2..=8).contains(&q)
+            || !tag_len.is_multiple_of(2)
+            || !(4..=bs.min(16)).contains(&tag_len)
+        {
+            return Err(Error::UnsupportedParameters);
+        }
+        Ok(Ccm {
+            key_exp: expand_key(key, rounds),
+            nonce_len,
+            tag_len,
+        })
+    }
+
+    // Length of the message length and counter field.
+    fn q(&self) -> usize {
+        2 * W::BYTES - 1 - self.nonce_len
+    }
+
+    fn check(&self, nonce: &[u8], len: usize) -> Result<(), Error> {
+        let q = self.q();
+        if nonce.len() != self.nonce_len || (q < 8 && (len as u64) >> (8 * q) != 0) {
+            return Err(Error::InvalidLength);
+        }
+        Ok(())
+    }
+
+    // The unmasked tag: CBC-MAC over B0, the encoded associated data and the
+    // plaintext, each zero padded to whole blocks.
+    fn mac(&self, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let (n, q) = (self.nonce_len, self.q());
+        let mut mac = [0u8; MAX_BLOCK];
+        mac[0] =
+            (u8::from(!aad.is_empty()) << 6) | ((self.tag_len as u8 - 2) / 2) << 3 | (q as u8 - 1);
+        mac[1..1 + n].copy_from_slice(nonce);
+        mac[1 + n..bs].copy_from_slice(&(plaintext.len() as u64).to_be_bytes()[8 - q..]);
+        self.encrypt_block(&mut mac[..bs]);
+
+        if !aad.is_empty() {
+            let mut encoded = encode_aad_len(aad.len());
+            encoded.extend_from_slice(aad);
+            self.absorb(&mut mac[..bs], &encoded);
+        }
+        self.absorb(&mut mac[..bs], plaintext);
+        mac
+    }
+
+    fn absorb(&self, mac: &mut [u8], data: &[u8]) {
+        for chunk in data.chunks(mac.len()) {
+            xor(mac, chunk);
+            self.encrypt_block(mac);
+        }
+    }
+
+    // XORs the keystream from counter 1 on into `data` and the one of
+    // counter 0 into `tag`.
+    fn apply_keystream(&self, nonce: &[u8], data: &mut [u8], tag: &mut [u8]) {
+        let bs = 2 * W::BYTES;
+        let (n, q) = (self.nonce_len, self.q());
+        let mut counter = [0u8; MAX_BLOCK];
+        counter[0] = q as u8 - 1;
+        counter[1..1 + n].copy_from_slice(nonce);
+
+        let mut ks = counter;
+        self.encrypt_block(&mut ks[..bs]);
+        xor(tag, &ks);
+        for (i, chunk) in data.chunks_mut(bs).enumerate() {
+            counter[1 + n..bs].copy_from_slice(&(i as u64 + 1).to_be_bytes()[8 - q..]);
+            let mut ks = counter;
+            self.encrypt_block(&mut ks[..bs]);
+            xor(chunk, &ks);
+        }
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        let ct = encrypt_kernel(
+            [
+                W::from_le_slice(block),
+                W::from_le_slice(&block[W::BYTES..]),
+            ],
+            &self.key_exp,
+        );
+        ct[0].write_le(block);
+        ct[1].write_le(&mut block[W::BYTES..]);
+    }
+}
+
+///
+/// Fails with [`Error::InvalidLength`] if `nonce` has the wrong length or
+/// `plaintext` is too long for the length field, and with
+/// [`Error::AuthenticationFailed`] if the ciphertext, tag, nonce or `aad` was
+/// altered.
+///
+impl<W: Word> AeadAlgo for Ccm<W> {
+    fn nonce_len(&self) -> usize {
+        self.nonce_len
+    }
+
+    fn tag_len(&self) -> usize {
+        self.tag_len
+    }
+
+    fn seal_with_raw_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.check(nonce, plaintext.len())?;
+        let mut tag = self.mac(nonce, aad, plaintext);
+        let mut out = Vec::with_capacity(plaintext.len() + self.tag_len);
+        out.extend_from_slice(plaintext);
+        self.apply_keystream(nonce, &mut out, &mut tag);
+        out.extend_from_slice(&tag[..self.tag_len]);
+        Ok(out)
+    }
+
+    fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        let len = ciphertext
+            .len()
+            .checked_sub(self.tag_len)
+            .ok_or(Error::AuthenticationFailed)?;
+        self.check(nonce, len)?;
+        let (ct, tag) = ciphertext.split_at(len);
+        let mut pt = ct.to_vec();
+        let mut expected = [0u8; MAX_BLOCK];
+        self.apply_keystream(nonce, &mut pt, &mut expected);
+        xor(&mut expected, &self.mac(nonce, aad, &pt));
+
+        let diff = expected[..self.tag_len]
+            .iter()
+            .zip(tag)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            pt.fill(0);
+            return Err(Error::AuthenticationFailed);
+        }
+        Ok(pt)
+    }
+}
+
+// The length prefix of the associated data, in the shortest of the three
+// encodings of SP 800-38C.
+fn encode_aad_len(len: usize) -> Vec<u8> {
+    let len = len as u64;
+    if len < 0xff00 {
+        (len as u16).to_be_bytes().to_vec()
+    } else if len <= u32::MAX.into() {
+        [&[0xff, 0xfe][..], &(len as u32).to_be_bytes()].concat()
+    } else {
+        [&[0xff, 0xff][..], &len.to_be_bytes()].concat()
+    }
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_formatting_of_the_standard() {
+        let ccm = Ccm::<u64>::new(b"ccm key", 16, 13, 8).unwrap();
+        let nonce: Vec<u8> = (0x10..0x1d).collect();
+        let pt = [0x5a; 16];
+        let sealed = ccm.seal_with_raw_nonce(&nonce, &[], &pt).unwrap();
+
+        // B0: flags (t - 2) / 2 = 3 and q - 1 = 1, nonce, length 16
+        let mut mac = [0x19; 16];
+        mac[1..14].copy_from_slice(&nonce);
+        mac[14..].copy_from_slice(&[0, 16]);
+        ccm.encrypt_block(&mut mac);
+        xor(&mut mac, &pt);
+        ccm.encrypt_block(&mut mac);
+
+        // A_i: flags q - 1, nonce, counter i
+        let counter = |i: u8| {
+            let mut a = [1u8; 16];
+            a[1..14].copy_from_slice(&nonce);
+            a[14..].copy_from_slice(&[0, i]);
+            ccm.encrypt_block(&mut a);
+            a
+        };
+        let mut ct = pt;
+        xor(&mut ct, &counter(1));
+        xor(&mut mac, &counter(0));
+        assert_eq!(sealed[..16], ct);
+        assert_eq!(sealed[16..], mac[..8]);
+    }
+
+    #[test]
+    fn roundtrip_for_every_size() {
+        fn check<W: Word>(nonce_len: usize, tag_len: usize) {
+            let ccm = Ccm::<W>::new(b"ccm roundtrip key", 16, nonce_len, tag_len).unwrap();
+            let nonce = vec![0xc3; nonce_len];
+            let bs = 2 * W::BYTES;
+            for len in [0, 1, bs, 3 * bs + 1] {
+                let pt: Vec<u8> = (0..len).map(|i| (i * 11) as u8).collect();
+                for aad in [&b""[..], b"header"] {
+                    let sealed = ccm.seal_with_raw_nonce(&nonce, aad, &pt).unwrap();
+                    assert_eq!(sealed.len(), len + tag_len);
+                    assert_eq!(ccm.open(&nonce, aad, &sealed).unwrap(), pt);
+                }
+            }
+        }
+        check::<u32>(5, 4);
+        check::<u32>(1, 8);
+        check::<u64>(13, 8);
+        check::<u64>(7, 16);
+        check::<u128>(23, 16);
+        check::<u128>(29, 12);
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let ccm = Ccm::<u32>::new(b"key", 12, 5, 8).unwrap();
+        let nonce = [1, 2, 3, 4, 5];
+        let sealed = ccm
+            .seal_with_raw_nonce(&nonce, b"aad", b"a message of a few blocks")
+            .unwrap();
+        for i in [0, 8, sealed.len() - 8, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 1;
+            assert_eq!(
+                ccm.open(&nonce, b"aad", &bad),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        assert_eq!(
+            ccm.open(&nonce, b"aae", &sealed),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            ccm.open(&[1, 2, 3, 4, 6], b"aad", &sealed),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            ccm.open(&nonce, b"aad", &sealed[..7]),
+            Err(Error::AuthenticationFailed)
+        );
+        // empty and absent associated data are the same
+        let sealed = ccm.seal_with_raw_nonce(&nonce, b"", b"x").unwrap();
+        assert_eq!(ccm.open(&nonce, &[], &sealed).unwrap(), b"x");
+    }
+
+    #[test]
+    fn validates_parameters_and_lengths() {
+        assert!(Ccm::<u16>::new(b"k", 12, 1, 4).is_err());
+        for (nonce_len, tag_len) in [(6, 4), (0, 4), (5, 3), (5, 2), (5, 10), (20, 4)] {
+            assert_eq!(
+                Ccm::<u32>::new(b"k", 12, nonce_len, tag_len).err(),
+                Some(Error::UnsupportedParameters),
+                "{} {}",
+                nonce_len,
+                tag_len
+            );
+        }
+        assert!(Ccm::<u128>::new(b"k", 12, 23, 18).is_err());
+
+        let ccm = Ccm::<u32>::new(b"k", 12, 5, 4).unwrap();
+        assert_eq!(
+            ccm.seal_with_raw_nonce(&[0; 4], b"", b""),
+            Err(Error::InvalidLength)
+        );
+        // two length bytes
+        assert!(ccm.seal_with_raw_nonce(&[0; 5], b"", &[0; 0xffff]).is_ok());
+        assert_eq!(
+            ccm.seal_with_raw_nonce(&[0; 5], b"", &[0; 0x10000]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn aad_length_encodings() {
+        assert_eq!(encode_aad_len(0xfeff), [0xfe, 0xff]);
+        assert_eq!(encode_aad_len(0xff00), [0xff, 0xfe, 0, 0, 0xff, 0]);
+        assert_eq!(
+            encode_aad_len(1 << 32),
+            [0xff, 0xff, 0, 0, 0, 1, 0, 0, 0, 0]
+        );
+
+        let ccm = Ccm::<u64>::new(b"k", 12, 12, 16).unwrap();
+        let aad = vec![9u8; 0xff00];
+        let sealed = ccm.seal_with_raw_nonce(&[0; 12], &aad, b"data").unwrap();
+        assert_eq!(ccm.open(&[0; 12], &aad, &sealed).unwrap(), b"data");
+        assert!(ccm.open(&[0; 12], &aad[1..], &sealed).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//!
+//! Cipher feedback mode, full-block and 8-bit.
+//!
+//! Full-block CFB encrypts `C_i = P_i ^ E(C_(i-1))` with `C_0` the IV, as
+//! the envelope's CFB mode does. The last block may be partial, so data of
+//! any length encrypts to the same length. CFB-8 (NIST SP 800-38A with
+//! `s = 8`) feeds back one byte at a time: each byte is XORed with the
+//! first byte of the encrypted shift register, which then drops its first
+//! byte and takes the ciphertext byte at the end. It recovers from a lost or
+//! inserted byte after one block, for byte-oriented links, but costs one
+//! block encryption per byte.
+//!
+//! Neither is authenticated, and an IV must not repeat under one key.
+//!
+
+use crate::error::Error;
+use crate::rc5::encrypt_kernel;
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+///
+/// Encrypts `buf` in place with full-block CFB.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::cfb::{decrypt_cfb, encrypt_cfb};
+/// use rc5_cipher::expand_key;
+///
+/// let key_exp = expand_key::<u32>(b"serial link key1", 12);
+/// let iv = [3u8; 8];
+/// let mut buf = *b"any length works";
+///
+/// encrypt_cfb(&key_exp, &iv, &mut buf[..13]).unwrap();
+/// decrypt_cfb(&key_exp, &iv, &mut buf[..13]).unwrap();
+/// assert_eq!(&buf, b"any length works");
+/// ```
+///
+pub fn encrypt_cfb<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    cfb(key_exp, iv, buf, true)
+}
+
+///
+/// Decrypts `buf` in place with full-block CFB.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long.
+///
+pub fn decrypt_cfb<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    cfb(key_exp, iv, buf, false)
+}
+
+///
+/// Encrypts `buf` in place with CFB-8.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::cfb::{decrypt_cfb8, encrypt_cfb8};
+/// use rc5_cipher::expand_key;
+///
+/// let key_exp = expand_key::<u32>(b"serial link key1", 12);
+/// let iv = [3u8; 8];
+/// let mut buf = *b"AT+SEND=42\r\n";
+///
+/// encrypt_cfb8(&key_exp, &iv, &mut buf).unwrap();
+/// decrypt_cfb8(&key_exp, &iv, &mut buf).unwrap();
+/// assert_eq!(&buf, b"AT+SEND=42\r\n");
+/// ```
+///
+pub fn encrypt_cfb8<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    cfb8(key_exp, iv, buf, true)
+}
+
+///
+/// Decrypts `buf` in place with CFB-8.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long.
+///
+pub fn decrypt_cfb8<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8]) -> Result<(), Error> {
+    cfb8(key_exp, iv, buf, false)
+}
+
+fn cfb<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8], encrypt: bool) -> Result<(), Error> {
+    let bs = 2 * W::BYTES;
+    if iv.len() != bs {
+        return Err(Error::InvalidLength);
+    }
+    let mut prev = [0u8; MAX_BLOCK];
+    prev[..bs].copy_from_slice(iv);
+    let mut ks = [0u8; MAX_BLOCK];
+    for block in buf.chunks_mut(bs) {
+        ks[..bs].copy_from_slice(&prev[..bs]);
+        encrypt_block(key_exp, &mut ks[..bs]);
+        if encrypt {
+            xor(block, &ks);
+            prev[..block.len()].copy_from_slice(block);
+        } else {
+            prev[..block.len()].copy_from_slice(block);
+            xor(block, &ks);
+        }
+    }
+    Ok(())
+}
+
+fn cfb8<W: Word>(key_exp: &[W], iv: &[u8], buf: &mut [u8], encrypt: bool) -> Result<(), Error> {
+    let bs = 2 * W::BYTES;
+    if iv.len() != bs {
+        return Err(Error::InvalidLength);
+    }
+    let mut reg = [0u8; MAX_BLOCK];
+    reg[..bs].copy_from_slice(iv);
+    let mut ks = [0u8; MAX_BLOCK];
+    for byte in buf.iter_mut() {
+        ks[..bs].copy_from_slice(&reg[..bs]);
+        encrypt_block(key_exp, &mut ks[..bs]);
+        let ct = if encrypt { *byte ^ ks[0] } else { *byte };
+        *byte ^= ks[0];
+        reg.copy_within(1..bs, 0);
+        reg[bs - 1] = ct;
+    }
+    Ok(())
+}
+
+fn encrypt_block<W: Word>(key_exp: &[W], block: &mut [u8]) {
+    let ct = encrypt_kernel(
+        [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        key_exp,
+    );
+    ct[0].write_le(block);
+    ct[1].write_le(&mut block[W::BYTES..]);
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::expand_key;
+
+    #[test]
+    fn full_block_feeds_back_ciphertext() {
+        let key_exp = expand_key::<u32>(b"cfb key", 12);
+        let iv = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        let data: Vec<u8> = (0..20).collect();
+        let mut buf = data.clone();
+        encrypt_cfb(&key_exp, &iv, &mut buf).unwrap();
+
+        let mut prev = iv.to_vec();
+        for (i, block) in data.chunks(8).enumerate() {
+            encrypt_block(&key_exp, &mut prev);
+            let expected: Vec<u8> = block.iter().zip(&prev).map(|(p, k)| p ^ k).collect();
+            assert_eq!(buf[8 * i..8 * i + block.len()], expected[..], "block {}", i);
+            prev = expected;
+        }
+    }
+
+    #[test]
+    fn cfb8_shifts_one_byte_at_a_time() {
+        let key_exp = expand_key::<u32>(b"cfb key", 12);
+        let iv = [9u8; 8];
+        let mut buf = [0x41, 0x42, 0x43];
+        encrypt_cfb8(&key_exp, &iv, &mut buf).unwrap();
+
+        let mut reg = iv.to_vec();
+        for (i, p) in [0x41u8, 0x42, 0x43].into_iter().enumerate() {
+            let mut ks = reg.clone();
+            encrypt_block(&key_exp, &mut ks);
+            assert_eq!(buf[i], p ^ ks[0]);
+            reg.remove(0);
+            reg.push(buf[i]);
+        }
+
+        // a corrupted byte garbles only the next block's worth of bytes
+        let data: Vec<u8> = (0..40).collect();
+        let mut ct = data.clone();
+        encrypt_cfb8(&key_exp, &iv, &mut ct).unwrap();
+        ct[10] ^= 1;
+        decrypt_cfb8(&key_exp, &iv, &mut ct).unwrap();
+        assert_eq!(ct[..10], data[..10]);
+        assert_eq!(ct[19..], data[19..]);
+    }
+
+    #[test]
+    fn roundtrip_for_every_word_size() {
+        fn check<W: Word>() {
+            let key_exp = expand_key::<W>(b"cfb roundtrip key", 16);
+            let bs = 2 * W::BYTES;
+            let iv = vec![0x5a; bs];
+            for len in [0, 1, bs, 3 * bs + 1] {
+                let data: Vec<u8> = (0..len).map(|i| (i * 5) as u8).collect();
+                let mut full = data.clone();
+                encrypt_cfb(&key_exp, &iv, &mut full).unwrap();
+                let mut eight = data.clone();
+                encrypt_cfb8(&key_exp, &iv, &mut eight).unwrap();
+                if len > 1 {
+                    assert_ne!(full, data);
+                    assert_ne!(eight, full);
+                }
+                decrypt_cfb(&key_exp, &iv, &mut full).unwrap();
+                decrypt_cfb8(&key_exp, &iv, &mut eight).unwrap();
+                assert_eq!(full, data);
+                assert_eq!(eight, data);
+            }
+            let short = vec![0; bs - 1];
+            assert_eq!(
+                encrypt_cfb(&key_exp, &short, &mut [0; 4]),
+                Err(Error::InvalidLength)
+            );
+            assert_eq!(
+                decrypt_cfb8(&key_exp, &short, &mut [0; 4]),
+                Err(Error::InvalidLength)
+            );
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+}
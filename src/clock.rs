@@ -0,0 +1,108 @@
+//!
+//! Time sources for key lifetimes.
+//!
+//! Code enforcing how long a key may be used takes a [`Clock`] instead of
+//! reading `std::time` itself. [`MonotonicClock`] is the default; tests pass a
+//! [`ManualClock`] and advance it instead of sleeping, and targets without
+//! `std::time::Instant`, such as `wasm32-unknown-unknown` or a microcontroller
+//! with an RTC, implement the trait over the time source they have.
+//!
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+///
+/// A monotonic time source.
+///
+pub trait Clock {
+    /// Time elapsed since a fixed origin of the clock's choosing.
+    fn now(&self) -> Duration;
+}
+
+///
+/// [`Instant`]-based clock, with its origin at creation.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock(Instant);
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        MonotonicClock(Instant::now())
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.0.elapsed()
+    }
+}
+
+///
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test keeps one and hands a clone to the code under test.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use rc5_cipher::clock::ManualClock;
+/// use rc5_cipher::usage::{KeyUsage, UsagePolicy};
+///
+/// let clock = ManualClock::default();
+/// let policy = UsagePolicy {
+///     max_age: Some(Duration::from_secs(600)),
+///     ..UsagePolicy::default()
+/// };
+/// let usage = KeyUsage::with_clock(policy, clock.clone());
+///
+/// clock.advance(Duration::from_secs(599));
+/// assert!(usage.check().is_ok());
+/// clock.advance(Duration::from_secs(1));
+/// assert!(usage.check().is_err());
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Arc<AtomicU64>);
+
+impl ManualClock {
+    ///
+    /// Moves the time of this clock and all its clones forward. Saturates at
+    /// `u64::MAX` nanoseconds.
+    ///
+    pub fn advance(&self, by: Duration) {
+        let nanos = u64::try_from(by.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |t| {
+                Some(t.saturating_add(nanos))
+            });
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_is_shared_and_saturates() {
+        let clock = ManualClock::default();
+        let copy = clock.clone();
+        copy.advance(Duration::from_secs(5));
+        assert_eq!(clock.now(), Duration::from_secs(5));
+        clock.advance(Duration::MAX);
+        assert_eq!(copy.now(), Duration::from_nanos(u64::MAX));
+    }
+}
@@ -0,0 +1,569 @@
+//!
+//! Counter mode with random access into the keystream.
+//!
+//! Block `i` of the keystream is the encryption of `iv + i`, the IV read as a
+//! little-endian integer of the block size, so any byte range can be processed
+//! on its own. Encryption and decryption are the same operation.
+//!
+//! [`Rc5Ctr`] keeps the schedule and the position between calls, for data
+//! processed in sequence.
+//!
+//! Apart from [`Rc5Ctr::from_key`], nothing here allocates: given a
+//! pre-expanded key schedule, a firmware image can be decrypted in place page
+//! by page, e.g. as a DMA transfer or a flash page completes. The crate as a
+//! whole still needs `std`.
+//!
+//! The counter wraps after `2^(8 * block size)` blocks, only 128 KiB for
+//! RC5-8: keep each (key, IV) pair well below that.
+//!
+
+use core::marker::PhantomData;
+
+use crate::clock::{Clock, MonotonicClock};
+use crate::error::Error;
+use crate::rc5::{encrypt_kernel, expand_key};
+use crate::usage::KeyUsage;
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+///
+/// XORs the keystream starting at byte `offset` into `buf`.
+///
+/// Fails with [`Error::InvalidLength`] if `iv` isn't one block long.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::ctr::apply_keystream_at;
+/// use rc5_cipher::expand_key;
+///
+/// let key_exp = expand_key::<u32>(b"firmware key 123", 12);
+/// let iv = [7u8; 8];
+/// let image = b"page one........page two........".to_vec();
+///
+/// let mut encrypted = image.clone();
+/// apply_keystream_at(&key_exp, &iv, 0, &mut encrypted).unwrap();
+///
+/// // decrypt the second page alone, in place
+/// let mut page = encrypted[16..].to_vec();
+/// apply_keystream_at(&key_exp, &iv, 16, &mut page).unwrap();
+/// assert_eq!(page, image[16..]);
+/// ```
+///
+pub fn apply_keystream_at<W: Word>(
+    key_exp: &[W],
+    iv: &[u8],
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<(), Error> {
+    let bs = 2 * W::BYTES;
+    if iv.len() != bs {
+        return Err(Error::InvalidLength);
+    }
+
+    let mut block_index = offset / bs as u64;
+    let mut skip = (offset % bs as u64) as usize;
+    let mut keystream = [0u8; MAX_BLOCK];
+    let mut buf = buf;
+    while !buf.is_empty() {
+        keystream_block(key_exp, iv, block_index, &mut keystream[..bs]);
+        let take = (bs - skip).min(buf.len());
+        let (head, rest) = buf.split_at_mut(take);
+        for (b, k) in head.iter_mut().zip(&keystream[skip..skip + take]) {
+            *b ^= k;
+        }
+        buf = rest;
+        block_index = block_index.wrapping_add(1);
+        skip = 0;
+    }
+    Ok(())
+}
+
+///
+/// A counter mode cipher over a key schedule, owned or borrowed, that
+/// remembers its position in the keystream.
+///
+/// With a [`KeyUsage`] attached by [`with_usage`](Self::with_usage), the
+/// stream becomes a [`MeteredRc5Ctr`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::ctr::Rc5Ctr;
+///
+/// let nonce = [0x24u8; 16];
+/// let mut ctr = Rc5Ctr::<u64, _>::from_key(b"stream key 12345", 20, &nonce, 0).unwrap();
+///
+/// // any length, no padding
+/// let mut data = *b"first chunk, then the second";
+/// let (a, b) = data.split_at_mut(5);
+/// ctr.apply_keystream(a);
+/// ctr.apply_keystream(b);
+///
+/// let mut dec = Rc5Ctr::<u64, _>::from_key(b"stream key 12345", 20, &nonce, 0).unwrap();
+/// dec.apply_keystream(&mut data);
+/// assert_eq!(&data, b"first chunk, then the second");
+/// ```
+///
+pub struct Rc5Ctr<W: Word, S: AsRef<[W]>> {
+    key_exp: S,
+    nonce: [u8; MAX_BLOCK],
+    offset: u64,
+    _word: PhantomData<W>,
+}
+
+impl<W: Word> Rc5Ctr<W, Vec<W>> {
+    ///
+    /// Expands `key` once and starts at block `counter`.
+    ///
+    pub fn from_key(key: &[u8], rounds: usize, nonce: &[u8], counter: u64) -> Result<Self, Error> {
+        Self::new(expand_key(key, rounds), nonce, counter)
+    }
+}
+
+impl<W: Word, S: AsRef<[W]>> Rc5Ctr<W, S> {
+    ///
+    /// Starts at block `counter` of the keystream for `nonce`, which is the
+    /// IV of [`apply_keystream_at`].
+    ///
+    /// Fails with [`Error::InvalidLength`] if `nonce` isn't one block long.
+    ///
+    pub fn new(key_exp: S, nonce: &[u8], counter: u64) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        if nonce.len() != bs {
+            return Err(Error::InvalidLength);
+        }
+        let mut block = [0u8; MAX_BLOCK];
+        block[..bs].copy_from_slice(nonce);
+        Ok(Rc5Ctr {
+            key_exp,
+            nonce: block,
+            offset: counter.wrapping_mul(bs as u64),
+            _word: PhantomData,
+        })
+    }
+
+    ///
+    /// Enforces the policy of `usage`, the usage of this stream's key so far,
+    /// on the stream. The stream is recorded as one more message; fails with
+    /// [`Error::KeyExhausted`] if the key can't take another.
+    ///
+    /// The returned stream only has the fallible
+    /// [`try_apply_keystream`](MeteredRc5Ctr::try_apply_keystream), so
+    /// reaching a limit is an error to handle, never a panic.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::ctr::Rc5Ctr;
+    /// use rc5_cipher::usage::{KeyUsage, UsagePolicy};
+    /// use rc5_cipher::Error;
+    ///
+    /// let policy = UsagePolicy {
+    ///     max_bytes: Some(1024),
+    ///     ..UsagePolicy::default()
+    /// };
+    /// let ctr = Rc5Ctr::<u32, _>::from_key(b"stream key 12345", 20, &[1; 8], 0).unwrap();
+    /// let mut ctr = ctr.with_usage(KeyUsage::new(policy)).unwrap();
+    ///
+    /// assert_eq!(ctr.try_apply_keystream(&mut [0; 1000]), Ok(()));
+    /// assert_eq!(ctr.try_apply_keystream(&mut [0; 25]), Err(Error::KeyExhausted));
+    ///
+    /// // the next stream under this key carries the usage on
+    /// let usage = ctr.into_usage();
+    /// assert_eq!(usage.messages(), 1);
+    /// ```
+    ///
+    pub fn with_usage<C: Clock>(
+        self,
+        mut usage: KeyUsage<C>,
+    ) -> Result<MeteredRc5Ctr<W, S, C>, Error> {
+        usage.record_message(0)?;
+        Ok(MeteredRc5Ctr { ctr: self, usage })
+    }
+
+    ///
+    /// XORs the next `buf.len()` bytes of keystream into `buf`.
+    ///
+    pub fn apply_keystream(&mut self, buf: &mut [u8]) {
+        let bs = 2 * W::BYTES;
+        // cannot fail, the nonce length was checked in `new`
+        let _ = apply_keystream_at(self.key_exp.as_ref(), &self.nonce[..bs], self.offset, buf);
+        self.offset = self.offset.wrapping_add(buf.len() as u64);
+    }
+
+    ///
+    /// Keystream offset, in bytes, of the next byte used.
+    ///
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    ///
+    /// Moves to keystream byte `offset`.
+    ///
+    pub fn seek(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+}
+
+///
+/// An [`Rc5Ctr`] that counts as one message of its key and stops at the
+/// limits of a [`KeyUsage`], from [`Rc5Ctr::with_usage`].
+///
+pub struct MeteredRc5Ctr<W: Word, S: AsRef<[W]>, C: Clock = MonotonicClock> {
+    ctr: Rc5Ctr<W, S>,
+    usage: KeyUsage<C>,
+}
+
+impl<W: Word, S: AsRef<[W]>, C: Clock> MeteredRc5Ctr<W, S, C> {
+    ///
+    /// XORs the next `buf.len()` bytes of keystream into `buf`.
+    ///
+    /// Fails with [`Error::KeyExhausted`], leaving `buf` untouched, if the
+    /// bytes would exceed the usage policy.
+    ///
+    pub fn try_apply_keystream(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.usage.record_bytes(buf.len() as u64)?;
+        self.ctr.apply_keystream(buf);
+        Ok(())
+    }
+
+    pub fn usage(&self) -> &KeyUsage<C> {
+        &self.usage
+    }
+
+    ///
+    /// Ends the stream, returning the usage of its key to pass on to the
+    /// next stream.
+    ///
+    pub fn into_usage(self) -> KeyUsage<C> {
+        self.usage
+    }
+
+    ///
+    /// Keystream offset, in bytes, of the next byte used.
+    ///
+    pub fn position(&self) -> u64 {
+        self.ctr.position()
+    }
+}
+
+///
+/// Keystream generated ahead of use, for paths where latency matters more
+/// than throughput.
+///
+/// [`prefill`](Self::prefill) encrypts counter blocks into caller-provided
+/// storage while the path is idle, e.g. between packets; [`apply`](Self::apply)
+/// then costs only a copy and XOR per byte as long as the buffer holds enough.
+/// Bytes beyond what is buffered are generated on the spot, so the output is
+/// always the same as [`apply_keystream_at`]'s.
+///
+/// The storage is used in whole blocks, so it must hold at least one.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::ctr::{apply_keystream_at, KeystreamBuffer};
+/// use rc5_cipher::expand_key;
+///
+/// let key_exp = expand_key::<u32>(b"packet key 01234", 12);
+/// let iv = [1u8; 8];
+/// let mut storage = [0u8; 1024];
+/// let mut keystream = KeystreamBuffer::new(&key_exp, &iv, 0, &mut storage).unwrap();
+///
+/// // while idle
+/// assert_eq!(keystream.prefill(usize::MAX), 128);
+///
+/// // on send
+/// let mut packet = *b"sample payload";
+/// keystream.apply(&mut packet);
+///
+/// let mut expected = *b"sample payload";
+/// apply_keystream_at(&key_exp, &iv, 0, &mut expected).unwrap();
+/// assert_eq!(packet, expected);
+/// ```
+///
+pub struct KeystreamBuffer<'a, W: Word> {
+    key_exp: &'a [W],
+    iv: [u8; MAX_BLOCK],
+    storage: &'a mut [u8],
+    // Keystream offsets of the next byte to use and of the end of what has
+    // been generated. Byte `n` of the keystream is kept at `storage[n % cap]`.
+    consumed: u64,
+    generated: u64,
+}
+
+impl<'a, W: Word> KeystreamBuffer<'a, W> {
+    ///
+    /// Starts at keystream byte `offset`, with nothing generated yet.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `iv` isn't one block long or
+    /// `storage` is shorter than a block.
+    ///
+    pub fn new(
+        key_exp: &'a [W],
+        iv: &[u8],
+        offset: u64,
+        storage: &'a mut [u8],
+    ) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        if iv.len() != bs || storage.len() < bs {
+            return Err(Error::InvalidLength);
+        }
+        let cap = storage.len() / bs * bs;
+        let mut iv_block = [0u8; MAX_BLOCK];
+        iv_block[..bs].copy_from_slice(iv);
+        Ok(KeystreamBuffer {
+            key_exp,
+            iv: iv_block,
+            storage: &mut storage[..cap],
+            consumed: offset,
+            generated: offset,
+        })
+    }
+
+    ///
+    /// Generates up to `n` more blocks of keystream, as many as fit, and
+    /// returns how many were generated.
+    ///
+    pub fn prefill(&mut self, n: usize) -> usize {
+        let bs = 2 * W::BYTES;
+        let cap = self.storage.len() as u64;
+        let mut keystream = [0u8; MAX_BLOCK];
+        let mut done = 0;
+        while done < n {
+            // the first block may be partly used already
+            let skip = (self.generated % bs as u64) as usize;
+            let free = cap - (self.generated - self.consumed);
+            if ((bs - skip) as u64) > free {
+                break;
+            }
+            let index = self.generated / bs as u64;
+            keystream_block(self.key_exp, &self.iv[..bs], index, &mut keystream[..bs]);
+            let at = (self.generated % cap) as usize;
+            self.storage[at..at + bs - skip].copy_from_slice(&keystream[skip..bs]);
+            self.generated += (bs - skip) as u64;
+            done += 1;
+        }
+        done
+    }
+
+    ///
+    /// Bytes of keystream ready to use.
+    ///
+    pub fn buffered(&self) -> usize {
+        (self.generated - self.consumed) as usize
+    }
+
+    ///
+    /// Keystream offset of the next byte [`apply`](Self::apply) uses.
+    ///
+    pub fn position(&self) -> u64 {
+        self.consumed
+    }
+
+    ///
+    /// XORs the next `buf.len()` bytes of keystream into `buf`.
+    ///
+    pub fn apply(&mut self, buf: &mut [u8]) {
+        let cap = self.storage.len();
+        let take = self.buffered().min(buf.len());
+        let (head, rest) = buf.split_at_mut(take);
+
+        let at = (self.consumed % cap as u64) as usize;
+        let first = take.min(cap - at);
+        let (wrap_head, wrap_tail) = head.split_at_mut(first);
+        for (b, k) in wrap_head.iter_mut().zip(&self.storage[at..]) {
+            *b ^= k;
+        }
+        for (b, k) in wrap_tail.iter_mut().zip(self.storage.iter()) {
+            *b ^= k;
+        }
+        self.consumed += take as u64;
+
+        if !rest.is_empty() {
+            let bs = 2 * W::BYTES;
+            // cannot fail, the IV length was checked in `new`
+            let _ = apply_keystream_at(self.key_exp, &self.iv[..bs], self.consumed, rest);
+            self.consumed += rest.len() as u64;
+            self.generated = self.consumed;
+        }
+    }
+}
+
+// Writes E(iv + index) into `out`, which is one block long.
+fn keystream_block<W: Word>(key_exp: &[W], iv: &[u8], index: u64, out: &mut [u8]) {
+    let mut carry = index as u128;
+    for (o, &b) in out.iter_mut().zip(iv) {
+        let sum = b as u128 + (carry & 0xff);
+        *o = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+
+    let ct = encrypt_kernel(
+        [W::from_le_slice(out), W::from_le_slice(&out[W::BYTES..])],
+        key_exp,
+    );
+    ct[0].write_le(out);
+    ct[1].write_le(&mut out[W::BYTES..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::expand_key;
+
+    #[test]
+    fn usage_policy_stops_the_stream() {
+        use crate::usage::UsagePolicy;
+
+        let policy = UsagePolicy {
+            max_bytes: Some(16),
+            max_messages: Some(2),
+            ..UsagePolicy::default()
+        };
+        let ctr = Rc5Ctr::<u32, _>::from_key(b"key", 12, &[0; 8], 0).unwrap();
+        let mut ctr = ctr.with_usage(KeyUsage::new(policy)).unwrap();
+        let mut buf = [0u8; 10];
+        ctr.try_apply_keystream(&mut buf).unwrap();
+        assert_eq!(
+            ctr.try_apply_keystream(&mut [0; 7]),
+            Err(Error::KeyExhausted)
+        );
+        // nothing was processed
+        assert_eq!(ctr.position(), 10);
+
+        // a second stream under the key is the second message
+        let usage = ctr.into_usage();
+        let ctr = Rc5Ctr::<u32, _>::from_key(b"key", 12, &[1; 8], 0).unwrap();
+        let mut ctr = ctr.with_usage(usage).unwrap();
+        ctr.try_apply_keystream(&mut [0; 6]).unwrap();
+        assert_eq!(ctr.usage().messages(), 2);
+        let ctr2 = Rc5Ctr::<u32, _>::from_key(b"key", 12, &[2; 8], 0).unwrap();
+        assert!(ctr2.with_usage(ctr.into_usage()).is_err());
+    }
+
+    #[test]
+    fn keystream_is_encrypted_counter() {
+        let key_exp = expand_key::<u16>(b"key", 12);
+        // 0x..ff + 1 carries into the next bytes
+        let iv = [0xff, 0xff, 0x00, 0x05];
+        let mut buf = [0u8; 8];
+        apply_keystream_at(&key_exp, &iv, 0, &mut buf).unwrap();
+
+        let first = encrypt_kernel([0xffffu16, 0x0500], &key_exp);
+        let second = encrypt_kernel([0x0000u16, 0x0501], &key_exp);
+        let mut expected = [0u8; 8];
+        for (i, w) in [first[0], first[1], second[0], second[1]]
+            .iter()
+            .enumerate()
+        {
+            expected[2 * i..2 * i + 2].copy_from_slice(&w.to_le_bytes());
+        }
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn random_access_matches_sequential() {
+        let key_exp = expand_key::<u64>(b"0123456789abcdef", 16);
+        let iv = [3u8; 16];
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut whole = data.clone();
+        apply_keystream_at(&key_exp, &iv, 0, &mut whole).unwrap();
+        assert_ne!(whole, data);
+
+        // unaligned pieces, processed out of order
+        let mut pieces = data.clone();
+        for range in [150..200, 7..150, 0..7] {
+            let start = range.start as u64;
+            apply_keystream_at(&key_exp, &iv, start, &mut pieces[range]).unwrap();
+        }
+        assert_eq!(pieces, whole);
+
+        apply_keystream_at(&key_exp, &iv, 0, &mut whole).unwrap();
+        assert_eq!(whole, data);
+
+        assert_eq!(
+            apply_keystream_at(&key_exp, &iv[..8], 0, &mut whole),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn rc5_ctr_tracks_position() {
+        let key_exp = expand_key::<u16>(b"ctr key", 12);
+        let nonce = [9, 8, 7, 6];
+        let data: Vec<u8> = (0..50).collect();
+        let mut expected = data.clone();
+        apply_keystream_at(&key_exp, &nonce, 12, &mut expected).unwrap();
+
+        // counter 3 of 4-byte blocks is byte 12, borrowed schedule
+        let mut ctr = Rc5Ctr::new(&key_exp[..], &nonce, 3).unwrap();
+        let mut out = data.clone();
+        for chunk in out.chunks_mut(7) {
+            ctr.apply_keystream(chunk);
+        }
+        assert_eq!(out, expected);
+        assert_eq!(ctr.position(), 62);
+
+        let mut owned = Rc5Ctr::<u16, _>::from_key(b"ctr key", 12, &nonce, 0).unwrap();
+        owned.seek(12);
+        owned.apply_keystream(&mut out);
+        assert_eq!(out, data);
+
+        assert!(Rc5Ctr::new(&key_exp[..], &[0; 8], 0).is_err());
+    }
+
+    #[test]
+    fn buffer_matches_direct_keystream() {
+        let key_exp = expand_key::<u16>(b"buffered", 12);
+        let iv = [9, 8, 7, 6];
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let mut expected = data.clone();
+        apply_keystream_at(&key_exp, &iv, 5, &mut expected).unwrap();
+
+        // 30 bytes of storage hold 7 blocks; pieces wrap around it, run past
+        // what is buffered and restart mid-block
+        let mut storage = [0u8; 30];
+        let mut keystream = KeystreamBuffer::new(&key_exp, &iv, 5, &mut storage).unwrap();
+        let mut out = data.clone();
+        let mut pos = 0;
+        for (prefill, len) in [
+            (1, 3),
+            (usize::MAX, 20),
+            (2, 11),
+            (0, 9),
+            (3, 40),
+            (100, 217),
+        ] {
+            keystream.prefill(prefill);
+            assert!(keystream.buffered() <= 28);
+            keystream.apply(&mut out[pos..pos + len]);
+            pos += len;
+            assert_eq!(keystream.position(), 5 + pos as u64);
+        }
+        assert_eq!(pos, data.len());
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn buffer_fills_to_capacity() {
+        let key_exp = expand_key::<u32>(b"key", 12);
+        let mut storage = [0u8; 20];
+        let mut keystream = KeystreamBuffer::new(&key_exp, &[0; 8], 0, &mut storage).unwrap();
+        assert_eq!(keystream.prefill(5), 2);
+        assert_eq!(keystream.buffered(), 16);
+        keystream.apply(&mut [0; 3]);
+        // the partly used block can't be replaced yet
+        assert_eq!(keystream.prefill(5), 0);
+        keystream.apply(&mut [0; 5]);
+        assert_eq!(keystream.prefill(5), 1);
+
+        assert!(KeystreamBuffer::new(&key_exp, &[0; 8], 0, &mut [0; 7]).is_err());
+        assert!(KeystreamBuffer::new(&key_exp, &[0; 4], 0, &mut storage).is_err());
+    }
+}
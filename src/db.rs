@@ -0,0 +1,296 @@
+//!
+//! Column-level encryption for database values.
+//!
+//! Each encrypted value is a self-contained byte string for a `VARBINARY` or
+//! `BYTEA` column, tagged with the [`KeyId`] of its key:
+//!
+//! ```text
+//! offset  size  field
+//! 0       1     kind: 0x01 deterministic, 0x02 randomized
+//! 1       8     key ID
+//! 9       16    nonce, randomized values only
+//! ...     16    synthetic IV
+//! ...     ...   ciphertext
+//! ```
+//!
+//! Deterministic values are equal whenever the plaintexts are, so an indexed
+//! equality lookup still works: encrypt the searched value with
+//! [`ColumnCipher::search_terms`] and compare. That equality is visible to
+//! anyone reading the table; encrypt every column that isn't searched
+//! randomized. Values are bound to their column name, so a value copied to
+//! another column fails to decrypt.
+//!
+//! Both kinds use [`DeterministicAead`] over RC5-64/24, randomized values
+//! with a random nonce as extra associated data. A key must be at least 32
+//! bytes: its first half is the MAC key, the second the encryption key.
+//!
+//! Only available with the `db` feature.
+//!
+
+use crate::error::Error;
+use crate::key::{KeyId, Keyring};
+use crate::rng::{CryptoRng, RngCore};
+use crate::siv::DeterministicAead;
+
+const ROUNDS: usize = 24;
+const MIN_KEY: usize = 32;
+const NONCE_LEN: usize = 16;
+
+const DETERMINISTIC: u8 = 0x01;
+const RANDOMIZED: u8 = 0x02;
+
+// Kind and key ID.
+const PREFIX_LEN: usize = 9;
+
+///
+/// Bytes added to a value by [`ColumnCipher::encrypt_deterministic`].
+///
+pub const DETERMINISTIC_OVERHEAD: usize = PREFIX_LEN + 16;
+
+///
+/// Bytes added to a value by [`ColumnCipher::encrypt_randomized`].
+///
+pub const RANDOMIZED_OVERHEAD: usize = PREFIX_LEN + NONCE_LEN + 16;
+
+///
+/// Encrypts and decrypts column values with the keys of a [`Keyring`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::db::ColumnCipher;
+/// use rc5_cipher::key::{Keyring, Rc5Key};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let mut keyring = Keyring::new();
+/// let id = keyring.insert(None, Rc5Key::new(&[0x5a; 32]).unwrap());
+/// keyring.set_primary(&id).unwrap();
+/// let cipher = ColumnCipher::new(&keyring).unwrap();
+///
+/// // INSERT INTO users (email, notes) VALUES (?, ?)
+/// let email = cipher.encrypt_deterministic("users.email", b"alice@example.com");
+/// let notes = cipher.encrypt_randomized(&mut rng, "users.notes", b"prefers email");
+///
+/// // SELECT ... WHERE email IN (?)
+/// let terms = cipher.search_terms("users.email", b"alice@example.com");
+/// assert!(terms.contains(&email));
+///
+/// assert_eq!(cipher.decrypt("users.notes", &notes).unwrap(), b"prefers email");
+/// assert!(cipher.decrypt("users.email", &notes).is_err());
+/// ```
+///
+pub struct ColumnCipher {
+    keys: Vec<(KeyId, DeterministicAead<u64>)>,
+    primary: usize,
+}
+
+impl ColumnCipher {
+    ///
+    /// Prepares every key of `keyring`; new values are encrypted under its
+    /// primary key.
+    ///
+    /// Fails with [`Error::UnknownKey`] if the keyring has no primary key and
+    /// with [`Error::InvalidLength`] if a key is shorter than 32 bytes.
+    ///
+    pub fn new(keyring: &Keyring) -> Result<Self, Error> {
+        let (primary_id, _) = keyring.primary().ok_or(Error::UnknownKey)?;
+        let mut keys = Vec::new();
+        for id in keyring.ids() {
+            let key = keyring.get(&id).ok_or(Error::UnknownKey)?.as_bytes();
+            if key.len() < MIN_KEY {
+                return Err(Error::InvalidLength);
+            }
+            let (mac_key, enc_key) = key.split_at(key.len() / 2);
+            keys.push((id, DeterministicAead::new(mac_key, enc_key, ROUNDS)?));
+        }
+        let primary = keys
+            .iter()
+            .position(|(id, _)| *id == primary_id)
+            .ok_or(Error::UnknownKey)?;
+        Ok(ColumnCipher { keys, primary })
+    }
+
+    ///
+    /// Encrypts `value` for `column` so that equal values give equal output.
+    ///
+    pub fn encrypt_deterministic(&self, column: &str, value: &[u8]) -> Vec<u8> {
+        let (id, aead) = &self.keys[self.primary];
+        seal_deterministic(*id, aead, column, value)
+    }
+
+    ///
+    /// Encrypts `value` for `column` with a fresh nonce from `rng`.
+    ///
+    pub fn encrypt_randomized<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        column: &str,
+        value: &[u8],
+    ) -> Vec<u8> {
+        let (id, aead) = &self.keys[self.primary];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        let mut out = Vec::with_capacity(RANDOMIZED_OVERHEAD + value.len());
+        out.push(RANDOMIZED);
+        out.extend_from_slice(&id.0);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&aead.seal(&[column.as_bytes(), &nonce], value));
+        out
+    }
+
+    ///
+    /// The deterministic encryptions of `value` under every key, primary key
+    /// first, to look a value up while rows are still being rolled over to a
+    /// new key.
+    ///
+    pub fn search_terms(&self, column: &str, value: &[u8]) -> Vec<Vec<u8>> {
+        let primary = &self.keys[self.primary];
+        let others = self.keys.iter().filter(|(id, _)| *id != primary.0);
+        std::iter::once(primary)
+            .chain(others)
+            .map(|(id, aead)| seal_deterministic(*id, aead, column, value))
+            .collect()
+    }
+
+    ///
+    /// Decrypts a value of either kind stored in `column`.
+    ///
+    /// Fails with [`Error::UnknownKey`] if its key isn't in the keyring, with
+    /// [`Error::InvalidEncoding`] if it isn't an encrypted value, and with
+    /// [`Error::AuthenticationFailed`] if it was altered or belongs to another
+    /// column.
+    ///
+    pub fn decrypt(&self, column: &str, stored: &[u8]) -> Result<Vec<u8>, Error> {
+        let id = key_id(stored).ok_or(Error::InvalidEncoding)?;
+        let (_, aead) = self
+            .keys
+            .iter()
+            .find(|(k, _)| *k == id)
+            .ok_or(Error::UnknownKey)?;
+        let body = &stored[PREFIX_LEN..];
+        match stored[0] {
+            DETERMINISTIC => aead.open(&[column.as_bytes()], body),
+            _ if body.len() < NONCE_LEN => Err(Error::InvalidLength),
+            _ => {
+                let (nonce, sealed) = body.split_at(NONCE_LEN);
+                aead.open(&[column.as_bytes(), nonce], sealed)
+            }
+        }
+    }
+}
+
+///
+/// The ID of the key a stored value is encrypted under, e.g. to find rows
+/// still to re-encrypt after a rollover. `None` if `stored` isn't an
+/// encrypted value.
+///
+pub fn key_id(stored: &[u8]) -> Option<KeyId> {
+    if stored.len() < PREFIX_LEN || !matches!(stored[0], DETERMINISTIC | RANDOMIZED) {
+        return None;
+    }
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&stored[1..PREFIX_LEN]);
+    Some(KeyId(id))
+}
+
+fn seal_deterministic(
+    id: KeyId,
+    aead: &DeterministicAead<u64>,
+    column: &str,
+    value: &[u8],
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(DETERMINISTIC_OVERHEAD + value.len());
+    out.push(DETERMINISTIC);
+    out.extend_from_slice(&id.0);
+    out.extend_from_slice(&aead.seal(&[column.as_bytes()], value));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::Rc5Key;
+    use crate::rng::CounterRng;
+
+    fn keyring(keys: &[[u8; 32]]) -> Keyring {
+        let mut keyring = Keyring::new();
+        for key in keys {
+            let id = keyring.insert(None, Rc5Key::new(key).unwrap());
+            keyring.set_primary(&id).unwrap();
+        }
+        keyring
+    }
+
+    #[test]
+    fn layout_and_roundtrip() {
+        let keyring = keyring(&[[1; 32]]);
+        let id = keyring.primary().unwrap().0;
+        let cipher = ColumnCipher::new(&keyring).unwrap();
+        let mut rng = CounterRng(0);
+
+        let det = cipher.encrypt_deterministic("t.c", b"value");
+        assert_eq!(det.len(), DETERMINISTIC_OVERHEAD + 5);
+        assert_eq!(det[0], DETERMINISTIC);
+        assert_eq!(key_id(&det), Some(id));
+        assert_eq!(det, cipher.encrypt_deterministic("t.c", b"value"));
+        assert_ne!(det, cipher.encrypt_deterministic("t.d", b"value"));
+
+        let rand = cipher.encrypt_randomized(&mut rng, "t.c", b"value");
+        assert_eq!(rand.len(), RANDOMIZED_OVERHEAD + 5);
+        assert_eq!(rand[0], RANDOMIZED);
+        assert_ne!(rand, cipher.encrypt_randomized(&mut rng, "t.c", b"value"));
+
+        for stored in [&det, &rand] {
+            assert_eq!(cipher.decrypt("t.c", stored).unwrap(), b"value");
+            assert_eq!(
+                cipher.decrypt("t.d", stored),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+    }
+
+    #[test]
+    fn rollover_keeps_old_rows_readable() {
+        let old = ColumnCipher::new(&keyring(&[[1; 32]])).unwrap();
+        let row = old.encrypt_deterministic("t.c", b"bob");
+
+        let both = ColumnCipher::new(&keyring(&[[1; 32], [2; 32]])).unwrap();
+        assert_eq!(both.decrypt("t.c", &row).unwrap(), b"bob");
+        let terms = both.search_terms("t.c", b"bob");
+        assert_eq!(terms.len(), 2);
+        assert_eq!(terms[0], both.encrypt_deterministic("t.c", b"bob"));
+        assert_eq!(terms[1], row);
+
+        let new = ColumnCipher::new(&keyring(&[[2; 32]])).unwrap();
+        assert_eq!(new.decrypt("t.c", &row), Err(Error::UnknownKey));
+    }
+
+    #[test]
+    fn rejects_bad_keys_and_values() {
+        assert!(matches!(
+            ColumnCipher::new(&Keyring::new()),
+            Err(Error::UnknownKey)
+        ));
+        let mut short = Keyring::new();
+        let id = short.insert(None, Rc5Key::new(&[1; 31]).unwrap());
+        short.set_primary(&id).unwrap();
+        assert!(matches!(
+            ColumnCipher::new(&short),
+            Err(Error::InvalidLength)
+        ));
+
+        let cipher = ColumnCipher::new(&keyring(&[[1; 32]])).unwrap();
+        let mut rng = CounterRng(0);
+        let rand = cipher.encrypt_randomized(&mut rng, "t.c", b"");
+        assert_eq!(
+            cipher.decrypt("t.c", b"plain text"),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            cipher.decrypt("t.c", &rand[..20]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(key_id(&rand[..8]), None);
+    }
+}
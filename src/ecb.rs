@@ -0,0 +1,124 @@
+//!
+//! ECB mode over whole buffers. **Insecure: for test vectors and interop
+//! only.**
+//!
+//! Every block is encrypted on its own under the same key, so equal
+//! plaintext blocks give equal ciphertext blocks and the structure of the
+//! data shows through. Nothing is authenticated either. Use it to check
+//! known-answer vectors or to read data another system wrote this way;
+//! for anything new, use [`envelope`](crate::envelope).
+//!
+//! No padding is applied: buffers must be a whole number of blocks. For
+//! padded ECB, see [`LegacyProfile`](crate::legacy::LegacyProfile).
+//!
+
+use crate::error::Error;
+use crate::rc5::{decrypt_kernel, encrypt_kernel, expand_key};
+use crate::word::Word;
+
+///
+/// Encrypts `data` block by block with one expansion of `key`.
+///
+/// Fails with [`Error::InvalidLength`] if `data` isn't a whole number of
+/// blocks.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::ecb::{ecb_decrypt, ecb_encrypt};
+///
+/// let key = [0x00, 0x01, 0x02, 0x03];
+/// let ct = ecb_encrypt::<u8>(&key, &[0x00, 0x01, 0x00, 0x01], 12).unwrap();
+/// assert_eq!(ct, [0x21, 0x2A, 0x21, 0x2A]); // equal blocks show
+///
+/// assert_eq!(ecb_decrypt::<u8>(&key, &ct, 12).unwrap(), [0x00, 0x01, 0x00, 0x01]);
+/// assert!(ecb_encrypt::<u8>(&key, &[0x00], 12).is_err());
+/// ```
+///
+pub fn ecb_encrypt<W: Word>(key: &[u8], data: &[u8], rounds: usize) -> Result<Vec<u8>, Error> {
+    crypt::<W>(key, data, rounds, encrypt_kernel)
+}
+
+///
+/// Decrypts `data` block by block with one expansion of `key`.
+///
+/// Fails with [`Error::InvalidLength`] if `data` isn't a whole number of
+/// blocks.
+///
+pub fn ecb_decrypt<W: Word>(key: &[u8], data: &[u8], rounds: usize) -> Result<Vec<u8>, Error> {
+    crypt::<W>(key, data, rounds, decrypt_kernel)
+}
+
+fn crypt<W: Word>(
+    key: &[u8],
+    data: &[u8],
+    rounds: usize,
+    kernel: fn([W; 2], &[W]) -> [W; 2],
+) -> Result<Vec<u8>, Error> {
+    let bs = 2 * W::BYTES;
+    if !data.len().is_multiple_of(bs) {
+        return Err(Error::InvalidLength);
+    }
+    let key_exp = expand_key::<W>(key, rounds);
+    let mut out = data.to_vec();
+    for block in out.chunks_exact_mut(bs) {
+        let res = kernel(
+            [
+                W::from_le_slice(block),
+                W::from_le_slice(&block[W::BYTES..]),
+            ],
+            &key_exp,
+        );
+        res[0].write_le(block);
+        res[1].write_le(&mut block[W::BYTES..]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::encrypt;
+
+    #[test]
+    fn blocks_are_encrypted_independently() {
+        let key: Vec<u8> = (0..16).collect();
+        let data: Vec<u8> = (0..24).collect();
+        let ct = ecb_encrypt::<u32>(&key, &data, 12).unwrap();
+        for (i, block) in data.chunks(8).enumerate() {
+            let words = encrypt(
+                [u32::from_le_slice(block), u32::from_le_slice(&block[4..])],
+                &key,
+                12,
+            );
+            assert_eq!(u32::from_le_slice(&ct[8 * i..]), words[0]);
+            assert_eq!(u32::from_le_slice(&ct[8 * i + 4..]), words[1]);
+        }
+    }
+
+    #[test]
+    fn roundtrip_for_every_word_size() {
+        fn check<W: Word>() {
+            let bs = 2 * W::BYTES;
+            for len in [0, bs, 5 * bs] {
+                let data: Vec<u8> = (0..len).map(|i| (i * 3) as u8).collect();
+                let ct = ecb_encrypt::<W>(b"ecb key", &data, 16).unwrap();
+                assert_eq!(ct.len(), len);
+                assert_eq!(ecb_decrypt::<W>(b"ecb key", &ct, 16).unwrap(), data);
+            }
+            assert_eq!(
+                ecb_encrypt::<W>(b"ecb key", &vec![0; bs + 1], 16),
+                Err(Error::InvalidLength)
+            );
+            assert_eq!(
+                ecb_decrypt::<W>(b"ecb key", &vec![0; bs - 1], 16),
+                Err(Error::InvalidLength)
+            );
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+}
@@ -0,0 +1,560 @@
+//!
+//! Hex and base64url (RFC 4648 §5) transcoding.
+//!
+//! Both codecs avoid table lookups and data-dependent branches on the decoded
+//! characters, so that keys and other secrets can be parsed from text without
+//! leaking their value through timing. Only the input length is public.
+//!
+//! Hex is always encoded in lowercase and decoded case-insensitively. Base64url
+//! is always encoded without `=` padding; decoding accepts unpadded input and
+//! input carrying the canonical padding, but nothing else.
+//!
+//! [`EncodingWriter`] and [`DecodingReader`] apply the same codecs on the fly
+//! to any [`Write`] or [`Read`], so ciphertext streams can travel over text-only
+//! transports without a separate encoding pass.
+//!
+
+use std::io::{self, IoSlice, Read, Write};
+
+use crate::error::Error;
+
+///
+/// Encodes `data` as lowercase hex.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::encoding::hex_encode;
+///
+/// assert_eq!(hex_encode(&[0x00, 0x1f, 0xab]), "001fab");
+/// ```
+///
+pub fn hex_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(2 * data.len());
+    for &byte in data {
+        out.push(encode_nibble(byte >> 4) as char);
+        out.push(encode_nibble(byte & 0x0f) as char);
+    }
+    out
+}
+
+///
+/// Decodes hex text (upper or lower case) into bytes.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::encoding::hex_decode;
+///
+/// assert_eq!(hex_decode("001FaB").unwrap(), vec![0x00, 0x1f, 0xab]);
+/// assert!(hex_decode("0g").is_err());
+/// ```
+///
+pub fn hex_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let src = text.as_bytes();
+    if !src.len().is_multiple_of(2) {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let mut out = Vec::with_capacity(src.len() / 2);
+    let mut err = 0i16;
+    for pair in src.chunks_exact(2) {
+        let hi = decode_nibble(pair[0]);
+        let lo = decode_nibble(pair[1]);
+        err |= hi | lo;
+        out.push(((hi << 4) | lo) as u8);
+    }
+
+    if err < 0 {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(out)
+}
+
+///
+/// Encodes `data` as unpadded base64url.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::encoding::base64url_encode;
+///
+/// assert_eq!(base64url_encode(&[0xfb, 0xff]), "-_8");
+/// ```
+///
+pub fn base64url_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((4 * data.len()).div_ceil(3));
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0] as i16;
+        let b1 = *chunk.get(1).unwrap_or(&0) as i16;
+        let b2 = *chunk.get(2).unwrap_or(&0) as i16;
+
+        out.push(encode_6bits(b0 >> 2) as char);
+        out.push(encode_6bits(((b0 << 4) | (b1 >> 4)) & 0x3f) as char);
+        if chunk.len() > 1 {
+            out.push(encode_6bits(((b1 << 2) | (b2 >> 6)) & 0x3f) as char);
+        }
+        if chunk.len() > 2 {
+            out.push(encode_6bits(b2 & 0x3f) as char);
+        }
+    }
+    out
+}
+
+///
+/// Decodes base64url text, with or without canonical `=` padding, into bytes.
+///
+/// Non-canonical encodings (stray bits in the last character, padding that
+/// doesn't match the length) are rejected, so every byte string has exactly
+/// one accepted unpadded encoding.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::encoding::base64url_decode;
+///
+/// assert_eq!(base64url_decode("-_8").unwrap(), vec![0xfb, 0xff]);
+/// assert_eq!(base64url_decode("-_8=").unwrap(), vec![0xfb, 0xff]);
+/// assert!(base64url_decode("-_9").is_err());
+/// ```
+///
+pub fn base64url_decode(text: &str) -> Result<Vec<u8>, Error> {
+    let mut src = text.as_bytes();
+    if src.len().is_multiple_of(4) && !src.is_empty() {
+        // Strip canonical padding: at most two `=`, only on a full quantum.
+        if src[src.len() - 1] == b'=' {
+            src = &src[..src.len() - 1];
+            if src[src.len() - 1] == b'=' {
+                src = &src[..src.len() - 1];
+            }
+        }
+    }
+    if src.len() % 4 == 1 {
+        return Err(Error::InvalidEncoding);
+    }
+
+    let mut out = Vec::with_capacity(3 * src.len() / 4);
+    let mut err = 0i16;
+    for chunk in src.chunks(4) {
+        let c0 = decode_6bits(chunk[0]);
+        let c1 = decode_6bits(chunk[1]);
+        let c2 = chunk.get(2).map_or(0, |&c| decode_6bits(c));
+        let c3 = chunk.get(3).map_or(0, |&c| decode_6bits(c));
+        err |= c0 | c1 | c2 | c3;
+
+        out.push(((c0 << 2) | (c1 >> 4)) as u8);
+        match chunk.len() {
+            2 => err |= -(c1 & 0x0f),
+            3 => {
+                out.push(((c1 << 4) | (c2 >> 2)) as u8);
+                err |= -(c2 & 0x03);
+            }
+            _ => {
+                out.push(((c1 << 4) | (c2 >> 2)) as u8);
+                out.push(((c2 << 6) | c3) as u8);
+            }
+        }
+    }
+
+    if err < 0 {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(out)
+}
+
+///
+/// A [`Write`] adapter encoding everything written to it as text.
+///
+/// Base64url groups three bytes at a time, so the writer must be closed with
+/// [`EncodingWriter::finish`] to emit the last characters.
+///
+/// Example:
+///
+/// ```rust
+/// use std::io::Write;
+/// use rc5_cipher::encoding::EncodingWriter;
+///
+/// let mut writer = EncodingWriter::base64url(Vec::new());
+/// writer.write_all(&[0xfb]).unwrap();
+/// writer.write_all(&[0xff]).unwrap();
+/// let text = writer.finish().unwrap();
+///
+/// assert_eq!(text, b"-_8");
+/// ```
+///
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+    group: usize,
+    encode: fn(&[u8]) -> String,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn hex(inner: W) -> Self {
+        EncodingWriter {
+            inner,
+            pending: Vec::new(),
+            group: 1,
+            encode: hex_encode,
+        }
+    }
+
+    pub fn base64url(inner: W) -> Self {
+        EncodingWriter {
+            inner,
+            pending: Vec::with_capacity(3),
+            group: 3,
+            encode: base64url_encode,
+        }
+    }
+
+    ///
+    /// Writes out the buffered tail, flushes and returns the inner writer.
+    ///
+    pub fn finish(mut self) -> io::Result<W> {
+        let text = (self.encode)(&self.pending);
+        self.inner.write_all(text.as_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    // Encodes all of `bufs` into one piece of text, so that a header and a
+    // chunk reach the inner writer in a single write.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let mut len = 0;
+        for buf in bufs {
+            self.pending.extend_from_slice(buf);
+            len += buf.len();
+        }
+        let ready = self.pending.len() - self.pending.len() % self.group;
+        let text = (self.encode)(&self.pending[..ready]);
+        self.inner.write_all(text.as_bytes())?;
+        self.pending.drain(..ready);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+///
+/// A [`Read`] adapter decoding hex or base64url text from the inner reader.
+///
+/// Line breaks (`\n`, `\r`) are skipped, so line-wrapped text and text with a
+/// trailing newline decode as well. Invalid text fails the read with
+/// [`io::ErrorKind::InvalidData`].
+///
+/// Example:
+///
+/// ```rust
+/// use std::io::Read;
+/// use rc5_cipher::encoding::DecodingReader;
+///
+/// let mut reader = DecodingReader::hex(&b"001f\nAB\n"[..]);
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+///
+/// assert_eq!(data, [0x00, 0x1f, 0xab]);
+/// ```
+///
+pub struct DecodingReader<R: Read> {
+    inner: R,
+    text: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+    group: usize,
+    decode: fn(&str) -> Result<Vec<u8>, Error>,
+    eof: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn hex(inner: R) -> Self {
+        Self::with_codec(inner, 2, hex_decode)
+    }
+
+    pub fn base64url(inner: R) -> Self {
+        Self::with_codec(inner, 4, base64url_decode)
+    }
+
+    fn with_codec(inner: R, group: usize, decode: fn(&str) -> Result<Vec<u8>, Error>) -> Self {
+        DecodingReader {
+            inner,
+            text: Vec::new(),
+            decoded: Vec::new(),
+            pos: 0,
+            group,
+            decode,
+            eof: false,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Decodes the next batch of text into `decoded`. The last group is held
+    // back until the end of input, since only it may carry padding.
+    fn fill(&mut self) -> io::Result<()> {
+        let mut chunk = [0u8; 4096];
+        while self.pos == self.decoded.len() {
+            let usable = if self.eof {
+                if self.text.is_empty() {
+                    return Ok(());
+                }
+                self.text.len()
+            } else {
+                let n = self.inner.read(&mut chunk)?;
+                self.eof = n == 0;
+                let lines = chunk[..n].iter().filter(|&&c| c != b'\n' && c != b'\r');
+                self.text.extend(lines);
+                let ready = self.text.len().saturating_sub(self.group);
+                ready - ready % self.group
+            };
+            if usable == 0 {
+                continue;
+            }
+            // padding can only end the input
+            if !self.eof && self.text[..usable].contains(&b'=') {
+                return Err(invalid_data());
+            }
+
+            let text = std::str::from_utf8(&self.text[..usable]).map_err(|_| invalid_data())?;
+            self.decoded = (self.decode)(text).map_err(|_| invalid_data())?;
+            self.pos = 0;
+            self.text.drain(..usable);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.fill()?;
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+fn invalid_data() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, Error::InvalidEncoding)
+}
+
+// Maps 0..=15 to '0'..='9', 'a'..='f'.
+fn encode_nibble(src: u8) -> u8 {
+    let src = src as i16;
+    // if src > 9 add 'a' - '9' - 1
+    (src + 0x30 + (((9 - src) >> 8) & 0x27)) as u8
+}
+
+// Maps a hex character to 0..=15, anything else to a negative value.
+fn decode_nibble(src: u8) -> i16 {
+    let src = src as i16;
+    let mut ret = -1i16;
+    // '0'..='9' => 0..=9
+    ret += (((0x2f - src) & (src - 0x3a)) >> 8) & (src - 0x2f);
+    // 'A'..='F' => 10..=15
+    ret += (((0x40 - src) & (src - 0x47)) >> 8) & (src - 0x36);
+    // 'a'..='f' => 10..=15
+    ret += (((0x60 - src) & (src - 0x67)) >> 8) & (src - 0x56);
+    ret
+}
+
+// Maps 0..=63 to the base64url alphabet.
+fn encode_6bits(src: i16) -> u8 {
+    let mut diff = 0x41i16;
+    // 26..=51 => 'a'..='z'
+    diff += ((25 - src) >> 8) & 6;
+    // 52..=61 => '0'..='9'
+    diff -= ((51 - src) >> 8) & 75;
+    // 62 => '-'
+    diff -= ((61 - src) >> 8) & 13;
+    // 63 => '_'
+    diff += ((62 - src) >> 8) & 49;
+    (src + diff) as u8
+}
+
+// Maps a base64url character to 0..=63, anything else to a negative value.
+fn decode_6bits(src: u8) -> i16 {
+    let src = src as i16;
+    let mut ret = -1i16;
+    // 'A'..='Z' => 0..=25
+    ret += (((0x40 - src) & (src - 0x5b)) >> 8) & (src - 0x40);
+    // 'a'..='z' => 26..=51
+    ret += (((0x60 - src) & (src - 0x7b)) >> 8) & (src - 0x46);
+    // '0'..='9' => 52..=61
+    ret += (((0x2f - src) & (src - 0x3a)) >> 8) & (src + 5);
+    // '-' => 62
+    ret += (((0x2c - src) & (src - 0x2e)) >> 8) & 63;
+    // '_' => 63
+    ret += (((0x5e - src) & (src - 0x60)) >> 8) & 64;
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_roundtrip_all_bytes() {
+        let data: Vec<u8> = (0..=255u8).collect();
+        let text = hex_encode(&data);
+        assert_eq!(&text[..8], "00010203");
+        assert_eq!(&text[text.len() - 4..], "feff");
+        assert_eq!(hex_decode(&text).unwrap(), data);
+        assert_eq!(hex_decode(&text.to_uppercase()).unwrap(), data);
+    }
+
+    #[test]
+    fn hex_rejects_invalid() {
+        assert_eq!(hex_decode("abc"), Err(Error::InvalidEncoding));
+        assert_eq!(hex_decode("0G"), Err(Error::InvalidEncoding));
+        assert_eq!(hex_decode("/0"), Err(Error::InvalidEncoding));
+        assert_eq!(hex_decode(":0"), Err(Error::InvalidEncoding));
+        assert_eq!(hex_decode("0 "), Err(Error::InvalidEncoding));
+        assert_eq!(hex_decode(""), Ok(vec![]));
+    }
+
+    // Test vectors from RFC 4648 §10, translated to the URL-safe alphabet.
+    #[test]
+    fn base64url_rfc4648() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg"),
+            ("fo", "Zm8"),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg"),
+            ("fooba", "Zm9vYmE"),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in cases {
+            assert_eq!(base64url_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64url_decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(base64url_decode("Zg==").unwrap(), b"f");
+        assert_eq!(base64url_decode("Zm8=").unwrap(), b"fo");
+    }
+
+    #[test]
+    fn base64url_roundtrip_all_bytes() {
+        let data: Vec<u8> = (0..=255u8).collect();
+        for len in 0..data.len() {
+            let text = base64url_encode(&data[..len]);
+            assert!(!text.contains(['+', '/', '=']));
+            assert_eq!(base64url_decode(&text).unwrap(), &data[..len]);
+        }
+    }
+
+    #[test]
+    fn base64url_rejects_invalid() {
+        // standard alphabet characters
+        assert_eq!(base64url_decode("+/8"), Err(Error::InvalidEncoding));
+        // impossible length
+        assert_eq!(base64url_decode("Zm9vY"), Err(Error::InvalidEncoding));
+        // non-zero trailing bits
+        assert_eq!(base64url_decode("Zh"), Err(Error::InvalidEncoding));
+        assert_eq!(base64url_decode("Zm9"), Err(Error::InvalidEncoding));
+        // padding in the wrong place or amount
+        assert_eq!(base64url_decode("Zg="), Err(Error::InvalidEncoding));
+        assert_eq!(base64url_decode("Z==="), Err(Error::InvalidEncoding));
+        assert_eq!(base64url_decode("Zg==Zg=="), Err(Error::InvalidEncoding));
+    }
+
+    // Reads one byte at a time to exercise group boundaries.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    // Records the length of every write.
+    #[derive(Default)]
+    struct Calls(Vec<usize>);
+
+    impl Write for Calls {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn vectored_write_reaches_inner_writer_once() {
+        let mut writer = EncodingWriter::base64url(Calls::default());
+        let parts = [
+            IoSlice::new(b"head"),
+            IoSlice::new(b"er"),
+            IoSlice::new(b"chunk"),
+        ];
+        assert_eq!(writer.write_vectored(&parts).unwrap(), 11);
+        let calls = writer.finish().unwrap();
+        // 9 bytes as 12 characters, then the 2-byte tail
+        assert_eq!(calls.0, [12, 3]);
+    }
+
+    #[test]
+    fn streaming_layers_match_one_shot_codecs() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(700).collect();
+        for len in [0, 1, 2, 3, 4, 699, 700] {
+            let data = &data[..len];
+
+            let mut hex = EncodingWriter::hex(Vec::new());
+            let mut b64 = EncodingWriter::base64url(Vec::new());
+            for piece in data.chunks(5) {
+                hex.write_all(piece).unwrap();
+                b64.write_all(piece).unwrap();
+            }
+            let hex = hex.finish().unwrap();
+            let b64 = b64.finish().unwrap();
+            assert_eq!(hex, hex_encode(data).as_bytes());
+            assert_eq!(b64, base64url_encode(data).as_bytes());
+
+            let mut out = Vec::new();
+            DecodingReader::hex(Trickle(&hex))
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, data);
+            out.clear();
+            DecodingReader::base64url(Trickle(&b64))
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, data);
+        }
+    }
+
+    #[test]
+    fn decoding_reader_handles_lines_padding_and_errors() {
+        let mut out = Vec::new();
+        let text = b"Zm9v\r\nYmE=\n";
+        DecodingReader::base64url(&text[..])
+            .read_to_end(&mut out)
+            .unwrap();
+        assert_eq!(out, b"foobar"[..5]);
+
+        let err = DecodingReader::hex(&b"00zz"[..])
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = DecodingReader::base64url(&b"Zg==Zg=="[..])
+            .read_to_end(&mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,1015 @@
+//!
+//! Self-describing ciphertext: a [`Header`] followed by the IV and the data.
+//!
+//! ```text
+//! header | IV (one block, absent for ECB) | ciphertext
+//! ```
+//!
+//! [`decrypt_auto`] reads the word size, rounds and mode from the header, so
+//! the reader only needs the key. ECB and CBC pad with [`Pkcs7`]; CFB and CTR
+//! are stream modes and keep the length of the data. CTR counts as in
+//! [`crate::ctr`].
+//!
+//! [`encrypt_with_id`] writes the header in compact form, naming the
+//! parameters by a [`crate::suite`] algorithm ID; [`decrypt_auto`] reads both.
+//!
+//! With the `key` feature, [`encrypt_with_keyring`] records the ID of the key
+//! in the header and [`decrypt_with_keyring`] picks the key by it.
+//!
+//! These envelopes aren't authenticated: combine them with a MAC, or use the
+//! AEAD formats, whenever the ciphertext can be tampered with.
+//!
+//! [`encrypt_authenticated`] writes an authenticated envelope with a header
+//! announcing [`MacAlg::Cmac`] and CTR mode:
+//!
+//! ```text
+//! header (with metadata) | nonce (one block) | SIV (one block) | ciphertext
+//! ```
+//!
+//! The data is encrypted with [`DeterministicAead`], the header bytes and the
+//! random nonce as associated data. Metadata entries, e.g. a file name or a
+//! content type, are stored in clear in the header, so [`read_metadata`]
+//! returns them without the keys, and any change to them makes
+//! [`decrypt_authenticated`] fail. [`EnvelopeEncryptor`] writes the same
+//! envelopes while enforcing a [`UsagePolicy`](crate::usage::UsagePolicy) on
+//! the keys.
+//!
+
+use core::marker::PhantomData;
+
+use zeroize::Zeroizing;
+
+use crate::algo::{BlockAlgorithm, BlockCipherAlgo};
+use crate::audit::{self, Operation};
+use crate::clock::{Clock, MonotonicClock};
+use crate::error::{Error, Failure};
+#[cfg(feature = "key")]
+use crate::header::EXT_KEY_ID;
+use crate::header::{Extension, Header, MacAlg, Mode, EXT_METADATA};
+#[cfg(feature = "key")]
+use crate::key::Keyring;
+use crate::padding::{Padding, Pkcs7};
+use crate::params::{ParameterFloor, Rc5Params};
+use crate::rng::{CryptoRng, RngCore};
+use crate::siv::DeterministicAead;
+use crate::suite;
+use crate::usage::KeyUsage;
+use crate::word::Word;
+
+// Suite of the schedule expanded for authenticated input without a header.
+const DECOY_WORD_BYTES: u8 = 8;
+const DECOY_ROUNDS: usize = 20;
+
+// Key decrypting keyring envelopes whose key is unknown.
+#[cfg(feature = "key")]
+const DECOY_KEY: [u8; 16] = [0; 16];
+
+///
+/// Encrypts `data` with RC5 over `W` words in `mode`, prefixed by a header
+/// describing the parameters and a random IV drawn from `rng`.
+///
+/// Fails with [`Error::WeakParameters`] below the default
+/// [`ParameterFloor`](crate::params::ParameterFloor).
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_auto, encrypt_with_header};
+/// use rc5_cipher::header::Mode;
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let key = [7u8; 16];
+/// let bytes = encrypt_with_header::<u64, _>(&mut rng, &key, 16, Mode::Cbc, b"hello").unwrap();
+///
+/// assert_eq!(decrypt_auto(&key, &bytes).unwrap(), b"hello");
+/// ```
+///
+pub fn encrypt_with_header<W: Word, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key: &[u8],
+    rounds: u8,
+    mode: Mode,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let header = Header::new::<W>(rounds, mode, MacAlg::None);
+    encrypt_full(rng, key, None, &header, data)
+}
+
+///
+/// Encrypts `data` like [`encrypt_with_header`] under the primary key of
+/// `keyring`, recording its ID in the header.
+///
+/// Fails with [`Error::UnknownKey`] if the keyring has no primary key.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_with_keyring, encrypt_with_keyring};
+/// use rc5_cipher::header::Mode;
+/// use rc5_cipher::key::{Keyring, Rc5Key};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let mut keyring = Keyring::new();
+/// let old = keyring.insert(None, Rc5Key::new(b"the old key 0001").unwrap());
+/// keyring.set_primary(&old).unwrap();
+/// let bytes = encrypt_with_keyring::<u64, _>(&mut rng, &keyring, 16, Mode::Cbc, b"hi").unwrap();
+///
+/// let new = keyring.insert(None, Rc5Key::new(b"the new key 0001").unwrap());
+/// keyring.set_primary(&new).unwrap();
+/// assert_eq!(decrypt_with_keyring(&keyring, &bytes).unwrap(), b"hi");
+/// ```
+///
+#[cfg(feature = "key")]
+pub fn encrypt_with_keyring<W: Word, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    keyring: &Keyring,
+    rounds: u8,
+    mode: Mode,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (id, key) = keyring.primary().ok_or(Error::UnknownKey)?;
+    let mut header = Header::new::<W>(rounds, mode, MacAlg::None);
+    header.extensions.push(Extension {
+        kind: EXT_KEY_ID,
+        value: id.0.to_vec(),
+    });
+    encrypt_full(rng, key.as_bytes(), Some(&id.to_string()), &header, data)
+}
+
+///
+/// Decrypts the output of [`encrypt_with_keyring`] with the key of `keyring`
+/// its header names, see [`Keyring::key_for`].
+///
+/// Like [`decrypt_authenticated`], fails with [`Error::AuthenticationFailed`]
+/// whether the header is malformed, names a key the keyring doesn't hold, or
+/// the data doesn't decrypt. An unknown key is replaced by a decoy key, so
+/// that every cause runs the same decryption.
+///
+#[cfg(feature = "key")]
+pub fn decrypt_with_keyring(keyring: &Keyring, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let key = Header::parse(bytes).and_then(|(header, _)| keyring.key_for(&header));
+    let decoy_or_key = key.as_ref().map_or(&DECOY_KEY[..], |key| key.as_bytes());
+    let key_id = key.as_ref().ok().map(|key| key.id().to_string());
+    match (key, decrypt_audited(decoy_or_key, key_id.as_deref(), bytes)) {
+        (Ok(_), Ok(data)) => Ok(data),
+        _ => Err(Error::AuthenticationFailed),
+    }
+}
+
+///
+/// Encrypts `data` with the parameters of algorithm ID `id`, like
+/// [`encrypt_with_header`] but with a 5-byte compact header.
+///
+/// Fails with [`Error::UnsupportedParameters`] if `id` is unknown or its suite
+/// has a MAC, and with [`Error::WeakParameters`] below the default
+/// [`ParameterFloor`](crate::params::ParameterFloor).
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_auto, encrypt_with_id};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let key = [7u8; 16];
+/// // RC5-64/24 in CTR mode, unauthenticated
+/// let bytes = encrypt_with_id(&mut rng, &key, 0x40, b"hi").unwrap();
+///
+/// assert_eq!(bytes.len(), 5 + 16 + 2);
+/// assert_eq!(decrypt_auto(&key, &bytes).unwrap(), b"hi");
+/// ```
+///
+pub fn encrypt_with_id<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key: &[u8],
+    id: u8,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let suite = suite::lookup(id).ok_or(Error::UnsupportedParameters)?;
+    if suite.mac != MacAlg::None {
+        return Err(Error::UnsupportedParameters);
+    }
+    let header = suite.header();
+    let params = Some(params(&header, key));
+    audit::operation(
+        Operation::Encrypt,
+        None,
+        params,
+        |_| data.len() as u64,
+        || {
+            let cipher = BlockAlgorithm::from_header(&header).instantiate(key)?;
+            // a suite registered under several IDs is written with its first
+            let compact = header
+                .to_compact_bytes()
+                .ok_or(Error::UnsupportedParameters)?;
+            Ok(seal(&*cipher, rng, compact, header.mode, data))
+        },
+    )
+}
+
+///
+/// Encrypts and authenticates `data` with RC5 over `W` words, storing
+/// `metadata` as name-value pairs readable before decryption.
+///
+/// `enc_key` and `mac_key` must be independent, as for
+/// [`DeterministicAead::new`]. Fails with [`Error::InvalidLength`] if there
+/// are more than 255 entries or an entry's name and value exceed 254 bytes,
+/// and with [`Error::WeakParameters`] below the default [`ParameterFloor`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_authenticated, encrypt_authenticated, read_metadata};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let (enc_key, mac_key) = (b"file encrypt key", b"file mac key 001");
+/// let metadata: [(&str, &[u8]); 2] = [("name", b"report.pdf"), ("type", b"application/pdf")];
+/// let bytes = encrypt_authenticated::<u64, _>(&mut rng, enc_key, mac_key, 20, &metadata, b"%PDF")
+///     .unwrap();
+///
+/// let listed = read_metadata(&bytes).unwrap();
+/// assert_eq!(listed[0], ("name".to_string(), b"report.pdf".to_vec()));
+/// assert_eq!(decrypt_authenticated(enc_key, mac_key, &bytes).unwrap(), b"%PDF");
+/// ```
+///
+pub fn encrypt_authenticated<W: Word, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    enc_key: &[u8],
+    mac_key: &[u8],
+    rounds: u8,
+    metadata: &[(&str, &[u8])],
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut header = Header::new::<W>(rounds, Mode::Ctr, MacAlg::Cmac);
+    if metadata.len() > u8::MAX as usize {
+        return Err(Error::InvalidLength);
+    }
+    for (name, value) in metadata {
+        if name.len() + value.len() > u8::MAX as usize - 1 {
+            return Err(Error::InvalidLength);
+        }
+        let mut entry = vec![name.len() as u8];
+        entry.extend_from_slice(name.as_bytes());
+        entry.extend_from_slice(value);
+        header.extensions.push(Extension {
+            kind: EXT_METADATA,
+            value: entry,
+        });
+    }
+    let params = params(&header, enc_key);
+    audit::operation(
+        Operation::Encrypt,
+        None,
+        Some(params),
+        |_| data.len() as u64,
+        || {
+            ParameterFloor::default().check(&params)?;
+            let mut out = header.to_bytes();
+            let header_len = out.len();
+            let mut nonce = vec![0u8; 2 * W::BYTES];
+            rng.fill_bytes(&mut nonce);
+            out.extend_from_slice(&nonce);
+
+            let siv = DeterministicAead::<W>::new(mac_key, enc_key, rounds as usize)?;
+            let sealed = siv.seal(&[&out[..header_len], &nonce], data);
+            out.extend_from_slice(&sealed);
+            Ok(out)
+        },
+    )
+}
+
+///
+/// Writes authenticated envelopes like [`encrypt_authenticated`] under one
+/// pair of keys, as long as their [`KeyUsage`] allows.
+///
+/// Every message is recorded before it is encrypted. Once the policy is
+/// exceeded, encryption fails with [`Error::KeyExhausted`] until
+/// [`rekey`](Self::rekey) installs new keys.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_authenticated, EnvelopeEncryptor};
+/// use rc5_cipher::usage::{KeyUsage, UsagePolicy};
+/// use rc5_cipher::Error;
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let policy = UsagePolicy {
+///     max_messages: Some(1),
+///     ..UsagePolicy::default()
+/// };
+/// let usage = KeyUsage::new(policy);
+/// let mut sealer = EnvelopeEncryptor::<u64, _>::new(b"enc key 00000001", b"mac key 00000001", 20, usage);
+///
+/// let bytes = sealer.encrypt(&mut rng, &[], b"first").unwrap();
+/// assert_eq!(sealer.encrypt(&mut rng, &[], b"second"), Err(Error::KeyExhausted));
+///
+/// sealer.rekey(b"enc key 00000002", b"mac key 00000002");
+/// let next = sealer.encrypt(&mut rng, &[], b"second").unwrap();
+/// assert_eq!(decrypt_authenticated(b"enc key 00000002", b"mac key 00000002", &next).unwrap(), b"second");
+/// ```
+///
+pub struct EnvelopeEncryptor<W: Word, C: Clock = MonotonicClock> {
+    enc_key: Zeroizing<Vec<u8>>,
+    mac_key: Zeroizing<Vec<u8>>,
+    rounds: u8,
+    usage: KeyUsage<C>,
+    _word: PhantomData<W>,
+}
+
+impl<W: Word, C: Clock> EnvelopeEncryptor<W, C> {
+    ///
+    /// Keys the encryptor; `usage` is that of the keys so far, usually a
+    /// fresh [`KeyUsage::new`].
+    ///
+    pub fn new(enc_key: &[u8], mac_key: &[u8], rounds: u8, usage: KeyUsage<C>) -> Self {
+        EnvelopeEncryptor {
+            enc_key: Zeroizing::new(enc_key.to_vec()),
+            mac_key: Zeroizing::new(mac_key.to_vec()),
+            rounds,
+            usage,
+            _word: PhantomData,
+        }
+    }
+
+    ///
+    /// Encrypts `data` with [`encrypt_authenticated`], failing with
+    /// [`Error::KeyExhausted`] if the keys may not take another message of
+    /// this length.
+    ///
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        metadata: &[(&str, &[u8])],
+        data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        self.usage.record_message(data.len() as u64)?;
+        encrypt_authenticated::<W, R>(
+            rng,
+            &self.enc_key,
+            &self.mac_key,
+            self.rounds,
+            metadata,
+            data,
+        )
+    }
+
+    ///
+    /// Replaces the keys and starts their usage over.
+    ///
+    pub fn rekey(&mut self, enc_key: &[u8], mac_key: &[u8]) {
+        // assigning drops, and so wipes, the old keys
+        self.enc_key = Zeroizing::new(enc_key.to_vec());
+        self.mac_key = Zeroizing::new(mac_key.to_vec());
+        self.usage.rekey();
+    }
+
+    pub fn usage(&self) -> &KeyUsage<C> {
+        &self.usage
+    }
+}
+
+///
+/// Decrypts the output of [`encrypt_authenticated`].
+///
+/// Fails with [`Error::AuthenticationFailed`], and only with it, whether
+/// `bytes` is truncated, its header is malformed or not that of an
+/// authenticated envelope, or the data or header was altered, so that a
+/// remote sender can't tell the causes apart by the error. The cause is still
+/// reported to the [audit hook](crate::audit).
+///
+pub fn decrypt_authenticated(
+    enc_key: &[u8],
+    mac_key: &[u8],
+    bytes: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut buf = bytes.to_vec();
+    let len = decrypt_authenticated_in_place(enc_key, mac_key, &mut buf)?.len();
+    buf.drain(..bytes.len() - len);
+    Ok(buf)
+}
+
+///
+/// Like [`decrypt_authenticated`], but decrypts `buf` in place and returns the
+/// plaintext part of it.
+///
+/// Apart from the header and the key schedules, nothing is allocated, which
+/// suits the common case of small secrets such as a config value. Large data
+/// is decrypted and authenticated in one pass, chunk by chunk. On failure
+/// the contents of `buf` are unspecified; the plaintext part is zeroed.
+///
+/// A rejected header goes through the same key expansion and MAC as a
+/// rejected tag, for the word size and rounds the header names, so the causes
+/// also take comparable time.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::envelope::{decrypt_authenticated_in_place, encrypt_authenticated};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let (enc_key, mac_key) = (b"config enc key 1", b"config mac key 1");
+/// let mut sealed =
+///     encrypt_authenticated::<u64, _>(&mut rng, enc_key, mac_key, 20, &[], b"db-password").unwrap();
+///
+/// let secret = decrypt_authenticated_in_place(enc_key, mac_key, &mut sealed).unwrap();
+/// assert_eq!(secret, b"db-password");
+/// ```
+///
+pub fn decrypt_authenticated_in_place<'b>(
+    enc_key: &[u8],
+    mac_key: &[u8],
+    buf: &'b mut [u8],
+) -> Result<&'b mut [u8], Error> {
+    let audited = Header::parse(buf)
+        .ok()
+        .map(|(header, _)| params(&header, enc_key));
+    audit::operation(
+        Operation::Decrypt,
+        None,
+        audited,
+        |out: &&mut [u8]| out.len() as u64,
+        move || {
+            let parsed = Header::parse(buf);
+            let (word_bytes, rounds) = match &parsed {
+                Ok((header, _)) => (header.word_bytes, header.rounds as usize),
+                Err(_) => (DECOY_WORD_BYTES, DECOY_ROUNDS),
+            };
+            let checked = parsed.and_then(|(header, len)| {
+                if header.mac != MacAlg::Cmac || header.mode != Mode::Ctr {
+                    return Err(Error::UnsupportedParameters);
+                }
+                ParameterFloor::default().check(&params(&header, enc_key))?;
+                if buf.len() < len + 4 * header.word_bytes as usize {
+                    return Err(Error::InvalidLength);
+                }
+                Ok((header, len))
+            });
+            let (header, len) = match checked {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    // Do the work of a failing tag check, then report the cause.
+                    let decoy = match word_bytes {
+                        1 => decoy_siv::<u8>,
+                        2 => decoy_siv::<u16>,
+                        4 => decoy_siv::<u32>,
+                        16 => decoy_siv::<u128>,
+                        _ => decoy_siv::<u64>,
+                    };
+                    decoy(enc_key, mac_key, rounds, buf);
+                    return Err(err);
+                }
+            };
+            let open = match header.word_bytes {
+                1 => open_siv::<u8>,
+                2 => open_siv::<u16>,
+                4 => open_siv::<u32>,
+                8 => open_siv::<u64>,
+                16 => open_siv::<u128>,
+                _ => {
+                    decoy_siv::<u64>(enc_key, mac_key, rounds, buf);
+                    return Err(Error::UnsupportedParameters);
+                }
+            };
+            open(enc_key, mac_key, header.rounds as usize, buf, len)
+        },
+    )
+    .map_err(|_| Error::AuthenticationFailed)
+}
+
+fn open_siv<'b, W: Word>(
+    enc_key: &[u8],
+    mac_key: &[u8],
+    rounds: usize,
+    buf: &'b mut [u8],
+    header_len: usize,
+) -> Result<&'b mut [u8], Error> {
+    let (header, rest) = buf.split_at_mut(header_len);
+    let (nonce, sealed) = rest.split_at_mut(2 * W::BYTES);
+    DeterministicAead::<W>::new(mac_key, enc_key, rounds)?.open_in_place(&[header, nonce], sealed)
+}
+
+fn decoy_siv<W: Word>(enc_key: &[u8], mac_key: &[u8], rounds: usize, buf: &mut [u8]) {
+    if let Ok(decoy) = DeterministicAead::<W>::new(mac_key, enc_key, rounds) {
+        let _ = decoy.open_in_place(&[], buf);
+    }
+}
+
+///
+/// The metadata entries in the header of `bytes`, in order.
+///
+/// Nothing is verified: the entries are only known to be genuine once
+/// [`decrypt_authenticated`] succeeds. Fails with [`Error::InvalidHeader`] for
+/// a malformed entry.
+///
+pub fn read_metadata(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let (header, _) = Header::parse(bytes)?;
+    header
+        .extensions
+        .iter()
+        .filter(|ext| ext.kind == EXT_METADATA)
+        .map(|ext| {
+            let (&len, rest) = ext.value.split_first().ok_or(Error::InvalidHeader)?;
+            if rest.len() < len as usize {
+                return Err(Error::InvalidHeader);
+            }
+            let (name, value) = rest.split_at(len as usize);
+            let name = String::from_utf8(name.to_vec()).map_err(|_| Error::InvalidHeader)?;
+            Ok((name, value.to_vec()))
+        })
+        .collect()
+}
+
+fn encrypt_full<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key: &[u8],
+    key_id: Option<&str>,
+    header: &Header,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let params = Some(params(header, key));
+    audit::operation(
+        Operation::Encrypt,
+        key_id,
+        params,
+        |_| data.len() as u64,
+        || {
+            let cipher = BlockAlgorithm::from_header(header).instantiate(key)?;
+            Ok(seal(&*cipher, rng, header.to_bytes(), header.mode, data))
+        },
+    )
+}
+
+fn seal<R: RngCore + CryptoRng>(
+    cipher: &dyn BlockCipherAlgo,
+    rng: &mut R,
+    header: Vec<u8>,
+    mode: Mode,
+    data: &[u8],
+) -> Vec<u8> {
+    let bs = cipher.block_size();
+
+    let mut out = header;
+    let mut iv = vec![0u8; bs];
+    if mode != Mode::Ecb {
+        rng.fill_bytes(&mut iv);
+        out.extend_from_slice(&iv);
+    }
+
+    let mut body = data.to_vec();
+    if matches!(mode, Mode::Ecb | Mode::Cbc) {
+        Pkcs7::pad(&mut body, bs);
+    }
+    apply(cipher, mode, &iv, &mut body, true);
+    out.extend_from_slice(&body);
+    out
+}
+
+///
+/// Decrypts the output of [`encrypt_with_header`], picking the word size,
+/// rounds and mode from its header.
+///
+/// Fails with a [`Failure::Crypto`] that doesn't say why for any fault of
+/// `bytes`: a malformed or truncated header or body, bad padding, a header
+/// announcing a MAC, since those formats carry a tag this function can't
+/// check, or parameters below the default [`ParameterFloor`], whether they
+/// are spelled out or named by an algorithm ID.
+///
+pub fn decrypt_auto(key: &[u8], bytes: &[u8]) -> Result<Vec<u8>, Failure> {
+    decrypt_audited(key, None, bytes).map_err(Failure::decrypting)
+}
+
+fn decrypt_audited(key: &[u8], key_id: Option<&str>, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let params = Header::parse(bytes)
+        .ok()
+        .map(|(header, _)| params(&header, key));
+    audit::operation(
+        Operation::Decrypt,
+        key_id,
+        params,
+        |out: &Vec<u8>| out.len() as u64,
+        || decrypt_body(key, bytes),
+    )
+}
+
+fn decrypt_body(key: &[u8], bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let (header, len) = Header::parse(bytes)?;
+    if header.mac != MacAlg::None {
+        return Err(Error::UnsupportedParameters);
+    }
+    let cipher = BlockAlgorithm::from_header(&header).instantiate(key)?;
+    let bs = cipher.block_size();
+
+    let rest = &bytes[len..];
+    let (iv, body) = match header.mode {
+        Mode::Ecb => (&[][..], rest),
+        _ if rest.len() < bs => return Err(Error::InvalidLength),
+        _ => rest.split_at(bs),
+    };
+    let padded = matches!(header.mode, Mode::Ecb | Mode::Cbc);
+    if padded && (body.is_empty() || body.len() % bs != 0) {
+        return Err(Error::InvalidLength);
+    }
+
+    let mut out = body.to_vec();
+    apply(&*cipher, header.mode, iv, &mut out, false);
+    if padded {
+        let keep = out.len() - bs + Pkcs7::unpad(&out[out.len() - bs..])?;
+        out.truncate(keep);
+    }
+    Ok(out)
+}
+
+// Runs `mode` over `buf` in place. ECB and CBC expect whole blocks.
+fn apply(cipher: &dyn BlockCipherAlgo, mode: Mode, iv: &[u8], buf: &mut [u8], encrypt: bool) {
+    let bs = cipher.block_size();
+    debug_assert!(mode == Mode::Ecb || iv.len() == bs, "IV size mismatch");
+    debug_assert!(
+        !matches!(mode, Mode::Ecb | Mode::Cbc) || buf.len().is_multiple_of(bs),
+        "partial block in a padded mode"
+    );
+    let mut prev = iv.to_vec();
+    let mut ks = vec![0u8; bs];
+    for (i, block) in buf.chunks_mut(bs).enumerate() {
+        match mode {
+            Mode::Ecb if encrypt => cipher.encrypt_block(block),
+            Mode::Ecb => cipher.decrypt_block(block),
+            Mode::Cbc if encrypt => {
+                xor(block, &prev);
+                cipher.encrypt_block(block);
+                prev.copy_from_slice(block);
+            }
+            Mode::Cbc => {
+                let ct = block.to_vec();
+                cipher.decrypt_block(block);
+                xor(block, &prev);
+                prev = ct;
+            }
+            Mode::Cfb => {
+                ks.copy_from_slice(&prev);
+                cipher.encrypt_block(&mut ks);
+                if encrypt {
+                    xor(block, &ks);
+                    prev[..block.len()].copy_from_slice(block);
+                } else {
+                    prev[..block.len()].copy_from_slice(block);
+                    xor(block, &ks);
+                }
+            }
+            Mode::Ctr => {
+                add_le(iv, i as u128, &mut ks);
+                cipher.encrypt_block(&mut ks);
+                xor(block, &ks);
+            }
+        }
+    }
+}
+
+fn params(header: &Header, key: &[u8]) -> Rc5Params {
+    Rc5Params {
+        word_bytes: header.word_bytes as usize,
+        rounds: header.rounds as usize,
+        key_bytes: key.len(),
+    }
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+// Writes `iv + index` into `out`, both read as little-endian integers.
+fn add_le(iv: &[u8], index: u128, out: &mut [u8]) {
+    let mut carry = index;
+    for (o, &b) in out.iter_mut().zip(iv) {
+        let sum = b as u128 + (carry & 0xff);
+        *o = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::Event;
+    use crate::ctr::apply_keystream_at;
+    use crate::error::CryptoError;
+    use crate::rc5::expand_key;
+    use crate::rng::CounterRng;
+
+    const KEY: &[u8] = b"envelope key 128";
+
+    #[test]
+    fn roundtrips_every_mode_and_word_size() {
+        let mut rng = CounterRng(0);
+        let data: Vec<u8> = (0..45).collect();
+        for mode in [Mode::Ecb, Mode::Cbc, Mode::Cfb, Mode::Ctr] {
+            for len in [0, 1, 16, 45] {
+                let data = &data[..len];
+                let bytes = [
+                    encrypt_with_header::<u32, _>(&mut rng, KEY, 20, mode, data).unwrap(),
+                    encrypt_with_header::<u64, _>(&mut rng, KEY, 24, mode, data).unwrap(),
+                    encrypt_with_header::<u128, _>(&mut rng, KEY, 28, mode, data).unwrap(),
+                ];
+                for bytes in bytes {
+                    assert_eq!(decrypt_auto(KEY, &bytes).unwrap(), data, "{:?}", mode);
+                }
+            }
+            // blocks of 16 and 32 bits are below the floor
+            assert_eq!(
+                encrypt_with_header::<u8, _>(&mut rng, KEY, 12, mode, b"x"),
+                Err(Error::WeakParameters)
+            );
+            assert_eq!(
+                encrypt_with_header::<u16, _>(&mut rng, KEY, 16, mode, b"x"),
+                Err(Error::WeakParameters)
+            );
+        }
+    }
+
+    #[test]
+    fn compact_header_by_algorithm_id() {
+        let mut rng = CounterRng(0);
+        for id in 0x40..=0x44 {
+            let bytes = encrypt_with_id(&mut rng, KEY, id, b"compact").unwrap();
+            assert_eq!(bytes[3..5], [0, id]);
+            assert_eq!(decrypt_auto(KEY, &bytes).unwrap(), b"compact");
+        }
+
+        // a suite with a MAC, and IDs with no suite
+        for id in [0x01, 0x7f, 0xff] {
+            assert_eq!(
+                encrypt_with_id(&mut rng, KEY, id, b"x"),
+                Err(Error::UnsupportedParameters)
+            );
+        }
+    }
+
+    #[cfg(feature = "key")]
+    #[test]
+    fn keyring_picks_the_key_by_id() {
+        use crate::key::{Keyring, Rc5Key};
+
+        let mut rng = CounterRng(0);
+        let mut keyring = Keyring::new();
+        assert_eq!(
+            encrypt_with_keyring::<u32, _>(&mut rng, &keyring, 12, Mode::Ctr, b"x"),
+            Err(Error::UnknownKey)
+        );
+
+        let mut sealed = Vec::new();
+        for key in [KEY, b"second key 12345"] {
+            let id = keyring.insert(None, Rc5Key::new(key).unwrap());
+            keyring.set_primary(&id).unwrap();
+            let bytes =
+                encrypt_with_keyring::<u32, _>(&mut rng, &keyring, 12, Mode::Ctr, b"rollover")
+                    .unwrap();
+            assert_eq!(decrypt_auto(key, &bytes).unwrap(), b"rollover");
+            sealed.push((id, bytes));
+        }
+        for (_, bytes) in &sealed {
+            assert_eq!(decrypt_with_keyring(&keyring, bytes).unwrap(), b"rollover");
+        }
+
+        // the audit trail names the key, on whatever thread did the work
+        let (id, bytes) = sealed[1].clone();
+        let mut keyring = std::thread::spawn(move || {
+            let records = crate::audit::tests::capture(|| {
+                decrypt_with_keyring(&keyring, &bytes).unwrap();
+            });
+            let hex = id.to_string();
+            assert!(records
+                .iter()
+                .any(|r| r.key_id.as_deref() == Some(hex.as_str())));
+            keyring
+        })
+        .join()
+        .unwrap();
+
+        // a removed key, no key ID and a malformed header look the same
+        keyring.remove(&sealed[0].0);
+        assert_eq!(
+            decrypt_with_keyring(&keyring, &sealed[0].1),
+            Err(Error::AuthenticationFailed)
+        );
+        let plain = encrypt_with_header::<u32, _>(&mut rng, KEY, 12, Mode::Ctr, b"x").unwrap();
+        assert_eq!(
+            decrypt_with_keyring(&keyring, &plain),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            decrypt_with_keyring(&keyring, &sealed[1].1[..3]),
+            Err(Error::AuthenticationFailed)
+        );
+    }
+
+    #[test]
+    fn authenticated_metadata() {
+        let mut rng = CounterRng(0);
+        let mac_key = b"envelope mac key";
+        let metadata: [(&str, &[u8]); 3] = [
+            ("name", b"notes.txt"),
+            ("mtime", &1_790_000_000u64.to_le_bytes()),
+            ("", b""),
+        ];
+        let bytes = [
+            encrypt_authenticated::<u32, _>(&mut rng, KEY, mac_key, 20, &metadata, b"abc"),
+            encrypt_authenticated::<u64, _>(&mut rng, KEY, mac_key, 24, &metadata, b"abc"),
+            encrypt_authenticated::<u128, _>(&mut rng, KEY, mac_key, 28, &metadata, b"abc"),
+        ];
+        for bytes in bytes {
+            let bytes = bytes.unwrap();
+            let listed = read_metadata(&bytes).unwrap();
+            let listed: Vec<(&str, &[u8])> = listed.iter().map(|(n, v)| (&n[..], &v[..])).collect();
+            assert_eq!(listed, metadata);
+            assert_eq!(decrypt_authenticated(KEY, mac_key, &bytes).unwrap(), b"abc");
+            assert_eq!(decrypt_body(KEY, &bytes), Err(Error::UnsupportedParameters));
+        }
+
+        // a renamed file, a wrong key and altered data are all rejected
+        let bytes =
+            encrypt_authenticated::<u32, _>(&mut rng, KEY, mac_key, 12, &metadata, b"abc").unwrap();
+        let pos = bytes.windows(5).position(|w| w == b"notes").unwrap();
+        for i in [pos, 20, bytes.len() - 1] {
+            let mut bad = bytes.clone();
+            bad[i] ^= 1;
+            assert_eq!(
+                decrypt_authenticated(KEY, mac_key, &bad),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        assert_eq!(
+            decrypt_authenticated(KEY, b"other mac key 12", &bytes),
+            Err(Error::AuthenticationFailed)
+        );
+
+        let mut buf = bytes.clone();
+        let plaintext = decrypt_authenticated_in_place(KEY, mac_key, &mut buf).unwrap();
+        assert_eq!(plaintext, b"abc");
+        let mut bad = bytes.clone();
+        bad[bytes.len() - 1] ^= 1;
+        assert_eq!(
+            decrypt_authenticated_in_place(KEY, mac_key, &mut bad),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(bad[bytes.len() - 3..], [0, 0, 0]);
+    }
+
+    #[test]
+    fn encryptor_enforces_the_usage_policy() {
+        use crate::clock::ManualClock;
+        use crate::usage::UsagePolicy;
+        use std::time::Duration;
+
+        let mut rng = CounterRng(0);
+        let clock = ManualClock::default();
+        let policy = UsagePolicy {
+            max_bytes: Some(10),
+            max_age: Some(Duration::from_secs(60)),
+            ..UsagePolicy::default()
+        };
+        let usage = KeyUsage::with_clock(policy, clock.clone());
+        let mut sealer = EnvelopeEncryptor::<u32, _>::new(KEY, b"envelope mac key", 12, usage);
+
+        let bytes = sealer.encrypt(&mut rng, &[], b"0123456").unwrap();
+        assert_eq!(
+            decrypt_authenticated(KEY, b"envelope mac key", &bytes).unwrap(),
+            b"0123456"
+        );
+        assert_eq!(
+            sealer.encrypt(&mut rng, &[], b"4567"),
+            Err(Error::KeyExhausted)
+        );
+        assert_eq!(sealer.usage().bytes(), 7);
+        sealer.encrypt(&mut rng, &[], b"789").unwrap();
+
+        sealer.rekey(b"second key 12345", b"second mac 12345");
+        sealer.encrypt(&mut rng, &[], b"0123456789").unwrap();
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sealer.encrypt(&mut rng, &[], b""), Err(Error::KeyExhausted));
+    }
+
+    #[test]
+    fn authenticated_rejects_bad_input() {
+        let mut rng = CounterRng(0);
+        let long = [0u8; 251];
+        assert_eq!(
+            encrypt_authenticated::<u32, _>(&mut rng, KEY, KEY, 12, &[("name", &long)], b""),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            encrypt_authenticated::<u32, _>(&mut rng, KEY, KEY, 8, &[], b""),
+            Err(Error::WeakParameters)
+        );
+
+        let plain = encrypt_with_header::<u32, _>(&mut rng, KEY, 12, Mode::Ctr, b"x").unwrap();
+        assert_eq!(read_metadata(&plain).unwrap(), []);
+
+        // every cause gives the same error, and the audit trail the real one
+        let bytes = encrypt_authenticated::<u32, _>(&mut rng, KEY, KEY, 12, &[], b"").unwrap();
+        let inputs: [&[u8]; 3] = [&plain, &bytes[..bytes.len() - 1], b"junk"];
+        let records = crate::audit::tests::capture(|| {
+            for input in inputs {
+                assert_eq!(
+                    decrypt_authenticated(KEY, KEY, input),
+                    Err(Error::AuthenticationFailed)
+                );
+            }
+        });
+        let causes: Vec<_> = records
+            .iter()
+            .filter_map(|r| match r.event {
+                Event::Finished(_, Err(err)) => Some(err),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            causes,
+            [
+                Error::UnsupportedParameters,
+                Error::InvalidLength,
+                Error::InvalidHeader
+            ]
+        );
+    }
+
+    #[test]
+    fn operations_are_audited() {
+        let records = crate::audit::tests::capture(|| {
+            let bytes =
+                encrypt_with_header::<u32, _>(&mut CounterRng(0), KEY, 16, Mode::Cbc, b"abc")
+                    .unwrap();
+            decrypt_auto(KEY, &bytes).unwrap();
+            decrypt_auto(KEY, &bytes[..5]).unwrap_err();
+        });
+
+        let events: Vec<_> = records.iter().map(|r| (r.event, r.bytes)).collect();
+        assert_eq!(
+            events,
+            [
+                (Event::Started(Operation::Encrypt), 0),
+                (Event::KeyLoaded, 0),
+                (Event::Finished(Operation::Encrypt, Ok(())), 3),
+                (Event::Started(Operation::Decrypt), 0),
+                (Event::KeyLoaded, 0),
+                (Event::Finished(Operation::Decrypt, Ok(())), 3),
+                (Event::Started(Operation::Decrypt), 0),
+                (
+                    Event::Finished(Operation::Decrypt, Err(Error::InvalidHeader)),
+                    0
+                ),
+            ]
+        );
+        let params = Rc5Params {
+            word_bytes: 4,
+            rounds: 16,
+            key_bytes: KEY.len(),
+        };
+        assert_eq!(records[0].params, Some(params));
+        assert_eq!(records[6].params, None);
+    }
+
+    #[test]
+    fn ctr_matches_ctr_module() {
+        let mut rng = CounterRng(0);
+        let data = b"counter mode must agree with ctr.rs";
+        let bytes = encrypt_with_header::<u32, _>(&mut rng, KEY, 12, Mode::Ctr, data).unwrap();
+
+        let (_, len) = Header::parse(&bytes).unwrap();
+        let (iv, body) = bytes[len..].split_at(8);
+        let mut expected = data.to_vec();
+        apply_keystream_at(&expand_key::<u32>(KEY, 12), iv, 0, &mut expected).unwrap();
+        assert_eq!(body, expected);
+    }
+
+    #[test]
+    fn rejects_wrong_key_and_bad_input() {
+        let mut rng = CounterRng(0);
+        let bytes = encrypt_with_header::<u32, _>(&mut rng, KEY, 12, Mode::Cbc, b"data").unwrap();
+
+        assert!(decrypt_auto(b"another key 1234", &bytes).is_err());
+        assert_eq!(decrypt_body(b"short", &bytes), Err(Error::WeakParameters));
+        assert_eq!(
+            decrypt_body(KEY, &bytes[..bytes.len() - 1]),
+            Err(Error::InvalidLength)
+        );
+
+        let mut macced = bytes.clone();
+        macced[6] = MacAlg::Cmac as u8;
+        assert_eq!(
+            decrypt_body(KEY, &macced),
+            Err(Error::UnsupportedParameters)
+        );
+
+        // callers only learn that the data is at fault
+        for bad in [&bytes[..bytes.len() - 1], &macced[..]] {
+            assert_eq!(decrypt_auto(KEY, bad), Err(Failure::Crypto(CryptoError)));
+        }
+    }
+}
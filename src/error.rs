@@ -0,0 +1,447 @@
+use std::fmt;
+
+///
+/// Errors returned by the fallible APIs of this crate, except those that open
+/// received data, which return a [`Failure`].
+///
+/// New variants may be added in minor releases.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input is not valid hex or base64url text.
+    InvalidEncoding,
+    /// A wire header is truncated or contains an invalid field.
+    InvalidHeader,
+    /// A wire header was written by a newer, unsupported format version.
+    UnsupportedVersion(u8),
+    /// A wire header carries a critical extension this version can't handle.
+    UnknownCriticalField(u8),
+    /// A buffer, IV or key has a length the operation doesn't accept.
+    InvalidLength,
+    /// The padding of the decrypted data is malformed.
+    InvalidPadding,
+    /// The authentication tag doesn't match the data.
+    AuthenticationFailed,
+    /// Reading or writing the underlying stream failed.
+    Io(std::io::ErrorKind),
+    /// The operation was stopped through a cancellation token.
+    Cancelled,
+    /// The key has processed too much data and must be replaced.
+    KeyExhausted,
+    /// No parameters meet the requested requirements.
+    UnsupportedParameters,
+    /// The parameters are below the configured security floor.
+    WeakParameters,
+    /// Secret shares are missing, duplicated or of different lengths.
+    InvalidShares,
+    /// Cached key material outlived its lifetime and was wiped.
+    KeyExpired,
+    /// No key with the requested ID or label is available.
+    UnknownKey,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidEncoding => write!(f, "invalid hex or base64url encoding"),
+            Error::InvalidHeader => write!(f, "malformed header"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported header version {}", v),
+            Error::UnknownCriticalField(k) => write!(f, "unknown critical header field {:#04x}", k),
+            Error::InvalidLength => write!(f, "invalid length"),
+            Error::InvalidPadding => write!(f, "invalid padding"),
+            Error::AuthenticationFailed => write!(f, "authentication failed"),
+            Error::Io(kind) => write!(f, "I/O error: {}", kind),
+            Error::Cancelled => write!(f, "operation cancelled"),
+            Error::KeyExhausted => write!(f, "key usage limit reached"),
+            Error::UnsupportedParameters => write!(f, "unsupported cipher parameters"),
+            Error::WeakParameters => write!(f, "cipher parameters below the security floor"),
+            Error::InvalidShares => write!(f, "invalid set of key shares"),
+            Error::KeyExpired => write!(f, "key material expired"),
+            Error::UnknownKey => write!(f, "no matching key"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.kind())
+    }
+}
+
+impl Error {
+    ///
+    /// The [`ErrorCode`] of this error.
+    ///
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::InvalidEncoding => ErrorCode::InvalidEncoding,
+            Error::InvalidHeader => ErrorCode::InvalidHeader,
+            Error::UnsupportedVersion(_) => ErrorCode::UnsupportedVersion,
+            Error::UnknownCriticalField(_) => ErrorCode::UnknownCriticalField,
+            Error::InvalidLength => ErrorCode::InvalidLength,
+            Error::InvalidPadding => ErrorCode::InvalidPadding,
+            Error::AuthenticationFailed => ErrorCode::AuthenticationFailed,
+            Error::Io(_) => ErrorCode::Io,
+            Error::Cancelled => ErrorCode::Cancelled,
+            Error::KeyExhausted => ErrorCode::KeyExhausted,
+            Error::UnsupportedParameters => ErrorCode::UnsupportedParameters,
+            Error::WeakParameters => ErrorCode::WeakParameters,
+            Error::InvalidShares => ErrorCode::InvalidShares,
+            Error::KeyExpired => ErrorCode::KeyExpired,
+            Error::UnknownKey => ErrorCode::UnknownKey,
+        }
+    }
+}
+
+///
+/// Why [`Header::parse_with_limits`](crate::header::Header::parse_with_limits)
+/// rejected a header, in more detail than the [`Error`] it converts to.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The input ends before the field at this offset.
+    Truncated(usize),
+    /// The input doesn't start with the header magic.
+    BadMagic,
+    /// The header was written by a newer, unsupported format version.
+    UnsupportedVersion(u8),
+    /// The word size byte isn't 1, 2, 4, 8 or 16.
+    InvalidWordSize(u8),
+    /// The mode byte names no [`Mode`](crate::header::Mode).
+    InvalidMode(u8),
+    /// The MAC byte names no [`MacAlg`](crate::header::MacAlg).
+    InvalidMac(u8),
+    /// A compact header names an algorithm ID that isn't registered.
+    UnknownSuite(u8),
+    /// A critical extension this version can't handle.
+    UnknownCriticalField(u8),
+    /// An extension that may appear only once appears again.
+    DuplicateField(u8),
+    /// The header declares more extensions than the limit.
+    TooManyFields(u8),
+    /// The header is longer than the limit, in bytes.
+    TooLong(usize),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::Truncated(at) => write!(f, "header truncated at byte {}", at),
+            HeaderError::BadMagic => write!(f, "not a header"),
+            HeaderError::UnsupportedVersion(v) => write!(f, "unsupported header version {}", v),
+            HeaderError::InvalidWordSize(b) => write!(f, "invalid word size {}", b),
+            HeaderError::InvalidMode(b) => write!(f, "invalid mode {}", b),
+            HeaderError::InvalidMac(b) => write!(f, "invalid MAC algorithm {}", b),
+            HeaderError::UnknownSuite(id) => write!(f, "unknown algorithm ID {:#04x}", id),
+            HeaderError::UnknownCriticalField(k) => {
+                write!(f, "unknown critical header field {:#04x}", k)
+            }
+            HeaderError::DuplicateField(k) => write!(f, "duplicate header field {:#04x}", k),
+            HeaderError::TooManyFields(n) => write!(f, "too many header fields ({})", n),
+            HeaderError::TooLong(max) => write!(f, "header longer than {} bytes", max),
+        }
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+impl From<HeaderError> for Error {
+    fn from(err: HeaderError) -> Self {
+        match err {
+            HeaderError::UnsupportedVersion(v) => Error::UnsupportedVersion(v),
+            HeaderError::UnknownCriticalField(k) => Error::UnknownCriticalField(k),
+            HeaderError::UnknownSuite(_) => Error::UnsupportedParameters,
+            _ => Error::InvalidHeader,
+        }
+    }
+}
+
+///
+/// One byte per [`Error`] variant, for passing failures over FFI, in log
+/// records or in a status register without formatting anything.
+///
+/// Codes start at 1, so that 0 can mean success, and never change meaning.
+/// The payloads of [`Error::UnsupportedVersion`],
+/// [`Error::UnknownCriticalField`] and [`Error::Io`] are dropped; converting
+/// back fills in 0 and [`std::io::ErrorKind::Other`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::error::{Error, ErrorCode};
+///
+/// let code = Error::InvalidPadding.code() as u8;
+/// assert_eq!(code, 6);
+/// assert_eq!(ErrorCode::try_from(code).map(Error::from), Ok(Error::InvalidPadding));
+/// assert!(ErrorCode::try_from(0).is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ErrorCode {
+    InvalidEncoding = 1,
+    InvalidHeader = 2,
+    UnsupportedVersion = 3,
+    UnknownCriticalField = 4,
+    InvalidLength = 5,
+    InvalidPadding = 6,
+    AuthenticationFailed = 7,
+    Io = 8,
+    Cancelled = 9,
+    KeyExhausted = 10,
+    UnsupportedParameters = 11,
+    WeakParameters = 12,
+    InvalidShares = 13,
+    KeyExpired = 14,
+    UnknownKey = 15,
+}
+
+impl From<Error> for ErrorCode {
+    fn from(err: Error) -> Self {
+        err.code()
+    }
+}
+
+impl From<ErrorCode> for Error {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::InvalidEncoding => Error::InvalidEncoding,
+            ErrorCode::InvalidHeader => Error::InvalidHeader,
+            ErrorCode::UnsupportedVersion => Error::UnsupportedVersion(0),
+            ErrorCode::UnknownCriticalField => Error::UnknownCriticalField(0),
+            ErrorCode::InvalidLength => Error::InvalidLength,
+            ErrorCode::InvalidPadding => Error::InvalidPadding,
+            ErrorCode::AuthenticationFailed => Error::AuthenticationFailed,
+            ErrorCode::Io => Error::Io(std::io::ErrorKind::Other),
+            ErrorCode::Cancelled => Error::Cancelled,
+            ErrorCode::KeyExhausted => Error::KeyExhausted,
+            ErrorCode::UnsupportedParameters => Error::UnsupportedParameters,
+            ErrorCode::WeakParameters => Error::WeakParameters,
+            ErrorCode::InvalidShares => Error::InvalidShares,
+            ErrorCode::KeyExpired => Error::KeyExpired,
+            ErrorCode::UnknownKey => Error::UnknownKey,
+        }
+    }
+}
+
+impl From<ErrorCode> for u8 {
+    fn from(code: ErrorCode) -> Self {
+        code as u8
+    }
+}
+
+///
+/// Fails with the byte itself if it isn't a code.
+///
+impl TryFrom<u8> for ErrorCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, u8> {
+        const CODES: [ErrorCode; 15] = [
+            ErrorCode::InvalidEncoding,
+            ErrorCode::InvalidHeader,
+            ErrorCode::UnsupportedVersion,
+            ErrorCode::UnknownCriticalField,
+            ErrorCode::InvalidLength,
+            ErrorCode::InvalidPadding,
+            ErrorCode::AuthenticationFailed,
+            ErrorCode::Io,
+            ErrorCode::Cancelled,
+            ErrorCode::KeyExhausted,
+            ErrorCode::UnsupportedParameters,
+            ErrorCode::WeakParameters,
+            ErrorCode::InvalidShares,
+            ErrorCode::KeyExpired,
+            ErrorCode::UnknownKey,
+        ];
+        CODES
+            .get(usize::from(byte).wrapping_sub(1))
+            .copied()
+            .ok_or(byte)
+    }
+}
+
+///
+/// An [`Error`] sorted by what the application should do about it.
+///
+/// [`CryptoError`] covers data that failed to decrypt or verify: a wrong
+/// tag, bad padding or a corrupted header. It carries no detail on purpose.
+/// Telling these apart to whoever sent the data, e.g. with different HTTP
+/// statuses or messages, hands them a padding or format oracle. Everything
+/// else is a [`UsageError`], whose details are safe to log and act on.
+///
+/// Which is which depends on the call site. `decrypt_auto`, `unwrap_key`,
+/// `open_image` and the `open` functions of `migrate` and `kem` already
+/// return a `Failure`; for errors from other APIs that decrypt or verify
+/// received data use [`Failure::decrypting`]: a truncated body, an
+/// unknown suite or low rounds in a header are then also the sender's doing,
+/// and only environment errors such as I/O stay usage errors. `From<Error>`
+/// is for the application's own operations, e.g. encrypting or loading keys,
+/// where wrong lengths or parameters are bugs to report.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::error::{Error, Failure};
+///
+/// fn status(failure: Failure) -> u16 {
+///     match failure {
+///         Failure::Crypto(_) => 400,
+///         Failure::Usage(_) => 500,
+///     }
+/// }
+///
+/// assert_eq!(status(Failure::decrypting(Error::InvalidPadding)), 400);
+/// assert_eq!(status(Failure::decrypting(Error::InvalidLength)), 400);
+/// assert_eq!(status(Failure::decrypting(Error::Io(std::io::ErrorKind::BrokenPipe))), 500);
+/// assert_eq!(status(Failure::from(Error::InvalidLength)), 500);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The caller or the environment is at fault.
+    Usage(UsageError),
+    /// The data is at fault, for reasons kept hidden.
+    Crypto(CryptoError),
+}
+
+///
+/// A failure caused by how an API was called or by its environment.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageError(Error);
+
+impl UsageError {
+    ///
+    /// The underlying error.
+    ///
+    pub fn kind(&self) -> Error {
+        self.0
+    }
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+///
+/// Data that failed to decrypt or authenticate, without saying why.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CryptoError;
+
+impl fmt::Display for CryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "decryption failed")
+    }
+}
+
+impl std::error::Error for CryptoError {}
+
+impl Failure {
+    ///
+    /// Classifies an error returned while decrypting or verifying received
+    /// data. Only errors the data can't cause, I/O, cancellation and key
+    /// lifetime limits, are usage errors; all others are crypto errors.
+    ///
+    pub fn decrypting(err: Error) -> Self {
+        match err {
+            Error::Io(_) | Error::Cancelled | Error::KeyExhausted | Error::KeyExpired => {
+                Failure::Usage(UsageError(err))
+            }
+            Error::InvalidEncoding
+            | Error::InvalidHeader
+            | Error::UnsupportedVersion(_)
+            | Error::UnknownCriticalField(_)
+            | Error::InvalidLength
+            | Error::InvalidPadding
+            | Error::AuthenticationFailed
+            | Error::UnsupportedParameters
+            | Error::WeakParameters
+            | Error::InvalidShares
+            | Error::UnknownKey => Failure::Crypto(CryptoError),
+        }
+    }
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::InvalidHeader
+            | Error::UnsupportedVersion(_)
+            | Error::UnknownCriticalField(_)
+            | Error::InvalidPadding
+            | Error::AuthenticationFailed => Failure::Crypto(CryptoError),
+            Error::InvalidEncoding
+            | Error::InvalidLength
+            | Error::Io(_)
+            | Error::Cancelled
+            | Error::KeyExhausted
+            | Error::UnsupportedParameters
+            | Error::WeakParameters
+            | Error::InvalidShares
+            | Error::KeyExpired
+            | Error::UnknownKey => Failure::Usage(UsageError(err)),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Usage(err) => err.fmt(f),
+            Failure::Crypto(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_roundtrip() {
+        for byte in 1..=15u8 {
+            let code = ErrorCode::try_from(byte).unwrap();
+            assert_eq!(u8::from(code), byte);
+            assert_eq!(Error::from(code).code(), code);
+        }
+        assert_eq!(ErrorCode::try_from(0), Err(0));
+        assert_eq!(ErrorCode::try_from(16), Err(16));
+        assert_eq!(
+            Error::UnsupportedVersion(3).code(),
+            ErrorCode::UnsupportedVersion
+        );
+    }
+
+    #[test]
+    fn decrypting_hides_data_errors() {
+        for byte in 1..=15u8 {
+            let err = Error::from(ErrorCode::try_from(byte).unwrap());
+            // Whatever is crypto for the caller's own calls is crypto here too.
+            if let Failure::Crypto(_) = Failure::from(err) {
+                assert_eq!(Failure::decrypting(err), Failure::Crypto(CryptoError));
+            }
+        }
+        for err in [
+            Error::InvalidLength,
+            Error::UnsupportedParameters,
+            Error::WeakParameters,
+            Error::UnknownKey,
+        ] {
+            assert!(matches!(Failure::from(err), Failure::Usage(_)));
+            assert_eq!(Failure::decrypting(err), Failure::Crypto(CryptoError));
+        }
+        let io = Error::Io(std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(Failure::decrypting(io), Failure::Usage(UsageError(io)));
+    }
+}
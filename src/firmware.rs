@@ -0,0 +1,356 @@
+//!
+//! Encrypted firmware update images.
+//!
+//! ```text
+//! header       Header with Mode::Ctr and MacAlg::HmacSha256
+//! iv           one block
+//! chunk_len    u32 BE
+//! image_len    u64 BE
+//! chunks       ct_0 || tag_0 || ct_1 || tag_1 || ...
+//! digest       SHA-256 of everything above (32 bytes)
+//! sig_len      u16 BE
+//! signature    sig_len bytes
+//! ```
+//!
+//! The image is encrypted with [RC5-CTR](crate::ctr) and cut into chunks of
+//! `chunk_len` bytes, the last one shorter. Each chunk carries the first 16
+//! bytes of `HMAC-SHA256(mac_key, digest of the preamble || i (u64 BE) ||
+//! ct_i)`, so a device can authenticate and decrypt a chunk as soon as it is
+//! received. The trailing digest covers the whole container; it is what the
+//! vendor signs and what a [`SignatureVerifier`] checks before anything is
+//! decrypted. This crate doesn't pick a signature scheme.
+//!
+//! The RC5 and MAC keys are derived from one device key with HKDF, see
+//! [`crate::kdf`].
+//!
+//! Only available with the `firmware` feature.
+//!
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::ctr::apply_keystream_at;
+use crate::error::{Error, Failure};
+use crate::header::{Header, MacAlg, Mode};
+use crate::kdf::derive_subkey;
+use crate::rc5::expand_key;
+use crate::rng::{CryptoRng, RngCore};
+use crate::word::Word;
+
+///
+/// Chunk length used by [`build_image`] callers that have no page size to
+/// match.
+///
+pub const DEFAULT_CHUNK_LEN: usize = 4096;
+
+const TAG_LEN: usize = 16;
+const DIGEST_LEN: usize = 32;
+
+///
+/// Checks the vendor signature over the digest of an image, e.g. Ed25519 with
+/// a public key burnt into the bootloader.
+///
+pub trait SignatureVerifier {
+    fn verify(&self, digest: &[u8; 32], signature: &[u8]) -> Result<(), Error>;
+}
+
+///
+/// Produces the signature over the digest of a new image.
+///
+pub trait Signer {
+    fn sign(&self, digest: &[u8; 32]) -> Vec<u8>;
+}
+
+///
+/// Encrypts `image` into the container format with a fresh IV from `rng`,
+/// the `device_key` and `rounds` rounds of RC5 with `W` words.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::firmware::{build_image, open_image, Signer, SignatureVerifier};
+/// use rc5_cipher::Error;
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// // stand-ins for a real signature scheme
+/// struct Vendor;
+/// impl Signer for Vendor {
+///     fn sign(&self, digest: &[u8; 32]) -> Vec<u8> {
+///         digest.to_vec()
+///     }
+/// }
+/// impl SignatureVerifier for Vendor {
+///     fn verify(&self, digest: &[u8; 32], signature: &[u8]) -> Result<(), Error> {
+///         if signature == digest { Ok(()) } else { Err(Error::AuthenticationFailed) }
+///     }
+/// }
+///
+/// let firmware = vec![0xeau8; 10_000];
+/// let key = [0x11u8; 16];
+/// let container = build_image::<u32, _>(&mut rng, &key, 20, 1024, &firmware, &Vendor).unwrap();
+///
+/// assert_eq!(open_image::<u32>(&key, &container, &Vendor).unwrap(), firmware);
+/// ```
+///
+pub fn build_image<W: Word, R: RngCore + CryptoRng>(
+    rng: &mut R,
+    device_key: &[u8],
+    rounds: u8,
+    chunk_len: usize,
+    image: &[u8],
+    signer: &dyn Signer,
+) -> Result<Vec<u8>, Error> {
+    if chunk_len == 0 || chunk_len > u32::MAX as usize {
+        return Err(Error::InvalidLength);
+    }
+    let keys = Keys::<W>::derive(device_key, rounds as usize)?;
+
+    let mut out = Header::new::<W>(rounds, Mode::Ctr, MacAlg::HmacSha256).to_bytes();
+    let mut iv = vec![0u8; 2 * W::BYTES];
+    rng.fill_bytes(&mut iv);
+    out.extend_from_slice(&iv);
+    out.extend_from_slice(&(chunk_len as u32).to_be_bytes());
+    out.extend_from_slice(&(image.len() as u64).to_be_bytes());
+    let preamble = Sha256::digest(&out);
+
+    for (i, chunk) in image.chunks(chunk_len).enumerate() {
+        let mut ct = chunk.to_vec();
+        apply_keystream_at(&keys.key_exp, &iv, (i * chunk_len) as u64, &mut ct)?;
+        let tag = keys.tag(&preamble, i as u64, &ct);
+        out.extend_from_slice(&ct);
+        out.extend_from_slice(&tag);
+    }
+
+    let digest: [u8; DIGEST_LEN] = Sha256::digest(&out).into();
+    let signature = signer.sign(&digest);
+    if signature.len() > u16::MAX as usize {
+        return Err(Error::InvalidLength);
+    }
+    out.extend_from_slice(&digest);
+    out.extend_from_slice(&(signature.len() as u16).to_be_bytes());
+    out.extend_from_slice(&signature);
+    Ok(out)
+}
+
+///
+/// Checks the signature and every chunk tag of `container`, then returns the
+/// decrypted image. Nothing is decrypted unless the signature verifies.
+///
+/// Fails with a [`Failure::Crypto`] that doesn't say why for any fault of
+/// `container`, e.g. a bad signature or chunk tag, a truncated body or
+/// parameters other than `W`, CTR and HMAC-SHA256 in its header.
+///
+pub fn open_image<W: Word>(
+    device_key: &[u8],
+    container: &[u8],
+    verifier: &dyn SignatureVerifier,
+) -> Result<Vec<u8>, Failure> {
+    open_image_body::<W>(device_key, container, verifier).map_err(Failure::decrypting)
+}
+
+fn open_image_body<W: Word>(
+    device_key: &[u8],
+    container: &[u8],
+    verifier: &dyn SignatureVerifier,
+) -> Result<Vec<u8>, Error> {
+    let (header, header_len) = Header::parse(container)?;
+    if header.word_bytes as usize != W::BYTES
+        || header.mode != Mode::Ctr
+        || header.mac != MacAlg::HmacSha256
+    {
+        return Err(Error::UnsupportedParameters);
+    }
+
+    let bs = 2 * W::BYTES;
+    let preamble_len = header_len + bs + 4 + 8;
+    let preamble = container.get(..preamble_len).ok_or(Error::InvalidLength)?;
+    let iv = &preamble[header_len..header_len + bs];
+    let field = &preamble[header_len + bs..];
+    let chunk_len = u32::from_be_bytes(field[..4].try_into().unwrap()) as usize;
+    let image_len = u64::from_be_bytes(field[4..12].try_into().unwrap());
+    if chunk_len == 0 {
+        return Err(Error::InvalidHeader);
+    }
+
+    // the chunk area is fully determined by the lengths in the preamble
+    let chunks = image_len.div_ceil(chunk_len as u64);
+    let body_len = chunks
+        .checked_mul(TAG_LEN as u64)
+        .and_then(|tags| tags.checked_add(image_len))
+        .and_then(|len| usize::try_from(len).ok())
+        .ok_or(Error::InvalidLength)?;
+    let digest_at = preamble_len
+        .checked_add(body_len)
+        .ok_or(Error::InvalidLength)?;
+    let signature_at = digest_at
+        .checked_add(DIGEST_LEN + 2)
+        .ok_or(Error::InvalidLength)?;
+    let trailer = container
+        .get(digest_at..signature_at)
+        .ok_or(Error::InvalidLength)?;
+    let sig_len = u16::from_be_bytes([trailer[DIGEST_LEN], trailer[DIGEST_LEN + 1]]) as usize;
+    let signature = &container[signature_at..];
+    if signature.len() != sig_len {
+        return Err(Error::InvalidLength);
+    }
+
+    let digest: [u8; DIGEST_LEN] = Sha256::digest(&container[..digest_at]).into();
+    if digest[..] != trailer[..DIGEST_LEN] {
+        return Err(Error::AuthenticationFailed);
+    }
+    verifier.verify(&digest, signature)?;
+
+    let keys = Keys::<W>::derive(device_key, header.rounds as usize)?;
+    let preamble = Sha256::digest(preamble);
+    let mut image = Vec::with_capacity(image_len as usize);
+    let mut body = &container[preamble_len..digest_at];
+    let mut i = 0u64;
+    while !body.is_empty() {
+        let len = chunk_len.min(body.len() - TAG_LEN);
+        let (ct, rest) = body.split_at(len);
+        let (tag, rest) = rest.split_at(TAG_LEN);
+        if !ct_eq(&keys.tag(&preamble, i, ct), tag) {
+            return Err(Error::AuthenticationFailed);
+        }
+
+        let start = image.len();
+        image.extend_from_slice(ct);
+        apply_keystream_at(&keys.key_exp, iv, start as u64, &mut image[start..])?;
+        body = rest;
+        i += 1;
+    }
+    Ok(image)
+}
+
+struct Keys<W: Word> {
+    key_exp: Vec<W>,
+    mac_key: Vec<u8>,
+}
+
+impl<W: Word> Keys<W> {
+    fn derive(device_key: &[u8], rounds: usize) -> Result<Self, Error> {
+        let enc_key = derive_subkey(device_key, &[], b"rc5-cipher firmware enc", 16)?;
+        Ok(Keys {
+            key_exp: expand_key(&enc_key, rounds),
+            mac_key: derive_subkey(device_key, &[], b"rc5-cipher firmware mac", 32)?,
+        })
+    }
+
+    fn tag(&self, preamble: &[u8], index: u64, ct: &[u8]) -> [u8; TAG_LEN] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.mac_key).unwrap();
+        mac.update(preamble);
+        mac.update(&index.to_be_bytes());
+        mac.update(ct);
+        mac.finalize().into_bytes()[..TAG_LEN].try_into().unwrap()
+    }
+}
+
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CryptoError;
+    use crate::rng::CounterRng;
+
+    struct Plain;
+
+    impl Signer for Plain {
+        fn sign(&self, digest: &[u8; 32]) -> Vec<u8> {
+            let mut sig = digest.to_vec();
+            sig.reverse();
+            sig
+        }
+    }
+
+    impl SignatureVerifier for Plain {
+        fn verify(&self, digest: &[u8; 32], signature: &[u8]) -> Result<(), Error> {
+            if self.sign(digest) == signature {
+                Ok(())
+            } else {
+                Err(Error::AuthenticationFailed)
+            }
+        }
+    }
+
+    fn image(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7) as u8).collect()
+    }
+
+    #[test]
+    fn layout_and_roundtrip() {
+        let key = [5u8; 16];
+        for len in [0, 1, 256, 1000] {
+            let container =
+                build_image::<u16, _>(&mut CounterRng(0), &key, 16, 256, &image(len), &Plain)
+                    .unwrap();
+            let chunks = len.div_ceil(256);
+            assert_eq!(
+                container.len(),
+                8 + 4 + 4 + 8 + len + 16 * chunks + 32 + 2 + 32
+            );
+            assert_eq!(
+                open_image::<u16>(&key, &container, &Plain).unwrap(),
+                image(len)
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let key = [5u8; 16];
+        let container =
+            build_image::<u32, _>(&mut CounterRng(0), &key, 12, 100, &image(250), &Plain).unwrap();
+        let open = |c: &[u8]| open_image_body::<u32>(&key, c, &Plain);
+
+        // every byte up to the signature is covered by the digest
+        for pos in [0, 10, 30, 200, container.len() - 40] {
+            let mut bad = container.clone();
+            bad[pos] ^= 1;
+            assert!(open(&bad).is_err(), "byte {} not covered", pos);
+        }
+        let mut bad_sig = container.clone();
+        *bad_sig.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&bad_sig), Err(Error::AuthenticationFailed));
+
+        assert_eq!(
+            open(&container[..container.len() - 1]),
+            Err(Error::InvalidLength)
+        );
+        // digest and signature match, the chunk tags don't
+        assert_eq!(
+            open_image_body::<u32>(&[6u8; 16], &container, &Plain),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            open_image_body::<u64>(&key, &container, &Plain),
+            Err(Error::UnsupportedParameters)
+        );
+
+        // callers only learn that the container is at fault
+        for (device_key, c) in [(&[6u8; 16], &container[..]), (&key, &bad_sig[..])] {
+            assert_eq!(
+                open_image::<u32>(device_key, c, &Plain),
+                Err(Failure::Crypto(CryptoError))
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_lengths_overflowing_the_trailer() {
+        let key = [5u8; 16];
+        let mut container =
+            build_image::<u16, _>(&mut CounterRng(0), &key, 16, 256, &image(10), &Plain).unwrap();
+        // one-byte chunks put the digest within 17 bytes of usize::MAX
+        let image_len = (usize::MAX as u64 - 24) / 17;
+        container[12..16].copy_from_slice(&1u32.to_be_bytes());
+        container[16..24].copy_from_slice(&image_len.to_be_bytes());
+        assert_eq!(
+            open_image_body::<u16>(&key, &container, &Plain),
+            Err(Error::InvalidLength)
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//!
+//! Parameters and key schedules fixed at compile time.
+//!
+//! [`rc5_static!`](crate::rc5_static) checks an `RC5-w/r/b` parameter string
+//! while the crate using it compiles, and given a key also expands the key
+//! schedule then, as a constant. The result is a [`FixedRc5`] that needs no
+//! setup at run time and can sit in a `static`, e.g. in firmware that can't
+//! afford the key expansion at boot or keeps its key in flash anyway.
+//!
+//! ```text
+//! rc5_static!("RC5-32/20/16")                          -> Rc5Params
+//! rc5_static!("RC5-32/20/16", key = env!("FW_KEY"))    -> FixedRc5<u32, 42>
+//! ```
+//!
+//! The key is given as hex text, `2b` digits long. Malformed parameters, a key
+//! of the wrong length or a schedule length other than `2 * (r + 1)` stop the
+//! build with the reason as the error. A key baked in this way is part of the
+//! binary: anyone who can read the binary can read the key.
+//!
+
+use core::fmt;
+
+use crate::algo::BlockCipherAlgo;
+use crate::params::Rc5Params;
+use crate::rc5::{decrypt_kernel, encrypt_kernel, MAX_KEY_BYTES};
+use crate::word::{magic_p, magic_q, Word};
+
+type Kernel<W> = fn([W; 2], &[W]) -> [W; 2];
+
+///
+/// Checks an `RC5-w/r/b` parameter string at compile time, and with a
+/// `key = "<hex>"` argument builds the [`FixedRc5`] for it.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::BlockCipherAlgo;
+/// use rc5_cipher::fixed::FixedRc5;
+/// use rc5_cipher::rc5_static;
+///
+/// // in firmware: rc5_static!("RC5-32/12/16", key = env!("FW_KEY"))
+/// static CIPHER: FixedRc5<u32, 26> =
+///     rc5_static!("RC5-32/12/16", key = "000102030405060708090a0b0c0d0e0f");
+///
+/// let mut block = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77];
+/// CIPHER.encrypt_block(&mut block);
+/// assert_eq!(block, [0x2d, 0xdc, 0x14, 0x9b, 0xcf, 0x08, 0x8b, 0x9e]);
+///
+/// let params = rc5_static!("RC5-64/24/32");
+/// assert_eq!((params.word_bytes, params.rounds, params.key_bytes), (8, 24, 32));
+/// ```
+///
+/// Anything invalid fails the build:
+///
+/// ```compile_fail
+/// let params = rc5_cipher::rc5_static!("RC5-24/12/16");
+/// ```
+///
+#[macro_export]
+macro_rules! rc5_static {
+    ($spec:expr) => {{
+        const PARAMS: $crate::params::Rc5Params = $crate::fixed::parse_params($spec);
+        PARAMS
+    }};
+    ($spec:expr, key = $key:expr) => {{
+        type Cipher = $crate::fixed::FixedRc5<
+                        <$crate::fixed::WordBytes<{ $crate::fixed::parse_params($spec).word_bytes }>
+                            as $crate::fixed::WordOfSize>::Word,
+                        { 2 * ($crate::fixed::parse_params($spec).rounds + 1) },
+                    >;
+        const CIPHER: Cipher = Cipher::new($spec, $key);
+        CIPHER
+    }};
+}
+
+///
+/// A word size in bytes, mapped to its word type by [`WordOfSize`]. Lets
+/// [`rc5_static!`](crate::rc5_static) pick the type from the parameters.
+///
+pub struct WordBytes<const N: usize>;
+
+///
+/// The word type of a [`WordBytes`] size.
+///
+pub trait WordOfSize {
+    type Word: Word;
+}
+
+///
+/// RC5 over `W` words with a schedule of `T = 2 * (r + 1)` words expanded at
+/// compile time.
+///
+/// `Debug` shows the parameters only, never the schedule.
+///
+#[derive(Clone, PartialEq, Eq)]
+pub struct FixedRc5<W, const T: usize> {
+    key_exp: [W; T],
+}
+
+impl<W: Word, const T: usize> fmt::Debug for FixedRc5<W, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FixedRc5(RC5-{}/{})", W::BITS, T / 2 - 1)
+    }
+}
+
+impl<W: Word, const T: usize> BlockCipherAlgo for FixedRc5<W, T> {
+    fn block_size(&self) -> usize {
+        2 * W::BYTES
+    }
+
+    fn encrypt_block(&self, block: &mut [u8]) {
+        crypt(&self.key_exp, block, encrypt_kernel)
+    }
+
+    fn decrypt_block(&self, block: &mut [u8]) {
+        crypt(&self.key_exp, block, decrypt_kernel)
+    }
+}
+
+fn crypt<W: Word>(key_exp: &[W], block: &mut [u8], kernel: Kernel<W>) {
+    let out = kernel(
+        [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        key_exp,
+    );
+    out[0].write_le(block);
+    out[1].write_le(&mut block[W::BYTES..]);
+}
+
+///
+/// Parses `RC5-w/r/b`, with the word size `w` in bits, in any case. Meant for
+/// const contexts, where a panic is a compile error.
+///
+/// Panics unless `w` is 8, 16, 32, 64 or 128 and `r` and `b` are at most
+/// 255.
+///
+pub const fn parse_params(spec: &str) -> Rc5Params {
+    let s = spec.as_bytes();
+    assert!(
+        s.len() > 4 && s[0] | 0x20 == b'r' && s[1] | 0x20 == b'c' && s[2] == b'5' && s[3] == b'-',
+        "parameters must read RC5-w/r/b"
+    );
+    let (bits, i) = number(s, 4);
+    assert!(
+        i < s.len() && s[i] == b'/',
+        "parameters must read RC5-w/r/b"
+    );
+    let (rounds, i) = number(s, i + 1);
+    assert!(
+        i < s.len() && s[i] == b'/',
+        "parameters must read RC5-w/r/b"
+    );
+    let (key_bytes, i) = number(s, i + 1);
+    assert!(i == s.len(), "parameters must read RC5-w/r/b");
+
+    assert!(
+        matches!(bits, 8 | 16 | 32 | 64 | 128),
+        "word size must be 8, 16, 32, 64 or 128 bits"
+    );
+    assert!(rounds <= 255, "at most 255 rounds");
+    assert!(key_bytes <= MAX_KEY_BYTES, "key longer than 255 bytes");
+    Rc5Params {
+        word_bytes: bits / 8,
+        rounds,
+        key_bytes,
+    }
+}
+
+// The decimal number at `s[i..]` and the index past it.
+const fn number(s: &[u8], mut i: usize) -> (usize, usize) {
+    let start = i;
+    let mut n = 0;
+    while i < s.len() && s[i].is_ascii_digit() {
+        n = n * 10 + (s[i] - b'0') as usize;
+        assert!(n <= 1000, "parameter out of range");
+        i += 1;
+    }
+    assert!(i > start, "parameters must read RC5-w/r/b");
+    (n, i)
+}
+
+// Hex text to bytes, in a buffer of the longest key.
+const fn decode_hex(hex: &str) -> ([u8; MAX_KEY_BYTES], usize) {
+    let s = hex.as_bytes();
+    assert!(
+        s.len().is_multiple_of(2) && s.len() / 2 <= MAX_KEY_BYTES,
+        "key must be hex of at most 255 bytes"
+    );
+    let mut out = [0u8; MAX_KEY_BYTES];
+    let mut i = 0;
+    while i < s.len() / 2 {
+        out[i] = nibble(s[2 * i]) << 4 | nibble(s[2 * i + 1]);
+        i += 1;
+    }
+    (out, s.len() / 2)
+}
+
+const fn nibble(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("key must be hex"),
+    }
+}
+
+// `expand_key` as a const fn for each word type, since trait methods can't
+// be called in const contexts, and the constructor and word size mapping
+// built on it.
+macro_rules! const_expand {
+    ($($name:ident $t:ty),*) => {$(
+        impl WordOfSize for WordBytes<{ <$t>::BITS as usize / 8 }> {
+            type Word = $t;
+        }
+
+        impl<const T: usize> FixedRc5<$t, T> {
+            ///
+            /// Expands the hex-encoded `key` for the parameters in `spec`.
+            /// Meant for const contexts, where a panic is a compile error.
+            ///
+            /// Panics if `spec` is invalid, as for [`parse_params`], if its
+            /// word size isn't that of this type, if `key` isn't hex of the
+            /// key length in `spec` or if `T` isn't `2 * (r + 1)`.
+            ///
+            pub const fn new(spec: &str, key: &str) -> Self {
+                let params = parse_params(spec);
+                assert!(
+                    params.word_bytes == <$t>::BITS as usize / 8,
+                    "word size differs from the parameters"
+                );
+                assert!(
+                    T == 2 * (params.rounds + 1),
+                    "schedule length must be 2 * (rounds + 1)"
+                );
+                let (buf, len) = decode_hex(key);
+                assert!(
+                    len == params.key_bytes,
+                    "key length differs from the parameters"
+                );
+                FixedRc5 {
+                    key_exp: $name(buf.split_at(len).0),
+                }
+            }
+        }
+
+        const fn $name<const T: usize>(key: &[u8]) -> [$t; T] {
+            const U: usize = <$t>::BITS as usize / 8;
+            let c = if key.is_empty() { 1 } else { key.len().div_ceil(U) };
+            let mut l = [0 as $t; MAX_KEY_BYTES];
+            let mut i = key.len();
+            while i > 0 {
+                i -= 1;
+                l[i / U] = l[i / U].wrapping_shl(8).wrapping_add(key[i] as $t);
+            }
+
+            let mut s = [0 as $t; T];
+            s[0] = magic_p(<$t>::BITS) as $t;
+            let mut i = 1;
+            while i < T {
+                s[i] = s[i - 1].wrapping_add(magic_q(<$t>::BITS) as $t);
+                i += 1;
+            }
+
+            let (mut i, mut j) = (0, 0);
+            let (mut a, mut b) = (0 as $t, 0 as $t);
+            let mut k = 0;
+            while k < 3 * if c > T { c } else { T } {
+                s[i] = s[i].wrapping_add(a.wrapping_add(b)).rotate_left(3);
+                a = s[i];
+                l[j] = l[j].wrapping_add(a.wrapping_add(b)).rotate_left(a.wrapping_add(b) as u32);
+                b = l[j];
+                i = (i + 1) % T;
+                j = (j + 1) % c;
+                k += 1;
+            }
+            s
+        }
+    )*};
+}
+
+const_expand! { expand_u8 u8, expand_u16 u16, expand_u32 u32, expand_u64 u64, expand_u128 u128 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::expand_key;
+
+    #[test]
+    fn schedules_match_expand_key() {
+        let key: Vec<u8> = (0..255).map(|i| (i * 37) as u8).collect();
+        for len in [0, 1, 5, 16, 255] {
+            let key = &key[..len];
+            assert_eq!(expand_u8::<26>(key)[..], expand_key::<u8>(key, 12)[..]);
+            assert_eq!(expand_u16::<34>(key)[..], expand_key::<u16>(key, 16)[..]);
+            assert_eq!(expand_u32::<2>(key)[..], expand_key::<u32>(key, 0)[..]);
+            assert_eq!(expand_u64::<50>(key)[..], expand_key::<u64>(key, 24)[..]);
+            assert_eq!(expand_u128::<58>(key)[..], expand_key::<u128>(key, 28)[..]);
+        }
+    }
+
+    #[test]
+    fn macro_builds_constants() {
+        const PARAMS: Rc5Params = rc5_static!("rc5-16/16/8");
+        assert_eq!(
+            PARAMS,
+            Rc5Params {
+                word_bytes: 2,
+                rounds: 16,
+                key_bytes: 8
+            }
+        );
+
+        let cipher = rc5_static!("RC5-64/24/4", key = "0001FEff");
+        let key_exp = expand_u64(&[0x00, 0x01, 0xfe, 0xff]);
+        assert_eq!(cipher, FixedRc5 { key_exp });
+        assert_eq!(cipher.block_size(), 16);
+        assert_eq!(std::mem::size_of_val(&cipher), 8 * 50);
+        let mut block = [7u8; 16];
+        cipher.encrypt_block(&mut block);
+        assert_ne!(block, [7u8; 16]);
+        cipher.decrypt_block(&mut block);
+        assert_eq!(block, [7u8; 16]);
+
+        let empty = rc5_static!("RC5-8/12/0", key = "");
+        assert_eq!(
+            empty,
+            FixedRc5 {
+                key_exp: expand_u8(&[])
+            }
+        );
+        assert_eq!(std::mem::size_of_val(&empty), 26);
+    }
+
+    #[test]
+    fn debug_leaves_out_the_schedule() {
+        let cipher = rc5_static!("RC5-32/12/4", key = "00010203");
+        assert_eq!(format!("{:?}", cipher), "FixedRc5(RC5-32/12)");
+    }
+
+    #[test]
+    fn rejects_bad_parameters() {
+        for spec in [
+            "RC5-32/12",
+            "RC6-32/12/16",
+            "RC5-24/12/16",
+            "RC5-32/256/16",
+            "RC5-32/12/256",
+            "RC5-32//16",
+            "RC5-32/12/16/",
+            "RC5-99999999999/12/16",
+        ] {
+            assert!(
+                std::panic::catch_unwind(|| parse_params(spec)).is_err(),
+                "{}",
+                spec
+            );
+        }
+        let bad_keys: [fn() -> FixedRc5<u32, 26>; 4] = [
+            || FixedRc5::<u32, 26>::new("RC5-32/12/2", "00"),
+            || FixedRc5::<u32, 26>::new("RC5-32/12/1", "0g"),
+            || FixedRc5::<u32, 26>::new("RC5-32/12/1", "000"),
+            || FixedRc5::<u32, 26>::new("RC5-64/12/1", "00"),
+        ];
+        for bad in bad_keys {
+            assert!(std::panic::catch_unwind(bad).is_err());
+        }
+        assert!(
+            std::panic::catch_unwind(|| FixedRc5::<u32, 24>::new("RC5-32/12/1", "00")).is_err()
+        );
+    }
+}
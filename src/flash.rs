@@ -0,0 +1,343 @@
+//!
+//! Transparent encryption of NOR flash behind the `embedded-storage` traits.
+//!
+//! [`EncryptedFlash`] wraps a flash driver and encrypts every cipher block in
+//! XEX fashion: block `j` of erase sector `s` is stored as
+//! `E(k1, P ^ T) ^ T` with the tweak `T = E(k2, s || j)`. Identical data
+//! therefore looks different in every location, and a block can be read or
+//! written without touching its neighbours.
+//!
+//! NOR flash reads erased bytes as `0xff`, and code on top of it (wear
+//! levelling, key-value stores) relies on that. A stored block that is all
+//! `0xff` is therefore returned as erased instead of being decrypted; a
+//! plaintext block encrypting to all ones is as likely as guessing the key.
+//!
+//! The tweak encodes the sector and block index in one word each, so a word
+//! type can only address `2^BITS` sectors of `2^BITS` blocks; [`EncryptedFlash::new`]
+//! refuses flash larger than that instead of letting tweaks repeat.
+//!
+//! Only available with the `flash` feature. `embedded-storage` itself is
+//! `no_std`, but this crate isn't: the adapter is meant for hosted targets
+//! driving flash (e.g. over SPI on Linux), not for bare-metal firmware.
+//!
+
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+use crate::rc5::{decrypt_kernel, encrypt_kernel, expand_key};
+use crate::word::Word;
+
+///
+/// Errors of [`EncryptedFlash`]: those of the inner driver, or accesses that
+/// don't line up with the cipher blocks.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashError<E> {
+    Inner(E),
+    NotAligned,
+    OutOfBounds,
+}
+
+impl<E: NorFlashError> NorFlashError for FlashError<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            FlashError::Inner(err) => err.kind(),
+            FlashError::NotAligned => NorFlashErrorKind::NotAligned,
+            FlashError::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+        }
+    }
+}
+
+///
+/// A [`NorFlash`] storing everything encrypted on the inner flash `F`.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// use embedded_storage::nor_flash::{NorFlash, ReadNorFlash};
+/// use rc5_cipher::flash::EncryptedFlash;
+///
+/// let mut flash = EncryptedFlash::<_, u32>::new(spi_flash, &data_key, &tweak_key, 16);
+/// flash.erase(0, 4096)?;
+/// flash.write(0, b"settings")?;
+///
+/// let mut buf = [0u8; 8];
+/// flash.read(0, &mut buf)?;
+/// ```
+///
+pub struct EncryptedFlash<F, W: Word> {
+    inner: F,
+    data_key: Vec<W>,
+    tweak_key: Vec<W>,
+}
+
+impl<F: NorFlash, W: Word> EncryptedFlash<F, W> {
+    ///
+    /// Wraps `inner`. The erase size of `inner` must be a multiple of the
+    /// block size, `2 * W::BYTES`, and both the number of sectors and the
+    /// blocks per sector must fit in a word.
+    ///
+    pub fn new(inner: F, data_key: &[u8], tweak_key: &[u8], rounds: usize) -> Self {
+        assert!(
+            F::ERASE_SIZE.is_multiple_of(2 * W::BYTES),
+            "erase size is not a multiple of the block size"
+        );
+        assert!(
+            fits_word::<W>(F::ERASE_SIZE / (2 * W::BYTES))
+                && fits_word::<W>(inner.capacity().div_ceil(F::ERASE_SIZE)),
+            "flash too large for the tweaks of this word size"
+        );
+        assert!(
+            Self::WRITE_SIZE <= WRITE_BUF,
+            "write size too large for the write buffer"
+        );
+        EncryptedFlash {
+            inner,
+            data_key: expand_key(data_key, rounds),
+            tweak_key: expand_key(tweak_key, rounds),
+        }
+    }
+
+    ///
+    /// Returns the inner flash, e.g. to power it down.
+    ///
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn tweak(&self, offset: usize) -> [W; 2] {
+        let sector = offset / F::ERASE_SIZE;
+        let block = offset % F::ERASE_SIZE / (2 * W::BYTES);
+        encrypt_kernel(
+            [W::from_usize(sector), W::from_usize(block)],
+            &self.tweak_key,
+        )
+    }
+
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<(), FlashError<F::Error>> {
+        let offset = offset as usize;
+        if len > self.inner.capacity() || offset > self.inner.capacity() - len {
+            return Err(FlashError::OutOfBounds);
+        }
+        if !offset.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(FlashError::NotAligned);
+        }
+        Ok(())
+    }
+}
+
+impl<F: NorFlash, W: Word> ErrorType for EncryptedFlash<F, W> {
+    type Error = FlashError<F::Error>;
+}
+
+impl<F: NorFlash, W: Word> ReadNorFlash for EncryptedFlash<F, W> {
+    const READ_SIZE: usize = lcm(F::READ_SIZE, 2 * W::BYTES);
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        self.inner.read(offset, bytes).map_err(FlashError::Inner)?;
+
+        let bs = 2 * W::BYTES;
+        for (i, block) in bytes.chunks_exact_mut(bs).enumerate() {
+            if block.iter().all(|&b| b == 0xff) {
+                continue;
+            }
+            let tweak = self.tweak(offset as usize + i * bs);
+            let ct = load::<W>(block, tweak);
+            let pt = decrypt_kernel(ct, &self.data_key);
+            store(pt, tweak, block);
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+}
+
+impl<F: NorFlash, W: Word> NorFlash for EncryptedFlash<F, W> {
+    const WRITE_SIZE: usize = lcm(F::WRITE_SIZE, 2 * W::BYTES);
+    const ERASE_SIZE: usize = F::ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.inner.erase(from, to).map_err(FlashError::Inner)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+
+        let bs = 2 * W::BYTES;
+        let step = WRITE_BUF / Self::WRITE_SIZE * Self::WRITE_SIZE;
+        let mut buf = [0u8; WRITE_BUF];
+        let mut at = offset as usize;
+        for chunk in bytes.chunks(step) {
+            let buf = &mut buf[..chunk.len()];
+            buf.copy_from_slice(chunk);
+            for (i, block) in buf.chunks_exact_mut(bs).enumerate() {
+                let tweak = self.tweak(at + i * bs);
+                let pt = load::<W>(block, tweak);
+                let ct = encrypt_kernel(pt, &self.data_key);
+                store(ct, tweak, block);
+            }
+            self.inner
+                .write(at as u32, buf)
+                .map_err(FlashError::Inner)?;
+            at += chunk.len();
+        }
+        Ok(())
+    }
+}
+
+// Writes are encrypted through a stack buffer of this size.
+const WRITE_BUF: usize = 256;
+
+// Whether the indexes `0..count` all fit in a word.
+fn fits_word<W: Word>(count: usize) -> bool {
+    W::BITS >= usize::BITS || count <= 1 << W::BITS
+}
+
+// Reads a little-endian block and XORs the tweak into it.
+fn load<W: Word>(block: &[u8], tweak: [W; 2]) -> [W; 2] {
+    debug_assert_eq!(block.len(), 2 * W::BYTES, "block size mismatch");
+    [
+        W::from_le_slice(block) ^ tweak[0],
+        W::from_le_slice(&block[W::BYTES..]) ^ tweak[1],
+    ]
+}
+
+fn store<W: Word>(words: [W; 2], tweak: [W; 2], block: &mut [u8]) {
+    (words[0] ^ tweak[0]).write_le(block);
+    (words[1] ^ tweak[1]).write_le(&mut block[W::BYTES..]);
+}
+
+const fn lcm(a: usize, b: usize) -> usize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+
+    const CAPACITY: usize = 4 * 256;
+
+    // NOR flash in memory: writes can only clear bits.
+    struct MemFlash(Vec<u8>);
+
+    impl ErrorType for MemFlash {
+        type Error = Infallible;
+    }
+
+    impl ReadNorFlash for MemFlash {
+        const READ_SIZE: usize = 1;
+
+        fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Infallible> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for MemFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = 256;
+
+        fn erase(&mut self, from: u32, to: u32) -> Result<(), Infallible> {
+            self.0[from as usize..to as usize].fill(0xff);
+            Ok(())
+        }
+
+        fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Infallible> {
+            let offset = offset as usize;
+            for (dst, src) in self.0[offset..].iter_mut().zip(bytes) {
+                *dst &= src;
+            }
+            Ok(())
+        }
+    }
+
+    fn flash() -> EncryptedFlash<MemFlash, u32> {
+        EncryptedFlash::new(
+            MemFlash(vec![0xff; CAPACITY]),
+            b"data key",
+            b"tweak key",
+            12,
+        )
+    }
+
+    #[test]
+    fn roundtrip_and_erased_state() {
+        let mut flash = flash();
+        assert_eq!(EncryptedFlash::<MemFlash, u32>::WRITE_SIZE, 8);
+
+        let mut buf = [0u8; 16];
+        flash.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [0xff; 16]);
+
+        flash.write(8, b"settings").unwrap();
+        flash.read(0, &mut buf).unwrap();
+        assert_eq!(buf[..8], [0xff; 8]);
+        assert_eq!(&buf[8..], b"settings");
+
+        flash.erase(0, 256).unwrap();
+        flash.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [0xff; 16]);
+    }
+
+    #[test]
+    fn location_changes_the_ciphertext() {
+        let mut flash = flash();
+        for offset in [0, 8, 256, 264] {
+            flash.write(offset, b"same data").unwrap_err();
+            flash.write(offset, b"samedata").unwrap();
+        }
+
+        let raw = flash.into_inner().0;
+        let stored = [&raw[0..8], &raw[8..16], &raw[256..264], &raw[264..272]];
+        for (i, a) in stored.iter().enumerate() {
+            assert_ne!(*a, b"samedata");
+            for b in &stored[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn writes_span_several_buffers() {
+        let mut flash = flash();
+        let data: Vec<u8> = (0..CAPACITY).map(|i| i as u8).collect();
+        flash.write(0, &data).unwrap();
+
+        let mut buf = vec![0u8; CAPACITY];
+        flash.read(0, &mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    #[should_panic(expected = "flash too large")]
+    fn rejects_flash_beyond_the_tweak_space() {
+        // 256-byte sectors hold 128 blocks of u8 words, but 260 sectors
+        // don't fit in a u8 sector index.
+        EncryptedFlash::<_, u8>::new(MemFlash(vec![0xff; 260 * 256]), b"k", b"t", 12);
+    }
+
+    #[test]
+    fn rejects_misaligned_access() {
+        let mut flash = flash();
+        assert_eq!(flash.write(4, &[0; 8]), Err(FlashError::NotAligned));
+        assert_eq!(flash.read(0, &mut [0; 7]), Err(FlashError::NotAligned));
+        assert_eq!(
+            flash.write(CAPACITY as u32, &[0; 8]),
+            Err(FlashError::OutOfBounds)
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//!
+//! Hashing built from the block cipher alone.
+//!
+//! [`mmo_compress`] is the Matyas–Meyer–Oseas compression function,
+//! `H_i = E(H_(i-1), m_i) ^ m_i`, with the chaining value as the RC5 key.
+//! [`mmo_hash`] iterates it over a message with Merkle–Damgård padding, and
+//! [`tree_hash`] builds a binary hash tree over chunks, so integrity trees can
+//! be computed where RC5 is the only primitive available.
+//!
+//! The digest is one block, so collisions take about `2^w` work for `w`-bit
+//! words: use `u64` or `u128`. Smaller words only detect accidental
+//! corruption. Every block needs a fresh key expansion, which makes hashing
+//! far slower than encryption.
+//!
+
+use crate::rc5::{encrypt_kernel, expand_key};
+use crate::word::Word;
+
+///
+/// One Matyas–Meyer–Oseas step: encrypts `block` under the chaining value `h`,
+/// as little-endian key bytes, and XORs the block back in.
+///
+pub fn mmo_compress<W: Word>(h: [W; 2], block: [W; 2], rounds: usize) -> [W; 2] {
+    let mut key = [0u8; 32];
+    let key = &mut key[..2 * W::BYTES];
+    h[0].write_le(key);
+    h[1].write_le(&mut key[W::BYTES..]);
+    let ct = encrypt_kernel(block, &expand_key(key, rounds));
+    [ct[0] ^ block[0], ct[1] ^ block[1]]
+}
+
+///
+/// Hashes `data` into one block.
+///
+/// The chaining value starts as the words `P` and `Q`. The message is padded
+/// with `0x80`, zeros to a block boundary and its length in bytes as a
+/// little-endian `u64`, itself zero padded to whole blocks.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::hash::mmo_hash;
+///
+/// let digest = mmo_hash::<u64>(b"index page", 16);
+/// assert_eq!(digest.len(), 16);
+/// assert_ne!(digest, mmo_hash::<u64>(b"index page!", 16));
+/// ```
+///
+pub fn mmo_hash<W: Word>(data: &[u8], rounds: usize) -> Vec<u8> {
+    let bs = 2 * W::BYTES;
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    padded.resize(padded.len().next_multiple_of(bs), 0);
+    let mut len = (data.len() as u64).to_le_bytes().to_vec();
+    len.resize(len.len().next_multiple_of(bs), 0);
+    padded.extend_from_slice(&len);
+
+    let mut h = [W::P, W::Q];
+    for block in padded.chunks_exact(bs) {
+        let m = [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ];
+        h = mmo_compress(h, m, rounds);
+    }
+
+    let mut digest = vec![0u8; bs];
+    h[0].write_le(&mut digest);
+    h[1].write_le(&mut digest[W::BYTES..]);
+    digest
+}
+
+///
+/// Hash of one leaf of a [`tree_hash`].
+///
+pub fn leaf_hash<W: Word>(chunk: &[u8], rounds: usize) -> Vec<u8> {
+    let mut input = Vec::with_capacity(1 + chunk.len());
+    input.push(0x00);
+    input.extend_from_slice(chunk);
+    mmo_hash::<W>(&input, rounds)
+}
+
+///
+/// Hash of an inner node of a [`tree_hash`] from its children's hashes.
+///
+pub fn node_hash<W: Word>(left: &[u8], right: &[u8], rounds: usize) -> Vec<u8> {
+    let mut input = Vec::with_capacity(1 + left.len() + right.len());
+    input.push(0x01);
+    input.extend_from_slice(left);
+    input.extend_from_slice(right);
+    mmo_hash::<W>(&input, rounds)
+}
+
+///
+/// Root of a binary hash tree over `chunks`.
+///
+/// The tree has the shape of RFC 6962's: the left subtree covers the largest
+/// power of two of chunks smaller than their count. Leaves and inner nodes are
+/// hashed with different prefixes, so a node can't pass for a leaf. An empty
+/// list hashes to the hash of the empty message.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::hash::{leaf_hash, node_hash, tree_hash};
+///
+/// let chunks: [&[u8]; 2] = [b"chunk 0", b"chunk 1"];
+/// let root = tree_hash::<u64>(&chunks, 16);
+///
+/// let leaves = [leaf_hash::<u64>(chunks[0], 16), leaf_hash::<u64>(chunks[1], 16)];
+/// assert_eq!(root, node_hash::<u64>(&leaves[0], &leaves[1], 16));
+/// ```
+///
+pub fn tree_hash<W: Word>(chunks: &[&[u8]], rounds: usize) -> Vec<u8> {
+    match chunks.len() {
+        0 => mmo_hash::<W>(&[], rounds),
+        1 => leaf_hash::<W>(chunks[0], rounds),
+        n => {
+            let split = 1 << (n - 1).ilog2();
+            let left = tree_hash::<W>(&chunks[..split], rounds);
+            let right = tree_hash::<W>(&chunks[split..], rounds);
+            node_hash::<W>(&left, &right, rounds)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_is_encryption_xor_block() {
+        let h = [0x0706050403020100u64, 0x0f0e0d0c0b0a0908];
+        let m = [1u64, 2];
+        let key: Vec<u8> = (0..16).collect();
+        let ct = encrypt_kernel(m, &expand_key(&key, 12));
+        assert_eq!(mmo_compress(h, m, 12), [ct[0] ^ 1, ct[1] ^ 2]);
+    }
+
+    #[test]
+    fn padding_separates_lengths() {
+        // a message ending in what looks like padding
+        let a = mmo_hash::<u32>(b"abc", 12);
+        let b = mmo_hash::<u32>(b"abc\x80\0\0\0\0", 12);
+        let c = mmo_hash::<u32>(b"", 12);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, mmo_hash::<u32>(b"abc", 12));
+        assert_ne!(a, mmo_hash::<u32>(b"abc", 16));
+    }
+
+    #[test]
+    fn tree_shape() {
+        let chunks: Vec<Vec<u8>> = (0..5u8).map(|i| vec![i; 10]).collect();
+        let refs: Vec<&[u8]> = chunks.iter().map(|c| &c[..]).collect();
+        let leaf = |i: usize| leaf_hash::<u64>(&chunks[i], 12);
+        let node = |l: &[u8], r: &[u8]| node_hash::<u64>(l, r, 12);
+
+        // ((0 1) (2 3)) 4
+        let expected = node(
+            &node(&node(&leaf(0), &leaf(1)), &node(&leaf(2), &leaf(3))),
+            &leaf(4),
+        );
+        assert_eq!(tree_hash::<u64>(&refs, 12), expected);
+
+        // (0 1) 2
+        let expected = node(&node(&leaf(0), &leaf(1)), &leaf(2));
+        assert_eq!(tree_hash::<u64>(&refs[..3], 12), expected);
+
+        assert_eq!(tree_hash::<u64>(&refs[..1], 12), leaf(0));
+        assert_eq!(tree_hash::<u64>(&[], 12), mmo_hash::<u64>(b"", 12));
+    }
+}
@@ -0,0 +1,592 @@
+//!
+//! Compact binary header shared by the envelope and stream formats.
+//!
+//! ```text
+//! offset  size  field
+//! 0       2     magic "R5"
+//! 2       1     version
+//! 3       1     word size in bytes (1, 2, 4, 8 or 16)
+//! 4       1     rounds
+//! 5       1     mode
+//! 6       1     MAC algorithm
+//! 7       1     number of extension fields
+//! 8       ...   extension fields: type (1), length (1), value (length)
+//! ```
+//!
+//! A header whose parameters have an algorithm ID in [`crate::suite`] and that
+//! has no extensions can be written in compact form instead, with a zero word
+//! size:
+//!
+//! ```text
+//! offset  size  field
+//! 0       2     magic "R5"
+//! 2       1     version
+//! 3       1     0
+//! 4       1     algorithm ID
+//! ```
+//!
+//! Extension types with the high bit set are *critical*: a reader that doesn't
+//! understand one must reject the header. Unknown non-critical extensions are
+//! preserved but otherwise ignored, so new optional fields can be added without
+//! breaking older readers.
+//!
+//! Each extension type appears at most once, except those in
+//! [`Header::REPEATABLE`]; a second copy could otherwise mean one thing to
+//! a reader taking the first and another to one taking the last. Readers
+//! facing untrusted input can cap the header size and extension count with
+//! [`HeaderLimits`].
+//!
+
+use crate::error::{Error, HeaderError};
+use crate::suite::{self, Suite};
+use crate::word::Word;
+
+const MAGIC: [u8; 2] = *b"R5";
+const FIXED_LEN: usize = 8;
+const COMPACT_LEN: usize = 5;
+
+///
+/// Mask of the extension type bit marking a field as critical.
+///
+pub const CRITICAL: u8 = 0x80;
+
+///
+/// Salt of the HKDF-derived file key, see [`crate::kdf`]. Critical, since data
+/// encrypted under a derived key can't be read without it.
+///
+pub const EXT_KDF_SALT: u8 = CRITICAL | 0x01;
+
+///
+/// Argon2id parameters and salt of a passphrase-derived key, see
+/// [`crate::passphrase`]. Critical for the same reason as [`EXT_KDF_SALT`].
+///
+pub const EXT_ARGON2ID: u8 = CRITICAL | 0x02;
+
+///
+/// ID of the key the data is encrypted under, see [`crate::key::Keyring`].
+/// Not critical: a reader holding the right key can ignore it.
+///
+pub const EXT_KEY_ID: u8 = 0x03;
+
+///
+/// One metadata entry of an authenticated envelope, see
+/// [`crate::envelope::encrypt_authenticated`]: name length (1), UTF-8 name,
+/// value. Not critical, so any reader can skip or show it.
+///
+pub const EXT_METADATA: u8 = 0x04;
+
+///
+/// Block cipher mode of operation recorded in a header.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Ecb = 1,
+    Cbc = 2,
+    Cfb = 3,
+    Ctr = 4,
+}
+
+impl Mode {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            1 => Some(Mode::Ecb),
+            2 => Some(Mode::Cbc),
+            3 => Some(Mode::Cfb),
+            4 => Some(Mode::Ctr),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Message authentication algorithm recorded in a header.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacAlg {
+    None = 0,
+    CbcMac = 1,
+    Cmac = 2,
+    HmacSha256 = 3,
+}
+
+impl MacAlg {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(MacAlg::None),
+            1 => Some(MacAlg::CbcMac),
+            2 => Some(MacAlg::Cmac),
+            3 => Some(MacAlg::HmacSha256),
+            _ => None,
+        }
+    }
+}
+
+///
+/// Type-length-value field following the fixed part of a header.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub kind: u8,
+    pub value: Vec<u8>,
+}
+
+impl Extension {
+    pub fn is_critical(&self) -> bool {
+        self.kind & CRITICAL != 0
+    }
+}
+
+///
+/// Bounds [`Header::parse_with_limits`] enforces on top of the format.
+///
+/// The default is the largest header the format can express, 8 bytes plus
+/// 255 extensions of 255 bytes each, so anything this crate writes parses.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::error::HeaderError;
+/// use rc5_cipher::header::{Extension, Header, HeaderLimits, MacAlg, Mode};
+///
+/// let mut header = Header::new::<u32>(12, Mode::Ctr, MacAlg::Cmac);
+/// header.extensions.push(Extension { kind: 0x10, value: vec![0; 200] });
+///
+/// let limits = HeaderLimits { max_len: 64, max_extensions: 4 };
+/// assert_eq!(
+///     Header::parse_with_limits(&header.to_bytes(), &limits),
+///     Err(HeaderError::TooLong(64))
+/// );
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderLimits {
+    /// Longest header accepted, in bytes.
+    pub max_len: usize,
+    /// Most extensions accepted.
+    pub max_extensions: u8,
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        HeaderLimits {
+            max_len: Header::MAX_LEN,
+            max_extensions: u8::MAX,
+        }
+    }
+}
+
+///
+/// Parameters needed to decrypt the data that follows the header.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub word_bytes: u8,
+    pub rounds: u8,
+    pub mode: Mode,
+    pub mac: MacAlg,
+    pub extensions: Vec<Extension>,
+}
+
+impl Header {
+    ///
+    /// Highest header version this crate reads and the one it writes.
+    ///
+    pub const VERSION: u8 = 1;
+
+    ///
+    /// Longest header the format can express.
+    ///
+    pub const MAX_LEN: usize = FIXED_LEN + u8::MAX as usize * (2 + u8::MAX as usize);
+
+    ///
+    /// Extension types that may appear more than once.
+    ///
+    pub const REPEATABLE: &'static [u8] = &[EXT_METADATA];
+
+    ///
+    /// Critical extension types this version understands.
+    ///
+    pub const KNOWN_CRITICAL: &'static [u8] = &[
+        #[cfg(feature = "hkdf")]
+        EXT_KDF_SALT,
+        #[cfg(feature = "argon2")]
+        EXT_ARGON2ID,
+    ];
+
+    ///
+    /// Creates a header for word type `W` with no extensions.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::header::{Header, MacAlg, Mode};
+    ///
+    /// let header = Header::new::<u32>(12, Mode::Cbc, MacAlg::Cmac);
+    /// let bytes = header.to_bytes();
+    ///
+    /// assert_eq!(bytes, [b'R', b'5', 1, 4, 12, 2, 2, 0]);
+    /// assert_eq!(Header::parse(&bytes).unwrap(), (header, bytes.len()));
+    /// ```
+    ///
+    pub fn new<W: Word>(rounds: u8, mode: Mode, mac: MacAlg) -> Self {
+        Header {
+            version: Self::VERSION,
+            word_bytes: W::BYTES as u8,
+            rounds,
+            mode,
+            mac,
+            extensions: Vec::new(),
+        }
+    }
+
+    ///
+    /// Returns the value of the first extension of type `kind`, if any.
+    ///
+    pub fn extension(&self, kind: u8) -> Option<&[u8]> {
+        self.extensions
+            .iter()
+            .find(|ext| ext.kind == kind)
+            .map(|ext| &ext.value[..])
+    }
+
+    ///
+    /// Serializes the header. Panics if an extension value or the number of
+    /// extensions doesn't fit in a byte.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        assert!(self.extensions.len() <= u8::MAX as usize);
+        let mut out = Vec::with_capacity(FIXED_LEN);
+        out.extend_from_slice(&MAGIC);
+        out.push(self.version);
+        out.push(self.word_bytes);
+        out.push(self.rounds);
+        out.push(self.mode as u8);
+        out.push(self.mac as u8);
+        out.push(self.extensions.len() as u8);
+        for ext in &self.extensions {
+            assert!(ext.value.len() <= u8::MAX as usize);
+            out.push(ext.kind);
+            out.push(ext.value.len() as u8);
+            out.extend_from_slice(&ext.value);
+        }
+        out
+    }
+
+    ///
+    /// Serializes the header in compact form, or returns `None` if it has
+    /// extensions or its parameters have no algorithm ID.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::header::{Header, MacAlg, Mode};
+    ///
+    /// let header = Header::new::<u64>(24, Mode::Ctr, MacAlg::Cmac);
+    /// let bytes = header.to_compact_bytes().unwrap();
+    ///
+    /// assert_eq!(bytes, [b'R', b'5', 1, 0, 0x01]);
+    /// assert_eq!(Header::parse(&bytes).unwrap(), (header, bytes.len()));
+    /// ```
+    ///
+    pub fn to_compact_bytes(&self) -> Option<Vec<u8>> {
+        if !self.extensions.is_empty() {
+            return None;
+        }
+        let id = suite::id_of(&Suite::of(self))?;
+        let mut out = Vec::with_capacity(COMPACT_LEN);
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&[self.version, 0, id]);
+        Some(out)
+    }
+
+    ///
+    /// Parses a header from the start of `bytes`, returning it together with
+    /// the number of bytes it occupies.
+    ///
+    /// Fails with [`Error::UnsupportedParameters`] for a compact header whose
+    /// algorithm ID is unknown, e.g. a private one not registered in this
+    /// process. [`parse_with_limits`](Self::parse_with_limits) tells the other
+    /// [`Error::InvalidHeader`] cases apart.
+    ///
+    pub fn parse(bytes: &[u8]) -> Result<(Header, usize), Error> {
+        Ok(Self::parse_with_limits(bytes, &HeaderLimits::default())?)
+    }
+
+    ///
+    /// Like [`parse`](Self::parse), but within `limits` and with the reason
+    /// for a rejection.
+    ///
+    pub fn parse_with_limits(
+        bytes: &[u8],
+        limits: &HeaderLimits,
+    ) -> Result<(Header, usize), HeaderError> {
+        let byte = |at: usize| bytes.get(at).copied().ok_or(HeaderError::Truncated(at));
+        if bytes.get(..2).ok_or(HeaderError::Truncated(0))? != MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+
+        let version = byte(2)?;
+        if version == 0 || version > Self::VERSION {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+
+        if byte(3)? == 0 {
+            let id = byte(4)?;
+            let suite = suite::lookup(id).ok_or(HeaderError::UnknownSuite(id))?;
+            let header = Header {
+                version,
+                ..suite.header()
+            };
+            return Ok((header, COMPACT_LEN));
+        }
+
+        let word_bytes = byte(3)?;
+        if !matches!(word_bytes, 1 | 2 | 4 | 8 | 16) {
+            return Err(HeaderError::InvalidWordSize(word_bytes));
+        }
+        let rounds = byte(4)?;
+        let mode = byte(5)?;
+        let mode = Mode::from_u8(mode).ok_or(HeaderError::InvalidMode(mode))?;
+        let mac = byte(6)?;
+        let mac = MacAlg::from_u8(mac).ok_or(HeaderError::InvalidMac(mac))?;
+
+        let count = byte(7)?;
+        if count > limits.max_extensions {
+            return Err(HeaderError::TooManyFields(count));
+        }
+        let mut pos = FIXED_LEN;
+        let mut seen = [false; 256];
+        let mut extensions = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            let kind = byte(pos)?;
+            let len = usize::from(byte(pos + 1)?);
+            pos += 2;
+            if pos + len > limits.max_len {
+                return Err(HeaderError::TooLong(limits.max_len));
+            }
+            let value = bytes
+                .get(pos..pos + len)
+                .ok_or(HeaderError::Truncated(pos))?;
+            if kind & CRITICAL != 0 && !Self::KNOWN_CRITICAL.contains(&kind) {
+                return Err(HeaderError::UnknownCriticalField(kind));
+            }
+            if seen[usize::from(kind)] && !Self::REPEATABLE.contains(&kind) {
+                return Err(HeaderError::DuplicateField(kind));
+            }
+            seen[usize::from(kind)] = true;
+            extensions.push(Extension {
+                kind,
+                value: value.to_vec(),
+            });
+            pos += len;
+        }
+        if pos > limits.max_len {
+            return Err(HeaderError::TooLong(limits.max_len));
+        }
+
+        let header = Header {
+            version,
+            word_bytes,
+            rounds,
+            mode,
+            mac,
+            extensions,
+        };
+        Ok((header, pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_with_extensions() {
+        let mut header = Header::new::<u64>(24, Mode::Ctr, MacAlg::None);
+        header.extensions.push(Extension {
+            kind: 0x10,
+            value: vec![0xaa; 16],
+        });
+        header.extensions.push(Extension {
+            kind: 0x11,
+            value: vec![],
+        });
+
+        let mut bytes = header.to_bytes();
+        assert_eq!(bytes.len(), 8 + 2 + 16 + 2);
+
+        // trailing payload is not part of the header
+        bytes.extend_from_slice(&[1, 2, 3]);
+        let (parsed, len) = Header::parse(&bytes).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(len, bytes.len() - 3);
+        assert_eq!(parsed.extension(0x10), Some(&[0xaa; 16][..]));
+        assert_eq!(parsed.extension(0x12), None);
+    }
+
+    #[test]
+    fn unknown_critical_extension_is_rejected() {
+        let mut header = Header::new::<u32>(12, Mode::Cbc, MacAlg::Cmac);
+        header.extensions.push(Extension {
+            kind: CRITICAL | 0x7f,
+            value: vec![0],
+        });
+
+        assert_eq!(
+            Header::parse(&header.to_bytes()),
+            Err(Error::UnknownCriticalField(0xff))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let bytes = Header::new::<u32>(12, Mode::Ecb, MacAlg::None).to_bytes();
+
+        assert_eq!(Header::parse(&bytes[..7]), Err(Error::InvalidHeader));
+
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+
+        let mut bad = bytes.clone();
+        bad[2] = 2;
+        assert_eq!(Header::parse(&bad), Err(Error::UnsupportedVersion(2)));
+
+        let mut bad = bytes.clone();
+        bad[3] = 3;
+        assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+
+        let mut bad = bytes.clone();
+        bad[5] = 0x42;
+        assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+
+        // extension count pointing past the end
+        let mut bad = bytes.clone();
+        bad[7] = 1;
+        assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+        bad.extend_from_slice(&[0x01, 4, 0, 0]);
+        assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn reports_why_parsing_failed() {
+        let bytes = Header::new::<u32>(12, Mode::Ecb, MacAlg::None).to_bytes();
+        let parse = |bytes: &[u8]| Header::parse_with_limits(bytes, &HeaderLimits::default());
+
+        assert_eq!(parse(b"R"), Err(HeaderError::Truncated(0)));
+        assert_eq!(parse(&bytes[..6]), Err(HeaderError::Truncated(6)));
+        assert_eq!(parse(b"R6\x01"), Err(HeaderError::BadMagic));
+        for (at, val, err) in [
+            (3, 3, HeaderError::InvalidWordSize(3)),
+            (5, 9, HeaderError::InvalidMode(9)),
+            (6, 9, HeaderError::InvalidMac(9)),
+        ] {
+            let mut bad = bytes.clone();
+            bad[at] = val;
+            assert_eq!(parse(&bad), Err(err));
+            assert_eq!(Header::parse(&bad), Err(Error::InvalidHeader));
+        }
+
+        let mut bad = bytes.clone();
+        bad[7] = 1;
+        bad.extend_from_slice(&[0x01, 4, 0, 0]);
+        assert_eq!(parse(&bad), Err(HeaderError::Truncated(10)));
+    }
+
+    #[test]
+    fn rejects_duplicate_fields() {
+        let mut header = Header::new::<u32>(12, Mode::Ctr, MacAlg::Cmac);
+        for kind in [EXT_KEY_ID, 0x10, EXT_KEY_ID] {
+            header.extensions.push(Extension {
+                kind,
+                value: vec![kind],
+            });
+        }
+        let limits = HeaderLimits::default();
+        assert_eq!(
+            Header::parse_with_limits(&header.to_bytes(), &limits),
+            Err(HeaderError::DuplicateField(EXT_KEY_ID))
+        );
+        assert_eq!(Header::parse(&header.to_bytes()), Err(Error::InvalidHeader));
+
+        // metadata entries repeat by design
+        header.extensions[2].kind = EXT_METADATA;
+        header.extensions.push(Extension {
+            kind: EXT_METADATA,
+            value: vec![0],
+        });
+        let bytes = header.to_bytes();
+        assert_eq!(Header::parse(&bytes), Ok((header, bytes.len())));
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let mut header = Header::new::<u64>(20, Mode::Ctr, MacAlg::Cmac);
+        for kind in 0x10..0x13 {
+            header.extensions.push(Extension {
+                kind,
+                value: vec![0; 10],
+            });
+        }
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), 8 + 3 * 12);
+
+        let limits = |max_len, max_extensions| HeaderLimits {
+            max_len,
+            max_extensions,
+        };
+        assert!(Header::parse_with_limits(&bytes, &limits(44, 3)).is_ok());
+        assert_eq!(
+            Header::parse_with_limits(&bytes, &limits(43, 3)),
+            Err(HeaderError::TooLong(43))
+        );
+        assert_eq!(
+            Header::parse_with_limits(&bytes, &limits(44, 2)),
+            Err(HeaderError::TooManyFields(3))
+        );
+        assert_eq!(
+            Header::parse_with_limits(
+                &Header::new::<u8>(12, Mode::Ctr, MacAlg::None).to_bytes(),
+                &limits(7, 3)
+            ),
+            Err(HeaderError::TooLong(7))
+        );
+
+        // the default admits the largest header the format can express
+        let mut largest = Header::new::<u64>(20, Mode::Ctr, MacAlg::Cmac);
+        largest.extensions = (0..255)
+            .map(|_| Extension {
+                kind: EXT_METADATA,
+                value: vec![0; 255],
+            })
+            .collect();
+        let bytes = largest.to_bytes();
+        assert_eq!(bytes.len(), Header::MAX_LEN);
+        assert!(Header::parse(&bytes).is_ok());
+    }
+
+    #[test]
+    fn compact_form() {
+        let header = Header::new::<u64>(24, Mode::Ctr, MacAlg::None);
+        let mut bytes = header.to_compact_bytes().unwrap();
+        bytes.extend_from_slice(&[9, 9]);
+        assert_eq!(Header::parse(&bytes).unwrap(), (header.clone(), 5));
+
+        // no ID for these parameters, or extensions that need the full form
+        assert_eq!(
+            Header::new::<u64>(25, Mode::Ctr, MacAlg::None).to_compact_bytes(),
+            None
+        );
+        let mut extended = header;
+        extended.extensions.push(Extension {
+            kind: 0x10,
+            value: vec![],
+        });
+        assert_eq!(extended.to_compact_bytes(), None);
+
+        assert_eq!(Header::parse(&bytes[..4]), Err(Error::InvalidHeader));
+        bytes[4] = 0x7f;
+        assert_eq!(Header::parse(&bytes), Err(Error::UnsupportedParameters));
+    }
+}
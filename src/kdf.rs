@@ -0,0 +1,133 @@
+//!
+//! Per-file subkeys derived from a master key with HKDF-SHA256 (RFC 5869).
+//!
+//! Each file gets a random salt, recorded in its header as the critical
+//! [`EXT_KDF_SALT`] extension, and is encrypted under
+//! `HKDF(master, salt, "rc5-cipher file key")`. A leaked file key then exposes
+//! neither the master key nor any other file.
+//!
+//! Only available with the `hkdf` feature.
+//!
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::error::Error;
+use crate::header::{Extension, Header, EXT_KDF_SALT};
+use crate::rng::{CryptoRng, RngCore};
+
+///
+/// Length of the salts drawn by [`new_file_key`].
+///
+pub const SALT_LEN: usize = 32;
+
+const FILE_KEY_INFO: &[u8] = b"rc5-cipher file key";
+
+///
+/// Expands `master` into `len` bytes bound to `salt` and `info`. Fails if
+/// `len` exceeds the HKDF-SHA256 limit of 8160 bytes.
+///
+pub fn derive_subkey(
+    master: &[u8],
+    salt: &[u8],
+    info: &[u8],
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut okm = vec![0u8; len];
+    Hkdf::<Sha256>::new(Some(salt), master)
+        .expand(info, &mut okm)
+        .map_err(|_| Error::InvalidLength)?;
+    Ok(okm)
+}
+
+///
+/// Draws a fresh salt from `rng`, records it in `header` and returns the
+/// `len`-byte key to encrypt the file with.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::header::{Header, MacAlg, Mode};
+/// use rc5_cipher::kdf::{file_key, new_file_key};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let master = b"master key material";
+/// let mut header = Header::new::<u32>(12, Mode::Cbc, MacAlg::Cmac);
+/// let key = new_file_key(&mut rng, master, &mut header, 16).unwrap();
+/// let bytes = header.to_bytes();
+///
+/// // the reader only needs the master key and the header
+/// let (parsed, _) = Header::parse(&bytes).unwrap();
+/// assert_eq!(file_key(master, &parsed, 16).unwrap(), key);
+/// ```
+///
+pub fn new_file_key<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    master: &[u8],
+    header: &mut Header,
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut salt = vec![0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let key = derive_subkey(master, &salt, FILE_KEY_INFO, len)?;
+
+    header.extensions.retain(|ext| ext.kind != EXT_KDF_SALT);
+    header.extensions.push(Extension {
+        kind: EXT_KDF_SALT,
+        value: salt,
+    });
+    Ok(key)
+}
+
+///
+/// Recomputes the key of a file from `master` and the salt in its `header`.
+/// Fails with [`Error::InvalidHeader`] if the header has no salt.
+///
+pub fn file_key(master: &[u8], header: &Header, len: usize) -> Result<Vec<u8>, Error> {
+    let salt = header.extension(EXT_KDF_SALT).ok_or(Error::InvalidHeader)?;
+    derive_subkey(master, salt, FILE_KEY_INFO, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex_decode;
+    use crate::header::{MacAlg, Mode};
+    use crate::rng::CounterRng;
+
+    #[test]
+    fn rfc5869_case_1() {
+        let ikm = [0x0b; 22];
+        let salt = hex_decode("000102030405060708090a0b0c").unwrap();
+        let info = hex_decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+        let okm = hex_decode(
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865",
+        )
+        .unwrap();
+        assert_eq!(derive_subkey(&ikm, &salt, &info, 42).unwrap(), okm);
+        assert_eq!(
+            derive_subkey(&ikm, &salt, &info, 255 * 32 + 1),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn file_keys_differ_per_salt_and_roundtrip() {
+        let master = [0x42; 16];
+        let mut a = Header::new::<u32>(12, Mode::Cbc, MacAlg::Cmac);
+        let mut b = a.clone();
+        let plain = a.clone();
+
+        let key_a = new_file_key(&mut CounterRng(0), &master, &mut a, 16).unwrap();
+        let key_b = new_file_key(&mut CounterRng(100), &master, &mut b, 16).unwrap();
+        assert_ne!(key_a, key_b);
+        assert_ne!(key_a, master);
+
+        let (parsed, _) = Header::parse(&a.to_bytes()).unwrap();
+        assert_eq!(parsed.extension(EXT_KDF_SALT).unwrap().len(), SALT_LEN);
+        assert_eq!(file_key(&master, &parsed, 16).unwrap(), key_a);
+
+        assert_eq!(file_key(&master, &plain, 16), Err(Error::InvalidHeader));
+    }
+}
@@ -0,0 +1,249 @@
+//!
+//! Encryption to a recipient's public key, with the public-key part plugged in.
+//!
+//! This crate implements no public-key algorithm. A [`Kem`] implementation,
+//! e.g. X25519 DHKEM or RSA-KEM from another crate, provides a fresh shared
+//! secret and its encapsulation for the recipient; [`seal`] uses the secret as
+//! the RC5 content key and [`open`] recovers it with the recipient's secret
+//! key.
+//!
+//! ```text
+//! offset  size  field
+//! 0       2     encapsulation length, little-endian
+//! 2       ...   encapsulation
+//! ...     16    synthetic IV
+//! ...     ...   ciphertext
+//! ```
+//!
+//! The content is encrypted with [`DeterministicAead`] over RC5-64/24, the
+//! first half of the shared secret as the MAC key and the second as the
+//! encryption key, and the encapsulation as associated data. Every message
+//! has a fresh secret, so equal plaintexts still encrypt differently. Since
+//! anyone can encrypt to a public key, this authenticates the message, not the
+//! sender.
+//!
+
+use crate::error::{Error, Failure};
+use crate::rng::{CryptoRng, RngCore};
+use crate::siv::DeterministicAead;
+
+const ROUNDS: usize = 24;
+
+// Shortest shared secret: two 16-byte keys.
+const MIN_SECRET: usize = 32;
+
+///
+/// A key encapsulation mechanism.
+///
+/// The shared secret must be uniformly random and at least 32 bytes long, as
+/// the output of a KEM's key derivation is; a raw Diffie-Hellman result must
+/// be hashed first.
+///
+pub trait Kem {
+    type PublicKey: ?Sized;
+    type SecretKey: ?Sized;
+
+    /// Returns a fresh shared secret and its encapsulation for `recipient`.
+    fn encapsulate<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        recipient: &Self::PublicKey,
+    ) -> Result<(Vec<u8>, Vec<u8>), Error>;
+
+    /// Recovers the shared secret from its encapsulation.
+    fn decapsulate(&self, secret: &Self::SecretKey, encapsulation: &[u8])
+        -> Result<Vec<u8>, Error>;
+}
+
+///
+/// Encrypts `plaintext` to `recipient`.
+///
+/// Fails with [`Error::InvalidLength`] if the shared secret is shorter than
+/// 32 bytes or the encapsulation longer than 65535, and with the errors of
+/// [`Kem::encapsulate`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::error::Error;
+/// use rc5_cipher::hash::mmo_hash;
+/// use rc5_cipher::kem::{self, Kem};
+/// use rc5_cipher::rng::{CryptoRng, RngCore};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// // a stand-in with equal public and secret keys; use a real KEM
+/// struct Toy;
+///
+/// impl Kem for Toy {
+///     type PublicKey = [u8];
+///     type SecretKey = [u8];
+///
+///     fn encapsulate<R: RngCore + CryptoRng>(
+///         &self,
+///         rng: &mut R,
+///         recipient: &[u8],
+///     ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+///         let mut encapsulation = vec![0u8; 16];
+///         rng.fill_bytes(&mut encapsulation);
+///         Ok((self.decapsulate(recipient, &encapsulation)?, encapsulation))
+///     }
+///
+///     fn decapsulate(&self, secret: &[u8], encapsulation: &[u8]) -> Result<Vec<u8>, Error> {
+///         Ok(mmo_hash::<u128>(&[secret, encapsulation].concat(), 20))
+///     }
+/// }
+///
+/// let sealed = kem::seal(&Toy, &mut rng, b"recipient key", b"hello").unwrap();
+/// assert_eq!(kem::open(&Toy, b"recipient key", &sealed).unwrap(), b"hello");
+/// ```
+///
+pub fn seal<K: Kem, R: RngCore + CryptoRng>(
+    kem: &K,
+    rng: &mut R,
+    recipient: &K::PublicKey,
+    plaintext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let (mut shared, encapsulation) = kem.encapsulate(rng, recipient)?;
+    let aead = content_cipher(&shared);
+    shared.fill(0);
+    let aead = aead?;
+    let len = u16::try_from(encapsulation.len()).map_err(|_| Error::InvalidLength)?;
+
+    let mut out = Vec::with_capacity(2 + encapsulation.len() + 16 + plaintext.len());
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&encapsulation);
+    out.extend_from_slice(&aead.seal(&[&encapsulation], plaintext));
+    Ok(out)
+}
+
+///
+/// Decrypts the output of [`seal`] with the recipient's secret key.
+///
+/// Fails with a [`Failure::Crypto`] that doesn't say why if `sealed` is
+/// truncated, was altered or is for another recipient. Errors of
+/// [`Kem::decapsulate`] are classified like any other decryption error, see
+/// [`Failure::decrypting`].
+///
+pub fn open<K: Kem>(kem: &K, secret: &K::SecretKey, sealed: &[u8]) -> Result<Vec<u8>, Failure> {
+    open_body(kem, secret, sealed).map_err(Failure::decrypting)
+}
+
+fn open_body<K: Kem>(kem: &K, secret: &K::SecretKey, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed.len() < 2 {
+        return Err(Error::InvalidLength);
+    }
+    let len = usize::from(u16::from_le_bytes([sealed[0], sealed[1]]));
+    if sealed.len() < 2 + len {
+        return Err(Error::InvalidLength);
+    }
+    let (encapsulation, body) = sealed[2..].split_at(len);
+
+    let mut shared = kem.decapsulate(secret, encapsulation)?;
+    let aead = content_cipher(&shared);
+    shared.fill(0);
+    aead?.open(&[encapsulation], body)
+}
+
+fn content_cipher(shared: &[u8]) -> Result<DeterministicAead<u64>, Error> {
+    if shared.len() < MIN_SECRET {
+        return Err(Error::InvalidLength);
+    }
+    let (mac_key, enc_key) = shared.split_at(shared.len() / 2);
+    DeterministicAead::new(mac_key, enc_key, ROUNDS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CryptoError;
+    use crate::hash::mmo_hash;
+    use crate::rng::CounterRng;
+
+    // Public and secret keys are equal, the secret is `secret_len` bytes.
+    struct Toy {
+        secret_len: usize,
+    }
+
+    impl Kem for Toy {
+        type PublicKey = [u8];
+        type SecretKey = [u8];
+
+        fn encapsulate<R: RngCore + CryptoRng>(
+            &self,
+            rng: &mut R,
+            recipient: &[u8],
+        ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+            let mut encapsulation = vec![0u8; 12];
+            rng.fill_bytes(&mut encapsulation);
+            Ok((self.decapsulate(recipient, &encapsulation)?, encapsulation))
+        }
+
+        fn decapsulate(&self, secret: &[u8], encapsulation: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut shared = mmo_hash::<u128>(&[secret, encapsulation].concat(), 20);
+            shared.resize(self.secret_len, 0);
+            Ok(shared)
+        }
+    }
+
+    const KEM: Toy = Toy { secret_len: 32 };
+
+    #[test]
+    fn roundtrip_and_layout() {
+        let mut rng = CounterRng(0);
+        for len in [0, 1, 100] {
+            let pt: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = seal(&KEM, &mut rng, b"alice", &pt).unwrap();
+            assert_eq!(sealed[..2], [12, 0]);
+            assert_eq!(sealed.len(), 2 + 12 + 16 + len);
+            assert_eq!(open(&KEM, b"alice", &sealed).unwrap(), pt);
+        }
+
+        // a fresh secret per message
+        let a = seal(&KEM, &mut rng, b"alice", b"same").unwrap();
+        let b = seal(&KEM, &mut rng, b"alice", b"same").unwrap();
+        assert_ne!(a[14..], b[14..]);
+    }
+
+    #[test]
+    fn rejects_tampering_and_wrong_recipient() {
+        let sealed = seal(&KEM, &mut CounterRng(0), b"alice", b"for alice").unwrap();
+        assert_eq!(
+            open(&KEM, b"bob", &sealed),
+            Err(Failure::Crypto(CryptoError))
+        );
+        assert_eq!(
+            open_body(&KEM, b"bob", &sealed),
+            Err(Error::AuthenticationFailed)
+        );
+        for i in [2, 13, 14, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 1;
+            assert_eq!(
+                open_body(&KEM, b"alice", &bad),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        assert_eq!(
+            open_body(&KEM, b"alice", &sealed[..1]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            open_body(&KEM, b"alice", &sealed[..13]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            open_body(&KEM, b"alice", &sealed[..29]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn rejects_short_secrets() {
+        let short = Toy { secret_len: 31 };
+        assert_eq!(
+            seal(&short, &mut CounterRng(0), b"alice", b"x"),
+            Err(Error::InvalidLength)
+        );
+    }
+}
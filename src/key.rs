@@ -0,0 +1,460 @@
+//!
+//! Key material that is wiped when dropped, and loading it from key files.
+//!
+//! [`Rc5Key::from_reader`] accepts the formats keys usually come in: the raw
+//! bytes, or hex or base64 text as written by e.g. `openssl rand -hex 16` or
+//! `head -c 16 /dev/urandom | base64`, with surrounding whitespace. Every
+//! buffer holding key bytes or their encoding is zeroized on the way.
+//!
+//! A [`Keyring`] holds several keys by [`KeyId`] and label. Envelopes written
+//! with [`encrypt_with_keyring`](crate::envelope::encrypt_with_keyring) name
+//! their key in the header, so readers pick it without trial decryption, and
+//! keys can be rolled over in stages: add the new key, make it primary once
+//! every reader has it, and remove the old one once no data needs it.
+//!
+//! Only available with the `key` feature.
+//!
+
+use std::fmt;
+use std::io::Read;
+
+use zeroize::Zeroizing;
+
+use crate::encoding::{base64url_decode, hex_decode, hex_encode};
+use crate::error::Error;
+use crate::header::{Header, EXT_KEY_ID};
+use crate::kdf::derive_subkey;
+use crate::rc5::MAX_KEY_BYTES;
+
+const KEY_ID_INFO: &[u8] = b"rc5 key id";
+
+// Longest key file read, enough for a 255-byte key as padded base64 with
+// line breaks.
+const MAX_KEY_FILE: usize = 1024;
+
+///
+/// Encoding of a key file.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Hex if the trimmed text is all hex digits, else base64 if it is all
+    /// base64 characters, else raw bytes.
+    Auto,
+    Raw,
+    /// Upper or lower case hex digits.
+    Hex,
+    /// Standard or URL-safe alphabet, padded or not.
+    Base64,
+}
+
+///
+/// An RC5 key, between 1 and [`MAX_KEY_BYTES`] bytes long.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::key::{KeyFormat, Rc5Key};
+///
+/// let file = "  00112233445566778899aabbccddeeff\n";
+/// let key = Rc5Key::from_reader(file.as_bytes(), KeyFormat::Auto).unwrap();
+/// assert_eq!(key.len(), 16);
+/// assert_eq!(key.as_bytes()[15], 0xff);
+///
+/// let same = Rc5Key::from_reader(&b"ABEiM0RVZneImaq7zN3u/w=="[..], KeyFormat::Auto).unwrap();
+/// assert_eq!(same, key);
+/// ```
+///
+#[derive(Clone)]
+pub struct Rc5Key(Zeroizing<Vec<u8>>);
+
+// constant time in the key bytes, only the lengths may leak
+impl PartialEq for Rc5Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+impl Eq for Rc5Key {}
+
+impl Rc5Key {
+    ///
+    /// Fails with [`Error::InvalidLength`] if `bytes` is empty or longer than
+    /// [`MAX_KEY_BYTES`].
+    ///
+    pub fn new(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.is_empty() || bytes.len() > MAX_KEY_BYTES {
+            return Err(Error::InvalidLength);
+        }
+        Ok(Rc5Key(Zeroizing::new(bytes.to_vec())))
+    }
+
+    ///
+    /// Reads a key file in `format`.
+    ///
+    /// Fails with [`Error::Io`] if reading fails, [`Error::InvalidEncoding`]
+    /// if the text doesn't decode, and [`Error::InvalidLength`] if the file is
+    /// over 1 KiB or the key is empty or too long.
+    ///
+    pub fn from_reader<R: Read>(mut reader: R, format: KeyFormat) -> Result<Self, Error> {
+        // a fixed buffer, so no reallocation leaves copies behind
+        let mut buf = Zeroizing::new([0u8; MAX_KEY_FILE + 1]);
+        let mut len = 0;
+        loop {
+            match reader.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+            if len > MAX_KEY_FILE {
+                return Err(Error::InvalidLength);
+            }
+        }
+        let bytes = &buf[..len];
+        let text = bytes.trim_ascii();
+
+        let format = match format {
+            KeyFormat::Auto if is_hex(text) => KeyFormat::Hex,
+            KeyFormat::Auto if is_base64(text) => KeyFormat::Base64,
+            KeyFormat::Auto => KeyFormat::Raw,
+            explicit => explicit,
+        };
+        let key = match format {
+            KeyFormat::Raw => return Self::new(bytes),
+            KeyFormat::Hex => Zeroizing::new(hex_decode(as_str(text)?)?),
+            _ => {
+                let url_safe: Zeroizing<Vec<u8>> = Zeroizing::new(
+                    text.iter()
+                        .map(|&c| match c {
+                            b'+' => b'-',
+                            b'/' => b'_',
+                            c => c,
+                        })
+                        .collect(),
+                );
+                Zeroizing::new(base64url_decode(as_str(&url_safe)?)?)
+            }
+        };
+        Self::new(&key)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    ///
+    /// A public identifier of the key: 8 bytes derived from it with
+    /// HKDF-SHA256 under the dedicated info label `"rc5 key id"`, so it is
+    /// unrelated to any other value computed from the key.
+    ///
+    /// The ID is published in headers. Like any fast function of the key, it
+    /// lets a key with little entropy, e.g. a password, be guessed offline:
+    /// use uniformly random keys, or stretch passwords with Argon2id first.
+    ///
+    pub fn id(&self) -> KeyId {
+        let okm = derive_subkey(&self.0, &[], KEY_ID_INFO, 8)
+            .expect("8 bytes is within the HKDF-SHA256 output limit");
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&okm);
+        KeyId(id)
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl fmt::Debug for Rc5Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rc5Key({} bytes)", self.0.len())
+    }
+}
+
+///
+/// Fingerprint of an [`Rc5Key`], stored in the [`EXT_KEY_ID`] header
+/// extension. Displayed as hex.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyId(pub [u8; 8]);
+
+impl fmt::Display for KeyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex_encode(&self.0))
+    }
+}
+
+struct Entry {
+    id: KeyId,
+    label: Option<String>,
+    key: Rc5Key,
+}
+
+///
+/// Keys by ID and label, one of them the primary key new data is encrypted
+/// under.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::key::{Keyring, Rc5Key};
+///
+/// let mut keyring = Keyring::new();
+/// let old = keyring.insert(Some("2026-09"), Rc5Key::new(b"september key 01").unwrap());
+/// keyring.set_primary(&old).unwrap();
+///
+/// // rollover: the new key first only decrypts, then becomes primary
+/// let new = keyring.insert(Some("2026-10"), Rc5Key::new(b"october key 0001").unwrap());
+/// keyring.set_primary(&new).unwrap();
+/// assert_eq!(keyring.primary().unwrap().0, new);
+///
+/// assert_eq!(keyring.by_label("2026-09").unwrap().id(), old);
+/// keyring.remove(&old);
+/// assert!(keyring.get(&old).is_none());
+/// ```
+///
+#[derive(Default)]
+pub struct Keyring {
+    entries: Vec<Entry>,
+    primary: Option<KeyId>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Adds `key`, replacing a key with the same ID, and returns its ID.
+    ///
+    /// A `label` already held by another key moves to this one, so a label
+    /// like `"current"` can follow a rollover.
+    ///
+    pub fn insert(&mut self, label: Option<&str>, key: Rc5Key) -> KeyId {
+        let id = key.id();
+        self.entries.retain(|e| e.id != id);
+        if let Some(label) = label {
+            for entry in &mut self.entries {
+                if entry.label.as_deref() == Some(label) {
+                    entry.label = None;
+                }
+            }
+        }
+        self.entries.push(Entry {
+            id,
+            label: label.map(str::to_owned),
+            key,
+        });
+        id
+    }
+
+    ///
+    /// Removes a key, e.g. once no data encrypted under it remains. Removing
+    /// the primary key leaves the keyring without one.
+    ///
+    pub fn remove(&mut self, id: &KeyId) -> Option<Rc5Key> {
+        if self.primary.as_ref() == Some(id) {
+            self.primary = None;
+        }
+        let pos = self.entries.iter().position(|e| e.id == *id)?;
+        Some(self.entries.remove(pos).key)
+    }
+
+    ///
+    /// Makes `id` the key new data is encrypted under.
+    ///
+    /// Fails with [`Error::UnknownKey`] if the keyring doesn't hold it.
+    ///
+    pub fn set_primary(&mut self, id: &KeyId) -> Result<(), Error> {
+        self.get(id).ok_or(Error::UnknownKey)?;
+        self.primary = Some(*id);
+        Ok(())
+    }
+
+    pub fn primary(&self) -> Option<(KeyId, &Rc5Key)> {
+        let id = self.primary?;
+        self.get(&id).map(|key| (id, key))
+    }
+
+    pub fn get(&self, id: &KeyId) -> Option<&Rc5Key> {
+        self.entries.iter().find(|e| e.id == *id).map(|e| &e.key)
+    }
+
+    pub fn by_label(&self, label: &str) -> Option<&Rc5Key> {
+        self.entries
+            .iter()
+            .find(|e| e.label.as_deref() == Some(label))
+            .map(|e| &e.key)
+    }
+
+    ///
+    /// IDs of the keys held, in insertion order.
+    ///
+    pub fn ids(&self) -> impl Iterator<Item = KeyId> + '_ {
+        self.entries.iter().map(|e| e.id)
+    }
+
+    ///
+    /// The key named by the [`EXT_KEY_ID`] extension of `header`.
+    ///
+    /// Fails with [`Error::UnknownKey`] if the header names no key or one the
+    /// keyring doesn't hold.
+    ///
+    pub fn key_for(&self, header: &Header) -> Result<&Rc5Key, Error> {
+        let value = header.extension(EXT_KEY_ID).ok_or(Error::UnknownKey)?;
+        let id: [u8; 8] = value.try_into().map_err(|_| Error::InvalidHeader)?;
+        self.get(&KeyId(id)).ok_or(Error::UnknownKey)
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("ids", &self.ids().collect::<Vec<_>>())
+            .field("primary", &self.primary)
+            .finish()
+    }
+}
+
+fn is_hex(text: &[u8]) -> bool {
+    !text.is_empty() && text.len().is_multiple_of(2) && text.iter().all(u8::is_ascii_hexdigit)
+}
+
+fn is_base64(text: &[u8]) -> bool {
+    !text.is_empty()
+        && text
+            .iter()
+            .all(|&c| c.is_ascii_alphanumeric() || matches!(c, b'+' | b'/' | b'-' | b'_' | b'='))
+}
+
+fn as_str(text: &[u8]) -> Result<&str, Error> {
+    std::str::from_utf8(text).map_err(|_| Error::InvalidEncoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &[u8], format: KeyFormat) -> Result<Vec<u8>, Error> {
+        Rc5Key::from_reader(file, format).map(|k| k.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn detects_formats() {
+        let key: Vec<u8> = (0xf0..=0xff).collect();
+        assert_eq!(
+            load(b"F0F1F2F3F4F5F6F7F8F9FAFBFCFDFEFF", KeyFormat::Auto),
+            Ok(key.clone())
+        );
+        assert_eq!(
+            load(b"\t8PHy8/T19vf4+fr7/P3+/w==\r\n", KeyFormat::Auto),
+            Ok(key.clone())
+        );
+        assert_eq!(
+            load(b"8PHy8_T19vf4-fr7_P3-_w", KeyFormat::Auto),
+            Ok(key.clone())
+        );
+        assert_eq!(load(&key, KeyFormat::Auto), Ok(key.clone()));
+
+        // raw keys are taken as is, whitespace included
+        assert_eq!(
+            load(b" raw key\n", KeyFormat::Auto),
+            Ok(b" raw key\n".to_vec())
+        );
+        assert_eq!(load(b"abcd", KeyFormat::Raw), Ok(b"abcd".to_vec()));
+        assert_eq!(load(b"abcd", KeyFormat::Auto), Ok(vec![0xab, 0xcd]));
+        assert_eq!(load(b"abcd", KeyFormat::Base64), Ok(vec![0x69, 0xb7, 0x1d]));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert_eq!(load(b"", KeyFormat::Auto), Err(Error::InvalidLength));
+        assert_eq!(load(b"  \n", KeyFormat::Hex), Err(Error::InvalidLength));
+        assert_eq!(load(b"abc", KeyFormat::Hex), Err(Error::InvalidEncoding));
+        assert_eq!(
+            load(b"a*b=", KeyFormat::Base64),
+            Err(Error::InvalidEncoding)
+        );
+        assert_eq!(
+            load(&[b'a'; 512], KeyFormat::Hex),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            load(&[b'a'; 2000], KeyFormat::Auto),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            load(&[0xff; 300], KeyFormat::Raw),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(load(&[0xff; 255], KeyFormat::Raw).unwrap().len(), 255);
+    }
+
+    #[test]
+    fn debug_hides_the_key() {
+        let key = Rc5Key::new(b"secret").unwrap();
+        assert_eq!(format!("{:?}", key), "Rc5Key(6 bytes)");
+    }
+
+    #[test]
+    fn key_equality() {
+        let key = Rc5Key::new(b"secret").unwrap();
+        assert_eq!(key, Rc5Key::new(b"secret").unwrap());
+        assert_ne!(key, Rc5Key::new(b"secreT").unwrap());
+        assert_ne!(key, Rc5Key::new(b"secret!").unwrap());
+    }
+
+    #[test]
+    fn keyring_lookup_and_rollover() {
+        let a = Rc5Key::new(b"key a").unwrap();
+        let b = Rc5Key::new(b"key b").unwrap();
+        assert_ne!(a.id(), b.id());
+        assert_eq!(a.id(), Rc5Key::new(b"key a").unwrap().id());
+        assert_eq!(a.id().to_string().len(), 16);
+
+        let mut keyring = Keyring::new();
+        assert_eq!(keyring.set_primary(&a.id()), Err(Error::UnknownKey));
+        let id_a = keyring.insert(Some("current"), a.clone());
+        let id_b = keyring.insert(None, b.clone());
+        keyring.set_primary(&id_a).unwrap();
+        assert_eq!(keyring.primary(), Some((id_a, &a)));
+
+        // the label follows the new key, the old one stays by ID
+        assert_eq!(keyring.insert(Some("current"), b.clone()), id_b);
+        assert_eq!(keyring.by_label("current"), Some(&b));
+        assert_eq!(keyring.get(&id_a), Some(&a));
+        assert_eq!(keyring.ids().collect::<Vec<_>>(), [id_a, id_b]);
+
+        assert_eq!(keyring.remove(&id_a), Some(a));
+        assert_eq!(keyring.primary(), None);
+        assert_eq!(keyring.remove(&id_a), None);
+    }
+
+    #[test]
+    fn keyring_reads_header_key_id() {
+        use crate::header::{Extension, MacAlg, Mode};
+
+        let key = Rc5Key::new(b"key").unwrap();
+        let mut keyring = Keyring::new();
+        let id = keyring.insert(None, key.clone());
+
+        let mut header = Header::new::<u32>(12, Mode::Cbc, MacAlg::None);
+        assert_eq!(keyring.key_for(&header), Err(Error::UnknownKey));
+        header.extensions.push(Extension {
+            kind: EXT_KEY_ID,
+            value: id.0.to_vec(),
+        });
+        assert_eq!(keyring.key_for(&header), Ok(&key));
+
+        header.extensions[0].value[0] ^= 1;
+        assert_eq!(keyring.key_for(&header), Err(Error::UnknownKey));
+        header.extensions[0].value.pop();
+        assert_eq!(keyring.key_for(&header), Err(Error::InvalidHeader));
+    }
+}
@@ -0,0 +1,825 @@
+//!
+//! Decryption of data produced by older RC5 libraries.
+//!
+//! Implementations that predate any RC5 standard for byte streams (Delphi
+//! component packs, .NET ports, PHP extensions) agree on the block function but
+//! differ in everything around it: how bytes map onto words, how a text key is
+//! turned into key bytes, where the IV lives and how the last block is padded.
+//! A [`LegacyProfile`] bundles one such set of choices so that migration code
+//! can read old data without reimplementing the glue.
+//!
+
+use crate::audit::{self, Operation};
+use crate::error::Error;
+use crate::padding::{AnsiX923, Iso7816, Padding, Pkcs7, ZeroPadding};
+use crate::params::Rc5Params;
+use crate::rc5::{decrypt_kernel, encrypt_kernel, expand_key};
+use crate::rng::{CryptoRng, RngCore};
+use crate::usage::UsageCounter;
+use crate::word::Word;
+
+///
+/// Byte order used to load the two words of a block.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    /// Little-endian words, as in the RC5 reference implementation.
+    Little,
+    /// Big-endian words, common in ports from Java and Delphi.
+    Big,
+}
+
+///
+/// How the user-supplied key is turned into RC5 key bytes.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyHandling {
+    /// The key bytes are used as given.
+    Raw,
+    /// The key is truncated or right-padded with zeros to exactly this many
+    /// bytes, e.g. ASCII passwords zero-padded to 16 bytes.
+    ZeroPadded(usize),
+}
+
+///
+/// Padding applied to the last block.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyPadding {
+    /// No padding, the data must be a multiple of the block size.
+    None,
+    /// Zero bytes up to the block size; trailing zeros of the plaintext's last
+    /// block are indistinguishable from padding and are stripped as well.
+    Zero,
+    /// PKCS#7 / PKCS#5 padding.
+    Pkcs7,
+    /// ANSI X9.23 padding.
+    AnsiX923,
+    /// ISO/IEC 7816-4 padding.
+    Iso7816,
+}
+
+///
+/// Chaining mode and the source of its IV.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chaining {
+    Ecb,
+    /// CBC with an all-zero IV.
+    CbcZeroIv,
+    /// CBC with the same IV for every message.
+    CbcFixedIv(Vec<u8>),
+    /// CBC with the IV stored as the first block of the ciphertext.
+    CbcPrependedIv,
+}
+
+///
+/// A bundle of legacy conventions for turning RC5 into a byte cipher.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+///
+/// let profile = LegacyProfile {
+///     rounds: 12,
+///     endian: Endian::Big,
+///     key: KeyHandling::ZeroPadded(16),
+///     padding: LegacyPadding::Pkcs7,
+///     chaining: Chaining::CbcZeroIv,
+/// };
+///
+/// let ct = profile.encrypt::<u32>(b"secret", b"hello legacy world").unwrap();
+/// let pt = profile.decrypt::<u32>(b"secret", &ct).unwrap();
+///
+/// assert_eq!(pt, b"hello legacy world");
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyProfile {
+    pub rounds: usize,
+    pub endian: Endian,
+    pub key: KeyHandling,
+    pub padding: LegacyPadding,
+    pub chaining: Chaining,
+}
+
+impl LegacyProfile {
+    ///
+    /// RC5-CBC-Pad of RFC 2040: little-endian words, the key bytes as given,
+    /// CBC with an IV of one block agreed out of band, and PKCS#7 padding.
+    /// The RFC allows 0 to 255 rounds and keys of 0 to 255 bytes; it uses
+    /// RC5-32, so use `u32` words to interoperate.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::legacy::LegacyProfile;
+    ///
+    /// // RFC 2040, section 9
+    /// let profile = LegacyProfile::rfc2040_cbc_pad(8, &[0; 8]);
+    /// let ct = profile.encrypt::<u32>(&[1, 2, 3, 4, 5], &[0xff; 8]).unwrap();
+    ///
+    /// assert_eq!(ct, [
+    ///     0x78, 0x75, 0xdb, 0xf6, 0x73, 0x8c, 0x64, 0x78,
+    ///     0x8f, 0x34, 0xc3, 0xc6, 0x81, 0xc9, 0x96, 0x95,
+    /// ]);
+    /// ```
+    ///
+    pub fn rfc2040_cbc_pad(rounds: usize, iv: &[u8]) -> Self {
+        LegacyProfile {
+            padding: LegacyPadding::Pkcs7,
+            ..Self::rfc2040_cbc(rounds, iv)
+        }
+    }
+
+    ///
+    /// RC5-CBC of RFC 2040: like [`rfc2040_cbc_pad`](Self::rfc2040_cbc_pad),
+    /// without padding, for data that is a whole number of blocks.
+    ///
+    pub fn rfc2040_cbc(rounds: usize, iv: &[u8]) -> Self {
+        LegacyProfile {
+            rounds,
+            endian: Endian::Little,
+            key: KeyHandling::Raw,
+            padding: LegacyPadding::None,
+            chaining: Chaining::CbcFixedIv(iv.to_vec()),
+        }
+    }
+
+    ///
+    /// Decrypts `data` produced by a library following this profile.
+    ///
+    pub fn decrypt<W: Word>(&self, key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        let params = Some(self.params::<W>(key));
+        audit::operation(
+            Operation::Decrypt,
+            None,
+            params,
+            |out: &Vec<u8>| out.len() as u64,
+            || {
+                let mut decryptor = self.decryptor::<W>(key)?;
+                let mut out = Vec::with_capacity(data.len());
+                decryptor.update(data, &mut out);
+                decryptor.finish(&mut out)?;
+                Ok(out)
+            },
+        )
+    }
+
+    ///
+    /// Returns an incremental decryptor for data that doesn't fit in memory.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+    /// # use rand_core::OsRng;
+    /// # let mut rng = OsRng;
+    ///
+    /// let profile = LegacyProfile {
+    ///     rounds: 12,
+    ///     endian: Endian::Little,
+    ///     key: KeyHandling::Raw,
+    ///     padding: LegacyPadding::Pkcs7,
+    ///     chaining: Chaining::CbcPrependedIv,
+    /// };
+    /// let ct = profile.encrypt_with_rng::<u32, _>(&mut rng, b"key", b"split across several updates").unwrap();
+    ///
+    /// let mut decryptor = profile.decryptor::<u32>(b"key").unwrap();
+    /// let mut pt = Vec::new();
+    /// for piece in ct.chunks(5) {
+    ///     decryptor.update(piece, &mut pt);
+    /// }
+    /// decryptor.finish(&mut pt).unwrap();
+    ///
+    /// assert_eq!(pt, b"split across several updates");
+    /// ```
+    ///
+    pub fn decryptor<W: Word>(&self, key: &[u8]) -> Result<LegacyDecryptor<'_, W>, Error> {
+        let bs = 2 * W::BYTES;
+        let prev = match &self.chaining {
+            Chaining::Ecb => None,
+            Chaining::CbcZeroIv | Chaining::CbcPrependedIv => Some(vec![0u8; bs]),
+            Chaining::CbcFixedIv(iv) => {
+                if iv.len() != bs {
+                    return Err(Error::InvalidLength);
+                }
+                Some(iv.clone())
+            }
+        };
+        Ok(LegacyDecryptor {
+            profile: self,
+            key_exp: expand_key::<W>(&self.key_bytes(key), self.rounds),
+            prev,
+            need_iv: self.chaining == Chaining::CbcPrependedIv,
+            pending: Vec::with_capacity(bs),
+            held: None,
+            usage: UsageCounter::new(bs),
+        })
+    }
+
+    ///
+    /// Encrypts `data` the way a library following this profile would. Use the
+    /// modern modes for anything that isn't a compatibility test.
+    ///
+    /// Fails with [`Error::UnsupportedParameters`] for
+    /// [`Chaining::CbcPrependedIv`], whose IV must be fresh for every message:
+    /// use [`encrypt_with_rng`](Self::encrypt_with_rng) instead. Fails with
+    /// [`Error::InvalidLength`] if `data` isn't a whole number of blocks with
+    /// [`LegacyPadding::None`], or if a fixed IV isn't one block.
+    ///
+    pub fn encrypt<W: Word>(&self, key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+        if self.chaining == Chaining::CbcPrependedIv {
+            return Err(Error::UnsupportedParameters);
+        }
+        self.encrypt_chained::<W>(key, data, &[])
+    }
+
+    ///
+    /// Like [`LegacyProfile::encrypt`], but for [`Chaining::CbcPrependedIv`]
+    /// the IV is drawn from `rng`. Pass a seeded generator to get reproducible
+    /// ciphertexts in tests.
+    ///
+    pub fn encrypt_with_rng<W: Word, R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let mut iv = vec![0u8; 2 * W::BYTES];
+        if self.chaining == Chaining::CbcPrependedIv {
+            rng.fill_bytes(&mut iv);
+        }
+        self.encrypt_chained::<W>(key, data, &iv)
+    }
+
+    ///
+    /// Like [`LegacyProfile::encrypt_with_rng`], but with the IV given, to
+    /// reproduce a ciphertext captured from another implementation. Never
+    /// encrypt two messages under the same IV. Fails with
+    /// [`Error::InvalidLength`] unless `iv` is one block.
+    ///
+    pub fn encrypt_with_iv<W: Word>(
+        &self,
+        key: &[u8],
+        data: &[u8],
+        iv: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if iv.len() != 2 * W::BYTES {
+            return Err(Error::InvalidLength);
+        }
+        self.encrypt_chained::<W>(key, data, iv)
+    }
+
+    fn encrypt_chained<W: Word>(
+        &self,
+        key: &[u8],
+        data: &[u8],
+        iv: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        let params = Some(self.params::<W>(key));
+        audit::operation(
+            Operation::Encrypt,
+            None,
+            params,
+            |_| data.len() as u64,
+            || {
+                let bs = 2 * W::BYTES;
+                let mut buf = data.to_vec();
+                self.pad(&mut buf, bs)?;
+
+                let mut out = Vec::with_capacity(buf.len() + bs);
+                let mut prev = match &self.chaining {
+                    Chaining::Ecb => None,
+                    Chaining::CbcZeroIv => Some(vec![0u8; bs]),
+                    Chaining::CbcFixedIv(iv) if iv.len() != bs => return Err(Error::InvalidLength),
+                    Chaining::CbcFixedIv(iv) => Some(iv.clone()),
+                    Chaining::CbcPrependedIv => {
+                        out.extend_from_slice(iv);
+                        Some(iv.to_vec())
+                    }
+                };
+
+                let key_exp = expand_key::<W>(&self.key_bytes(key), self.rounds);
+                for block in buf.chunks_exact_mut(bs) {
+                    if let Some(prev) = prev.as_ref() {
+                        xor_in_place(block, prev);
+                    }
+                    let ct = encrypt_kernel(self.load::<W>(block), &key_exp);
+                    self.store(ct, block);
+                    if let Some(prev) = prev.as_mut() {
+                        prev.copy_from_slice(block);
+                    }
+                    out.extend_from_slice(block);
+                }
+                Ok(out)
+            },
+        )
+    }
+
+    pub(crate) fn params<W: Word>(&self, key: &[u8]) -> Rc5Params {
+        Rc5Params {
+            word_bytes: W::BYTES,
+            rounds: self.rounds,
+            key_bytes: self.key_bytes(key).len(),
+        }
+    }
+
+    fn key_bytes(&self, key: &[u8]) -> Vec<u8> {
+        match self.key {
+            KeyHandling::Raw => key.to_vec(),
+            KeyHandling::ZeroPadded(len) => {
+                let mut out = key[..key.len().min(len)].to_vec();
+                out.resize(len, 0);
+                out
+            }
+        }
+    }
+
+    fn load<W: Word>(&self, block: &[u8]) -> [W; 2] {
+        debug_assert_eq!(block.len(), 2 * W::BYTES, "block size mismatch");
+        let mut buf = block.to_vec();
+        if self.endian == Endian::Big {
+            buf[..W::BYTES].reverse();
+            buf[W::BYTES..].reverse();
+        }
+        [W::from_le_slice(&buf), W::from_le_slice(&buf[W::BYTES..])]
+    }
+
+    fn store<W: Word>(&self, words: [W; 2], out: &mut [u8]) {
+        words[0].write_le(out);
+        words[1].write_le(&mut out[W::BYTES..]);
+        if self.endian == Endian::Big {
+            out[..W::BYTES].reverse();
+            out[W::BYTES..2 * W::BYTES].reverse();
+        }
+    }
+
+    fn pad(&self, buf: &mut Vec<u8>, bs: usize) -> Result<(), Error> {
+        match self.padding {
+            LegacyPadding::None if !buf.len().is_multiple_of(bs) => {
+                return Err(Error::InvalidLength)
+            }
+            LegacyPadding::None => {}
+            LegacyPadding::Zero => ZeroPadding::pad(buf, bs),
+            LegacyPadding::Pkcs7 => Pkcs7::pad(buf, bs),
+            LegacyPadding::AnsiX923 => AnsiX923::pad(buf, bs),
+            LegacyPadding::Iso7816 => Iso7816::pad(buf, bs),
+        }
+        Ok(())
+    }
+
+    fn unpad(&self, block: &mut Vec<u8>) -> Result<(), Error> {
+        let len = match self.padding {
+            LegacyPadding::None => block.len(),
+            LegacyPadding::Zero => ZeroPadding::unpad(block)?,
+            LegacyPadding::Pkcs7 => Pkcs7::unpad(block)?,
+            LegacyPadding::AnsiX923 => AnsiX923::unpad(block)?,
+            LegacyPadding::Iso7816 => Iso7816::unpad(block)?,
+        };
+        block.truncate(len);
+        Ok(())
+    }
+}
+
+///
+/// Incremental decryptor returned by [`LegacyProfile::decryptor`].
+///
+/// The last full block is held back until [`LegacyDecryptor::finish`], since
+/// only then is it known to carry the padding.
+///
+pub struct LegacyDecryptor<'a, W: Word> {
+    profile: &'a LegacyProfile,
+    key_exp: Vec<W>,
+    prev: Option<Vec<u8>>,
+    need_iv: bool,
+    pending: Vec<u8>,
+    held: Option<Vec<u8>>,
+    usage: UsageCounter,
+}
+
+impl<W: Word> LegacyDecryptor<'_, W> {
+    ///
+    /// Feeds ciphertext, appending any plaintext that is ready to `out`.
+    ///
+    pub fn update(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        let bs = 2 * W::BYTES;
+        let mut data = data;
+        while !data.is_empty() {
+            let take = (bs - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < bs {
+                break;
+            }
+
+            if self.need_iv {
+                self.prev = Some(self.pending.clone());
+                self.need_iv = false;
+            } else {
+                let block = self.decrypt_pending();
+                // without a hard limit recording can't fail
+                let _ = self.usage.record(1);
+                if let Some(held) = self.held.replace(block) {
+                    out.extend_from_slice(&held);
+                }
+            }
+            self.pending.clear();
+        }
+    }
+
+    ///
+    /// Blocks decrypted so far under this key. Legacy data is usually far
+    /// from the birthday bound, but RC5-16 streams reach it after 256 KiB.
+    ///
+    pub fn usage(&self) -> &UsageCounter {
+        &self.usage
+    }
+
+    ///
+    /// Checks that the ciphertext ended on a block boundary and appends the
+    /// unpadded last block to `out`.
+    ///
+    pub fn finish(self, out: &mut Vec<u8>) -> Result<(), Error> {
+        if self.need_iv || !self.pending.is_empty() {
+            return Err(Error::InvalidLength);
+        }
+        let Some(mut last) = self.held else {
+            // only the schemes that may add nothing accept empty input
+            return match self.profile.padding {
+                LegacyPadding::None | LegacyPadding::Zero => Ok(()),
+                _ => Err(Error::InvalidPadding),
+            };
+        };
+        self.profile.unpad(&mut last)?;
+        out.extend_from_slice(&last);
+        Ok(())
+    }
+
+    fn decrypt_pending(&mut self) -> Vec<u8> {
+        let ct = self.profile.load::<W>(&self.pending);
+        let pt = decrypt_kernel(ct, &self.key_exp);
+        let mut block = vec![0u8; 2 * W::BYTES];
+        self.profile.store(pt, &mut block);
+        if let Some(prev) = self.prev.as_mut() {
+            xor_in_place(&mut block, prev);
+            prev.copy_from_slice(&self.pending);
+        }
+        block
+    }
+}
+
+///
+/// A parameter set under which [`probe`] decrypted the data consistently.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeMatch {
+    pub word_bytes: usize,
+    pub profile: LegacyProfile,
+    pub plaintext: Vec<u8>,
+}
+
+// Round counts and key lengths seen in the wild.
+const PROBE_ROUNDS: [usize; 4] = [12, 16, 20, 32];
+const PROBE_KEY_LENGTHS: [usize; 3] = [8, 16, 32];
+
+///
+/// Tries the common word sizes, round counts, byte orders, key handlings,
+/// paddings and chainings on `data` and returns those that decrypt it
+/// consistently, for recovering data whose producer is unknown.
+///
+/// A combination is consistent if the padding checks out and the plaintext
+/// starts with `known_prefix`. Without a prefix, padding is the only evidence:
+/// [`LegacyPadding::None`] and [`LegacyPadding::Zero`] can't fail and aren't
+/// tried, and roughly one in 256 wrong combinations still has valid PKCS#7
+/// padding by chance. Supply a few known bytes (a file magic, a JSON `{`)
+/// whenever possible. IVs other than all-zero or prepended can't be probed.
+///
+/// Data that decrypts under a padding scheme also decrypts under the ones that
+/// check less, so matches are sorted with the most specific padding first.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::legacy::{probe, Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+///
+/// let profile = LegacyProfile {
+///     rounds: 16,
+///     endian: Endian::Big,
+///     key: KeyHandling::ZeroPadded(16),
+///     padding: LegacyPadding::Pkcs7,
+///     chaining: Chaining::CbcZeroIv,
+/// };
+/// let blob = profile.encrypt::<u32>(b"hunter2", b"%PDF-1.4 ...").unwrap();
+///
+/// let found = probe(b"hunter2", &blob, Some(b"%PDF"));
+/// assert_eq!((found[0].word_bytes, &found[0].profile), (4, &profile));
+/// assert_eq!(found[0].plaintext, b"%PDF-1.4 ...");
+/// ```
+///
+pub fn probe(key: &[u8], data: &[u8], known_prefix: Option<&[u8]>) -> Vec<ProbeMatch> {
+    let mut keys = vec![KeyHandling::Raw];
+    for len in PROBE_KEY_LENGTHS {
+        if len != key.len() {
+            keys.push(KeyHandling::ZeroPadded(len));
+        }
+    }
+    // most specific first
+    let paddings: &[LegacyPadding] = match known_prefix {
+        Some(_) => &[
+            LegacyPadding::Pkcs7,
+            LegacyPadding::AnsiX923,
+            LegacyPadding::Iso7816,
+            LegacyPadding::Zero,
+            LegacyPadding::None,
+        ],
+        None => &[
+            LegacyPadding::Pkcs7,
+            LegacyPadding::AnsiX923,
+            LegacyPadding::Iso7816,
+        ],
+    };
+
+    let mut found = Vec::new();
+    for word_bytes in [1, 2, 4, 8, 16] {
+        for rounds in PROBE_ROUNDS {
+            for endian in [Endian::Little, Endian::Big] {
+                for &key_handling in &keys {
+                    for &padding in paddings {
+                        for chaining in
+                            [Chaining::Ecb, Chaining::CbcZeroIv, Chaining::CbcPrependedIv]
+                        {
+                            let profile = LegacyProfile {
+                                rounds,
+                                endian,
+                                key: key_handling,
+                                padding,
+                                chaining,
+                            };
+                            let plaintext = match word_bytes {
+                                1 => profile.decrypt::<u8>(key, data),
+                                2 => profile.decrypt::<u16>(key, data),
+                                4 => profile.decrypt::<u32>(key, data),
+                                8 => profile.decrypt::<u64>(key, data),
+                                _ => profile.decrypt::<u128>(key, data),
+                            };
+                            let Ok(plaintext) = plaintext else { continue };
+                            if known_prefix.is_some_and(|prefix| !plaintext.starts_with(prefix)) {
+                                continue;
+                            }
+                            found.push(ProbeMatch {
+                                word_bytes,
+                                profile,
+                                plaintext,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    found.sort_by_key(|m| paddings.iter().position(|&p| p == m.profile.padding));
+    found
+}
+
+fn xor_in_place(dst: &mut [u8], src: &[u8]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::CounterRng;
+    use crate::usage::UsageStatus;
+
+    fn profile(endian: Endian, padding: LegacyPadding, chaining: Chaining) -> LegacyProfile {
+        LegacyProfile {
+            rounds: 12,
+            endian,
+            key: KeyHandling::Raw,
+            padding,
+            chaining,
+        }
+    }
+
+    #[test]
+    fn ecb_little_endian_matches_block_api() {
+        let key = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ];
+        let p = profile(Endian::Little, LegacyPadding::None, Chaining::Ecb);
+        let pt = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77];
+
+        let ct = p.encrypt::<u32>(&key, &pt).unwrap();
+        // [0x9B14DC2D, 0x9E8B08CF] in little-endian bytes
+        assert_eq!(ct, [0x2D, 0xDC, 0x14, 0x9B, 0xCF, 0x08, 0x8B, 0x9E]);
+        assert_eq!(p.decrypt::<u32>(&key, &ct).unwrap(), pt);
+    }
+
+    #[test]
+    fn big_endian_swaps_words() {
+        let key = [0x00, 0x01, 0x02, 0x03];
+        let le = profile(Endian::Little, LegacyPadding::None, Chaining::Ecb);
+        let be = profile(Endian::Big, LegacyPadding::None, Chaining::Ecb);
+
+        let ct = le.encrypt::<u16>(&key, &[0x00, 0x01, 0x02, 0x03]).unwrap();
+        let ct_be = be.encrypt::<u16>(&key, &[0x01, 0x00, 0x03, 0x02]).unwrap();
+        assert_eq!(ct_be, [ct[1], ct[0], ct[3], ct[2]]);
+    }
+
+    #[test]
+    fn zero_padded_ascii_key() {
+        let mut p = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::CbcZeroIv);
+        p.key = KeyHandling::ZeroPadded(16);
+
+        // `profile` uses the key as given
+        let raw = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::CbcZeroIv);
+        let mut padded_key = b"password".to_vec();
+        padded_key.resize(16, 0);
+
+        let ct = p.encrypt::<u32>(b"password", b"data").unwrap();
+        assert_eq!(ct, raw.encrypt::<u32>(&padded_key, b"data").unwrap());
+        assert_eq!(p.decrypt::<u32>(b"password", &ct).unwrap(), b"data");
+    }
+
+    #[test]
+    fn cbc_iv_sources() {
+        let key = b"0123456789abcdef";
+        let iv = vec![7u8; 8];
+        for chaining in [
+            Chaining::CbcZeroIv,
+            Chaining::CbcFixedIv(iv.clone()),
+            Chaining::CbcPrependedIv,
+        ] {
+            let p = profile(Endian::Little, LegacyPadding::Pkcs7, chaining.clone());
+            let ct = p
+                .encrypt_with_rng::<u32, _>(&mut CounterRng(0), key, b"sixteen byte msg")
+                .unwrap();
+            assert_eq!(p.decrypt::<u32>(key, &ct).unwrap(), b"sixteen byte msg");
+        }
+
+        let fixed = profile(
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcFixedIv(iv),
+        );
+        let zero = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::CbcZeroIv);
+        assert_ne!(
+            fixed.encrypt::<u32>(key, b"x").unwrap(),
+            zero.encrypt::<u32>(key, b"x").unwrap()
+        );
+
+        let prepended = profile(
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcPrependedIv,
+        );
+        assert_eq!(
+            prepended
+                .encrypt_with_iv::<u32>(key, b"x", &[0; 8])
+                .unwrap()[8..],
+            zero.encrypt::<u32>(key, b"x").unwrap()[..]
+        );
+        // a prepended IV is never chosen for the caller
+        assert_eq!(
+            prepended.encrypt::<u32>(key, b"x"),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(
+            prepended.encrypt_with_iv::<u32>(key, b"x", &[0; 4]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn prepended_iv_comes_from_rng() {
+        let p = profile(
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcPrependedIv,
+        );
+        let ct = p
+            .encrypt_with_rng::<u32, _>(&mut CounterRng(1), b"key", b"data")
+            .unwrap();
+        assert_eq!(ct[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            ct,
+            p.encrypt_with_rng::<u32, _>(&mut CounterRng(1), b"key", b"data")
+                .unwrap()
+        );
+        assert_eq!(p.decrypt::<u32>(b"key", &ct).unwrap(), b"data");
+
+        // other chainings carry no IV and ignore the generator
+        let ecb = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::Ecb);
+        assert_eq!(
+            ecb.encrypt_with_rng::<u32, _>(&mut CounterRng(1), b"key", b"data")
+                .unwrap(),
+            ecb.encrypt::<u32>(b"key", b"data").unwrap()
+        );
+    }
+
+    #[test]
+    fn decryptor_counts_blocks() {
+        let p = profile(
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcPrependedIv,
+        );
+        let ct = p
+            .encrypt_with_rng::<u16, _>(&mut CounterRng(0), b"key", &[0u8; 4100 * 4])
+            .unwrap();
+
+        let mut decryptor = p.decryptor::<u16>(b"key").unwrap();
+        decryptor.update(&ct, &mut Vec::new());
+        // the IV block isn't decrypted, the padding block is
+        assert_eq!(decryptor.usage().blocks(), 4101);
+        assert_eq!(decryptor.usage().status(), UsageStatus::RekeySoon);
+    }
+
+    #[test]
+    fn zero_padding_strips_trailing_zeros() {
+        let p = profile(Endian::Little, LegacyPadding::Zero, Chaining::Ecb);
+        let ct = p.encrypt::<u32>(b"key", b"abc\0").unwrap();
+        assert_eq!(ct.len(), 8);
+        assert_eq!(p.decrypt::<u32>(b"key", &ct).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let p = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::CbcZeroIv);
+        assert_eq!(p.decrypt::<u32>(b"key", &[0; 7]), Err(Error::InvalidLength));
+
+        let ecb = profile(Endian::Little, LegacyPadding::None, Chaining::Ecb);
+        let bad_padding = ecb
+            .encrypt::<u32>(b"key", &[1, 2, 3, 4, 5, 6, 7, 0])
+            .unwrap();
+        let p = profile(Endian::Little, LegacyPadding::Pkcs7, Chaining::Ecb);
+        assert_eq!(
+            p.decrypt::<u32>(b"key", &bad_padding),
+            Err(Error::InvalidPadding)
+        );
+
+        let p = profile(
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcFixedIv(vec![0; 4]),
+        );
+        assert_eq!(p.decrypt::<u32>(b"key", &[0; 8]), Err(Error::InvalidLength));
+        assert_eq!(p.encrypt::<u32>(b"key", b"x"), Err(Error::InvalidLength));
+    }
+
+    #[test]
+    fn unpadded_encrypt_rejects_partial_blocks() {
+        let ecb = profile(Endian::Little, LegacyPadding::None, Chaining::Ecb);
+        assert_eq!(
+            ecb.encrypt::<u32>(b"key", &[0; 7]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(ecb.encrypt::<u32>(b"key", &[0; 16]).unwrap().len(), 16);
+
+        let rfc = LegacyProfile::rfc2040_cbc(12, &[0; 8]);
+        assert_eq!(
+            rfc.encrypt::<u32>(b"key", b"abc"),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn probe_finds_the_producing_profile() {
+        let profile = LegacyProfile {
+            rounds: 20,
+            endian: Endian::Little,
+            key: KeyHandling::Raw,
+            padding: LegacyPadding::Iso7816,
+            chaining: Chaining::CbcPrependedIv,
+        };
+        let blob = profile
+            .encrypt_with_rng::<u64, _>(&mut CounterRng(9), b"k3y", b"{\"id\": 7}")
+            .unwrap();
+
+        let found = probe(b"k3y", &blob, None);
+        assert!(found
+            .iter()
+            .any(|m| m.word_bytes == 8 && m.profile == profile && m.plaintext == b"{\"id\": 7}"));
+
+        // Iso7816 padding also reads as unpadded or zero-padded data, and
+        // zero padding "k3y" to a full word doesn't change the key schedule
+        let found = probe(b"k3y", &blob, Some(b"{\"id\""));
+        assert_eq!(found[0].profile, profile);
+        for m in &found[1..] {
+            assert_eq!(
+                (m.word_bytes, m.profile.rounds, &m.profile.chaining),
+                (8, 20, &profile.chaining)
+            );
+        }
+    }
+
+    #[test]
+    fn probe_without_match_is_empty() {
+        assert!(probe(b"key", &[0x5a; 7], None).is_empty());
+    }
+}
@@ -1,4 +1,59 @@
+pub mod algo;
+pub mod audit;
+pub mod cancel;
+pub mod cbc;
+pub mod ccm;
+pub mod cfb;
+pub mod clock;
+pub mod ctr;
+#[cfg(feature = "db")]
+pub mod db;
+pub mod ecb;
+pub mod encoding;
+pub mod envelope;
+pub mod error;
+#[cfg(feature = "firmware")]
+pub mod firmware;
+pub mod fixed;
+#[cfg(feature = "flash")]
+pub mod flash;
+pub mod hash;
+pub mod header;
+#[cfg(feature = "hkdf")]
+pub mod kdf;
+pub mod kem;
+#[cfg(feature = "key")]
+pub mod key;
+pub mod legacy;
+pub mod lrw;
+pub mod mac;
+#[cfg(feature = "migrate")]
+pub mod migrate;
+pub mod modes;
+pub mod ocb;
+pub mod padding;
+pub mod params;
+#[cfg(feature = "argon2")]
+pub mod passphrase;
+pub mod prefetch;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(feature = "proto")]
+pub mod proto;
 pub mod rc5;
+pub mod rng;
+#[cfg(feature = "shamir")]
+pub mod shamir;
+pub mod siv;
+pub mod suite;
+#[cfg(feature = "timed")]
+pub mod timed;
+pub mod usage;
 pub mod word;
+pub mod wrap;
+pub mod xts;
 
-pub use rc5::{decrypt, encrypt, expand_key};
+pub use error::Error;
+pub use rc5::{
+    decrypt, decrypt_kernel, encrypt, encrypt_kernel, expand_key, try_decrypt, try_encrypt, Rc5,
+};
@@ -0,0 +1,291 @@
+//!
+//! LRW tweakable mode for legacy volume images.
+//!
+//! Block `i` of a volume is encrypted as `C = E(K1, P ^ T) ^ T` with
+//! `T = K2 ⊗ i`, the product in GF(2^n) for the block size `n`. Some older
+//! disk-encryption tools used LRW before XTS replaced it; this lets migration
+//! tooling read and rewrite their images.
+//!
+//! Blocks and block indices are little-endian: bit `j` of the integer they
+//! encode is the coefficient of `x^j`. The field polynomials are the
+//! low-weight ones also used by CMAC:
+//!
+//! | block bits | polynomial                     |
+//! |------------|--------------------------------|
+//! | 16         | x^16 + x^5 + x^3 + x + 1       |
+//! | 32         | x^32 + x^7 + x^3 + x^2 + 1     |
+//! | 64         | x^64 + x^4 + x^3 + x + 1       |
+//! | 128        | x^128 + x^7 + x^2 + x + 1      |
+//! | 256        | x^256 + x^10 + x^5 + x^2 + 1   |
+//!
+//! Other block sizes, e.g. from a 24-bit [`Word`], have no polynomial here:
+//! [`Lrw::new`] and the other modes built on the same field fail for them
+//! with [`Error::UnsupportedParameters`].
+//!
+//! LRW is not secure when `K2` itself gets
+//! encrypted, e.g. a volume holding its own key; use it to read old images,
+//! not to create new ones.
+//!
+
+use crate::error::Error;
+use crate::rc5::{decrypt_kernel, encrypt_kernel};
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+///
+/// An LRW cipher over an expanded RC5 key and a tweak key.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::expand_key;
+/// use rc5_cipher::lrw::Lrw;
+///
+/// let key_exp = expand_key::<u64>(b"volume data key!", 16);
+/// let lrw = Lrw::new(&key_exp, &[0x5c; 16]).unwrap();
+///
+/// // sector 3 of a volume with 512-byte sectors
+/// let mut sector = vec![0u8; 512];
+/// lrw.encrypt_at(3 * 512 / 16, &mut sector).unwrap();
+/// assert_ne!(sector[..16], sector[16..32]);
+///
+/// lrw.decrypt_at(3 * 512 / 16, &mut sector).unwrap();
+/// assert_eq!(sector, vec![0u8; 512]);
+/// ```
+///
+pub struct Lrw<'a, W: Word> {
+    key_exp: &'a [W],
+    tweak_key: [u8; MAX_BLOCK],
+}
+
+impl<'a, W: Word> Lrw<'a, W> {
+    ///
+    /// Fails with [`Error::InvalidLength`] if `tweak_key` isn't one block
+    /// long, and with [`Error::UnsupportedParameters`] for a block size
+    /// without a field polynomial.
+    ///
+    pub fn new(key_exp: &'a [W], tweak_key: &[u8]) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        check_block_size(bs)?;
+        if tweak_key.len() != bs {
+            return Err(Error::InvalidLength);
+        }
+        let mut key = [0u8; MAX_BLOCK];
+        key[..bs].copy_from_slice(tweak_key);
+        Ok(Lrw {
+            key_exp,
+            tweak_key: key,
+        })
+    }
+
+    ///
+    /// Encrypts `buf` in place as the blocks starting at index `first_block`.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `buf` isn't a whole number of
+    /// blocks.
+    ///
+    pub fn encrypt_at(&self, first_block: u64, buf: &mut [u8]) -> Result<(), Error> {
+        self.apply(first_block, buf, encrypt_kernel)
+    }
+
+    ///
+    /// Decrypts `buf` in place as the blocks starting at index `first_block`.
+    ///
+    pub fn decrypt_at(&self, first_block: u64, buf: &mut [u8]) -> Result<(), Error> {
+        self.apply(first_block, buf, decrypt_kernel)
+    }
+
+    fn apply(
+        &self,
+        first_block: u64,
+        buf: &mut [u8],
+        kernel: fn([W; 2], &[W]) -> [W; 2],
+    ) -> Result<(), Error> {
+        let bs = 2 * W::BYTES;
+        if !buf.len().is_multiple_of(bs) {
+            return Err(Error::InvalidLength);
+        }
+
+        let mut index = [0u8; MAX_BLOCK];
+        let mut tweak = [0u8; MAX_BLOCK];
+        for (i, block) in buf.chunks_exact_mut(bs).enumerate() {
+            let n = u128::from(first_block) + i as u128;
+            let len = bs.min(16);
+            index[..len].copy_from_slice(&n.to_le_bytes()[..len]);
+            gf_mul(&self.tweak_key[..bs], &index[..bs], &mut tweak[..bs]);
+
+            xor(block, &tweak[..bs]);
+            let out = kernel(
+                [
+                    W::from_le_slice(block),
+                    W::from_le_slice(&block[W::BYTES..]),
+                ],
+                self.key_exp,
+            );
+            out[0].write_le(block);
+            out[1].write_le(&mut block[W::BYTES..]);
+            xor(block, &tweak[..bs]);
+        }
+        Ok(())
+    }
+}
+
+// Low terms of the field polynomial for a block of `bs` bytes.
+fn poly(bs: usize) -> Option<u16> {
+    match bs {
+        2 => Some(0x2b),
+        4 => Some(0x8d),
+        8 => Some(0x1b),
+        16 => Some(0x87),
+        32 => Some(0x425),
+        _ => None,
+    }
+}
+
+///
+/// Fails with [`Error::UnsupportedParameters`] unless there is a field
+/// polynomial for blocks of `bs` bytes. Every mode that calls [`double`]
+/// checks this when it is built, so `double` never sees another size.
+///
+pub(crate) fn check_block_size(bs: usize) -> Result<(), Error> {
+    poly(bs).map(|_| ()).ok_or(Error::UnsupportedParameters)
+}
+
+// `out = a ⊗ b` in GF(2^(8 * a.len())), without secret-dependent branches.
+fn gf_mul(a: &[u8], b: &[u8], out: &mut [u8]) {
+    let n = a.len();
+    let mut a_shift = [0u8; MAX_BLOCK];
+    a_shift[..n].copy_from_slice(a);
+    out.fill(0);
+
+    for bit in 0..8 * n {
+        let mask = ((b[bit / 8] >> (bit % 8)) & 1).wrapping_neg();
+        for (o, x) in out.iter_mut().zip(&a_shift[..n]) {
+            *o ^= x & mask;
+        }
+
+        double(&mut a_shift[..n]);
+    }
+}
+
+///
+/// Multiplies `block` by `x` in GF(2^(8 * block.len())), in the convention
+/// and with the polynomials of this module.
+///
+pub(crate) fn double(block: &mut [u8]) {
+    let n = block.len();
+    let low = poly(n).expect("block size checked when the mode was built");
+    let carry = (block[n - 1] >> 7).wrapping_neg();
+    for i in (1..n).rev() {
+        block[i] = (block[i] << 1) | (block[i - 1] >> 7);
+    }
+    block[0] <<= 1;
+    block[0] ^= (low as u8) & carry;
+    block[1] ^= ((low >> 8) as u8) & carry;
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::expand_key;
+
+    fn mul(a: &[u8], b: &[u8]) -> Vec<u8> {
+        let mut out = vec![0u8; a.len()];
+        gf_mul(a, b, &mut out);
+        out
+    }
+
+    #[test]
+    fn field_reduction() {
+        for bs in [2usize, 4, 8, 16, 32] {
+            // x^(n-1) * x wraps to the polynomial's low terms
+            let mut top = vec![0u8; bs];
+            top[bs - 1] = 0x80;
+            let mut x = vec![0u8; bs];
+            x[0] = 2;
+            let mut expected = vec![0u8; bs];
+            let low = poly(bs).unwrap().to_le_bytes();
+            expected[..2].copy_from_slice(&low);
+            assert_eq!(mul(&top, &x), expected, "{} bytes", bs);
+
+            // one is the identity and multiplication commutes and distributes
+            let a: Vec<u8> = (0..bs as u8).map(|i| i.wrapping_mul(37) ^ 0xa5).collect();
+            let b: Vec<u8> = (0..bs as u8).map(|i| i.wrapping_mul(91) ^ 0x3c).collect();
+            let c: Vec<u8> = (0..bs as u8).map(|i| i.wrapping_mul(13) ^ 0x71).collect();
+            let mut one = vec![0u8; bs];
+            one[0] = 1;
+            assert_eq!(mul(&a, &one), a);
+            assert_eq!(mul(&a, &b), mul(&b, &a));
+            let b_c: Vec<u8> = b.iter().zip(&c).map(|(x, y)| x ^ y).collect();
+            let ab_ac: Vec<u8> = mul(&a, &b)
+                .iter()
+                .zip(mul(&a, &c))
+                .map(|(x, y)| x ^ y)
+                .collect();
+            assert_eq!(mul(&a, &b_c), ab_ac);
+        }
+    }
+
+    #[test]
+    fn tweak_is_key_times_index() {
+        let key_exp = expand_key::<u32>(b"lrw", 12);
+        let tweak_key = [1, 2, 3, 4, 5, 6, 7, 8];
+        let lrw = Lrw::new(&key_exp, &tweak_key).unwrap();
+
+        // index 0 has a zero tweak, index 1 the tweak key itself
+        let mut buf = [0x11u8; 16];
+        lrw.encrypt_at(0, &mut buf).unwrap();
+        let plain = encrypt_kernel([0x11111111u32, 0x11111111], &key_exp);
+        assert_eq!(buf[..4], plain[0].to_le_bytes());
+
+        let mut masked = [0x11u8; 8];
+        xor(&mut masked, &tweak_key);
+        let ct = encrypt_kernel(
+            [
+                u32::from_le_slice(&masked),
+                u32::from_le_slice(&masked[4..]),
+            ],
+            &key_exp,
+        );
+        let mut expected = [0u8; 8];
+        ct[0].write_le(&mut expected);
+        ct[1].write_le(&mut expected[4..]);
+        xor(&mut expected, &tweak_key);
+        assert_eq!(buf[8..], expected);
+    }
+
+    #[test]
+    fn roundtrip_and_random_access() {
+        let key_exp = expand_key::<u128>(b"lrw volume key 0", 20);
+        let lrw = Lrw::new(&key_exp, &[0xee; 32]).unwrap();
+        let data: Vec<u8> = (0..32 * 6).map(|i| i as u8).collect();
+
+        let mut whole = data.clone();
+        lrw.encrypt_at(100, &mut whole).unwrap();
+        let mut part = data[64..128].to_vec();
+        lrw.encrypt_at(102, &mut part).unwrap();
+        assert_eq!(part, whole[64..128]);
+
+        lrw.decrypt_at(100, &mut whole).unwrap();
+        assert_eq!(whole, data);
+    }
+
+    #[test]
+    fn rejects_bad_parameters() {
+        let key_exp = expand_key::<u16>(b"k", 12);
+        assert!(matches!(
+            Lrw::new(&key_exp, &[0; 2]),
+            Err(Error::InvalidLength)
+        ));
+        let lrw = Lrw::new(&key_exp, &[0; 4]).unwrap();
+        assert_eq!(lrw.encrypt_at(0, &mut [0; 6]), Err(Error::InvalidLength));
+    }
+}
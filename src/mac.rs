@@ -0,0 +1,703 @@
+//!
+//! Message authentication codes from the RC5 block cipher alone.
+//!
+//! [`Cmac`] is CMAC (OMAC1) from NIST SP 800-38B, the standard way to
+//! authenticate messages of any length with a block cipher. Its subkeys are
+//! `L = E(K, 0)` doubled once and twice in the fields of [`lrw`](crate::lrw),
+//! whose reduction polynomials are the ones for 2w-bit blocks; doubling is
+//! little-endian, so tags are not comparable with AES-CMAC implementations.
+//! [`CbcMac`] is the older length-prefixed CBC-MAC.
+//!
+//! [`CmacState`] computes a CMAC incrementally, and can be saved and resumed:
+//! an append-only log keeps the checkpoint next to its tag and extends both
+//! on every append, without reading the records written before. Checkpoints
+//! are sealed with [`DeterministicAead`] under keys derived from the CMAC
+//! key, since the chaining value they hold would let anyone who reads two of
+//! them splice messages and forge tags.
+//!
+//! Plain CBC-MAC is only secure for messages of one fixed length: the tag of
+//! `m` lets anyone forge the tag of `m || (m ^ tag)`. [`CbcMac`] therefore
+//! chains the message length, as a little-endian `u64` zero padded to whole
+//! blocks, ahead of the message, which is then zero padded to a block
+//! boundary. Messages of any length can share a key.
+//!
+//! A key must never also encrypt: CBC encryption under the MAC key hands out
+//! the intermediate chaining values the tag is made of. [`CbcMac::new`] only
+//! takes the key the caller holds and expands a subkey derived from it under
+//! a fixed label with [`mmo_hash`], so the same bytes passed to an encryption
+//! API yield an unrelated schedule. Keep the keys separate anyway.
+//!
+//! The tag is one block of `2w` bits for `w`-bit words, and tags start to
+//! collide after about `2^w` messages under one key: use `u32` or wider.
+//!
+
+use crate::error::Error;
+use crate::hash::mmo_hash;
+use crate::lrw::{check_block_size, double};
+use crate::rc5::{encrypt_kernel, expand_key};
+use crate::siv::DeterministicAead;
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+// Associated data of sealed checkpoints.
+const CHECKPOINT_LABEL: &[u8] = b"rc5 cmac checkpoint";
+
+// Length of each checkpoint sealing key, in bytes.
+const CHECKPOINT_KEY_BYTES: usize = 16;
+
+// Bytes handed to the `prepare` callback of `Cmac::tag_xored_in_place` at
+// once, a multiple of every block size.
+const CHUNK: usize = 4096;
+
+///
+/// A length-prefixed CBC-MAC keyed with a subkey of the caller's key.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::mac::CbcMac;
+///
+/// let mac = CbcMac::<u64>::new(b"telemetry mac key", 20);
+/// let tag = mac.tag(b"temp=21.5");
+/// assert_eq!(tag.len(), 16);
+///
+/// assert!(mac.verify(b"temp=21.5", &tag).is_ok());
+/// assert!(mac.verify(b"temp=31.5", &tag).is_err());
+/// ```
+///
+pub struct CbcMac<W: Word> {
+    key_exp: Vec<W>,
+}
+
+impl<W: Word> CbcMac<W> {
+    ///
+    /// Expands the MAC subkey of `key` for `rounds` rounds.
+    ///
+    pub fn new(key: &[u8], rounds: usize) -> Self {
+        let mut input = b"rc5 cbc-mac key\0".to_vec();
+        input.extend_from_slice(key);
+        let subkey = mmo_hash::<u64>(&input, 16);
+        input.fill(0);
+        CbcMac {
+            key_exp: expand_key(&subkey, rounds),
+        }
+    }
+
+    ///
+    /// The tag of `data`, one block long.
+    ///
+    pub fn tag(&self, data: &[u8]) -> Vec<u8> {
+        let bs = 2 * W::BYTES;
+        let mut len = (data.len() as u64).to_le_bytes().to_vec();
+        len.resize(len.len().next_multiple_of(bs), 0);
+
+        let mut mac = [0u8; MAX_BLOCK];
+        let (whole, rest) = data.split_at(data.len() / bs * bs);
+        for block in len.chunks_exact(bs).chain(whole.chunks_exact(bs)) {
+            self.chain(&mut mac[..bs], block);
+        }
+        if !rest.is_empty() {
+            let mut last = [0u8; MAX_BLOCK];
+            last[..rest.len()].copy_from_slice(rest);
+            self.chain(&mut mac[..bs], &last[..bs]);
+        }
+        mac[..bs].to_vec()
+    }
+
+    ///
+    /// Checks `tag` against the tag of `data` in constant time.
+    ///
+    /// Fails with [`Error::AuthenticationFailed`] if they differ, including
+    /// in length.
+    ///
+    pub fn verify(&self, data: &[u8], tag: &[u8]) -> Result<(), Error> {
+        check(&self.tag(data), tag)
+    }
+
+    fn chain(&self, mac: &mut [u8], block: &[u8]) {
+        xor(mac, block);
+        encrypt_block(&self.key_exp, mac);
+    }
+}
+
+///
+/// CMAC with a key used for nothing else.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::mac::Cmac;
+///
+/// let cmac = Cmac::<u64>::new(b"log mac key 0001", 20).unwrap();
+/// let tag = cmac.tag(b"2024-05-01 login alice");
+/// assert_eq!(tag.len(), 16);
+///
+/// assert!(cmac.verify(b"2024-05-01 login alice", &tag).is_ok());
+/// assert!(cmac.verify(b"2024-05-01 login mallory", &tag).is_err());
+/// ```
+///
+pub struct Cmac<W: Word> {
+    key_exp: Vec<W>,
+    // subkeys for complete and padded last blocks
+    k1: [u8; MAX_BLOCK],
+    k2: [u8; MAX_BLOCK],
+}
+
+impl<W: Word> Cmac<W> {
+    ///
+    /// Expands `key` for `rounds` rounds and derives the subkeys.
+    ///
+    /// Unlike [`CbcMac::new`], the key is used as given, as the standard
+    /// requires: it must not also encrypt. Fails with
+    /// [`Error::UnsupportedParameters`] for a block size without a field
+    /// polynomial, e.g. of a 24-bit word.
+    ///
+    pub fn new(key: &[u8], rounds: usize) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        check_block_size(bs)?;
+        let key_exp = expand_key::<W>(key, rounds);
+        let mut k1 = [0u8; MAX_BLOCK];
+        encrypt_block(&key_exp, &mut k1[..bs]);
+        double(&mut k1[..bs]);
+        let mut k2 = k1;
+        double(&mut k2[..bs]);
+        Ok(Cmac { key_exp, k1, k2 })
+    }
+
+    ///
+    /// The tag of `data`, one block long.
+    ///
+    pub fn tag(&self, data: &[u8]) -> Vec<u8> {
+        self.tag_xored(data, &[])[..2 * W::BYTES].to_vec()
+    }
+
+    ///
+    /// Checks `tag` against the tag of `data` in constant time.
+    ///
+    /// Fails with [`Error::AuthenticationFailed`] if they differ, including
+    /// in length. Truncated tags are not accepted.
+    ///
+    pub fn verify(&self, data: &[u8], tag: &[u8]) -> Result<(), Error> {
+        check(&self.tag(data), tag)
+    }
+
+    ///
+    /// An incremental CMAC of an empty message, to extend with
+    /// [`CmacState::update`].
+    ///
+    pub fn start(&self) -> CmacState<'_, W> {
+        CmacState {
+            cmac: self,
+            mac: [0u8; MAX_BLOCK],
+            pending: [0u8; MAX_BLOCK],
+            len: 0,
+        }
+    }
+
+    ///
+    /// Resumes an incremental CMAC from a [`CmacState::checkpoint`] made
+    /// under the same key.
+    ///
+    /// Fails with [`Error::AuthenticationFailed`] if `checkpoint` was
+    /// altered or made under another key, and with [`Error::InvalidLength`]
+    /// if it is too short to be one.
+    ///
+    pub fn resume(&self, checkpoint: &[u8]) -> Result<CmacState<'_, W>, Error> {
+        let bs = 2 * W::BYTES;
+        let mut state = self
+            .checkpoint_sealer()
+            .open(&[CHECKPOINT_LABEL], checkpoint)?;
+        let mut resumed = self.start();
+        let mut ok = false;
+        if state.len() >= 8 + bs {
+            let (len, rest) = state.split_at(8);
+            let len = u64::from_le_bytes(len.try_into().unwrap());
+            let (mac, pending) = rest.split_at(bs);
+            if pending.len() == pending_len(len, bs) {
+                resumed.mac[..bs].copy_from_slice(mac);
+                resumed.pending[..pending.len()].copy_from_slice(pending);
+                resumed.len = len;
+                ok = true;
+            }
+        }
+        state.fill(0);
+        match ok {
+            true => Ok(resumed),
+            false => Err(Error::InvalidLength),
+        }
+    }
+
+    // SIV under two keys made of E(K, c) for constant blocks `c`. They are
+    // never zero, the block behind the subkeys, and no CMAC tag reveals
+    // them, as tags only encrypt blocks masked with chaining values or
+    // subkeys.
+    fn checkpoint_sealer(&self) -> DeterministicAead<W> {
+        let bs = 2 * W::BYTES;
+        let mut keys = [0u8; 2 * CHECKPOINT_KEY_BYTES];
+        for (i, chunk) in keys.chunks_mut(bs).enumerate() {
+            let mut block = [0u8; MAX_BLOCK];
+            block[0] = b'c';
+            block[1] = i as u8;
+            encrypt_block(&self.key_exp, &mut block[..bs]);
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        let (mac_key, enc_key) = keys.split_at(CHECKPOINT_KEY_BYTES);
+        let sealer = DeterministicAead::new(mac_key, enc_key, self.key_exp.len() / 2 - 1);
+        keys.fill(0);
+        sealer.expect("block size checked by Cmac::new")
+    }
+
+    // CMAC of `data` with `xorend` XORed into its last bytes, as S2V needs,
+    // computed without copying `data`.
+    pub(crate) fn tag_xored(&self, data: &[u8], xorend: &[u8]) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let head = last_block_at(data.len(), bs);
+        let mut mac = [0u8; MAX_BLOCK];
+        self.chain(&mut mac[..bs], &data[..head], 0, data.len(), xorend);
+        self.finish(&mut mac[..bs], &data[head..], head, data.len(), xorend);
+        mac
+    }
+
+    // Like `tag_xored`, but hands each chunk of `data` and its offset to
+    // `prepare` right before absorbing it, e.g. to decrypt it in place while
+    // it is still in cache.
+    pub(crate) fn tag_xored_in_place(
+        &self,
+        data: &mut [u8],
+        xorend: &[u8],
+        mut prepare: impl FnMut(usize, &mut [u8]),
+    ) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let len = data.len();
+        let head = last_block_at(len, bs);
+        let mut mac = [0u8; MAX_BLOCK];
+        let (body, rest) = data.split_at_mut(head);
+        for (i, chunk) in body.chunks_mut(CHUNK).enumerate() {
+            prepare(i * CHUNK, chunk);
+            self.chain(&mut mac[..bs], chunk, i * CHUNK, len, xorend);
+        }
+        prepare(head, rest);
+        self.finish(&mut mac[..bs], rest, head, len, xorend);
+        mac
+    }
+
+    // Absorbs the whole blocks of `blocks`, found at `offset` of a message of
+    // `len` bytes.
+    fn chain(&self, mac: &mut [u8], blocks: &[u8], offset: usize, len: usize, xorend: &[u8]) {
+        let bs = mac.len();
+        let mut block = [0u8; MAX_BLOCK];
+        for (i, chunk) in blocks.chunks_exact(bs).enumerate() {
+            block[..bs].copy_from_slice(chunk);
+            xor_from(
+                &mut block[..bs],
+                offset + i * bs,
+                len - xorend.len(),
+                xorend,
+            );
+            xor(mac, &block[..bs]);
+            encrypt_block(&self.key_exp, mac);
+        }
+    }
+
+    // Absorbs the last, possibly partial or empty, block `rest`.
+    fn finish(&self, mac: &mut [u8], rest: &[u8], offset: usize, len: usize, xorend: &[u8]) {
+        let bs = mac.len();
+        let mut block = [0u8; MAX_BLOCK];
+        block[..rest.len()].copy_from_slice(rest);
+        xor_from(&mut block[..rest.len()], offset, len - xorend.len(), xorend);
+        if rest.len() == bs {
+            xor(&mut block[..bs], &self.k1[..bs]);
+        } else {
+            block[rest.len()] = 0x80;
+            xor(&mut block[..bs], &self.k2[..bs]);
+        }
+        xor(mac, &block[..bs]);
+        encrypt_block(&self.key_exp, mac);
+    }
+}
+
+///
+/// A CMAC computed over data supplied in pieces.
+///
+/// The last block of the message so far is held back, since CMAC only knows
+/// how to mask it once no more data follows, so [`tag`](Self::tag) can be
+/// taken at any point and the state still extended afterwards.
+///
+/// A [`checkpoint`](Self::checkpoint) is encrypted and authenticated, so it
+/// can be stored next to the log. It can't stop a rollback, though: resuming
+/// an older checkpoint gives the tags of the shorter log it was taken at, so
+/// compare [`len`](Self::len) with the log's actual length after resuming.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::mac::Cmac;
+///
+/// let cmac = Cmac::<u64>::new(b"log mac key 0001", 20).unwrap();
+///
+/// let mut state = cmac.start();
+/// state.update(b"record 1\n");
+/// let saved = state.checkpoint();
+///
+/// // later, on the next append
+/// let mut state = cmac.resume(&saved).unwrap();
+/// state.update(b"record 2\n");
+/// assert_eq!(state.tag(), cmac.tag(b"record 1\nrecord 2\n"));
+/// ```
+///
+pub struct CmacState<'a, W: Word> {
+    cmac: &'a Cmac<W>,
+    mac: [u8; MAX_BLOCK],
+    // the last, possibly partial, block seen so far
+    pending: [u8; MAX_BLOCK],
+    len: u64,
+}
+
+impl<W: Word> CmacState<'_, W> {
+    ///
+    /// Appends `data` to the message.
+    ///
+    pub fn update(&mut self, mut data: &[u8]) {
+        let bs = 2 * W::BYTES;
+        while !data.is_empty() {
+            let held = pending_len(self.len, bs);
+            if held == bs {
+                xor(&mut self.mac[..bs], &self.pending[..bs]);
+                encrypt_block(&self.cmac.key_exp, &mut self.mac[..bs]);
+            }
+            let start = held % bs;
+            let n = (bs - start).min(data.len());
+            self.pending[start..start + n].copy_from_slice(&data[..n]);
+            self.len += n as u64;
+            data = &data[n..];
+        }
+    }
+
+    ///
+    /// The tag of the message so far, as [`Cmac::tag`] would compute it.
+    ///
+    pub fn tag(&self) -> Vec<u8> {
+        let bs = 2 * W::BYTES;
+        let held = pending_len(self.len, bs);
+        let mut mac = self.mac;
+        self.cmac
+            .finish(&mut mac[..bs], &self.pending[..held], 0, held, &[]);
+        mac[..bs].to_vec()
+    }
+
+    ///
+    /// Checks `tag` against the tag of the message so far in constant time.
+    ///
+    pub fn verify(&self, tag: &[u8]) -> Result<(), Error> {
+        check(&self.tag(), tag)
+    }
+
+    ///
+    /// Length of the message so far, in bytes.
+    ///
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    ///
+    /// Whether nothing was added yet.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///
+    /// The state, sealed, to pass to [`Cmac::resume`]: the message length as
+    /// a little-endian `u64`, the chaining value and the held-back bytes,
+    /// encrypted behind a synthetic IV: 8 bytes and at most three blocks.
+    ///
+    pub fn checkpoint(&self) -> Vec<u8> {
+        let bs = 2 * W::BYTES;
+        let mut state = self.len.to_le_bytes().to_vec();
+        state.extend_from_slice(&self.mac[..bs]);
+        state.extend_from_slice(&self.pending[..pending_len(self.len, bs)]);
+        let sealed = self
+            .cmac
+            .checkpoint_sealer()
+            .seal(&[CHECKPOINT_LABEL], &state);
+        state.fill(0);
+        sealed
+    }
+}
+
+// Bytes of the held-back last block of a message of `len` bytes.
+fn pending_len(len: u64, bs: usize) -> usize {
+    match len {
+        0 => 0,
+        _ => ((len - 1) % bs as u64) as usize + 1,
+    }
+}
+
+// Start of the last block of a message of `len` bytes, which CMAC treats
+// apart. An empty message has one, empty, last block.
+fn last_block_at(len: usize, bs: usize) -> usize {
+    len.saturating_sub(1) / bs * bs
+}
+
+// Constant-time tag comparison.
+fn check(expected: &[u8], tag: &[u8]) -> Result<(), Error> {
+    let diff = expected
+        .iter()
+        .zip(tag)
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+    if expected.len() != tag.len() || diff != 0 {
+        return Err(Error::AuthenticationFailed);
+    }
+    Ok(())
+}
+
+fn encrypt_block<W: Word>(key_exp: &[W], block: &mut [u8]) {
+    let ct = encrypt_kernel(
+        [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        key_exp,
+    );
+    ct[0].write_le(block);
+    ct[1].write_le(&mut block[W::BYTES..]);
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+// XORs `with` into the bytes of `block`, which starts at `offset` of a
+// message, that lie at or after `start` of that message.
+fn xor_from(block: &mut [u8], offset: usize, start: usize, with: &[u8]) {
+    for (j, b) in block.iter_mut().enumerate() {
+        if let Some(k) = (offset + j).checked_sub(start) {
+            *b ^= with[k];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_is_cbc_over_length_and_zero_padded_data() {
+        let mac = CbcMac::<u32>::new(b"key", 12);
+        let data: Vec<u8> = (1..=11).collect();
+
+        let mut state = [11u32, 0];
+        state = encrypt_kernel(state, &mac.key_exp);
+        let mut padded = data.clone();
+        padded.resize(16, 0);
+        for block in padded.chunks(8) {
+            let m = [
+                state[0] ^ u32::from_le_slice(block),
+                state[1] ^ u32::from_le_slice(&block[4..]),
+            ];
+            state = encrypt_kernel(m, &mac.key_exp);
+        }
+        let mut expected = [0u8; 8];
+        state[0].write_le(&mut expected);
+        state[1].write_le(&mut expected[4..]);
+        assert_eq!(mac.tag(&data), expected);
+    }
+
+    #[test]
+    fn padding_and_extension_change_the_tag() {
+        let mac = CbcMac::<u64>::new(b"key", 16);
+        let tag = mac.tag(b"abc");
+        assert_ne!(tag, mac.tag(b"abc\0"));
+        assert_ne!(mac.tag(b""), mac.tag(&[0; 16]));
+
+        // the classic forgery on plain CBC-MAC: m || (m ^ tag)
+        let m = [7u8; 16];
+        let mut forged = m.to_vec();
+        forged.extend(m.iter().zip(mac.tag(&m)).map(|(a, b)| a ^ b));
+        assert!(mac.verify(&forged, &mac.tag(&m)).is_err());
+    }
+
+    #[test]
+    fn key_is_not_the_encryption_key() {
+        let mac = CbcMac::<u32>::new(b"shared key", 12);
+        assert_ne!(mac.key_exp, expand_key::<u32>(b"shared key", 12));
+    }
+
+    #[test]
+    fn cmac_pads_only_partial_blocks() {
+        let cmac = Cmac::<u32>::new(b"mac", 12).unwrap();
+        // one complete block: E(m ^ K1)
+        let mut expected = [0u8; 8];
+        expected.copy_from_slice(b"8 bytes!");
+        xor(&mut expected, &cmac.k1[..8]);
+        encrypt_block(&cmac.key_exp, &mut expected);
+        assert_eq!(cmac.tag(b"8 bytes!"), expected);
+
+        // empty: E(0x80 0.. ^ K2)
+        let mut expected = [0x80, 0, 0, 0, 0, 0, 0, 0];
+        xor(&mut expected, &cmac.k2[..8]);
+        encrypt_block(&cmac.key_exp, &mut expected);
+        assert_eq!(cmac.tag(b""), expected);
+
+        assert_ne!(cmac.tag(b"abc"), cmac.tag(b"abc\x80"));
+
+        // XORing the end in place equals CMAC over the XORed copy
+        let data: Vec<u8> = (0..21).collect();
+        let mut xored = data.clone();
+        xor(&mut xored[13..], &[0xff; 8]);
+        assert_eq!(
+            cmac.tag_xored(&data, &[0xff; 8]),
+            cmac.tag_xored(&xored, &[])
+        );
+    }
+
+    #[test]
+    fn in_place_tag_prepares_each_chunk_before_absorbing_it() {
+        let cmac = Cmac::<u64>::new(b"mac", 12).unwrap();
+        for len in [0, 5, 16, CHUNK, CHUNK + 1, 3 * CHUNK + 20] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+            let mut masked: Vec<u8> = data.iter().map(|b| b ^ 0x5c).collect();
+            let xorend = if len >= 16 { &[0xff; 16][..] } else { &[] };
+            let mut seen = 0;
+            let tag = cmac.tag_xored_in_place(&mut masked, xorend, |offset, chunk| {
+                assert_eq!(offset, seen);
+                assert!(chunk.len() <= CHUNK);
+                seen += chunk.len();
+                chunk.iter_mut().for_each(|b| *b ^= 0x5c);
+            });
+            assert_eq!(seen, len);
+            assert_eq!(masked, data);
+            assert_eq!(tag, cmac.tag_xored(&data, xorend));
+        }
+    }
+
+    #[test]
+    fn cmac_chains_whole_blocks_and_binds_the_last() {
+        fn check<W: Word>() {
+            let bs = 2 * W::BYTES;
+            let cmac = Cmac::<W>::new(b"cmac key", 16).unwrap();
+            let data: Vec<u8> = (0..3 * bs as u8).collect();
+
+            // two plain CBC steps, then the last block masked with K1
+            let mut expected = vec![0u8; bs];
+            for (i, block) in data.chunks(bs).enumerate() {
+                xor(&mut expected, block);
+                if i == 2 {
+                    xor(&mut expected, &cmac.k1[..bs]);
+                }
+                encrypt_block(&cmac.key_exp, &mut expected);
+            }
+            assert_eq!(cmac.tag(&data), expected);
+
+            // K2 is K1 doubled again
+            let mut k2 = cmac.k1;
+            double(&mut k2[..bs]);
+            assert_eq!(k2, cmac.k2);
+
+            let tag = cmac.tag(&data[..bs + 1]);
+            assert_eq!(cmac.verify(&data[..bs + 1], &tag), Ok(()));
+            assert_eq!(
+                cmac.verify(&data[..bs + 2], &tag),
+                Err(Error::AuthenticationFailed)
+            );
+            assert_eq!(
+                cmac.verify(&data[..bs + 1], &tag[1..]),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+
+    #[test]
+    fn incremental_matches_one_shot() {
+        fn check<W: Word>() {
+            let bs = 2 * W::BYTES;
+            let cmac = Cmac::<W>::new(b"log key", 16).unwrap();
+            let data: Vec<u8> = (0..5 * bs + 3).map(|i| (i * 11) as u8).collect();
+            for step in [1, bs - 1, bs, bs + 1, 3 * bs] {
+                let mut state = cmac.start();
+                assert_eq!(state.tag(), cmac.tag(b""));
+                for (i, piece) in data.chunks(step).enumerate() {
+                    state.update(piece);
+                    let end = ((i + 1) * step).min(data.len());
+                    assert_eq!(state.len(), end as u64);
+                    assert_eq!(state.tag(), cmac.tag(&data[..end]), "{} at {}", step, end);
+                }
+                state.update(b"");
+                assert!(state.verify(&cmac.tag(&data)).is_ok());
+            }
+        }
+        check::<u8>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+
+    #[test]
+    fn checkpoint_resumes_where_it_stopped() {
+        let cmac = Cmac::<u32>::new(b"log key", 12).unwrap();
+        let log: Vec<u8> = (0..100).collect();
+        for cut in [0, 1, 7, 8, 9, 16, 99, 100] {
+            let mut state = cmac.start();
+            state.update(&log[..cut]);
+            let saved = state.checkpoint();
+            assert_eq!(saved.len(), 8 + 8 + 8 + pending_len(cut as u64, 8));
+
+            let mut resumed = cmac.resume(&saved).unwrap();
+            assert_eq!(resumed.len(), cut as u64);
+            resumed.update(&log[cut..]);
+            assert_eq!(resumed.tag(), cmac.tag(&log), "cut {}", cut);
+        }
+
+        let mut state = cmac.start();
+        state.update(&log[..13]);
+        let saved = state.checkpoint();
+        // neither the chaining value nor the held-back bytes show
+        assert!(!saved.windows(8).any(|w| w == &state.mac[..8]));
+        assert!(!saved.windows(5).any(|w| w == &log[8..13]));
+
+        assert!(matches!(
+            cmac.resume(&saved[..7]),
+            Err(Error::InvalidLength)
+        ));
+        for i in [0, 8, saved.len() - 1] {
+            let mut bad = saved.clone();
+            bad[i] ^= 1;
+            assert!(matches!(
+                cmac.resume(&bad),
+                Err(Error::AuthenticationFailed)
+            ));
+        }
+        assert!(matches!(
+            cmac.resume(&saved[..saved.len() - 1]),
+            Err(Error::AuthenticationFailed)
+        ));
+        let other = Cmac::<u32>::new(b"other key", 12).unwrap();
+        assert!(matches!(
+            other.resume(&saved),
+            Err(Error::AuthenticationFailed)
+        ));
+    }
+
+    #[test]
+    fn verify_checks_length_and_bytes() {
+        let mac = CbcMac::<u16>::new(b"key", 12);
+        let mut tag = mac.tag(b"hello");
+        assert_eq!(tag.len(), 4);
+        assert_eq!(mac.verify(b"hello", &tag), Ok(()));
+        assert_eq!(
+            mac.verify(b"hello", &tag[..3]),
+            Err(Error::AuthenticationFailed)
+        );
+        tag[3] ^= 1;
+        assert_eq!(mac.verify(b"hello", &tag), Err(Error::AuthenticationFailed));
+    }
+}
@@ -0,0 +1,1008 @@
+//!
+//! Re-encryption of RC5-protected data under a modern AEAD.
+//!
+//! Data is decrypted with a [`LegacyProfile`] and sealed again, chunk by chunk,
+//! with AES-256-GCM or ChaCha20-Poly1305, so that archives larger than memory
+//! can be migrated in a single pass. After a header naming the algorithm and
+//! the key, the output uses the STREAM construction (Hoang, Reyhanitabar,
+//! Rogaway, Vizár) in the same layout as the RustCrypto
+//! `aead::stream::EncryptorBE32`:
+//!
+//! ```text
+//! algorithm (1 byte) || key ID (8 bytes) || nonce prefix (7 bytes)
+//!     || segment 0 || segment 1 || ... || last segment
+//! ```
+//!
+//! The algorithm byte is 1 for AES-256-GCM and 2 for ChaCha20-Poly1305, and
+//! the key ID is the [`Rc5Key::id`] of the AEAD key, so that the key can be
+//! looked up in a [`Keyring`].
+//!
+//! Every segment but the last seals exactly [`CHUNK_LEN`] plaintext bytes and
+//! is [`CHUNK_LEN`] + 16 bytes long. Segment `i` is sealed with the nonce
+//! `prefix || i (big-endian u32) || last`, where `last` is 1 for the final
+//! segment and 0 otherwise, so truncation and reordering are detected.
+//!
+//! [`open`] reads this format back, and [`recover`] does so while skipping
+//! over segments that fail authentication, for salvaging what is left of a
+//! partially corrupted archive. [`reencrypt`] moves such a stream to a new
+//! key in one pass, for key rotation, rewriting the header.
+//!
+//! Only available with the `migrate` feature, which enables `key`.
+//!
+
+use std::io::{self, IoSlice, Read, Write};
+use std::ops::Range;
+
+use crate::algo::{AeadAlgo, AeadAlgorithm};
+use crate::audit::{self, Operation};
+use crate::cancel::CancellationToken;
+use crate::error::{Error, Failure};
+use crate::key::{KeyId, Keyring, Rc5Key};
+use crate::legacy::LegacyProfile;
+use crate::rng::{CryptoRng, RngCore};
+use crate::word::Word;
+
+///
+/// Plaintext bytes sealed per segment.
+///
+pub const CHUNK_LEN: usize = 64 * 1024;
+
+///
+/// Bytes before the first segment: the algorithm, the key ID and the nonce
+/// prefix.
+///
+pub const HEADER_LEN: usize = 1 + 8 + 7;
+
+const READ_LEN: usize = 8 * 1024;
+
+///
+/// Decrypts everything from `reader` with `profile` and `rc5_key`, and writes
+/// it to `writer` sealed under `target` with the 32-byte `key`. Returns the number of
+/// plaintext bytes migrated.
+///
+/// The `nonce_prefix` is consumed; see [`NoncePrefix`] for how to obtain one.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+/// use rc5_cipher::algo::AeadAlgorithm;
+/// use rc5_cipher::migrate::{migrate, NoncePrefix, HEADER_LEN};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let profile = LegacyProfile {
+///     rounds: 12,
+///     endian: Endian::Little,
+///     key: KeyHandling::Raw,
+///     padding: LegacyPadding::Pkcs7,
+///     chaining: Chaining::CbcPrependedIv,
+/// };
+/// let old = profile.encrypt_with_rng::<u32, _>(&mut rng, b"old rc5 key", b"customer records").unwrap();
+///
+/// let mut new = Vec::new();
+/// let len = migrate::<u32, _, _>(
+///     &profile,
+///     b"old rc5 key",
+///     AeadAlgorithm::ChaCha20Poly1305,
+///     &[0x42; 32],
+///     NoncePrefix::assume_unique([0x01; 7]),
+///     &old[..],
+///     &mut new,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(len, 16);
+/// assert_eq!(new.len(), HEADER_LEN + 16 + 16);
+/// ```
+///
+pub fn migrate<W: Word, R: Read, O: Write>(
+    profile: &LegacyProfile,
+    rc5_key: &[u8],
+    target: AeadAlgorithm,
+    key: &[u8],
+    nonce_prefix: NoncePrefix,
+    reader: R,
+    writer: O,
+) -> Result<u64, Error> {
+    migrate_with_progress::<W, R, O, _>(
+        profile,
+        rc5_key,
+        target,
+        key,
+        nonce_prefix,
+        reader,
+        writer,
+        &CancellationToken::new(),
+        |_| {},
+    )
+}
+
+///
+/// The per-stream nonce prefix of the STREAM construction.
+///
+/// There's deliberately no `Clone`, `Default` or `From<[u8; 7]>`: a prefix is
+/// either drawn from a generator or explicitly asserted to be unique, and it is
+/// consumed by the stream it seals, so the same prefix can't be passed to two
+/// calls by accident.
+///
+pub struct NoncePrefix([u8; 7]);
+
+impl NoncePrefix {
+    ///
+    /// Draws a fresh prefix from `rng`.
+    ///
+    pub fn generate<G: RngCore + CryptoRng>(rng: &mut G) -> Self {
+        let mut prefix = [0u8; 7];
+        rng.fill_bytes(&mut prefix);
+        NoncePrefix(prefix)
+    }
+
+    ///
+    /// Uses `bytes` as the prefix. The caller guarantees that they are never
+    /// used again with the same key, e.g. because they come from a persistent
+    /// counter, or that the output only needs to reproduce a known vector.
+    ///
+    pub fn assume_unique(bytes: [u8; 7]) -> Self {
+        NoncePrefix(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 7] {
+        &self.0
+    }
+}
+
+///
+/// Running totals passed to the callback of [`migrate_with_progress`].
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Plaintext bytes sealed so far.
+    pub bytes: u64,
+    /// Segments written so far.
+    pub chunks: u64,
+}
+
+///
+/// Like [`migrate`], but calls `progress` after every segment is written, so
+/// that callers can report progress or emit metrics without wrapping the
+/// reader. The last call carries the same byte count that is returned.
+///
+/// `cancel` is checked before each segment; once it is set the migration
+/// stops with [`Error::Cancelled`], leaving only whole segments in `writer`.
+/// Such output lacks its final segment and is rejected by [`open`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::AeadAlgorithm;
+/// use rc5_cipher::cancel::CancellationToken;
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+/// use rc5_cipher::migrate::{migrate_with_progress, NoncePrefix, CHUNK_LEN};
+///
+/// let profile = LegacyProfile {
+///     rounds: 12,
+///     endian: Endian::Little,
+///     key: KeyHandling::Raw,
+///     padding: LegacyPadding::Pkcs7,
+///     chaining: Chaining::Ecb,
+/// };
+/// let old = profile.encrypt::<u32>(b"old", &vec![0u8; 2 * CHUNK_LEN + 5]).unwrap();
+///
+/// let mut seen = Vec::new();
+/// migrate_with_progress::<u32, _, _, _>(
+///     &profile,
+///     b"old",
+///     AeadAlgorithm::Aes256Gcm,
+///     &[1; 32],
+///     NoncePrefix::assume_unique([2; 7]),
+///     &old[..],
+///     Vec::new(),
+///     &CancellationToken::new(),
+///     |p| seen.push((p.chunks, p.bytes)),
+/// )
+/// .unwrap();
+///
+/// let chunk = CHUNK_LEN as u64;
+/// assert_eq!(seen, vec![(1, chunk), (2, 2 * chunk), (3, 2 * chunk + 5)]);
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn migrate_with_progress<W: Word, R: Read, O: Write, F: FnMut(&Progress)>(
+    profile: &LegacyProfile,
+    rc5_key: &[u8],
+    target: AeadAlgorithm,
+    key: &[u8],
+    nonce_prefix: NoncePrefix,
+    mut reader: R,
+    mut writer: O,
+    cancel: &CancellationToken,
+    mut progress: F,
+) -> Result<u64, Error> {
+    let params = Some(profile.params::<W>(rc5_key));
+    let key_id = Rc5Key::new(key).map(|key| key.id());
+    let audited = key_id.as_ref().ok().map(KeyId::to_string);
+    audit::operation(
+        Operation::Migrate,
+        audited.as_deref(),
+        params,
+        |n: &u64| *n,
+        || {
+            let mut decryptor = profile.decryptor::<W>(rc5_key)?;
+            let mut segment = Segment {
+                aead: target.instantiate(key)?,
+                prefix: nonce_prefix.0,
+                counter: 0,
+            };
+            let mut head = Some(stream_header(target, key_id?, &nonce_prefix));
+
+            let mut buf = vec![0u8; READ_LEN];
+            let mut plain = Vec::with_capacity(CHUNK_LEN + READ_LEN);
+            let mut done = Progress::default();
+            let mut write_segment =
+                |plain: &[u8], last: bool, writer: &mut O| -> Result<(), Error> {
+                    cancel.check()?;
+                    let sealed = segment.seal(plain, last)?;
+                    write_with_header(writer, &mut head, &sealed)?;
+                    done.bytes += plain.len() as u64;
+                    done.chunks += 1;
+                    progress(&done);
+                    Ok(())
+                };
+
+            loop {
+                let n = reader.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                decryptor.update(&buf[..n], &mut plain);
+                while plain.len() > CHUNK_LEN {
+                    write_segment(&plain[..CHUNK_LEN], false, &mut writer)?;
+                    plain.drain(..CHUNK_LEN);
+                }
+            }
+
+            decryptor.finish(&mut plain)?;
+            while plain.len() > CHUNK_LEN {
+                write_segment(&plain[..CHUNK_LEN], false, &mut writer)?;
+                plain.drain(..CHUNK_LEN);
+            }
+            write_segment(&plain, true, &mut writer)?;
+
+            writer.flush()?;
+            Ok(done.bytes)
+        },
+    )
+}
+
+///
+/// Outcome of [`recover`].
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovery {
+    /// Plaintext bytes written, including the zero-filled damaged ranges.
+    pub bytes: u64,
+    /// Plaintext byte ranges of the segments that failed authentication.
+    pub damaged: Vec<Range<u64>>,
+}
+
+///
+/// Decrypts a stream written by [`migrate`] from `reader` into `writer`,
+/// returning the number of plaintext bytes. Fails on the first segment that
+/// doesn't authenticate, after having written the plaintext preceding it.
+///
+/// Fails with a [`Failure::Crypto`] that doesn't say why if the stream was
+/// altered or truncated, sealed with another algorithm than `target`, or
+/// under another key than `key`. Only I/O errors, cancellation and key
+/// limits are a [`Failure::Usage`].
+///
+pub fn open<R: Read, O: Write>(
+    target: AeadAlgorithm,
+    key: &[u8],
+    reader: R,
+    writer: O,
+) -> Result<u64, Failure> {
+    let recovery = open_to(target, key, reader, writer, false).map_err(Failure::decrypting)?;
+    Ok(recovery.bytes)
+}
+
+///
+/// Like [`open`], but a segment that fails authentication is written as
+/// zeros and reported instead of aborting, so that the plaintext keeps its
+/// original offsets and every intact segment is recovered.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::AeadAlgorithm;
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+/// use rc5_cipher::migrate::{migrate, recover, NoncePrefix, CHUNK_LEN, HEADER_LEN};
+///
+/// let profile = LegacyProfile {
+///     rounds: 12,
+///     endian: Endian::Little,
+///     key: KeyHandling::Raw,
+///     padding: LegacyPadding::Pkcs7,
+///     chaining: Chaining::Ecb,
+/// };
+/// let data = vec![0x11u8; 3 * CHUNK_LEN];
+/// let old = profile.encrypt::<u32>(b"old", &data).unwrap();
+///
+/// let mut new = Vec::new();
+/// let algo = AeadAlgorithm::Aes256Gcm;
+/// let prefix = NoncePrefix::assume_unique([2; 7]);
+/// migrate::<u32, _, _>(&profile, b"old", algo, &[1; 32], prefix, &old[..], &mut new).unwrap();
+///
+/// // flip a bit inside the second segment
+/// new[HEADER_LEN + CHUNK_LEN + 16 + 100] ^= 1;
+///
+/// let mut out = Vec::new();
+/// let recovery = recover(algo, &[1; 32], &new[..], &mut out).unwrap();
+///
+/// let lost = CHUNK_LEN as u64..2 * CHUNK_LEN as u64;
+/// assert_eq!(recovery.damaged, vec![lost]);
+/// assert_eq!(out[..CHUNK_LEN], data[..CHUNK_LEN]);
+/// assert_eq!(out[2 * CHUNK_LEN..], data[2 * CHUNK_LEN..]);
+/// ```
+///
+pub fn recover<R: Read, O: Write>(
+    target: AeadAlgorithm,
+    key: &[u8],
+    reader: R,
+    writer: O,
+) -> Result<Recovery, Error> {
+    open_to(target, key, reader, writer, true)
+}
+
+///
+/// Decrypts a stream written by [`migrate`] with the key of `old_keyring` its
+/// header names, and writes it to `writer` sealed again under `to` with
+/// `new_key`, for rotating the key of an archive without holding more than one
+/// segment in memory. Returns the number of plaintext bytes.
+///
+/// The output gets a new header, with `to`, the ID of `new_key` and the fresh
+/// `nonce_prefix`, and keeps the segment boundaries of the input. `progress`
+/// and `cancel` work as in [`migrate_with_progress`]. Fails with
+/// [`Error::UnknownKey`] before writing anything if the keyring doesn't hold
+/// the old key. A segment that fails authentication stops the pass with
+/// [`Error::AuthenticationFailed`]; everything written before it is sealed
+/// under `new_key` but lacks its final segment, so [`open`] rejects it.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::AeadAlgorithm;
+/// use rc5_cipher::cancel::CancellationToken;
+/// use rc5_cipher::key::{Keyring, Rc5Key};
+/// use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+/// use rc5_cipher::migrate::{migrate, open, reencrypt, NoncePrefix, CHUNK_LEN};
+///
+/// let profile = LegacyProfile {
+///     rounds: 12,
+///     endian: Endian::Little,
+///     key: KeyHandling::Raw,
+///     padding: LegacyPadding::None,
+///     chaining: Chaining::Ecb,
+/// };
+/// let data = vec![0x11u8; 2 * CHUNK_LEN];
+/// let old = profile.encrypt::<u32>(b"old", &data).unwrap();
+/// let gcm = AeadAlgorithm::Aes256Gcm;
+///
+/// let mut archive = Vec::new();
+/// let prefix = NoncePrefix::assume_unique([1; 7]);
+/// migrate::<u32, _, _>(&profile, b"old", gcm, &[1; 32], prefix, &old[..], &mut archive).unwrap();
+///
+/// let mut keyring = Keyring::new();
+/// keyring.insert(Some("2026-09"), Rc5Key::new(&[1; 32]).unwrap());
+/// let new_key = Rc5Key::new(&[2; 32]).unwrap();
+///
+/// let mut rotated = Vec::new();
+/// let mut chunks = 0;
+/// reencrypt(
+///     &keyring,
+///     AeadAlgorithm::ChaCha20Poly1305,
+///     &new_key,
+///     NoncePrefix::assume_unique([2; 7]),
+///     &archive[..],
+///     &mut rotated,
+///     &CancellationToken::new(),
+///     |p| chunks = p.chunks,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(chunks, 2);
+/// let mut out = Vec::new();
+/// open(AeadAlgorithm::ChaCha20Poly1305, &[2; 32], &rotated[..], &mut out).unwrap();
+/// assert_eq!(out, data);
+/// ```
+///
+#[allow(clippy::too_many_arguments)]
+pub fn reencrypt<R: Read, O: Write, F: FnMut(&Progress)>(
+    old_keyring: &Keyring,
+    to: AeadAlgorithm,
+    new_key: &Rc5Key,
+    nonce_prefix: NoncePrefix,
+    mut reader: R,
+    mut writer: O,
+    cancel: &CancellationToken,
+    mut progress: F,
+) -> Result<u64, Error> {
+    audit::operation(
+        Operation::Migrate,
+        Some(&new_key.id().to_string()),
+        None,
+        |n: &u64| *n,
+        || {
+            let (from, old_id, old_prefix) = read_header(&mut reader)?;
+            let old_key = old_keyring.get(&old_id).ok_or(Error::UnknownKey)?;
+            let opener = Segment {
+                aead: from.instantiate(old_key.as_bytes())?,
+                prefix: old_prefix,
+                counter: 0,
+            };
+            let mut sealer = Segment {
+                aead: to.instantiate(new_key.as_bytes())?,
+                prefix: nonce_prefix.0,
+                counter: 0,
+            };
+            let mut head = Some(stream_header(to, new_key.id(), &nonce_prefix));
+            let mut done = Progress::default();
+            open_segments(opener, reader, false, |plain, last| {
+                cancel.check()?;
+                let sealed = sealer.seal(plain, last)?;
+                write_with_header(&mut writer, &mut head, &sealed)?;
+                done.bytes += plain.len() as u64;
+                done.chunks += 1;
+                progress(&done);
+                Ok(())
+            })?;
+            writer.flush()?;
+            Ok(done.bytes)
+        },
+    )
+}
+
+fn open_to<R: Read, O: Write>(
+    target: AeadAlgorithm,
+    key: &[u8],
+    mut reader: R,
+    mut writer: O,
+    skip_damaged: bool,
+) -> Result<Recovery, Error> {
+    let aead = target.instantiate(key)?;
+    let (algorithm, id, prefix) = read_header(&mut reader)?;
+    if algorithm != target {
+        return Err(Error::UnsupportedParameters);
+    }
+    if id != Rc5Key::new(key)?.id() {
+        return Err(Error::UnknownKey);
+    }
+    let segment = Segment {
+        aead,
+        prefix,
+        counter: 0,
+    };
+    let recovery = open_segments(segment, reader, skip_damaged, |plain, _| {
+        writer.write_all(plain)?;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(recovery)
+}
+
+// The header of a stream sealed under `algorithm` with the key `id`.
+fn stream_header(algorithm: AeadAlgorithm, id: KeyId, prefix: &NoncePrefix) -> [u8; HEADER_LEN] {
+    let mut head = [0u8; HEADER_LEN];
+    head[0] = match algorithm {
+        AeadAlgorithm::Aes256Gcm => 1,
+        AeadAlgorithm::ChaCha20Poly1305 => 2,
+    };
+    head[1..9].copy_from_slice(&id.0);
+    head[9..].copy_from_slice(&prefix.0);
+    head
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<(AeadAlgorithm, KeyId, [u8; 7]), Error> {
+    let mut head = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut head)
+        .map_err(|_| Error::InvalidLength)?;
+    let algorithm = match head[0] {
+        1 => AeadAlgorithm::Aes256Gcm,
+        2 => AeadAlgorithm::ChaCha20Poly1305,
+        _ => return Err(Error::InvalidHeader),
+    };
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&head[1..9]);
+    let mut prefix = [0u8; 7];
+    prefix.copy_from_slice(&head[9..]);
+    Ok((algorithm, KeyId(id), prefix))
+}
+
+// Authenticates the segments of `reader`, which follow the header, one at a
+// time and passes each plaintext to `emit`, together with whether it is the
+// last one.
+fn open_segments<R: Read, F: FnMut(&[u8], bool) -> Result<(), Error>>(
+    mut segment: Segment,
+    mut reader: R,
+    skip_damaged: bool,
+    mut emit: F,
+) -> Result<Recovery, Error> {
+    let seg_len = CHUNK_LEN + 16;
+    let mut recovery = Recovery {
+        bytes: 0,
+        damaged: Vec::new(),
+    };
+    // One byte more than a segment, to tell whether another one follows.
+    let mut buf = vec![0u8; seg_len + 1];
+    let mut filled = read_full(&mut reader, &mut buf)?;
+    loop {
+        let last = filled <= seg_len;
+        let len = filled.min(seg_len);
+        if len < 16 {
+            return Err(Error::InvalidLength);
+        }
+
+        match segment.open(&buf[..len], last) {
+            Ok(plain) => emit(&plain, last)?,
+            Err(Error::AuthenticationFailed) if skip_damaged => {
+                let start = recovery.bytes;
+                recovery.damaged.push(start..start + (len - 16) as u64);
+                emit(&vec![0u8; len - 16], last)?;
+            }
+            Err(err) => return Err(err),
+        }
+        recovery.bytes += (len - 16) as u64;
+
+        if last {
+            break;
+        }
+        buf[0] = buf[seg_len];
+        filled = 1 + read_full(&mut reader, &mut buf[1..])?;
+    }
+
+    Ok(recovery)
+}
+
+// Writes `segment`, preceded by the stream's header if it hasn't been
+// written yet, so that a stream starts with a single vectored write.
+fn write_with_header<O: Write>(
+    writer: &mut O,
+    head: &mut Option<[u8; HEADER_LEN]>,
+    segment: &[u8],
+) -> Result<(), Error> {
+    match head.take() {
+        Some(head) => write_all_vectored(writer, &mut [IoSlice::new(&head), IoSlice::new(segment)]),
+        None => Ok(writer.write_all(segment)?),
+    }
+}
+
+// `Write::write_all_vectored` isn't stable yet.
+fn write_all_vectored<O: Write>(
+    writer: &mut O,
+    mut parts: &mut [IoSlice<'_>],
+) -> Result<(), Error> {
+    while !parts.is_empty() {
+        match writer.write_vectored(parts) {
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+            Ok(n) => IoSlice::advance_slices(&mut parts, n),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, Error> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+struct Segment {
+    aead: Box<dyn AeadAlgo>,
+    prefix: [u8; 7],
+    counter: u32,
+}
+
+impl Segment {
+    fn seal(&mut self, plain: &[u8], last: bool) -> Result<Vec<u8>, Error> {
+        let nonce = self.next_nonce(last)?;
+        // unique by construction: the prefix is consumed and the counter only grows
+        self.aead.seal_with_raw_nonce(&nonce, &[], plain)
+    }
+
+    fn open(&mut self, sealed: &[u8], last: bool) -> Result<Vec<u8>, Error> {
+        let nonce = self.next_nonce(last)?;
+        self.aead.open(&nonce, &[], sealed)
+    }
+
+    fn next_nonce(&mut self, last: bool) -> Result<[u8; 12], Error> {
+        let mut nonce = [0u8; 12];
+        nonce[..7].copy_from_slice(&self.prefix);
+        nonce[7..11].copy_from_slice(&self.counter.to_be_bytes());
+        nonce[11] = last as u8;
+
+        self.counter = self.counter.checked_add(1).ok_or(Error::InvalidLength)?;
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CryptoError;
+    use crate::legacy::{Chaining, Endian, KeyHandling, LegacyPadding};
+    use crate::rng::CounterRng;
+
+    fn profile() -> LegacyProfile {
+        LegacyProfile {
+            rounds: 16,
+            endian: Endian::Big,
+            key: KeyHandling::ZeroPadded(16),
+            padding: LegacyPadding::Pkcs7,
+            chaining: Chaining::CbcZeroIv,
+        }
+    }
+
+    // The cause behind a failure of `open`, which only says that the data
+    // is at fault.
+    fn open_cause(target: AeadAlgorithm, key: &[u8], data: &[u8]) -> Result<u64, Error> {
+        open_to(target, key, data, Vec::new(), false).map(|r| r.bytes)
+    }
+
+    fn open_all(target: AeadAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        open(target, key, data, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn migrate_multi_segment() {
+        let key = [7u8; 32];
+        // spans two full segments plus a partial one
+        let data: Vec<u8> = (0..2 * CHUNK_LEN + 1000).map(|i| i as u8).collect();
+        let old = profile().encrypt::<u16>(b"legacy", &data).unwrap();
+
+        for target in [AeadAlgorithm::Aes256Gcm, AeadAlgorithm::ChaCha20Poly1305] {
+            let mut new = Vec::new();
+            let len = migrate::<u16, _, _>(
+                &profile(),
+                b"legacy",
+                target,
+                &key,
+                NoncePrefix::assume_unique([9; 7]),
+                &old[..],
+                &mut new,
+            )
+            .unwrap();
+            assert_eq!(len, data.len() as u64);
+            assert_eq!(new.len(), HEADER_LEN + data.len() + 3 * 16);
+
+            let pt = open_all(target, &key, &new);
+            assert_eq!(pt, data);
+        }
+    }
+
+    #[test]
+    fn exact_chunk_multiple_ends_with_full_last_segment() {
+        let key = [1u8; 32];
+        let mut p = profile();
+        p.padding = LegacyPadding::None;
+        let data = vec![0xa5u8; CHUNK_LEN];
+        let old = p.encrypt::<u32>(b"legacy", &data).unwrap();
+
+        let mut new = Vec::new();
+        migrate::<u32, _, _>(
+            &p,
+            b"legacy",
+            AeadAlgorithm::Aes256Gcm,
+            &key,
+            NoncePrefix::assume_unique([0; 7]),
+            &old[..],
+            &mut new,
+        )
+        .unwrap();
+        assert_eq!(new.len(), HEADER_LEN + CHUNK_LEN + 16);
+        assert_eq!(open_all(AeadAlgorithm::Aes256Gcm, &key, &new), data);
+    }
+
+    #[test]
+    fn legacy_errors_are_propagated() {
+        let old = profile().encrypt::<u32>(b"legacy", b"data").unwrap();
+        let result = migrate::<u32, _, _>(
+            &profile(),
+            b"legacy",
+            AeadAlgorithm::Aes256Gcm,
+            &[0; 32],
+            NoncePrefix::assume_unique([0; 7]),
+            &old[..old.len() - 1],
+            Vec::new(),
+        );
+        assert_eq!(result, Err(Error::InvalidLength));
+    }
+
+    #[test]
+    fn progress_is_reported_per_segment() {
+        let mut p = profile();
+        p.padding = LegacyPadding::None;
+        let data = vec![3u8; 2 * CHUNK_LEN];
+        let old = p.encrypt::<u32>(b"legacy", &data).unwrap();
+
+        let mut seen = Vec::new();
+        let len = migrate_with_progress::<u32, _, _, _>(
+            &p,
+            b"legacy",
+            AeadAlgorithm::Aes256Gcm,
+            &[0; 32],
+            NoncePrefix::assume_unique([0; 7]),
+            &old[..],
+            Vec::new(),
+            &CancellationToken::new(),
+            |progress| seen.push(*progress),
+        )
+        .unwrap();
+
+        let chunk = CHUNK_LEN as u64;
+        assert_eq!(
+            seen,
+            vec![
+                Progress {
+                    bytes: chunk,
+                    chunks: 1
+                },
+                Progress {
+                    bytes: 2 * chunk,
+                    chunks: 2
+                },
+            ]
+        );
+        assert_eq!(len, 2 * chunk);
+    }
+
+    #[test]
+    fn cancel_stops_at_segment_boundary() {
+        let mut p = profile();
+        p.padding = LegacyPadding::None;
+        let data = vec![3u8; 3 * CHUNK_LEN];
+        let old = p.encrypt::<u32>(b"legacy", &data).unwrap();
+
+        let token = CancellationToken::new();
+        let mut new = Vec::new();
+        let result = migrate_with_progress::<u32, _, _, _>(
+            &p,
+            b"legacy",
+            AeadAlgorithm::Aes256Gcm,
+            &[0; 32],
+            NoncePrefix::assume_unique([0; 7]),
+            &old[..],
+            &mut new,
+            &token,
+            |progress| {
+                if progress.chunks == 1 {
+                    token.cancel();
+                }
+            },
+        );
+
+        assert_eq!(result, Err(Error::Cancelled));
+        assert_eq!(new.len(), HEADER_LEN + CHUNK_LEN + 16);
+        assert_eq!(
+            open(AeadAlgorithm::Aes256Gcm, &[0; 32], &new[..], Vec::new()),
+            Err(Failure::Crypto(CryptoError))
+        );
+    }
+
+    #[test]
+    fn generated_prefix_leads_the_stream() {
+        let old = profile().encrypt::<u32>(b"legacy", b"data").unwrap();
+        let prefix = NoncePrefix::generate(&mut CounterRng(40));
+        assert_eq!(prefix.as_bytes(), &[40, 41, 42, 43, 44, 45, 46]);
+
+        let mut new = Vec::new();
+        migrate::<u32, _, _>(
+            &profile(),
+            b"legacy",
+            AeadAlgorithm::Aes256Gcm,
+            &[0; 32],
+            prefix,
+            &old[..],
+            &mut new,
+        )
+        .unwrap();
+        assert_eq!(new[9..HEADER_LEN], [40, 41, 42, 43, 44, 45, 46]);
+        assert_eq!(open_all(AeadAlgorithm::Aes256Gcm, &[0; 32], &new), b"data");
+    }
+
+    fn sealed(len: usize) -> (Vec<u8>, Vec<u8>) {
+        let mut p = profile();
+        p.chaining = Chaining::Ecb;
+        let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        let old = p.encrypt::<u32>(b"legacy", &data).unwrap();
+        let mut new = Vec::new();
+        migrate::<u32, _, _>(
+            &p,
+            b"legacy",
+            AeadAlgorithm::ChaCha20Poly1305,
+            &[4; 32],
+            NoncePrefix::assume_unique([5; 7]),
+            &old[..],
+            &mut new,
+        )
+        .unwrap();
+        (data, new)
+    }
+
+    #[test]
+    fn open_rejects_tampering_and_truncation() {
+        let algo = AeadAlgorithm::ChaCha20Poly1305;
+        let (data, new) = sealed(2 * CHUNK_LEN + 10);
+        assert_eq!(open_all(algo, &[4; 32], &new), data);
+
+        let mut tampered = new.clone();
+        tampered[HEADER_LEN + 3] ^= 0x80;
+        assert_eq!(
+            open_cause(algo, &[4; 32], &tampered[..]),
+            Err(Error::AuthenticationFailed)
+        );
+
+        // dropping the last segment makes the previous one look final
+        let truncated = &new[..HEADER_LEN + 2 * (CHUNK_LEN + 16)];
+        assert_eq!(
+            open_cause(algo, &[4; 32], truncated),
+            Err(Error::AuthenticationFailed)
+        );
+
+        assert_eq!(
+            open_cause(algo, &[4; 32], &new[..5]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn header_names_algorithm_and_key() {
+        let algo = AeadAlgorithm::ChaCha20Poly1305;
+        let (_, new) = sealed(10);
+        assert_eq!(new[0], 2);
+        assert_eq!(new[1..9], Rc5Key::new(&[4; 32]).unwrap().id().0);
+
+        assert_eq!(
+            open_cause(AeadAlgorithm::Aes256Gcm, &[4; 32], &new),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(open_cause(algo, &[5; 32], &new), Err(Error::UnknownKey));
+        let mut unknown = new.clone();
+        unknown[0] = 3;
+        assert_eq!(
+            open_cause(algo, &[4; 32], &unknown[..]),
+            Err(Error::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn recover_skips_damaged_segments() {
+        let algo = AeadAlgorithm::ChaCha20Poly1305;
+        let (data, mut new) = sealed(3 * CHUNK_LEN + 100);
+        let seg = CHUNK_LEN + 16;
+        new[HEADER_LEN + 5] ^= 1;
+        new[HEADER_LEN + 3 * seg + 20] ^= 1;
+
+        let mut out = Vec::new();
+        let recovery = recover(algo, &[4; 32], &new[..], &mut out).unwrap();
+
+        let chunk = CHUNK_LEN as u64;
+        assert_eq!(
+            recovery,
+            Recovery {
+                bytes: data.len() as u64,
+                damaged: vec![0..chunk, 3 * chunk..3 * chunk + 100],
+            }
+        );
+        assert_eq!(out.len(), data.len());
+        assert!(out[..CHUNK_LEN].iter().all(|&b| b == 0));
+        assert_eq!(
+            out[CHUNK_LEN..3 * CHUNK_LEN],
+            data[CHUNK_LEN..3 * CHUNK_LEN]
+        );
+    }
+
+    // Records the total length of every write call.
+    #[derive(Default)]
+    struct Calls(Vec<usize>);
+
+    impl Write for Calls {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            let len = bufs.iter().map(|b| b.len()).sum();
+            self.0.push(len);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn prefix_and_first_segment_share_a_write() {
+        let mut p = profile();
+        p.padding = LegacyPadding::None;
+        let old = p
+            .encrypt::<u32>(b"legacy", &vec![0u8; CHUNK_LEN + 8])
+            .unwrap();
+        let mut calls = Calls::default();
+        let prefix = NoncePrefix::assume_unique([0; 7]);
+        let gcm = AeadAlgorithm::Aes256Gcm;
+        migrate::<u32, _, _>(&p, b"legacy", gcm, &[0; 32], prefix, &old[..], &mut calls).unwrap();
+        assert_eq!(calls.0, [HEADER_LEN + CHUNK_LEN + 16, 8 + 16]);
+    }
+
+    #[test]
+    fn reencrypt_rotates_the_key() {
+        let mut p = profile();
+        p.padding = LegacyPadding::None;
+        let data: Vec<u8> = (0..2 * CHUNK_LEN + 16).map(|i| i as u8).collect();
+        let old = p.encrypt::<u32>(b"legacy", &data).unwrap();
+        let gcm = AeadAlgorithm::Aes256Gcm;
+        let mut archive = Vec::new();
+        let prefix = NoncePrefix::assume_unique([1; 7]);
+        migrate::<u32, _, _>(&p, b"legacy", gcm, &[1; 32], prefix, &old[..], &mut archive).unwrap();
+
+        let mut keyring = Keyring::new();
+        keyring.insert(None, Rc5Key::new(&[9; 32]).unwrap());
+        keyring.insert(None, Rc5Key::new(&[1; 32]).unwrap());
+        let new_key = Rc5Key::new(&[2; 32]).unwrap();
+        let chacha = AeadAlgorithm::ChaCha20Poly1305;
+
+        let mut seen = Vec::new();
+        let mut rotated = Vec::new();
+        let len = reencrypt(
+            &keyring,
+            chacha,
+            &new_key,
+            NoncePrefix::assume_unique([2; 7]),
+            &archive[..],
+            &mut rotated,
+            &CancellationToken::new(),
+            |progress| seen.push(progress.chunks),
+        )
+        .unwrap();
+        assert_eq!(len, data.len() as u64);
+        assert_eq!(seen, vec![1, 2, 3]);
+        assert_eq!(rotated.len(), archive.len());
+        assert_eq!(rotated[0], 2);
+        assert_eq!(rotated[1..9], new_key.id().0);
+        assert_eq!(rotated[9..HEADER_LEN], [2; 7]);
+        assert_eq!(open_all(chacha, &[2; 32], &rotated), data);
+        assert_eq!(
+            open_cause(chacha, &[1; 32], &rotated[..]),
+            Err(Error::UnknownKey)
+        );
+
+        // an old key missing from the keyring writes nothing
+        keyring.remove(&Rc5Key::new(&[1; 32]).unwrap().id());
+        let mut out = Vec::new();
+        let result = reencrypt(
+            &keyring,
+            chacha,
+            &new_key,
+            NoncePrefix::assume_unique([2; 7]),
+            &archive[..],
+            &mut out,
+            &CancellationToken::new(),
+            |_| {},
+        );
+        assert_eq!(result, Err(Error::UnknownKey));
+        assert!(out.is_empty());
+    }
+}
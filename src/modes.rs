@@ -0,0 +1,19 @@
+//!
+//! The block cipher modes of operation, gathered in one place.
+//!
+//! Each mode lives in its own top-level module; this one only re-exports
+//! them, so both `rc5_cipher::ctr` and `rc5_cipher::modes::ctr` work.
+//!
+//! Example:
+//!
+//! ```rust
+//! use rc5_cipher::modes::ctr::Rc5Ctr;
+//!
+//! let mut ctr = Rc5Ctr::<u32, _>::from_key(b"stream key 12345", 12, &[7; 8], 0).unwrap();
+//! let mut data = *b"no padding needed";
+//! ctr.apply_keystream(&mut data);
+//! assert_ne!(&data, b"no padding needed");
+//! ```
+//!
+
+pub use crate::{cbc, ccm, cfb, ctr, ecb, lrw, ocb, siv, xts};
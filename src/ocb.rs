@@ -0,0 +1,390 @@
+//!
+//! OCB single-pass authenticated encryption.
+//!
+//! The structure of OCB3 (RFC 7253) over the `2w`-bit RC5 block: every block
+//! of data costs one block cipher call, against two for [`ccm`](crate::ccm)
+//! or encrypt-then-MAC. Block `i` is encrypted as
+//! `C_i = Offset_i ^ E(Offset_i ^ P_i)` with
+//! `Offset_i = Offset_(i-1) ^ L_ntz(i)`, where `L_* = E(0)`, `L_$` is `L_*`
+//! doubled and `L_0`, `L_1`, ... are `L_$` doubled once more each time, in
+//! the fields of [`lrw`](crate::lrw). The tag encrypts the XOR of the
+//! plaintext blocks under an offset of its own, and is XORed with a hash of
+//! the associated data computed the same way.
+//!
+//! Unlike RFC 7253, the first offset is the encrypted nonce block, without
+//! the stretch-then-shift step that only works for 128-bit blocks, and
+//! doubling follows the little-endian convention of this crate: the output
+//! is not comparable with AES-OCB. As with every OCB, a nonce must never
+//! repeat under one key, and a key should encrypt far fewer than `2^w`
+//! blocks in total, since offsets collide beyond that. Only `u32` words and
+//! wider are supported.
+//!
+
+use crate::algo::AeadAlgo;
+use crate::error::Error;
+use crate::lrw::{check_block_size, double};
+use crate::rc5::{decrypt_kernel, encrypt_kernel, expand_key};
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+// L_i for every i a 64-bit block counter can have trailing zeros.
+const L_COUNT: usize = 64;
+
+type Kernel<W> = fn([W; 2], &[W]) -> [W; 2];
+
+///
+/// OCB over RC5 with a fixed nonce and tag length. The tag is appended to
+/// the ciphertext.
+///
+/// Example:
+///
+/// ```rust
+/// use rand_core::OsRng;
+/// use rc5_cipher::algo::{AeadAlgo, NonceCounter};
+/// use rc5_cipher::ocb::Ocb;
+///
+/// let ocb = Ocb::<u64>::new(b"telemetry key 01", 20, 12, 16).unwrap();
+/// let mut nonces = NonceCounter::generate(&mut OsRng, 12).unwrap();
+/// let nonce = nonces.next_nonce().unwrap();
+/// let sent = nonce.as_bytes().to_vec();
+///
+/// let sealed = ocb.seal(nonce, b"batch 7", &[0x42; 1000]).unwrap();
+/// assert_eq!(sealed.len(), 1000 + 16);
+///
+/// assert_eq!(ocb.open(&sent, b"batch 7", &sealed).unwrap(), [0x42; 1000]);
+/// assert!(ocb.open(&sent, b"batch 8", &sealed).is_err());
+/// ```
+///
+pub struct Ocb<W: Word> {
+    key_exp: Vec<W>,
+    nonce_len: usize,
+    tag_len: usize,
+    l_star: [u8; MAX_BLOCK],
+    l_dollar: [u8; MAX_BLOCK],
+    l: Vec<[u8; MAX_BLOCK]>,
+}
+
+impl<W: Word> Ocb<W> {
+    ///
+    /// Expands `key` for `rounds` rounds, for nonces of `nonce_len` bytes and
+    /// tags of `tag_len` bytes.
+    ///
+    /// Fails with [`Error::UnsupportedParameters`] for words narrower than
+    /// `u32` or without a field polynomial for their block, a nonce that is
+    /// empty or not shorter than a block, or a tag that is empty or longer
+    /// than a block.
+    ///
+    pub fn new(key: &[u8], rounds: usize, nonce_len: usize, tag_len: usize) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        check_block_size(bs)?;
+        if bs < 8 || !(1..bs).contains(&nonce_len) || !(1..=bs).contains(&tag_len) {
+            return Err(Error::UnsupportedParameters);
+        }
+        let key_exp = expand_key::<W>(key, rounds);
+        let mut l_star = [0u8; MAX_BLOCK];
+        crypt(&key_exp, &mut l_star[..bs], encrypt_kernel);
+        let mut l_dollar = l_star;
+        double(&mut l_dollar[..bs]);
+        let mut l = Vec::with_capacity(L_COUNT);
+        let mut next = l_dollar;
+        for _ in 0..L_COUNT {
+            double(&mut next[..bs]);
+            l.push(next);
+        }
+        Ok(Ocb {
+            key_exp,
+            nonce_len,
+            tag_len,
+            l_star,
+            l_dollar,
+            l,
+        })
+    }
+
+    // Encrypts or decrypts `data` in place and returns the full tag.
+    fn process(&self, nonce: &[u8], aad: &[u8], data: &mut [u8], encrypt: bool) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let mut offset = [0u8; MAX_BLOCK];
+        offset[..nonce.len()].copy_from_slice(nonce);
+        offset[nonce.len()] = 0x80;
+        offset[bs - 1] ^= self.tag_len as u8;
+        crypt(&self.key_exp, &mut offset[..bs], encrypt_kernel);
+
+        let mut checksum = [0u8; MAX_BLOCK];
+        let full = data.len() / bs * bs;
+        let (blocks, rest) = data.split_at_mut(full);
+        for (i, block) in blocks.chunks_exact_mut(bs).enumerate() {
+            xor(
+                &mut offset[..bs],
+                &self.l[(i + 1).trailing_zeros() as usize][..bs],
+            );
+            if encrypt {
+                xor(&mut checksum[..bs], block);
+            }
+            xor(block, &offset[..bs]);
+            let kernel: Kernel<W> = if encrypt {
+                encrypt_kernel
+            } else {
+                decrypt_kernel
+            };
+            crypt(&self.key_exp, block, kernel);
+            xor(block, &offset[..bs]);
+            if !encrypt {
+                xor(&mut checksum[..bs], block);
+            }
+        }
+        if !rest.is_empty() {
+            xor(&mut offset[..bs], &self.l_star[..bs]);
+            let mut pad = offset;
+            crypt(&self.key_exp, &mut pad[..bs], encrypt_kernel);
+            if encrypt {
+                xor(&mut checksum[..bs], rest);
+            }
+            xor(rest, &pad[..bs]);
+            if !encrypt {
+                xor(&mut checksum[..bs], rest);
+            }
+            checksum[rest.len()] ^= 0x80;
+        }
+
+        xor(&mut checksum[..bs], &offset[..bs]);
+        xor(&mut checksum[..bs], &self.l_dollar[..bs]);
+        crypt(&self.key_exp, &mut checksum[..bs], encrypt_kernel);
+        xor(&mut checksum[..bs], &self.hash(aad)[..bs]);
+        checksum
+    }
+
+    // The sum over the encrypted, offset blocks of the associated data.
+    fn hash(&self, aad: &[u8]) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let mut sum = [0u8; MAX_BLOCK];
+        let mut offset = [0u8; MAX_BLOCK];
+        let mut block = [0u8; MAX_BLOCK];
+        let chunks = aad.chunks_exact(bs);
+        let rest = chunks.remainder();
+        for (i, chunk) in chunks.enumerate() {
+            xor(
+                &mut offset[..bs],
+                &self.l[(i + 1).trailing_zeros() as usize][..bs],
+            );
+            block[..bs].copy_from_slice(chunk);
+            xor(&mut block[..bs], &offset[..bs]);
+            crypt(&self.key_exp, &mut block[..bs], encrypt_kernel);
+            xor(&mut sum[..bs], &block[..bs]);
+        }
+        if !rest.is_empty() {
+            xor(&mut offset[..bs], &self.l_star[..bs]);
+            block.fill(0);
+            block[..rest.len()].copy_from_slice(rest);
+            block[rest.len()] = 0x80;
+            xor(&mut block[..bs], &offset[..bs]);
+            crypt(&self.key_exp, &mut block[..bs], encrypt_kernel);
+            xor(&mut sum[..bs], &block[..bs]);
+        }
+        sum
+    }
+}
+
+///
+/// Fails with [`Error::InvalidLength`] if `nonce` has the wrong length, and
+/// with [`Error::AuthenticationFailed`] if the ciphertext, tag, nonce or
+/// `aad` was altered.
+///
+impl<W: Word> AeadAlgo for Ocb<W> {
+    fn nonce_len(&self) -> usize {
+        self.nonce_len
+    }
+
+    fn tag_len(&self) -> usize {
+        self.tag_len
+    }
+
+    fn seal_with_raw_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if nonce.len() != self.nonce_len {
+            return Err(Error::InvalidLength);
+        }
+        let mut out = Vec::with_capacity(plaintext.len() + self.tag_len);
+        out.extend_from_slice(plaintext);
+        let tag = self.process(nonce, aad, &mut out, true);
+        out.extend_from_slice(&tag[..self.tag_len]);
+        Ok(out)
+    }
+
+    fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if nonce.len() != self.nonce_len {
+            return Err(Error::InvalidLength);
+        }
+        let len = ciphertext
+            .len()
+            .checked_sub(self.tag_len)
+            .ok_or(Error::AuthenticationFailed)?;
+        let (ct, tag) = ciphertext.split_at(len);
+        let mut pt = ct.to_vec();
+        let expected = self.process(nonce, aad, &mut pt, false);
+
+        let diff = expected[..self.tag_len]
+            .iter()
+            .zip(tag)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            pt.fill(0);
+            return Err(Error::AuthenticationFailed);
+        }
+        Ok(pt)
+    }
+}
+
+fn crypt<W: Word>(key_exp: &[W], block: &mut [u8], kernel: Kernel<W>) {
+    let out = kernel(
+        [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        key_exp,
+    );
+    out[0].write_le(block);
+    out[1].write_le(&mut block[W::BYTES..]);
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_follow_the_doubled_l_values() {
+        let ocb = Ocb::<u32>::new(b"ocb key", 12, 4, 8).unwrap();
+        let nonce = [9, 8, 7, 6];
+        let pt: Vec<u8> = (0..24).collect();
+        let sealed = ocb.seal_with_raw_nonce(&nonce, &[], &pt).unwrap();
+
+        let encrypt = |block: &mut [u8]| crypt(&ocb.key_exp, block, encrypt_kernel);
+        let mut offset = [9, 8, 7, 6, 0x80, 0, 0, 8];
+        encrypt(&mut offset);
+        let mut l = [0u8; 8];
+        encrypt(&mut l);
+        double(&mut l);
+        let mut l0 = l;
+        double(&mut l0);
+        let mut l1 = l0;
+        double(&mut l1);
+
+        // ntz(1) = 0, ntz(2) = 1, ntz(3) = 0
+        for (i, li) in [l0, l1, l0].iter().enumerate() {
+            xor(&mut offset, li);
+            let mut block = [0u8; 8];
+            block.copy_from_slice(&pt[8 * i..8 * i + 8]);
+            xor(&mut block, &offset);
+            encrypt(&mut block);
+            xor(&mut block, &offset);
+            assert_eq!(sealed[8 * i..8 * i + 8], block, "block {}", i);
+        }
+
+        // no associated data hashes to zero: the tag only covers the checksum
+        let mut checksum = [0u8; 8];
+        for block in pt.chunks(8) {
+            xor(&mut checksum, block);
+        }
+        xor(&mut checksum, &offset);
+        xor(&mut checksum, &l);
+        encrypt(&mut checksum);
+        assert_eq!(sealed[24..], checksum);
+    }
+
+    #[test]
+    fn roundtrip_for_every_size() {
+        fn check<W: Word>(nonce_len: usize, tag_len: usize) {
+            let ocb = Ocb::<W>::new(b"ocb roundtrip key", 16, nonce_len, tag_len).unwrap();
+            let nonce = vec![0x3c; nonce_len];
+            let bs = 2 * W::BYTES;
+            for len in [0, 1, bs - 1, bs, 2 * bs, 7 * bs + 3] {
+                let pt: Vec<u8> = (0..len).map(|i| (i * 29) as u8).collect();
+                for aad in [&b""[..], b"x", &[0xaa; 40]] {
+                    let sealed = ocb.seal_with_raw_nonce(&nonce, aad, &pt).unwrap();
+                    assert_eq!(sealed.len(), len + tag_len);
+                    if len > 0 {
+                        assert_ne!(sealed[..len], pt[..]);
+                    }
+                    assert_eq!(ocb.open(&nonce, aad, &sealed).unwrap(), pt);
+                }
+            }
+        }
+        check::<u32>(4, 8);
+        check::<u32>(7, 4);
+        check::<u64>(12, 16);
+        check::<u64>(1, 12);
+        check::<u128>(15, 32);
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let ocb = Ocb::<u64>::new(b"ocb key", 16, 12, 16).unwrap();
+        let nonce = [5u8; 12];
+        let sealed = ocb
+            .seal_with_raw_nonce(&nonce, b"header", &[0x11; 37])
+            .unwrap();
+        for i in [0, 16, 32, 36, 37, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 0x40;
+            assert_eq!(
+                ocb.open(&nonce, b"header", &bad),
+                Err(Error::AuthenticationFailed),
+                "byte {}",
+                i
+            );
+        }
+        // swapped blocks keep the checksum but not the offsets
+        let mut swapped = sealed.clone();
+        let (a, b) = swapped.split_at_mut(16);
+        a.swap_with_slice(&mut b[..16]);
+        assert!(ocb.open(&nonce, b"header", &swapped).is_err());
+
+        assert!(ocb.open(&nonce, b"headex", &sealed).is_err());
+        assert!(ocb.open(&nonce, b"header\0", &sealed).is_err());
+        assert!(ocb.open(&[6u8; 12], b"header", &sealed).is_err());
+        assert_eq!(
+            ocb.open(&nonce, b"header", &sealed[..sealed.len() - 1]),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            ocb.open(&nonce, b"", &sealed[..15]),
+            Err(Error::AuthenticationFailed)
+        );
+
+        // a shorter tag is a different instance, not a truncation
+        let short = Ocb::<u64>::new(b"ocb key", 16, 12, 8).unwrap();
+        let resealed = short
+            .seal_with_raw_nonce(&nonce, b"header", &[0x11; 37])
+            .unwrap();
+        assert_ne!(resealed[..37], sealed[..37]);
+    }
+
+    #[test]
+    fn validates_parameters() {
+        assert!(Ocb::<u16>::new(b"k", 12, 2, 4).is_err());
+        for (nonce_len, tag_len) in [(0, 8), (8, 8), (4, 0), (4, 9)] {
+            assert_eq!(
+                Ocb::<u32>::new(b"k", 12, nonce_len, tag_len).err(),
+                Some(Error::UnsupportedParameters)
+            );
+        }
+        let ocb = Ocb::<u32>::new(b"k", 12, 4, 8).unwrap();
+        assert_eq!(
+            ocb.seal_with_raw_nonce(&[0; 3], b"", b"x"),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(ocb.open(&[0; 5], b"", &[0; 9]), Err(Error::InvalidLength));
+    }
+}
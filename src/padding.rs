@@ -0,0 +1,380 @@
+//!
+//! Block padding schemes.
+//!
+//! Each scheme is a zero-sized type implementing [`Padding`], so that code
+//! working on whole blocks can take the scheme as a type parameter. The
+//! exception is [`Iso10126`], whose padding is drawn from a random generator.
+//!
+//! Unpadding of [`Pkcs7`], [`AnsiX923`], [`Iso10126`] and [`Iso7816`] runs in time that only
+//! depends on the block length, and returns one error for every kind of
+//! malformed padding, so that it can't be used as a padding oracle.
+//!
+
+use crate::error::Error;
+use crate::rng::{CryptoRng, RngCore};
+
+///
+/// A reversible way of extending data to a multiple of the block size.
+///
+pub trait Padding {
+    ///
+    /// Appends padding to `buf` so that its length is a non-zero multiple of
+    /// `block_size`.
+    ///
+    fn pad(buf: &mut Vec<u8>, block_size: usize);
+
+    ///
+    /// Returns how many bytes of the padded last `block` are data.
+    ///
+    fn unpad(block: &[u8]) -> Result<usize, Error>;
+}
+
+///
+/// PKCS#7 (RFC 5652): `n` bytes of value `n`, always at least one byte.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::padding::{Padding, Pkcs7};
+///
+/// let mut buf = b"abcde".to_vec();
+/// Pkcs7::pad(&mut buf, 8);
+///
+/// assert_eq!(buf, b"abcde\x03\x03\x03");
+/// assert_eq!(Pkcs7::unpad(&buf), Ok(5));
+/// ```
+///
+pub struct Pkcs7;
+
+impl Padding for Pkcs7 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let n = block_size - buf.len() % block_size;
+        buf.resize(buf.len() + n, n as u8);
+    }
+
+    fn unpad(block: &[u8]) -> Result<usize, Error> {
+        let len = block.len();
+        let n = *block.last().ok_or(Error::InvalidPadding)?;
+
+        let mut bad = ct_eq(n, 0) | !ct_le(n as usize, len);
+        for (i, &b) in block.iter().enumerate() {
+            let in_padding = ct_le(len - i, n as usize);
+            bad |= in_padding & !ct_eq(b, n);
+        }
+
+        if bad != 0 {
+            return Err(Error::InvalidPadding);
+        }
+        Ok(len - n as usize)
+    }
+}
+
+///
+/// ANSI X9.23: `n - 1` zero bytes followed by the length byte `n`. A non-zero
+/// fill is rejected on removal; data padded with random bytes is
+/// [`Iso10126`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::padding::{AnsiX923, Padding};
+///
+/// let mut buf = b"abcde".to_vec();
+/// AnsiX923::pad(&mut buf, 8);
+///
+/// assert_eq!(buf, b"abcde\x00\x00\x03");
+/// assert_eq!(AnsiX923::unpad(&buf), Ok(5));
+/// ```
+///
+pub struct AnsiX923;
+
+impl Padding for AnsiX923 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let n = block_size - buf.len() % block_size;
+        buf.resize(buf.len() + n - 1, 0);
+        buf.push(n as u8);
+    }
+
+    fn unpad(block: &[u8]) -> Result<usize, Error> {
+        let len = block.len();
+        let n = *block.last().ok_or(Error::InvalidPadding)?;
+
+        let mut bad = ct_eq(n, 0) | !ct_le(n as usize, len);
+        for (i, &b) in block[..len - 1].iter().enumerate() {
+            let in_fill = ct_le(len - i, n as usize);
+            bad |= in_fill & !ct_eq(b, 0);
+        }
+
+        if bad != 0 {
+            return Err(Error::InvalidPadding);
+        }
+        Ok(len - n as usize)
+    }
+}
+
+///
+/// ISO 10126: `n - 1` random bytes followed by the length byte `n`.
+///
+/// Not a [`Padding`]: the trait's `pad` has no generator to draw the fill
+/// from, and a zero fill would be [`AnsiX923`] under another name. The random
+/// fill can't be checked on removal, only the length byte is.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::padding::Iso10126;
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let mut buf = b"abcde".to_vec();
+/// Iso10126::pad(&mut rng, &mut buf, 8);
+///
+/// assert_eq!(buf.len(), 8);
+/// assert_eq!(buf[7], 3);
+/// assert_eq!(Iso10126::unpad(&buf), Ok(5));
+/// ```
+///
+pub struct Iso10126;
+
+impl Iso10126 {
+    ///
+    /// Appends padding drawn from `rng` to `buf` so that its length is a
+    /// non-zero multiple of `block_size`.
+    ///
+    pub fn pad<R: RngCore + CryptoRng>(rng: &mut R, buf: &mut Vec<u8>, block_size: usize) {
+        let n = block_size - buf.len() % block_size;
+        let start = buf.len();
+        buf.resize(start + n - 1, 0);
+        rng.fill_bytes(&mut buf[start..]);
+        buf.push(n as u8);
+    }
+
+    ///
+    /// Returns how many bytes of the padded last `block` are data.
+    ///
+    pub fn unpad(block: &[u8]) -> Result<usize, Error> {
+        let n = *block.last().ok_or(Error::InvalidPadding)?;
+
+        let bad = ct_eq(n, 0) | !ct_le(n as usize, block.len());
+
+        if bad != 0 {
+            return Err(Error::InvalidPadding);
+        }
+        Ok(block.len() - n as usize)
+    }
+}
+
+///
+/// ISO/IEC 7816-4: a single `0x80` byte followed by zeros.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::padding::{Iso7816, Padding};
+///
+/// let mut buf = b"abcde".to_vec();
+/// Iso7816::pad(&mut buf, 8);
+///
+/// assert_eq!(buf, b"abcde\x80\x00\x00");
+/// assert_eq!(Iso7816::unpad(&buf), Ok(5));
+/// ```
+///
+pub struct Iso7816;
+
+impl Padding for Iso7816 {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        buf.push(0x80);
+        let len = buf.len().div_ceil(block_size) * block_size;
+        buf.resize(len, 0);
+    }
+
+    fn unpad(block: &[u8]) -> Result<usize, Error> {
+        // Scan from the end: zeros until the first non-zero byte, which must
+        // be the 0x80 marker.
+        let mut found = 0u8;
+        let mut bad = 0u8;
+        let mut pos = 0usize;
+        for (i, &b) in block.iter().enumerate().rev() {
+            let is_zero = ct_eq(b, 0);
+            let is_marker = ct_eq(b, 0x80);
+            bad |= !found & !is_zero & !is_marker;
+
+            let hit = !found & is_marker;
+            pos = ct_select(hit, i, pos);
+            found |= hit;
+        }
+        bad |= !found;
+
+        if bad != 0 {
+            return Err(Error::InvalidPadding);
+        }
+        Ok(pos)
+    }
+}
+
+///
+/// Zero bytes up to the block size, nothing if the data is already aligned.
+/// Trailing zeros of the data can't be told apart from padding and are
+/// removed too, so this is only suitable for text or fixed-length records.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::padding::{Padding, ZeroPadding};
+///
+/// let mut buf = b"abcde".to_vec();
+/// ZeroPadding::pad(&mut buf, 8);
+///
+/// assert_eq!(buf, b"abcde\x00\x00\x00");
+/// assert_eq!(ZeroPadding::unpad(&buf), Ok(5));
+/// ```
+///
+pub struct ZeroPadding;
+
+impl Padding for ZeroPadding {
+    fn pad(buf: &mut Vec<u8>, block_size: usize) {
+        let len = buf.len().div_ceil(block_size).max(1) * block_size;
+        buf.resize(len, 0);
+    }
+
+    fn unpad(block: &[u8]) -> Result<usize, Error> {
+        Ok(block.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1))
+    }
+}
+
+// 0xff if a == b, 0 otherwise.
+fn ct_eq(a: u8, b: u8) -> u8 {
+    (((a ^ b) as u16).wrapping_sub(1) >> 8) as u8
+}
+
+// 0xff if a <= b, 0 otherwise. Both must be below 2^63.
+fn ct_le(a: usize, b: usize) -> u8 {
+    !((((b as i64) - (a as i64)) >> 63) as u8)
+}
+
+// a if mask is 0xff, b if mask is 0.
+fn ct_select(mask: u8, a: usize, b: usize) -> usize {
+    let m = 0usize.wrapping_sub((mask & 1) as usize);
+    (a & m) | (b & !m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip<P: Padding>(block_size: usize) {
+        for len in 0..3 * block_size {
+            let data: Vec<u8> = (1..=len as u8).collect();
+            let mut buf = data.clone();
+            P::pad(&mut buf, block_size);
+
+            assert!(buf.len().is_multiple_of(block_size) && !buf.is_empty());
+            let last = &buf[buf.len() - block_size..];
+            let kept = buf.len() - block_size + P::unpad(last).unwrap();
+            assert_eq!(&buf[..kept], &data[..]);
+        }
+    }
+
+    #[test]
+    fn all_schemes_roundtrip() {
+        for block_size in [2, 4, 8, 16, 32] {
+            roundtrip::<Pkcs7>(block_size);
+            roundtrip::<AnsiX923>(block_size);
+            roundtrip::<Iso7816>(block_size);
+            roundtrip::<ZeroPadding>(block_size);
+        }
+    }
+
+    #[test]
+    fn aligned_input_gets_a_full_block() {
+        let mut buf = vec![1u8; 8];
+        Pkcs7::pad(&mut buf, 8);
+        assert_eq!(&buf[8..], [8u8; 8]);
+
+        let mut buf = vec![1u8; 8];
+        Iso7816::pad(&mut buf, 8);
+        assert_eq!(&buf[8..], [0x80, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut buf = vec![1u8; 8];
+        ZeroPadding::pad(&mut buf, 8);
+        assert_eq!(buf.len(), 8);
+    }
+
+    #[test]
+    fn x923_rejects_non_zero_fill() {
+        let block = [b'a', b'b', b'c', 0x5e, 0xc1, 0x07, 0x3a, 5];
+        assert_eq!(AnsiX923::unpad(&block), Err(Error::InvalidPadding));
+        assert_eq!(Iso10126::unpad(&block), Ok(3));
+
+        let block = [b'a', b'b', b'c', 0, 0, 0, 1, 5];
+        assert_eq!(AnsiX923::unpad(&block), Err(Error::InvalidPadding));
+        // data bytes in front of the fill aren't checked
+        let block = [0xff, 0xff, 0xff, 0, 0, 0, 0, 5];
+        assert_eq!(AnsiX923::unpad(&block), Ok(3));
+    }
+
+    #[test]
+    fn iso10126_fills_from_the_generator() {
+        let mut rng = crate::rng::CounterRng(0x40);
+        for len in 0..16 {
+            let mut buf = vec![0xaa; len];
+            Iso10126::pad(&mut rng, &mut buf, 8);
+            let n = buf.len() - len;
+            assert_eq!(buf.len() % 8, 0);
+            assert_eq!(buf[buf.len() - 1], n as u8);
+            assert!(buf[len..buf.len() - 1].iter().all(|&b| b >= 0x40));
+            assert_eq!(Iso10126::unpad(&buf[buf.len() - 8..]), Ok(8 - n));
+        }
+    }
+
+    #[test]
+    fn constant_time_helpers() {
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                assert_eq!(ct_eq(a, b) == 0xff, a == b);
+                assert_eq!(ct_le(a as usize, b as usize) == 0xff, a <= b);
+            }
+        }
+        assert_eq!(ct_select(0xff, 3, 7), 3);
+        assert_eq!(ct_select(0, 3, 7), 7);
+    }
+
+    #[test]
+    fn malformed_padding_is_rejected() {
+        assert_eq!(Pkcs7::unpad(&[1, 2, 3, 0]), Err(Error::InvalidPadding));
+        assert_eq!(Pkcs7::unpad(&[1, 2, 3, 5]), Err(Error::InvalidPadding));
+        assert_eq!(Pkcs7::unpad(&[1, 3, 2, 2, 3]), Err(Error::InvalidPadding));
+        assert_eq!(Pkcs7::unpad(&[]), Err(Error::InvalidPadding));
+
+        assert_eq!(
+            Pkcs7::unpad(&[9, 9, 9, 9, 9, 9, 9, 9]),
+            Err(Error::InvalidPadding)
+        );
+        assert_eq!(Pkcs7::unpad(&[2, 2, 2, 2]), Ok(2));
+        assert_eq!(Pkcs7::unpad(&[4, 4, 4, 4]), Ok(0));
+
+        assert_eq!(AnsiX923::unpad(&[1, 2, 3, 0]), Err(Error::InvalidPadding));
+        assert_eq!(AnsiX923::unpad(&[1, 2, 3, 9]), Err(Error::InvalidPadding));
+        assert_eq!(AnsiX923::unpad(&[1, 2, 3, 5]), Err(Error::InvalidPadding));
+        assert_eq!(AnsiX923::unpad(&[]), Err(Error::InvalidPadding));
+        assert_eq!(AnsiX923::unpad(&[1, 2, 3, 4]), Err(Error::InvalidPadding));
+        assert_eq!(AnsiX923::unpad(&[0, 0, 0, 4]), Ok(0));
+
+        assert_eq!(Iso10126::unpad(&[1, 2, 3, 0]), Err(Error::InvalidPadding));
+        assert_eq!(Iso10126::unpad(&[1, 2, 3, 5]), Err(Error::InvalidPadding));
+        assert_eq!(Iso10126::unpad(&[]), Err(Error::InvalidPadding));
+        assert_eq!(Iso10126::unpad(&[7, 7, 7, 4]), Ok(0));
+
+        assert_eq!(Iso7816::unpad(&[1, 2, 0, 0]), Err(Error::InvalidPadding));
+        assert_eq!(Iso7816::unpad(&[0, 0, 0, 0]), Err(Error::InvalidPadding));
+        assert_eq!(Iso7816::unpad(&[0x80, 0, 0, 1]), Err(Error::InvalidPadding));
+        assert_eq!(Iso7816::unpad(&[0x81, 0, 0, 0]), Err(Error::InvalidPadding));
+        assert_eq!(Iso7816::unpad(&[]), Err(Error::InvalidPadding));
+        assert_eq!(Iso7816::unpad(&[0x80, 0x80, 0, 0]), Ok(1));
+        assert_eq!(Iso7816::unpad(&[0x80, 0, 0, 0]), Ok(0));
+        assert_eq!(
+            Iso7816::unpad(&[0x80, 0x80, 1, 0]),
+            Err(Error::InvalidPadding)
+        );
+    }
+}
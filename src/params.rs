@@ -0,0 +1,356 @@
+//!
+//! Choosing RC5 parameters.
+//!
+//! RC5 leaves the word size, the number of rounds and the key length to the
+//! user. The published attacks only give guidance for some combinations, so
+//! this module offers a few vetted ones instead of expecting every user to
+//! read the cryptanalysis literature.
+//!
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::algo::BlockAlgorithm;
+use crate::error::Error;
+use crate::rc5::{encrypt_kernel, expand_key};
+use crate::word::Word;
+
+// Extra rounds on top of those the attack model says are needed.
+const MARGIN_ROUNDS: usize = 2;
+
+// Rivest's nominal minimum, never recommend fewer.
+const MIN_ROUNDS: usize = 12;
+
+// Largest rounds count a header can record.
+const MAX_ROUNDS: usize = 255;
+
+// Blocks timed per measurement, enough to dwarf the cost of reading the clock.
+const CALIBRATION_BLOCKS: u32 = 4096;
+
+///
+/// A complete set of RC5 parameters, `RC5-w/r/b` with `w` in bytes.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rc5Params {
+    pub word_bytes: usize,
+    pub rounds: usize,
+    pub key_bytes: usize,
+}
+
+impl Rc5Params {
+    ///
+    /// Recommends parameters reaching `target_security_bits` with
+    /// `word_bits`-bit words.
+    ///
+    /// The rounds count follows the differential attack of Biryukov and
+    /// Kushilevitz, which breaks RC5-32/12 with 2^44 chosen plaintexts, i.e.
+    /// about 3.7 bits of data complexity per round. For other word sizes this
+    /// is scaled with `log2(w)`, since a characteristic survives a round with
+    /// probability close to `1/w` per data-dependent rotation. Enough rounds
+    /// are chosen for the attack to need `2^target` plaintexts, plus a margin
+    /// of two rounds, and never fewer than 12. The key has `target` bits, and
+    /// at least 16 bytes, so that the result always passes the default
+    /// [`ParameterFloor`].
+    ///
+    /// Fails with [`Error::UnsupportedParameters`] if the word size isn't one
+    /// of 32, 64 or 128 bits, or if the target exceeds the block size, since
+    /// the whole codebook of a `2w`-bit block holds only `2^(2w)` plaintexts.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::params::Rc5Params;
+    /// use rc5_cipher::Error;
+    ///
+    /// let params = Rc5Params::recommend(64, 32).unwrap();
+    /// assert_eq!((params.word_bytes, params.rounds, params.key_bytes), (4, 20, 16));
+    ///
+    /// assert_eq!(Rc5Params::recommend(128, 32), Err(Error::UnsupportedParameters));
+    /// ```
+    ///
+    pub fn recommend(target_security_bits: usize, word_bits: usize) -> Result<Self, Error> {
+        // bits of attack data complexity per round, in hundredths:
+        // 44/12 * log2(w)/5
+        let per_round = match word_bits {
+            32 => 367,
+            64 => 440,
+            128 => 513,
+            _ => return Err(Error::UnsupportedParameters),
+        };
+        if target_security_bits == 0 || target_security_bits > 2 * word_bits {
+            return Err(Error::UnsupportedParameters);
+        }
+
+        let rounds = (target_security_bits * 100).div_ceil(per_round) + MARGIN_ROUNDS;
+        let floor = ParameterFloor::default();
+        Ok(Rc5Params {
+            word_bytes: word_bits / 8,
+            rounds: rounds.max(floor.min_rounds),
+            key_bytes: target_security_bits.div_ceil(8).max(floor.min_key_bytes),
+        })
+    }
+
+    pub fn algorithm(&self) -> BlockAlgorithm {
+        BlockAlgorithm::Rc5 {
+            word_bytes: self.word_bytes,
+            rounds: self.rounds,
+        }
+    }
+}
+
+///
+/// Parameter presets, from compatible to conservative.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::params::SecurityLevel;
+///
+/// let params = SecurityLevel::Standard.params();
+/// assert_eq!((params.word_bytes, params.rounds, params.key_bytes), (4, 20, 16));
+///
+/// let cipher = params.algorithm().instantiate(&[0u8; 16]).unwrap();
+/// assert_eq!(cipher.block_size(), 8);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecurityLevel {
+    /// RC5-32/12/16, Rivest's suggested parameters. 12 rounds are within
+    /// reach of differential attacks needing 2^44 chosen plaintexts (Biryukov
+    /// and Kushilevitz); only for interoperating with existing data.
+    Legacy,
+    /// RC5-32/20/16, with a wide margin over the best known attacks.
+    Standard,
+    /// RC5-64/24/32: 128-bit blocks, which keep the birthday bound out of
+    /// reach, and a 256-bit key.
+    High,
+}
+
+impl SecurityLevel {
+    pub fn params(&self) -> Rc5Params {
+        let (word_bytes, rounds, key_bytes) = match self {
+            SecurityLevel::Legacy => (4, 12, 16),
+            SecurityLevel::Standard => (4, 20, 16),
+            SecurityLevel::High => (8, 24, 32),
+        };
+        Rc5Params {
+            word_bytes,
+            rounds,
+            key_bytes,
+        }
+    }
+}
+
+///
+/// Minimum parameters the checked constructors accept, so that toy parameters
+/// from test vectors don't end up in production by accident.
+///
+/// The default floor is 32-bit words, 12 rounds and 16-byte keys: 8- and 16-bit
+/// words give 16- and 32-bit blocks, whose whole codebook is within easy reach
+/// whatever the rounds and key. Tests and interoperation with old data opt out
+/// explicitly with [`allow_weak_parameters`](Self::allow_weak_parameters).
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::BlockAlgorithm;
+/// use rc5_cipher::params::ParameterFloor;
+/// use rc5_cipher::Error;
+///
+/// let toy = BlockAlgorithm::Rc5 { word_bytes: 4, rounds: 8 };
+/// assert_eq!(toy.instantiate(&[0; 16]).err(), Some(Error::WeakParameters));
+///
+/// let floor = ParameterFloor::default().allow_weak_parameters();
+/// assert!(toy.instantiate_with(&[0; 16], &floor).is_ok());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParameterFloor {
+    pub min_word_bytes: usize,
+    pub min_rounds: usize,
+    pub min_key_bytes: usize,
+    allow_weak: bool,
+}
+
+impl Default for ParameterFloor {
+    fn default() -> Self {
+        ParameterFloor {
+            min_word_bytes: 4,
+            min_rounds: MIN_ROUNDS,
+            min_key_bytes: 16,
+            allow_weak: false,
+        }
+    }
+}
+
+impl ParameterFloor {
+    ///
+    /// Accepts any parameters, e.g. to reproduce published test vectors.
+    ///
+    pub fn allow_weak_parameters(mut self) -> Self {
+        self.allow_weak = true;
+        self
+    }
+
+    ///
+    /// Fails with [`Error::WeakParameters`] if `params` is below the floor.
+    ///
+    pub fn check(&self, params: &Rc5Params) -> Result<(), Error> {
+        if !self.allow_weak
+            && (params.word_bytes < self.min_word_bytes
+                || params.rounds < self.min_rounds
+                || params.key_bytes < self.min_key_bytes)
+        {
+            return Err(Error::WeakParameters);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Returns the highest rounds count, between 12 and 255, for which encrypting
+/// one block with `W` words takes at most `max_block_latency` on this host.
+///
+/// The cost of a block is measured at 12 and 24 rounds and interpolated
+/// linearly, so the result is only as stable as the host: calibrate at start-up
+/// on the target device, not at build time, and leave some headroom. Fails
+/// with [`Error::UnsupportedParameters`] if even 12 rounds are too slow.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use rc5_cipher::params::calibrate_rounds;
+///
+/// let rounds = calibrate_rounds::<u32>(Duration::from_millis(1)).unwrap();
+/// assert!((12..=255).contains(&rounds));
+/// ```
+///
+pub fn calibrate_rounds<W: Word>(max_block_latency: Duration) -> Result<usize, Error> {
+    let budget = max_block_latency.as_secs_f64();
+    let base = block_latency::<W>(MIN_ROUNDS);
+    if base > budget {
+        return Err(Error::UnsupportedParameters);
+    }
+    let per_round = (block_latency::<W>(2 * MIN_ROUNDS) - base).max(0.0) / MIN_ROUNDS as f64;
+    if per_round == 0.0 {
+        return Ok(MAX_ROUNDS);
+    }
+
+    let extra = ((budget - base) / per_round).floor() as usize;
+    Ok((MIN_ROUNDS + extra).min(MAX_ROUNDS))
+}
+
+// Seconds per block with `rounds` rounds, the best of three runs.
+fn block_latency<W: Word>(rounds: usize) -> f64 {
+    let key_exp = expand_key::<W>(&[0u8; 16], rounds);
+    let mut best = f64::MAX;
+    for _ in 0..3 {
+        let mut block = [W::ZERO; 2];
+        let start = Instant::now();
+        for _ in 0..CALIBRATION_BLOCKS {
+            block = encrypt_kernel(black_box(block), &key_exp);
+        }
+        black_box(block);
+        best = best.min(start.elapsed().as_secs_f64() / CALIBRATION_BLOCKS as f64);
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::{BlockCipherAlgo, Rc5Algo};
+
+    #[test]
+    fn floor_rejects_weak_parameters() {
+        let floor = ParameterFloor::default();
+        let params = |rounds, key_bytes| Rc5Params {
+            word_bytes: 4,
+            rounds,
+            key_bytes,
+        };
+        assert_eq!(floor.check(&params(12, 16)), Ok(()));
+        assert_eq!(floor.check(&params(11, 16)), Err(Error::WeakParameters));
+        assert_eq!(floor.check(&params(20, 8)), Err(Error::WeakParameters));
+        let narrow = Rc5Params {
+            word_bytes: 2,
+            ..params(20, 16)
+        };
+        assert_eq!(floor.check(&narrow), Err(Error::WeakParameters));
+        for level in [
+            SecurityLevel::Legacy,
+            SecurityLevel::Standard,
+            SecurityLevel::High,
+        ] {
+            assert_eq!(floor.check(&level.params()), Ok(()));
+        }
+
+        let custom = ParameterFloor {
+            min_rounds: 20,
+            ..ParameterFloor::default()
+        };
+        assert_eq!(custom.check(&params(16, 16)), Err(Error::WeakParameters));
+        let weak = custom.allow_weak_parameters();
+        assert_eq!(weak.check(&params(0, 0)), Ok(()));
+    }
+
+    #[test]
+    fn recommendations() {
+        let rounds = |target, w| Rc5Params::recommend(target, w).map(|p| p.rounds);
+        assert_eq!(rounds(64, 32), Ok(20));
+        assert_eq!(rounds(128, 64), Ok(32));
+        assert_eq!(rounds(256, 128), Ok(52));
+        // small targets still get the nominal minimum
+        assert_eq!(rounds(16, 32), Ok(12));
+
+        let params = Rc5Params::recommend(100, 64).unwrap();
+        assert_eq!(params.key_bytes, 16);
+        assert_eq!(params.word_bytes, 8);
+        assert_eq!(Rc5Params::recommend(200, 128).unwrap().key_bytes, 25);
+
+        // every recommendation clears the default floor
+        for (target, w) in [(16, 32), (64, 32), (100, 64), (256, 128)] {
+            let params = Rc5Params::recommend(target, w).unwrap();
+            assert_eq!(ParameterFloor::default().check(&params), Ok(()));
+        }
+        assert_eq!(rounds(16, 16), Err(Error::UnsupportedParameters));
+
+        assert_eq!(rounds(65, 32), Err(Error::UnsupportedParameters));
+        assert_eq!(rounds(0, 32), Err(Error::UnsupportedParameters));
+        assert_eq!(rounds(32, 24), Err(Error::UnsupportedParameters));
+    }
+
+    #[test]
+    fn presets_instantiate_the_matching_cipher() {
+        for level in [
+            SecurityLevel::Legacy,
+            SecurityLevel::Standard,
+            SecurityLevel::High,
+        ] {
+            let params = level.params();
+            let key = vec![0x5a; params.key_bytes];
+            let cipher = params.algorithm().instantiate(&key).unwrap();
+            assert_eq!(cipher.block_size(), 2 * params.word_bytes);
+
+            let mut block = vec![0x11; cipher.block_size()];
+            cipher.encrypt_block(&mut block);
+            let mut expected = vec![0x11; cipher.block_size()];
+            match params.word_bytes {
+                4 => Rc5Algo::<u32>::new(&key, params.rounds).encrypt_block(&mut expected),
+                8 => Rc5Algo::<u64>::new(&key, params.rounds).encrypt_block(&mut expected),
+                _ => unreachable!(),
+            }
+            assert_eq!(block, expected);
+        }
+    }
+
+    #[test]
+    fn calibration_respects_the_budget_bounds() {
+        assert_eq!(
+            calibrate_rounds::<u64>(Duration::ZERO),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(calibrate_rounds::<u16>(Duration::from_secs(1)), Ok(255));
+    }
+}
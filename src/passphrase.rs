@@ -0,0 +1,268 @@
+//!
+//! Keys derived from passphrases with Argon2id (RFC 9106).
+//!
+//! The cost parameters and a random salt are recorded in the header as the
+//! critical [`EXT_ARGON2ID`] extension:
+//!
+//! ```text
+//! memory KiB (u32 BE) || iterations (u32 BE) || lanes (u32 BE) || salt (16 bytes)
+//! ```
+//!
+//! so that a reader only needs the passphrase, and the costs can be raised for
+//! new files without breaking old ones. The costs come from the file, so
+//! [`passphrase_key`] refuses any above [`Argon2Params::LIMIT`]; use
+//! [`passphrase_key_with_limit`] to accept costlier files.
+//!
+//! Only available with the `argon2` feature.
+//!
+
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::error::Error;
+use crate::header::{Extension, Header, EXT_ARGON2ID};
+use crate::rng::{CryptoRng, RngCore};
+
+///
+/// Length of the salts drawn by [`new_passphrase_key`].
+///
+pub const SALT_LEN: usize = 16;
+
+///
+/// Argon2id cost parameters.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory in KiB.
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub lanes: u32,
+}
+
+impl Default for Argon2Params {
+    ///
+    /// 19 MiB, 2 iterations, 1 lane: the OWASP minimum for Argon2id.
+    ///
+    fn default() -> Self {
+        Argon2Params {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            lanes: 1,
+        }
+    }
+}
+
+impl Argon2Params {
+    ///
+    /// The highest costs [`passphrase_key`] accepts from a header: four times
+    /// the [default](Argon2Params::default), 76 MiB, 8 iterations, 4 lanes.
+    ///
+    pub const LIMIT: Argon2Params = Argon2Params {
+        memory_kib: 4 * 19 * 1024,
+        iterations: 8,
+        lanes: 4,
+    };
+
+    ///
+    /// Whether any cost in `self` is above the same cost in `limit`.
+    ///
+    pub fn exceeds(&self, limit: &Argon2Params) -> bool {
+        self.memory_kib > limit.memory_kib
+            || self.iterations > limit.iterations
+            || self.lanes > limit.lanes
+    }
+}
+
+///
+/// Draws a fresh salt from `rng`, records it with `params` in `header` and
+/// returns the `len`-byte key derived from `passphrase`.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::header::{Header, MacAlg, Mode};
+/// use rc5_cipher::passphrase::{new_passphrase_key, passphrase_key, Argon2Params};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let params = Argon2Params { memory_kib: 64, iterations: 1, lanes: 1 };
+/// let mut header = Header::new::<u32>(20, Mode::Cbc, MacAlg::None);
+/// let key = new_passphrase_key(&mut rng, b"correct horse", params, &mut header, 16).unwrap();
+///
+/// let (parsed, _) = Header::parse(&header.to_bytes()).unwrap();
+/// assert_eq!(passphrase_key(b"correct horse", &parsed, 16).unwrap(), key);
+/// ```
+///
+pub fn new_passphrase_key<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    passphrase: &[u8],
+    params: Argon2Params,
+    header: &mut Header,
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let key = derive(passphrase, &salt, params, len)?;
+
+    let mut value = Vec::with_capacity(12 + SALT_LEN);
+    value.extend_from_slice(&params.memory_kib.to_be_bytes());
+    value.extend_from_slice(&params.iterations.to_be_bytes());
+    value.extend_from_slice(&params.lanes.to_be_bytes());
+    value.extend_from_slice(&salt);
+    header.extensions.retain(|ext| ext.kind != EXT_ARGON2ID);
+    header.extensions.push(Extension {
+        kind: EXT_ARGON2ID,
+        value,
+    });
+    Ok(key)
+}
+
+///
+/// Reads the Argon2id parameters and salt recorded in `header`.
+///
+pub fn header_params(header: &Header) -> Result<(Argon2Params, &[u8]), Error> {
+    let value = header.extension(EXT_ARGON2ID).ok_or(Error::InvalidHeader)?;
+    if value.len() != 12 + SALT_LEN {
+        return Err(Error::InvalidHeader);
+    }
+    let word = |i: usize| u32::from_be_bytes(value[4 * i..4 * i + 4].try_into().unwrap());
+    let params = Argon2Params {
+        memory_kib: word(0),
+        iterations: word(1),
+        lanes: word(2),
+    };
+    Ok((params, &value[12..]))
+}
+
+///
+/// Recomputes the key derived from `passphrase` with the parameters in
+/// `header`.
+///
+/// Fails with [`Error::UnsupportedParameters`] if the recorded costs exceed
+/// [`Argon2Params::LIMIT`], so that a crafted header can't make the reader
+/// allocate or compute without bound.
+///
+pub fn passphrase_key(passphrase: &[u8], header: &Header, len: usize) -> Result<Vec<u8>, Error> {
+    passphrase_key_with_limit(passphrase, header, len, &Argon2Params::LIMIT)
+}
+
+///
+/// Like [`passphrase_key`], but accepts any costs up to `limit`.
+///
+pub fn passphrase_key_with_limit(
+    passphrase: &[u8],
+    header: &Header,
+    len: usize,
+    limit: &Argon2Params,
+) -> Result<Vec<u8>, Error> {
+    let (params, salt) = header_params(header)?;
+    if params.exceeds(limit) {
+        return Err(Error::UnsupportedParameters);
+    }
+    derive(passphrase, salt, params, len)
+}
+
+fn derive(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: Argon2Params,
+    len: usize,
+) -> Result<Vec<u8>, Error> {
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.lanes,
+        Some(len),
+    )
+    .map_err(|_| Error::UnsupportedParameters)?;
+    let mut key = vec![0u8; len];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key)
+        .map_err(|_| Error::UnsupportedParameters)?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::hex_decode;
+    use crate::header::{MacAlg, Mode};
+    use crate::rng::CounterRng;
+
+    const CHEAP: Argon2Params = Argon2Params {
+        memory_kib: 32,
+        iterations: 1,
+        lanes: 1,
+    };
+
+    #[test]
+    fn reference_implementation_vector() {
+        // echo -n password | argon2 somesalt -id -t 2 -k 65536 -p 1 -l 32 -r
+        let params = Argon2Params {
+            memory_kib: 65536,
+            iterations: 2,
+            lanes: 1,
+        };
+        let expected =
+            hex_decode("09316115d5cf24ed5a15a31a3ba326e5cf32edc24702987c02b6566f61913cf7").unwrap();
+        assert_eq!(
+            derive(b"password", b"somesalt", params, 32).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn header_roundtrip_and_errors() {
+        let mut header = Header::new::<u32>(20, Mode::Cbc, MacAlg::None);
+        let key = new_passphrase_key(&mut CounterRng(0), b"pw", CHEAP, &mut header, 16).unwrap();
+
+        let (parsed, _) = Header::parse(&header.to_bytes()).unwrap();
+        let (params, salt) = header_params(&parsed).unwrap();
+        assert_eq!(params, CHEAP);
+        assert_eq!(salt, (0..16).collect::<Vec<u8>>());
+        assert_eq!(passphrase_key(b"pw", &parsed, 16).unwrap(), key);
+        assert_ne!(passphrase_key(b"pW", &parsed, 16).unwrap(), key);
+
+        let plain = Header::new::<u32>(20, Mode::Cbc, MacAlg::None);
+        assert_eq!(passphrase_key(b"pw", &plain, 16), Err(Error::InvalidHeader));
+
+        let mut zero_lanes = header.clone();
+        zero_lanes.extensions[0].value[8..12].copy_from_slice(&[0; 4]);
+        assert_eq!(
+            passphrase_key(b"pw", &zero_lanes, 16),
+            Err(Error::UnsupportedParameters)
+        );
+        zero_lanes.extensions[0].value.pop();
+        assert_eq!(
+            passphrase_key(b"pw", &zero_lanes, 16),
+            Err(Error::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn header_costs_are_capped() {
+        let mut header = Header::new::<u32>(20, Mode::Cbc, MacAlg::None);
+        let key = new_passphrase_key(&mut CounterRng(0), b"pw", CHEAP, &mut header, 16).unwrap();
+
+        for (at, cost) in [(0, u32::MAX), (4, 9), (8, 5)] {
+            let mut costly = header.clone();
+            costly.extensions[0].value[at..at + 4].copy_from_slice(&cost.to_be_bytes());
+            assert_eq!(
+                passphrase_key(b"pw", &costly, 16),
+                Err(Error::UnsupportedParameters)
+            );
+        }
+
+        let tight = Argon2Params {
+            memory_kib: 16,
+            ..CHEAP
+        };
+        assert_eq!(
+            passphrase_key_with_limit(b"pw", &header, 16, &tight),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(
+            passphrase_key_with_limit(b"pw", &header, 16, &CHEAP).unwrap(),
+            key
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//!
+//! CTR keystream generated ahead on a background thread.
+//!
+//! [`KeystreamBuffer`](crate::ctr::KeystreamBuffer) moves block encryption
+//! out of the hot path only as far as the caller finds idle time to
+//! `prefill`. For bursty real-time streams, [`BackgroundKeystream`] instead
+//! keeps a worker thread encrypting counter blocks into two chunks: while the
+//! stream uses one, the worker refills the other. The chunks travel over
+//! bounded channels, so memory use is fixed and nothing is allocated once
+//! the stream is running.
+//!
+//! A burst larger than what the worker has ready waits for it, so size
+//! chunks to cover the largest expected burst.
+//!
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::ctr::apply_keystream_at;
+use crate::error::Error;
+use crate::word::Word;
+
+///
+/// A CTR keystream fed by a worker thread, for as long as the value lives.
+///
+/// The keystream is the same as [`apply_keystream_at`]'s. Dropping the value
+/// stops the worker.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::ctr::apply_keystream_at;
+/// use rc5_cipher::expand_key;
+/// use rc5_cipher::prefetch::BackgroundKeystream;
+///
+/// let key_exp = expand_key::<u64>(b"stream key 01234", 16);
+/// let iv = [4u8; 16];
+/// let mut keystream = BackgroundKeystream::spawn(key_exp.clone(), &iv, 0, 64).unwrap();
+///
+/// let mut frame = *b"audio frame";
+/// keystream.apply(&mut frame);
+///
+/// let mut expected = *b"audio frame";
+/// apply_keystream_at(&key_exp, &iv, 0, &mut expected).unwrap();
+/// assert_eq!(frame, expected);
+/// ```
+///
+pub struct BackgroundKeystream {
+    current: Vec<u8>,
+    used: usize,
+    position: u64,
+    full: Option<Receiver<Vec<u8>>>,
+    empty: Option<SyncSender<Vec<u8>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl BackgroundKeystream {
+    ///
+    /// Starts a worker generating the keystream from byte `offset` on, in
+    /// chunks of `chunk_blocks` blocks.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `iv` isn't one block long or
+    /// `chunk_blocks` is zero.
+    ///
+    pub fn spawn<W: Word + Send + 'static>(
+        key_exp: Vec<W>,
+        iv: &[u8],
+        offset: u64,
+        chunk_blocks: usize,
+    ) -> Result<Self, Error> {
+        let bs = 2 * W::BYTES;
+        if iv.len() != bs || chunk_blocks == 0 {
+            return Err(Error::InvalidLength);
+        }
+        let chunk = chunk_blocks * bs;
+
+        let (full_tx, full_rx) = sync_channel::<Vec<u8>>(2);
+        let (empty_tx, empty_rx) = sync_channel::<Vec<u8>>(2);
+        for _ in 0..2 {
+            empty_tx.send(vec![0; chunk]).unwrap();
+        }
+
+        let iv = iv.to_vec();
+        let worker = thread::spawn(move || {
+            let mut next = offset;
+            // ends when the stream is dropped and the channels disconnect
+            while let Ok(mut buf) = empty_rx.recv() {
+                buf.fill(0);
+                // cannot fail, the IV length was checked above
+                let _ = apply_keystream_at(&key_exp, &iv, next, &mut buf);
+                next = next.wrapping_add(buf.len() as u64);
+                if full_tx.send(buf).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(BackgroundKeystream {
+            current: Vec::new(),
+            used: 0,
+            position: offset,
+            full: Some(full_rx),
+            empty: Some(empty_tx),
+            worker: Some(worker),
+        })
+    }
+
+    ///
+    /// XORs the next `buf.len()` bytes of keystream into `buf`, waiting for
+    /// the worker if it has fallen behind.
+    ///
+    pub fn apply(&mut self, buf: &mut [u8]) {
+        self.position = self.position.wrapping_add(buf.len() as u64);
+        let mut buf = buf;
+        while !buf.is_empty() {
+            if self.used == self.current.len() {
+                self.next_chunk();
+            }
+            let take = (self.current.len() - self.used).min(buf.len());
+            let (head, rest) = buf.split_at_mut(take);
+            for (b, k) in head.iter_mut().zip(&self.current[self.used..]) {
+                *b ^= k;
+            }
+            self.used += take;
+            buf = rest;
+        }
+    }
+
+    ///
+    /// Keystream offset of the next byte [`apply`](Self::apply) uses.
+    ///
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn next_chunk(&mut self) {
+        let spent = std::mem::take(&mut self.current);
+        if !spent.is_empty() {
+            // the worker holds at most one chunk, so there is room
+            let _ = self.empty.as_ref().unwrap().send(spent);
+        }
+        self.current = self
+            .full
+            .as_ref()
+            .unwrap()
+            .recv()
+            .expect("keystream worker stopped");
+        self.used = 0;
+    }
+}
+
+impl Drop for BackgroundKeystream {
+    fn drop(&mut self) {
+        // disconnecting both channels wakes the worker whichever side it
+        // waits on
+        self.empty = None;
+        self.full = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc5::expand_key;
+
+    #[test]
+    fn matches_direct_keystream() {
+        let key_exp = expand_key::<u32>(b"background", 12);
+        let iv = [0x33u8; 8];
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+        let mut expected = data.clone();
+        apply_keystream_at(&key_exp, &iv, 13, &mut expected).unwrap();
+
+        // 3-block chunks, so bursts span several chunks and chunk boundaries
+        // fall mid-block relative to the unaligned offset
+        let mut keystream = BackgroundKeystream::spawn(key_exp, &iv, 13, 3).unwrap();
+        let mut out = data.clone();
+        let mut pos = 0;
+        for len in [1, 23, 24, 0, 100, 352, 500] {
+            keystream.apply(&mut out[pos..pos + len]);
+            pos += len;
+            assert_eq!(keystream.position(), 13 + pos as u64);
+        }
+        assert_eq!(pos, data.len());
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn rejects_bad_parameters() {
+        let key_exp = expand_key::<u16>(b"key", 12);
+        assert!(BackgroundKeystream::spawn(key_exp.clone(), &[0; 8], 0, 4).is_err());
+        assert!(BackgroundKeystream::spawn(key_exp, &[0; 4], 0, 0).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//!
+//! Instrumented kernels for tuning on a specific target.
+//!
+//! [`encrypt_profiled`] and [`decrypt_profiled`] run ECB over a buffer like
+//! the plain kernels, but read a caller-supplied timestamp between phases and
+//! report how the time split between key expansion, loading words from bytes,
+//! the rounds and storing words back. On a microcontroller the timestamp is
+//! typically a cycle counter such as the Cortex-M DWT `CYCCNT`, which shows
+//! whether e.g. a 64-bit word size pays off on a 32-bit core.
+//!
+//! Timestamps are `u64` ticks of any unit. A 32-bit cycle counter wraps
+//! within seconds at typical clock rates, so keep profiled runs short or
+//! extend the counter to 64 bits in the closure. The timestamp is read between
+//! every two phases of every block, so for small words the cost of the read
+//! itself is a large share of each phase: compare word sizes by their totals
+//! over many blocks rather than by single phases.
+//!
+//! Only available with the `profile` feature.
+//!
+
+use std::hint::black_box;
+
+use crate::error::Error;
+use crate::rc5::{decrypt_kernel, encrypt_kernel, expand_key};
+use crate::word::Word;
+
+///
+/// Ticks spent in each phase, summed over all blocks.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTicks {
+    /// Expanding the key into the round-key table, done once.
+    pub key_schedule: u64,
+    /// Converting input bytes to words.
+    pub load: u64,
+    /// The cipher rounds, including the pre- and post-whitening additions.
+    pub rounds: u64,
+    /// Converting output words to bytes.
+    pub store: u64,
+}
+
+impl PhaseTicks {
+    pub fn total(&self) -> u64 {
+        self.key_schedule + self.load + self.rounds + self.store
+    }
+}
+
+///
+/// Encrypts `data` in place, block by block, and reports the ticks per phase.
+///
+/// Fails with [`Error::InvalidLength`] if `data` isn't a whole number of
+/// blocks.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Instant;
+/// use rc5_cipher::profile::encrypt_profiled;
+///
+/// let start = Instant::now();
+/// let nanos = || start.elapsed().as_nanos() as u64;
+///
+/// let mut data = [0u8; 64];
+/// let ticks = encrypt_profiled::<u32>(&[0; 16], 12, &mut data, nanos).unwrap();
+/// assert!(ticks.total() >= ticks.rounds);
+/// ```
+///
+pub fn encrypt_profiled<W: Word>(
+    key: &[u8],
+    rounds: usize,
+    data: &mut [u8],
+    now: impl FnMut() -> u64,
+) -> Result<PhaseTicks, Error> {
+    profiled(key, rounds, data, now, encrypt_kernel::<W>)
+}
+
+///
+/// Decrypts `data` in place, block by block, and reports the ticks per phase.
+///
+pub fn decrypt_profiled<W: Word>(
+    key: &[u8],
+    rounds: usize,
+    data: &mut [u8],
+    now: impl FnMut() -> u64,
+) -> Result<PhaseTicks, Error> {
+    profiled(key, rounds, data, now, decrypt_kernel::<W>)
+}
+
+fn profiled<W: Word>(
+    key: &[u8],
+    rounds: usize,
+    data: &mut [u8],
+    mut now: impl FnMut() -> u64,
+    kernel: fn([W; 2], &[W]) -> [W; 2],
+) -> Result<PhaseTicks, Error> {
+    let bs = 2 * W::BYTES;
+    if !data.len().is_multiple_of(bs) {
+        return Err(Error::InvalidLength);
+    }
+
+    let mut ticks = PhaseTicks::default();
+    // black_box keeps each phase's work from moving across the timestamp reads
+    let mut mark = now();
+    let mut lap = |phase: &mut u64| {
+        let t = now();
+        *phase = phase.wrapping_add(t.wrapping_sub(mark));
+        mark = t;
+    };
+
+    let key_exp = black_box(expand_key::<W>(key, rounds));
+    lap(&mut ticks.key_schedule);
+
+    for block in data.chunks_exact_mut(bs) {
+        lap(&mut ticks.store);
+        let words = black_box([
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ]);
+        lap(&mut ticks.load);
+        let words = black_box(kernel(words, &key_exp));
+        lap(&mut ticks.rounds);
+        words[0].write_le(block);
+        words[1].write_le(&mut block[W::BYTES..]);
+        black_box(&mut *block);
+    }
+    lap(&mut ticks.store);
+    Ok(ticks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn output_matches_kernels() {
+        let key = b"profile key 0123";
+        let plain: Vec<u8> = (0..48).collect();
+        let key_exp = expand_key::<u64>(key, 16);
+
+        let mut data = plain.clone();
+        encrypt_profiled::<u64>(key, 16, &mut data, || 0).unwrap();
+        for (pt, ct) in plain.chunks(16).zip(data.chunks(16)) {
+            let pt = [u64::from_le_slice(pt), u64::from_le_slice(&pt[8..])];
+            let expected = encrypt_kernel(pt, &key_exp);
+            assert_eq!(
+                [u64::from_le_slice(ct), u64::from_le_slice(&ct[8..])],
+                expected
+            );
+        }
+
+        decrypt_profiled::<u64>(key, 16, &mut data, || 0).unwrap();
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn every_tick_is_attributed_once() {
+        // each read advances the clock by one, so the total is the read count
+        // minus one
+        let clock = Cell::new(1000);
+        let now = || {
+            clock.set(clock.get() + 1);
+            clock.get()
+        };
+
+        let mut data = [0u8; 32];
+        let ticks = encrypt_profiled::<u16>(&[1, 2, 3], 12, &mut data, now).unwrap();
+        assert_eq!(ticks.key_schedule, 1);
+        assert_eq!((ticks.load, ticks.rounds, ticks.store), (8, 8, 9));
+        assert_eq!(ticks.total(), 1 + 3 * 8 + 1);
+    }
+
+    #[test]
+    fn rejects_partial_blocks() {
+        assert_eq!(
+            encrypt_profiled::<u32>(&[0; 16], 12, &mut [0; 9], || 0),
+            Err(Error::InvalidLength)
+        );
+    }
+}
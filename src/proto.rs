@@ -0,0 +1,225 @@
+//!
+//! Protobuf messages for the [`envelope`](crate::envelope) format.
+//!
+//! The schema is `proto/rc5.proto`; the types below are what `prost-build`
+//! generates for it, written out so that building the crate doesn't need
+//! `protoc`. Services in other languages compile the same file.
+//!
+//! Only available with the `proto` feature.
+//!
+
+use crate::error::Error;
+use crate::header::{Extension as HeaderExtension, Header, MacAlg, Mode};
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Extension {
+    #[prost(uint32, tag = "1")]
+    pub kind: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Params {
+    #[prost(uint32, tag = "1")]
+    pub word_bytes: u32,
+    #[prost(uint32, tag = "2")]
+    pub rounds: u32,
+    #[prost(uint32, tag = "3")]
+    pub mode: u32,
+    #[prost(uint32, tag = "4")]
+    pub mac: u32,
+    #[prost(message, repeated, tag = "5")]
+    pub extensions: Vec<Extension>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Envelope {
+    #[prost(message, optional, tag = "1")]
+    pub params: Option<Params>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub nonce: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub ciphertext: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub tag: Vec<u8>,
+    #[prost(string, tag = "5")]
+    pub key_id: String,
+}
+
+impl Envelope {
+    ///
+    /// Splits the output of
+    /// [`encrypt_with_header`](crate::envelope::encrypt_with_header) into
+    /// message fields, labelled with `key_id`.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use prost::Message;
+    /// use rc5_cipher::envelope::{decrypt_auto, encrypt_with_header};
+    /// use rc5_cipher::header::Mode;
+    /// use rc5_cipher::proto::Envelope;
+    /// # use rand_core::OsRng;
+    /// # let mut rng = OsRng;
+    ///
+    /// let key = [3u8; 16];
+    /// let sealed = encrypt_with_header::<u32, _>(&mut rng, &key, 20, Mode::Ctr, b"field").unwrap();
+    ///
+    /// let wire = Envelope::from_envelope(&sealed, "users/2026-10").unwrap().encode_to_vec();
+    ///
+    /// let message = Envelope::decode(&wire[..]).unwrap();
+    /// assert_eq!(message.key_id, "users/2026-10");
+    /// assert_eq!(decrypt_auto(&key, &message.to_envelope().unwrap()).unwrap(), b"field");
+    /// ```
+    ///
+    pub fn from_envelope(bytes: &[u8], key_id: &str) -> Result<Self, Error> {
+        let (header, len) = Header::parse(bytes)?;
+        let rest = &bytes[len..];
+        let iv_len = match header.mode {
+            Mode::Ecb => 0,
+            _ => 2 * header.word_bytes as usize,
+        };
+        if rest.len() < iv_len {
+            return Err(Error::InvalidLength);
+        }
+        let (nonce, ciphertext) = rest.split_at(iv_len);
+
+        Ok(Envelope {
+            params: Some(Params {
+                word_bytes: header.word_bytes.into(),
+                rounds: header.rounds.into(),
+                mode: header.mode as u32,
+                mac: header.mac as u32,
+                extensions: header
+                    .extensions
+                    .iter()
+                    .map(|ext| Extension {
+                        kind: ext.kind.into(),
+                        value: ext.value.clone(),
+                    })
+                    .collect(),
+            }),
+            nonce: nonce.to_vec(),
+            ciphertext: ciphertext.to_vec(),
+            tag: Vec::new(),
+            key_id: key_id.to_owned(),
+        })
+    }
+
+    ///
+    /// Reassembles the envelope bytes for
+    /// [`decrypt_auto`](crate::envelope::decrypt_auto).
+    ///
+    /// Fails with [`Error::InvalidHeader`] if a parameter is missing or out of
+    /// range, and with [`Error::UnsupportedParameters`] if the message carries
+    /// a tag, which the byte envelope has no place for.
+    ///
+    pub fn to_envelope(&self) -> Result<Vec<u8>, Error> {
+        let params = self.params.as_ref().ok_or(Error::InvalidHeader)?;
+        if !self.tag.is_empty() {
+            return Err(Error::UnsupportedParameters);
+        }
+        let byte = |val: u32| u8::try_from(val).map_err(|_| Error::InvalidHeader);
+
+        let header = Header {
+            version: Header::VERSION,
+            word_bytes: byte(params.word_bytes)?,
+            rounds: byte(params.rounds)?,
+            mode: Mode::from_u8(byte(params.mode)?).ok_or(Error::InvalidHeader)?,
+            mac: MacAlg::from_u8(byte(params.mac)?).ok_or(Error::InvalidHeader)?,
+            extensions: params
+                .extensions
+                .iter()
+                .map(|ext| {
+                    if ext.value.len() > u8::MAX as usize {
+                        return Err(Error::InvalidHeader);
+                    }
+                    Ok(HeaderExtension {
+                        kind: byte(ext.kind)?,
+                        value: ext.value.clone(),
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        if header.extensions.len() > u8::MAX as usize {
+            return Err(Error::InvalidHeader);
+        }
+
+        let mut out = header.to_bytes();
+        // re-parse so word size and critical extensions are validated
+        Header::parse(&out)?;
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&self.ciphertext);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::encrypt_with_header;
+    use crate::rng::CounterRng;
+    use prost::Message;
+
+    const KEY: &[u8] = b"protobuf key 128";
+
+    #[test]
+    fn envelope_roundtrips_through_protobuf() {
+        let mut rng = CounterRng(0);
+        for mode in [Mode::Ecb, Mode::Cbc, Mode::Cfb, Mode::Ctr] {
+            let sealed =
+                encrypt_with_header::<u64, _>(&mut rng, KEY, 24, mode, b"payload").unwrap();
+            let message = Envelope::from_envelope(&sealed, "k1").unwrap();
+            assert_eq!(message.nonce.len(), if mode == Mode::Ecb { 0 } else { 16 });
+
+            let decoded = Envelope::decode(&message.encode_to_vec()[..]).unwrap();
+            assert_eq!(decoded, message);
+            assert_eq!(decoded.to_envelope().unwrap(), sealed);
+        }
+    }
+
+    #[test]
+    fn wire_format_is_stable() {
+        let message = Envelope {
+            params: Some(Params {
+                word_bytes: 4,
+                rounds: 12,
+                mode: 2,
+                mac: 0,
+                extensions: Vec::new(),
+            }),
+            nonce: vec![0xaa],
+            ciphertext: vec![0xbb],
+            tag: Vec::new(),
+            key_id: "k".into(),
+        };
+        assert_eq!(
+            message.encode_to_vec(),
+            [
+                0x0a, 0x06, 0x08, 0x04, 0x10, 0x0c, 0x18, 0x02, // params
+                0x12, 0x01, 0xaa, // nonce
+                0x1a, 0x01, 0xbb, // ciphertext
+                0x2a, 0x01, b'k', // key_id
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_messages() {
+        let mut message = Envelope::from_envelope(
+            &encrypt_with_header::<u32, _>(&mut CounterRng(0), KEY, 12, Mode::Cbc, b"x").unwrap(),
+            "k",
+        )
+        .unwrap();
+
+        message.tag = vec![0; 16];
+        assert_eq!(message.to_envelope(), Err(Error::UnsupportedParameters));
+        message.tag.clear();
+
+        message.params.as_mut().unwrap().word_bytes = 3;
+        assert_eq!(message.to_envelope(), Err(Error::InvalidHeader));
+        message.params = None;
+        assert_eq!(message.to_envelope(), Err(Error::InvalidHeader));
+    }
+}
@@ -55,34 +55,48 @@
  assert_eq!(pt, [0x33221100u32, 0x77665544]);
  ```
 
+ ## Example: many blocks under one key
+
+ [`Rc5`] expands the key once, so each block costs only the rounds.
+
+ ```rust
+ use rc5_cipher::Rc5;
+
+ let key = vec![
+     0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+     0x0E, 0x0F,
+ ];
+ let cipher = Rc5::<u32>::new(&key, 12);
+
+ assert_eq!(cipher.encrypt_block([0x33221100, 0x77665544]), [0x9B14DC2D, 0x9E8B08CF]);
+ assert_eq!(cipher.decrypt_block([0x9B14DC2D, 0x9E8B08CF]), [0x33221100, 0x77665544]);
+ ```
+
  ## Bibliography
 
  - Rivest original paper: https://www.grc.com/r&d/rc5.pdf
  - C implementation and tests: https://tools.ietf.org/id/draft-krovetz-rc6-rc5-vectors-00.html#rfc.section.4
- - Haskell implementation: https://hackage.haskell.org/package/cipher-rc5-0.1.2.2/docs/src/Crypto-Cipher-RC5.html
+ - Haskell implementation, not cross-checked by the tests: https://hackage.haskell.org/package/cipher-rc5-0.1.2.2/docs/src/Crypto-Cipher-RC5.html
 
 */
 
+use std::sync::atomic::{compiler_fence, Ordering};
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::Error;
 use crate::word::Word;
 
+///
+/// Data-dependent rotations map to the native rotate instructions, so 8- and
+/// 16-bit words are never promoted to `usize` and need no shift pairs.
+///
 pub fn rotl<W: Word>(x: W, y: W) -> W {
-    let w = W::BYTES * 8;
-    let a = y & W::from_usize(w - 1);
-    if a == W::ZERO {
-        x
-    } else {
-        (x << a) | (x >> (W::from_usize(w) - a))
-    }
+    x.rotl(y)
 }
 
 pub fn rotr<W: Word>(x: W, y: W) -> W {
-    let w = W::BYTES * 8;
-    let a = y & W::from_usize(w - 1);
-    if a == W::ZERO {
-        x
-    } else {
-        (x >> a) | (x << (W::from_usize(w) - a))
-    }
+    x.rotr(y)
 }
 
 ///
@@ -103,8 +117,29 @@ pub fn rotr<W: Word>(x: W, y: W) -> W {
 /// assert_eq!(ct, [0x21u8, 0x2A]);
 /// ```
 ///
-pub fn encrypt<W: Word>(pt: [W; 2], key: &Vec<u8>, rounds: usize) -> [W; 2] {
+pub fn encrypt<W: Word>(pt: [W; 2], key: &[u8], rounds: usize) -> [W; 2] {
     let key_exp = expand_key::<W>(key, rounds);
+    encrypt_kernel(pt, &key_exp)
+}
+
+///
+/// Encrypts a single block `pt` with an already expanded key `key_exp`, as
+/// returned by [`expand_key`]. The number of rounds is implied by the length of
+/// the expanded key.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::{encrypt_kernel, expand_key};
+///
+/// let key_exp = expand_key::<u8>(&[0x00, 0x01, 0x02, 0x03], 12);
+///
+/// assert_eq!(encrypt_kernel([0x00u8, 0x01], &key_exp), [0x21u8, 0x2A]);
+/// ```
+///
+pub fn encrypt_kernel<W: Word>(pt: [W; 2], key_exp: &[W]) -> [W; 2] {
+    debug_check_schedule(key_exp);
+    let rounds = key_exp.len() / 2 - 1;
     let mut a = pt[0].wrapping_add(&key_exp[0]);
     let mut b = pt[1].wrapping_add(&key_exp[1]);
     for i in 1..=rounds {
@@ -133,8 +168,29 @@ pub fn encrypt<W: Word>(pt: [W; 2], key: &Vec<u8>, rounds: usize) -> [W; 2] {
 /// ```
 ///
 #[allow(arithmetic_overflow)]
-pub fn decrypt<W: Word>(ct: [W; 2], key: &Vec<u8>, rounds: usize) -> [W; 2] {
+pub fn decrypt<W: Word>(ct: [W; 2], key: &[u8], rounds: usize) -> [W; 2] {
     let key_exp = expand_key::<W>(key, rounds);
+    decrypt_kernel(ct, &key_exp)
+}
+
+///
+/// Decrypts a single block `ct` with an already expanded key `key_exp`, as
+/// returned by [`expand_key`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::{decrypt_kernel, expand_key};
+///
+/// let key_exp = expand_key::<u8>(&[0x00, 0x01, 0x02, 0x03], 12);
+///
+/// assert_eq!(decrypt_kernel([0x21u8, 0x2A], &key_exp), [0x00u8, 0x01]);
+/// ```
+///
+#[allow(arithmetic_overflow)]
+pub fn decrypt_kernel<W: Word>(ct: [W; 2], key_exp: &[W]) -> [W; 2] {
+    debug_check_schedule(key_exp);
+    let rounds = key_exp.len() / 2 - 1;
     let mut a = ct[0];
     let mut b = ct[1];
     for i in (1..=rounds).rev() {
@@ -144,6 +200,205 @@ pub fn decrypt<W: Word>(ct: [W; 2], key: &Vec<u8>, rounds: usize) -> [W; 2] {
     [a.wrapping_sub(&key_exp[0]), b.wrapping_sub(&key_exp[1])]
 }
 
+///
+/// An RC5 cipher keyed once: the schedule is expanded in [`Rc5::new`] and
+/// reused by every block, so bulk encryption doesn't pay the key setup per
+/// block like [`encrypt`] does. The schedule is zeroized when dropped.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::Rc5;
+///
+/// let cipher = Rc5::<u32>::new(&[0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07], 12);
+/// let blocks = [[0u32, 0], [1, 2], [3, 4]];
+///
+/// for pt in blocks {
+///     let ct = cipher.encrypt_block(pt);
+///     assert_eq!(cipher.decrypt_block(ct), pt);
+/// }
+/// ```
+///
+pub struct Rc5<W: Word> {
+    key_exp: Zeroizing<KeyExp<W>>,
+}
+
+impl<W: Word> Rc5<W> {
+    ///
+    /// Expands `key` for `rounds` rounds, see [`expand_key`].
+    ///
+    pub fn new(key: &[u8], rounds: usize) -> Self {
+        Rc5 {
+            key_exp: Zeroizing::new(KeyExp(expand_key(key, rounds))),
+        }
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.key_exp.0.len() / 2 - 1
+    }
+
+    ///
+    /// Encrypts one block, like [`encrypt_kernel`] with the stored schedule.
+    ///
+    pub fn encrypt_block(&self, pt: [W; 2]) -> [W; 2] {
+        encrypt_kernel(pt, &self.key_exp.0)
+    }
+
+    ///
+    /// Decrypts one block, like [`decrypt_kernel`] with the stored schedule.
+    ///
+    pub fn decrypt_block(&self, ct: [W; 2]) -> [W; 2] {
+        decrypt_kernel(ct, &self.key_exp.0)
+    }
+}
+
+// An expanded key. `Word` has no `Zeroize` bound, so it is wiped word by word.
+pub(crate) struct KeyExp<W: Word>(pub(crate) Vec<W>);
+
+impl<W: Word> Zeroize for KeyExp<W> {
+    fn zeroize(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+// Overwrites `words` with zeros in a way the optimizer can't drop as a dead
+// store.
+pub(crate) fn wipe<W: Word>(words: &mut [W]) {
+    for word in words.iter_mut() {
+        *word = W::ZERO;
+    }
+    std::hint::black_box(words);
+    compiler_fence(Ordering::SeqCst);
+}
+
+///
+/// Encrypts a 128-bit block given as bytes, with RC5-64 and a schedule from
+/// [`expand_key`]. The words are read little-endian, as in the specification.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::expand_key;
+/// use rc5_cipher::rc5::{decrypt_block_128, encrypt_block_128};
+///
+/// let key: Vec<u8> = (0..24).collect();
+/// let key_exp = expand_key::<u64>(&key, 24);
+/// let pt: [u8; 16] = core::array::from_fn(|i| i as u8);
+///
+/// let ct = encrypt_block_128(pt, &key_exp);
+/// assert_eq!(ct[..4], [0xa4, 0x67, 0x72, 0x82]);
+/// assert_eq!(decrypt_block_128(ct, &key_exp), pt);
+/// ```
+///
+pub fn encrypt_block_128(block: [u8; 16], key_exp: &[u64]) -> [u8; 16] {
+    bytes_kernel(block, key_exp, encrypt_kernel)
+}
+
+///
+/// Decrypts a 128-bit block given as bytes, with RC5-64.
+///
+pub fn decrypt_block_128(block: [u8; 16], key_exp: &[u64]) -> [u8; 16] {
+    bytes_kernel(block, key_exp, decrypt_kernel)
+}
+
+///
+/// Encrypts a 256-bit block given as bytes, with RC5-128 and a schedule from
+/// [`expand_key`]. The words are read little-endian, as in the specification.
+///
+pub fn encrypt_block_256(block: [u8; 32], key_exp: &[u128]) -> [u8; 32] {
+    bytes_kernel(block, key_exp, encrypt_kernel)
+}
+
+///
+/// Decrypts a 256-bit block given as bytes, with RC5-128.
+///
+pub fn decrypt_block_256(block: [u8; 32], key_exp: &[u128]) -> [u8; 32] {
+    bytes_kernel(block, key_exp, decrypt_kernel)
+}
+
+///
+/// Encrypts one block given as bytes, the byte-slice counterpart of
+/// [`encrypt`] for any word size. The words are read little-endian.
+///
+/// Fails with [`Error::InvalidLength`] instead of panicking if `block` isn't
+/// `2 * W::BYTES` long or `key` is longer than [`MAX_KEY_BYTES`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::{try_decrypt, try_encrypt, Error};
+///
+/// let key = [0x00, 0x01, 0x02, 0x03];
+/// let ct = try_encrypt::<u8>(&[0x00, 0x01], &key, 12).unwrap();
+/// assert_eq!(ct, [0x21, 0x2A]);
+/// assert_eq!(try_decrypt::<u8>(&ct, &key, 12).unwrap(), [0x00, 0x01]);
+///
+/// assert_eq!(try_encrypt::<u8>(&[0x00, 0x01, 0x02], &key, 12), Err(Error::InvalidLength));
+/// ```
+///
+pub fn try_encrypt<W: Word>(block: &[u8], key: &[u8], rounds: usize) -> Result<Vec<u8>, Error> {
+    try_crypt::<W>(block, key, rounds, encrypt_kernel)
+}
+
+///
+/// Decrypts one block given as bytes, the byte-slice counterpart of
+/// [`decrypt`]. Fails like [`try_encrypt`].
+///
+pub fn try_decrypt<W: Word>(block: &[u8], key: &[u8], rounds: usize) -> Result<Vec<u8>, Error> {
+    try_crypt::<W>(block, key, rounds, decrypt_kernel)
+}
+
+fn try_crypt<W: Word>(
+    block: &[u8],
+    key: &[u8],
+    rounds: usize,
+    kernel: fn([W; 2], &[W]) -> [W; 2],
+) -> Result<Vec<u8>, Error> {
+    if block.len() != 2 * W::BYTES || key.len() > MAX_KEY_BYTES {
+        return Err(Error::InvalidLength);
+    }
+    let key_exp = expand_key::<W>(key, rounds);
+    let out = kernel(
+        [
+            W::from_le_slice(block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        &key_exp,
+    );
+    let mut bytes = vec![0u8; block.len()];
+    out[0].write_le(&mut bytes);
+    out[1].write_le(&mut bytes[W::BYTES..]);
+    Ok(bytes)
+}
+
+fn bytes_kernel<W: Word, const N: usize>(
+    block: [u8; N],
+    key_exp: &[W],
+    kernel: fn([W; 2], &[W]) -> [W; 2],
+) -> [u8; N] {
+    let out = kernel(
+        [
+            W::from_le_slice(&block),
+            W::from_le_slice(&block[W::BYTES..]),
+        ],
+        key_exp,
+    );
+    let mut bytes = [0u8; N];
+    out[0].write_le(&mut bytes);
+    out[1].write_le(&mut bytes[W::BYTES..]);
+    bytes
+}
+
+// A schedule of odd length silently ignores its last word and one shorter
+// than two words panics deep in the kernel; catch both up front in debug builds.
+fn debug_check_schedule<W>(key_exp: &[W]) {
+    debug_assert!(
+        key_exp.len() >= 2 && key_exp.len().is_multiple_of(2),
+        "key schedule length {} is not 2 * (rounds + 1)",
+        key_exp.len()
+    );
+}
+
 ///
 /// Expands `key` into and array of length `T` of type `W`
 ///
@@ -167,13 +422,13 @@ pub fn decrypt<W: Word>(ct: [W; 2], key: &Vec<u8>, rounds: usize) -> [W; 2] {
 /// ```
 ///
 #[allow(arithmetic_overflow)]
-pub fn expand_key<W: Word>(key: &Vec<u8>, rounds: usize) -> Vec<W> {
+pub fn expand_key<W: Word>(key: &[u8], rounds: usize) -> Vec<W> {
     let t = 2 * (rounds + 1);
     let b = key.len();
-    let w = W::BYTES * 8;
+    let w = W::BITS as usize;
 
     // c = max(1, ceil(8*b/w))
-    let c = std::cmp::max(1, (8 * b + w - 1) / w);
+    let c = std::cmp::max(1, (8 * b).div_ceil(w));
 
     // converting the secrey key from bytes to words
     let mut key_l: Vec<W> = vec![W::ZERO; c];
@@ -209,10 +464,132 @@ pub fn expand_key<W: Word>(key: &Vec<u8>, rounds: usize) -> Vec<W> {
     key_s
 }
 
+///
+/// Longest key accepted by [`expand_key_into`], in bytes.
+///
+pub const MAX_KEY_BYTES: usize = 255;
+
+///
+/// Allocation-free [`expand_key`] for small targets: expands `key` into
+/// `key_exp`, whose length `2 * (r + 1)` selects the number of rounds.
+///
+/// The schedule lives wherever the caller puts it, e.g. a `[u8; 26]` for
+/// RC5-8/12 on an AVR, and the key words are kept in a buffer on the stack
+/// instead of the heap, 32 bytes for keys up to 16 bytes. Keys are limited to
+/// [`MAX_KEY_BYTES`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::rc5::expand_key_into;
+/// use rc5_cipher::{encrypt_kernel, expand_key};
+///
+/// let key = [0x00, 0x01, 0x02, 0x03];
+/// let mut key_exp = [0u16; 2 * (16 + 1)];
+/// expand_key_into(&key, &mut key_exp);
+///
+/// assert_eq!(key_exp[..], expand_key::<u16>(&key, 16)[..]);
+/// let ct = encrypt_kernel([0x0100u16, 0x0302], &key_exp);
+/// ```
+///
+pub fn expand_key_into<W: Word>(key: &[u8], key_exp: &mut [W]) {
+    assert!(key.len() <= MAX_KEY_BYTES, "key longer than MAX_KEY_BYTES");
+    assert!(
+        key_exp.len() >= 2 && key_exp.len().is_multiple_of(2),
+        "key schedule length must be 2 * (rounds + 1)"
+    );
+    // Room for the key rounded up to whole words, at most 16 bytes more.
+    match key.len() {
+        0..=16 => mix_key::<W, 32>(key, key_exp),
+        17..=32 => mix_key::<W, 48>(key, key_exp),
+        _ => mix_key::<W, { MAX_KEY_BYTES + 16 }>(key, key_exp),
+    }
+}
+
+fn mix_key<W: Word, const L: usize>(key: &[u8], key_exp: &mut [W]) {
+    // The key words are the key bytes read little-endian and zero padded, so
+    // they are mixed in place in a byte buffer.
+    let u = W::BYTES;
+    let c = std::cmp::max(1, key.len().div_ceil(u));
+    let mut key_l = [0u8; L];
+    key_l[..key.len()].copy_from_slice(key);
+
+    let t = key_exp.len();
+    key_exp[0] = W::P;
+    for i in 1..t {
+        key_exp[i] = key_exp[i - 1].wrapping_add(&W::Q);
+    }
+
+    let mut i = 0;
+    let mut j = 0;
+    let mut a = W::ZERO;
+    let mut b = W::ZERO;
+    for _k in 0..3 * std::cmp::max(c, t) {
+        key_exp[i] = rotl(key_exp[i].wrapping_add(&a.wrapping_add(&b)), W::from_u8(3));
+        a = key_exp[i];
+        let l = W::from_le_slice(&key_l[j * u..]);
+        b = rotl(l.wrapping_add(&a.wrapping_add(&b)), a.wrapping_add(&b));
+        b.write_le(&mut key_l[j * u..]);
+        i = (i + 1) % t;
+        j = (j + 1) % c;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn cached_schedule_matches_one_shot() {
+        let key: Vec<u8> = (0..16).collect();
+        let cipher = Rc5::<u32>::new(&key, 12);
+        assert_eq!(cipher.rounds(), 12);
+        let pt = [0x33221100u32, 0x77665544];
+        assert_eq!(cipher.encrypt_block(pt), [0x9B14DC2D, 0x9E8B08CF]);
+        assert_eq!(cipher.encrypt_block(pt), encrypt(pt, &key, 12));
+        assert_eq!(cipher.decrypt_block([0x9B14DC2D, 0x9E8B08CF]), pt);
+    }
+
+    #[test]
+    fn key_exp_zeroizes() {
+        let mut key_exp = KeyExp(expand_key::<u32>(b"secret", 12));
+        assert!(key_exp.0.iter().any(|&w| w != 0));
+        key_exp.zeroize();
+        assert_eq!(key_exp.0, [0; 26]);
+    }
+
+    #[test]
+    fn try_variants_match_and_validate() {
+        let key: Vec<u8> = (0..16).collect();
+        let pt = [0x33221100u32, 0x77665544];
+        let mut bytes = [0u8; 8];
+        pt[0].write_le(&mut bytes);
+        pt[1].write_le(&mut bytes[4..]);
+
+        let ct = try_encrypt::<u32>(&bytes, &key, 12).unwrap();
+        let words = encrypt(pt, &key, 12);
+        assert_eq!(u32::from_le_slice(&ct), words[0]);
+        assert_eq!(u32::from_le_slice(&ct[4..]), words[1]);
+        assert_eq!(try_decrypt::<u32>(&ct, &key, 12).unwrap(), bytes);
+
+        for len in [0, 7, 9, 16] {
+            assert_eq!(
+                try_encrypt::<u32>(&vec![0; len], &key, 12),
+                Err(Error::InvalidLength)
+            );
+            assert_eq!(
+                try_decrypt::<u32>(&vec![0; len], &key, 12),
+                Err(Error::InvalidLength)
+            );
+        }
+        let long = vec![0u8; MAX_KEY_BYTES + 1];
+        assert_eq!(
+            try_encrypt::<u64>(&[0; 16], &long, 12),
+            Err(Error::InvalidLength)
+        );
+        assert!(try_encrypt::<u64>(&[0; 16], &long[1..], 12).is_ok());
+    }
+
     #[test]
     fn test_left_right_shift() {
         let a = 0x77u8; // 0111 0111
@@ -237,84 +614,111 @@ mod tests {
         assert_eq!(rotr(a, 5 * 8u8), a);
     }
 
-    #[test]
-    fn test_rivest_1() {
-        let key = vec![
-            0x00u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-            0x00, 0x00,
-        ];
-        let pt = [0x00000000u32, 0x00000000];
-        let rounds = 12;
-
-        let ct = encrypt(pt, &key, rounds);
-        assert_eq!(ct, [0xEEDBA521u32, 0x6D8F4B15]);
-
-        let pt = decrypt(ct, &key, rounds);
-        assert_eq!(pt, [0x00000000u32, 0x00000000]);
+    fn rotl_shifts<W: Word>(x: W, y: W) -> W {
+        let w = W::BITS as usize;
+        let a = y & W::from_usize(w - 1);
+        if a == W::ZERO {
+            x
+        } else {
+            (x << a) | (x >> (W::from_usize(w) - a))
+        }
     }
 
     #[test]
-    fn test_rivest_2() {
-        let key = vec![
-            0x91, 0x5F, 0x46, 0x19, 0xBE, 0x41, 0xB2, 0x51, 0x63, 0x55, 0xA5, 0x01, 0x10, 0xA9,
-            0xCE, 0x91,
-        ];
-        let pt = [0xEEDBA521u32, 0x6D8F4B15];
-        let rounds = 12;
-
-        let ct = encrypt(pt, &key, rounds);
-        assert_eq!(ct, [0xAC13C0F7u32, 0x52892B5B]);
-
-        let pt = decrypt(ct, &key, rounds);
-        assert_eq!(pt, [0xEEDBA521u32, 0x6D8F4B15]);
+    fn native_rotation_matches_shifts() {
+        for x in [0u8, 0x77, 0x80, 0xff] {
+            for y in 0..=255u8 {
+                assert_eq!(rotl(x, y), rotl_shifts(x, y));
+                assert_eq!(rotr(rotl(x, y), y), x);
+            }
+        }
+        for x in [0u16, 0x7731, 0x8001, 0xffff] {
+            for y in 0..=u16::MAX {
+                assert_eq!(rotl(x, y), rotl_shifts(x, y));
+                assert_eq!(rotr(rotl(x, y), y), x);
+            }
+        }
     }
 
     #[test]
-    fn test_rivest_3() {
-        let key = vec![
-            0x78, 0x33, 0x48, 0xE7, 0x5A, 0xEB, 0x0F, 0x2F, 0xD7, 0xB1, 0x69, 0xBB, 0x8D, 0xC1,
-            0x67, 0x87,
-        ];
-        let pt = [0xAC13C0F7u32, 0x52892B5B];
-        let rounds = 12;
-
-        let ct = encrypt(pt, &key, rounds);
-        assert_eq!(ct, [0xB7B3422Fu32, 0x92FC6903]);
-
-        let pt = decrypt(ct, &key, rounds);
-        assert_eq!(pt, [0xAC13C0F7u32, 0x52892B5B]);
+    fn expand_key_into_matches_expand_key() {
+        fn check<W: Word, const T: usize>(key: &[u8]) {
+            let mut key_exp = [W::ZERO; T];
+            expand_key_into(key, &mut key_exp);
+            assert_eq!(key_exp[..], expand_key::<W>(key, T / 2 - 1)[..]);
+        }
+        let long: Vec<u8> = (0..MAX_KEY_BYTES as u8).collect();
+        // Both sides of every key buffer size.
+        for len in [0, 3, 16, 17, 32, 33, MAX_KEY_BYTES] {
+            let key = &long[..len];
+            check::<u8, 26>(key);
+            check::<u16, 34>(key);
+            check::<u32, 26>(key);
+            check::<u64, 50>(key);
+            check::<u128, 4>(key);
+        }
     }
 
     #[test]
-    fn test_rivest_4() {
-        let key = vec![
-            0xDC, 0x49, 0xDB, 0x13, 0x75, 0xA5, 0x58, 0x4F, 0x64, 0x85, 0xB4, 0x13, 0xB5, 0xF1,
-            0x2B, 0xAF,
-        ];
-        let pt = [0xB7B3422Fu32, 0x92FC6903];
-        let rounds = 12;
+    fn small_word_schedules_fit_in_tens_of_bytes() {
+        // RC5-8/12 and RC5-16/16, the parameters used on 8- and 16-bit MCUs.
+        assert_eq!(std::mem::size_of::<[u8; 2 * (12 + 1)]>(), 26);
+        assert_eq!(std::mem::size_of::<[u16; 2 * (16 + 1)]>(), 68);
+
+        let mut key_exp = [0u8; 26];
+        expand_key_into(&[1, 2, 3, 4, 5, 6, 7, 8], &mut key_exp);
+        let ct = encrypt_kernel([0x00u8, 0x01], &key_exp);
+        assert_eq!(decrypt_kernel(ct, &key_exp), [0x00, 0x01]);
+    }
 
-        let ct = encrypt(pt, &key, rounds);
-        assert_eq!(ct, [0xB278C165u32, 0xCC97D184]);
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not 2 * (rounds + 1)")]
+    fn debug_rejects_odd_schedule() {
+        let key_exp = expand_key::<u32>(b"key", 12);
+        encrypt_kernel([0u32, 0], &key_exp[..25]);
+    }
 
-        let pt = decrypt(ct, &key, rounds);
-        assert_eq!(pt, [0xB7B3422Fu32, 0x92FC6903]);
+    #[test]
+    fn decrypt_inverts_encrypt_all_u8_blocks() {
+        let key_exp = expand_key::<u8>(&[0xde, 0xad, 0xbe, 0xef], 12);
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                let ct = encrypt_kernel([a, b], &key_exp);
+                assert_eq!(decrypt_kernel(ct, &key_exp), [a, b]);
+            }
+        }
     }
 
+    // draft-krovetz-rc6-rc5-vectors-00, section 4, as bytes
     #[test]
-    fn test_rivest_5() {
-        let key = vec![
-            0x52, 0x69, 0xF1, 0x49, 0xD4, 0x1B, 0xA0, 0x15, 0x24, 0x97, 0x57, 0x4D, 0x7F, 0x15,
-            0x31, 0x25,
-        ];
-        let pt = [0xB278C165u32, 0xCC97D184];
-        let rounds = 12;
+    fn byte_blocks_128_and_256() {
+        let key: Vec<u8> = (0..32).collect();
+        let pt: [u8; 32] = core::array::from_fn(|i| i as u8);
 
-        let ct = encrypt(pt, &key, rounds);
-        assert_eq!(ct, [0x15E444EBu32, 0x249831DA]);
+        let key_exp = expand_key::<u64>(&key[..24], 24);
+        let pt_128: [u8; 16] = pt[..16].try_into().unwrap();
+        let ct_128 = encrypt_block_128(pt_128, &key_exp);
+        assert_eq!(
+            ct_128,
+            [
+                0xa4, 0x67, 0x72, 0x82, 0x0e, 0xdb, 0xce, 0x02, 0x35, 0xab, 0xea, 0x32, 0xae, 0x71,
+                0x78, 0xda
+            ]
+        );
+        assert_eq!(decrypt_block_128(ct_128, &key_exp), pt_128);
 
-        let pt = decrypt(ct, &key, rounds);
-        assert_eq!(pt, [0xB278C165u32, 0xCC97D184]);
+        let key_exp = expand_key::<u128>(&key, 28);
+        let ct_256 = encrypt_block_256(pt, &key_exp);
+        assert_eq!(
+            ct_256,
+            [
+                0xec, 0xa5, 0x91, 0x09, 0x21, 0xa4, 0xf4, 0xcf, 0xdd, 0x7a, 0xd7, 0xad, 0x20, 0xa1,
+                0xfc, 0xba, 0x06, 0x8e, 0xc7, 0xa7, 0xcd, 0x75, 0x2d, 0x68, 0xfe, 0x91, 0x4b, 0x7f,
+                0xe1, 0x80, 0xb4, 0x40
+            ]
+        );
+        assert_eq!(decrypt_block_256(ct_256, &key_exp), pt);
     }
 
     #[test]
@@ -352,108 +756,70 @@ mod tests {
 
         assert_eq!(pt, [0x144702EAu32, 0x844D5CAD]);
     }
+}
 
-    // Test cases from https://tools.ietf.org/id/draft-krovetz-rc6-rc5-vectors-00.html#rfc.section.4
-
-    #[test]
-    fn encrypt_decrypt_8_12_4() {
-        let rounds = 12;
-        let key = vec![0x00, 0x01, 0x02, 0x03];
-
-        let pt = [0x00u8, 0x01];
-
-        let ct = encrypt(pt, &key, rounds);
-
-        assert_eq!(ct, [0x21u8, 0x2A]);
-
-        let pt = decrypt(ct, &key, rounds);
+// Proof harnesses for the Kani model checker, run with `cargo kani`.
+#[cfg(kani)]
+mod proofs {
+    use super::*;
 
-        assert_eq!(pt, [0x00u8, 0x01]);
+    #[kani::proof]
+    fn rotr_inverts_rotl_u8() {
+        let x: u8 = kani::any();
+        let y: u8 = kani::any();
+        assert_eq!(rotr(rotl(x, y), y), x);
     }
 
-    #[test]
-    fn encrypt_16_16_8() {
-        let rounds = 16;
-        let key = vec![0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
-
-        let pt = [0x0100u16, 0x0302];
-
-        let ct = encrypt(pt, &key, rounds);
-
-        assert_eq!(ct, [0xA823, 0x2ED7]);
-
-        let pt = decrypt(ct, &key, rounds);
-
-        assert_eq!(pt, [0x0100u16, 0x0302]);
+    #[kani::proof]
+    fn rotr_inverts_rotl_u16() {
+        let x: u16 = kani::any();
+        let y: u16 = kani::any();
+        assert_eq!(rotr(rotl(x, y), y), x);
     }
 
-    #[test]
-    fn encrypt_decrypt_32_20_16() {
-        let rounds = 20;
-        let key = vec![
-            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
-            0x0E, 0x0F,
-        ];
-        let pt = [0x03020100u32, 0x07060504];
-
-        let ct = encrypt(pt, &key, rounds);
-
-        assert_eq!(ct, [0x0EDC0E2Au32, 0x73FF3194]);
-
-        let pt = decrypt(ct, &key, rounds);
-
-        assert_eq!(pt, [0x03020100u32, 0x07060504]);
+    #[kani::proof]
+    fn rotr_inverts_rotl_u32() {
+        let x: u32 = kani::any();
+        let y: u32 = kani::any();
+        assert_eq!(rotr(rotl(x, y), y), x);
     }
 
-    #[test]
-    fn encrypt_64_24_24() {
-        let rounds = 24;
-        let key = vec![
-            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
-            0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
-        ];
-        let pt = [0x0706050403020100u64, 0x0F0E0D0C0B0A0908];
-
-        let ct = encrypt(pt, &key, rounds);
-
-        assert_eq!(ct, [0x02CEDB0E827267A4u64, 0xDA7871AE32EAAB35]);
-
-        let pt = decrypt(ct, &key, rounds);
-
-        assert_eq!(pt, [0x0706050403020100u64, 0x0F0E0D0C0B0A0908]);
+    #[kani::proof]
+    fn rotr_inverts_rotl_u64() {
+        let x: u64 = kani::any();
+        let y: u64 = kani::any();
+        assert_eq!(rotr(rotl(x, y), y), x);
     }
 
-    #[test]
-    fn encrypt_kernel_128_28_32() {
-        let rounds = 28;
-        let key = vec![
-            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
-            0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B,
-            0x1C, 0x1D, 0x1E, 0x1F,
-        ];
-        let pt = [
-            0x0F0E0D0C0B0A09080706050403020100u128,
-            0x1F1E1D1C1B1A19181716151413121110,
-        ];
-
-        let ct = encrypt(pt, &key, rounds);
-
-        assert_eq!(
-            ct,
-            [
-                0xBAFCA120ADD77ADDCFF4A4210991A5ECu128,
-                0x40B480E17F4B91FE682D75CDA7C78E06
-            ]
-        );
+    // Any key, any block and any schedule: the kernels only depend on the
+    // schedule, so this covers every key of every length.
+    #[kani::proof]
+    #[kani::unwind(4)]
+    fn decrypt_inverts_encrypt_u8() {
+        let key_exp: [u8; 6] = kani::any();
+        let pt: [u8; 2] = kani::any();
+        assert_eq!(decrypt_kernel(encrypt_kernel(pt, &key_exp), &key_exp), pt);
+    }
 
-        let pt = decrypt(ct, &key, rounds);
+    #[kani::proof]
+    #[kani::unwind(4)]
+    fn decrypt_inverts_encrypt_u16() {
+        let key_exp: [u16; 6] = kani::any();
+        let pt: [u16; 2] = kani::any();
+        assert_eq!(decrypt_kernel(encrypt_kernel(pt, &key_exp), &key_exp), pt);
+    }
 
-        assert_eq!(
-            pt,
-            [
-                0x0F0E0D0C0B0A09080706050403020100u128,
-                0x1F1E1D1C1B1A19181716151413121110,
-            ]
-        );
+    #[kani::proof]
+    #[kani::unwind(20)]
+    fn expand_key_does_not_panic() {
+        const MAX_KEY: usize = 8;
+        let len: usize = kani::any();
+        kani::assume(len <= MAX_KEY);
+        let bytes: [u8; MAX_KEY] = kani::any();
+        let rounds: usize = kani::any();
+        kani::assume(rounds <= 2);
+
+        let key_exp = expand_key::<u16>(&bytes[..len], rounds);
+        assert_eq!(key_exp.len(), 2 * (rounds + 1));
     }
 }
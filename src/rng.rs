@@ -0,0 +1,100 @@
+//!
+//! Randomness used by this crate.
+//!
+//! Nothing here reaches for a global or thread-local generator: every API that
+//! needs random bytes, e.g. for an IV or a nonce, takes an `R: RngCore +
+//! CryptoRng` argument, normally [`OsRng`] (with the `getrandom` feature). The
+//! crate's unit tests use a private counter generator for reproducible output;
+//! it is deliberately not exported, so nothing predictable can stand in for a
+//! real generator outside them.
+//!
+
+#[cfg(feature = "getrandom")]
+pub use rand_core::OsRng;
+pub use rand_core::{CryptoRng, RngCore};
+
+///
+/// Returns `len` random bytes, e.g. a fresh key.
+///
+/// Example:
+///
+/// ```rust
+/// use rand_core::OsRng;
+/// use rc5_cipher::rng::generate_key;
+///
+/// assert_eq!(generate_key(&mut OsRng, 16).len(), 16);
+/// ```
+///
+pub fn generate_key<R: RngCore + CryptoRng>(rng: &mut R, len: usize) -> Vec<u8> {
+    let mut key = vec![0u8; len];
+    rng.fill_bytes(&mut key);
+    key
+}
+
+///
+/// A reproducible generator for the unit tests: a byte counter starting at the
+/// seed, wrapping at 255. It is not random at all and only exists under
+/// `cfg(test)`.
+///
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct CounterRng(pub u8);
+
+#[cfg(test)]
+impl RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for b in dest {
+            *b = self.0;
+            self.0 = self.0.wrapping_add(1);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CryptoRng for CounterRng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_rng_wraps() {
+        let mut bytes = [0u8; 3];
+        CounterRng(254).fill_bytes(&mut bytes);
+        assert_eq!(bytes, [254, 255, 0]);
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        assert_eq!(
+            generate_key(&mut CounterRng(7), 16),
+            generate_key(&mut CounterRng(7), 16)
+        );
+        assert_ne!(
+            generate_key(&mut CounterRng(7), 16),
+            generate_key(&mut CounterRng(8), 16)
+        );
+    }
+
+    #[cfg(feature = "getrandom")]
+    #[test]
+    fn os_rng_fills_keys() {
+        let a = generate_key(&mut OsRng, 32);
+        let b = generate_key(&mut OsRng, 32);
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+    }
+}
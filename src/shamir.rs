@@ -0,0 +1,244 @@
+//!
+//! Shamir secret sharing of keys over GF(2^8).
+//!
+//! [`split_key`] turns a key into `n` shares such that any `k` of them
+//! [`recover_key`] it and fewer reveal nothing about it, so a master key
+//! protecting long-term archives can be escrowed across custodians. Each key
+//! byte is the constant term of its own random polynomial of degree `k - 1`;
+//! share `x` holds the polynomials evaluated at `x`.
+//!
+//! Field arithmetic avoids table lookups and secret-dependent branches. Shares
+//! and the recovered [`Rc5Key`] are zeroized when dropped, and their `Debug`
+//! output leaves out the bytes.
+//!
+//! Only available with the `shamir` feature, which enables `key`.
+//!
+
+use std::fmt;
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::Error;
+use crate::key::Rc5Key;
+use crate::rng::{CryptoRng, RngCore};
+
+///
+/// One custodian's share of a key.
+///
+#[derive(Clone, PartialEq, Eq)]
+pub struct Share {
+    /// The evaluation point, 1 to 255. Not secret.
+    pub index: u8,
+    pub value: Vec<u8>,
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl fmt::Debug for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Share({}, {} bytes)", self.index, self.value.len())
+    }
+}
+
+///
+/// Splits `key` into `n` shares, any `k` of which recover it.
+///
+/// Fails with [`Error::UnsupportedParameters`] unless `1 <= k <= n <= 255`.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::key::Rc5Key;
+/// use rc5_cipher::shamir::{recover_key, split_key};
+/// # use rand_core::OsRng;
+/// # let mut rng = OsRng;
+///
+/// let master = Rc5Key::new(&[0x42; 32]).unwrap();
+/// let shares = split_key(&mut rng, &master, 5, 3).unwrap();
+///
+/// let key = recover_key(&[shares[4].clone(), shares[0].clone(), shares[2].clone()]).unwrap();
+/// assert_eq!(key, master);
+/// ```
+///
+pub fn split_key<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key: &Rc5Key,
+    n: usize,
+    k: usize,
+) -> Result<Vec<Share>, Error> {
+    if k == 0 || k > n || n > 255 {
+        return Err(Error::UnsupportedParameters);
+    }
+    let key = key.as_bytes();
+
+    let mut shares: Vec<Share> = (1..=n as u8)
+        .map(|index| Share {
+            index,
+            value: Vec::with_capacity(key.len()),
+        })
+        .collect();
+    let mut coeffs = Zeroizing::new(vec![0u8; k]);
+    for &secret in key {
+        coeffs[0] = secret;
+        rng.fill_bytes(&mut coeffs[1..]);
+        for share in &mut shares {
+            // Horner's rule
+            let y = coeffs
+                .iter()
+                .rev()
+                .fold(0, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.value.push(y);
+        }
+    }
+    Ok(shares)
+}
+
+///
+/// Recovers the key from at least `k` distinct shares of one [`split_key`].
+///
+/// Fewer than `k` shares yield an unrelated key rather than an error, since
+/// the shares don't record `k`: check the result, e.g. by opening data
+/// authenticated under it. Fails with [`Error::InvalidShares`] if the shares
+/// are empty, differ in length, repeat an index or don't hold a valid key
+/// length.
+///
+pub fn recover_key(shares: &[Share]) -> Result<Rc5Key, Error> {
+    let len = shares.first().ok_or(Error::InvalidShares)?.value.len();
+    for (i, share) in shares.iter().enumerate() {
+        let repeated = shares[..i].iter().any(|s| s.index == share.index);
+        if share.index == 0 || share.value.len() != len || repeated {
+            return Err(Error::InvalidShares);
+        }
+    }
+
+    // Lagrange basis polynomials evaluated at 0: prod x_j / (x_j - x_i),
+    // subtraction being XOR in GF(2^8).
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|si| {
+            shares
+                .iter()
+                .filter(|sj| sj.index != si.index)
+                .fold(1, |acc, sj| {
+                    gf_mul(acc, gf_mul(sj.index, gf_inv(sj.index ^ si.index)))
+                })
+        })
+        .collect();
+
+    let mut key = Zeroizing::new(vec![0u8; len]);
+    for (share, &l) in shares.iter().zip(&basis) {
+        for (k, &y) in key.iter_mut().zip(&share.value) {
+            *k ^= gf_mul(y, l);
+        }
+    }
+    Rc5Key::new(&key).map_err(|_| Error::InvalidShares)
+}
+
+// Multiplication modulo x^8 + x^4 + x^3 + x + 1, as in AES.
+fn gf_mul(a: u8, b: u8) -> u8 {
+    let (mut a, mut b, mut p) = (a, b, 0u8);
+    for _ in 0..8 {
+        p ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    p
+}
+
+// a^254 = a^-1 for non-zero a.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::CounterRng;
+
+    #[test]
+    fn field_arithmetic() {
+        // FIPS 197 §4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn any_k_shares_recover_the_key() {
+        let key = Rc5Key::new(&(0..24).collect::<Vec<u8>>()).unwrap();
+        let shares = split_key(&mut CounterRng(3), &key, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for a in 0..5 {
+            for b in a + 1..5 {
+                for c in b + 1..5 {
+                    let subset = [shares[a].clone(), shares[b].clone(), shares[c].clone()];
+                    assert_eq!(recover_key(&subset).unwrap(), key);
+                }
+            }
+        }
+        assert_eq!(recover_key(&shares).unwrap(), key);
+        assert_ne!(recover_key(&shares[..2]).unwrap(), key);
+    }
+
+    #[test]
+    fn one_of_one_is_the_key() {
+        let key = Rc5Key::new(b"key").unwrap();
+        let shares = split_key(&mut CounterRng(0), &key, 3, 1).unwrap();
+        for share in &shares {
+            assert_eq!(share.value, b"key");
+        }
+    }
+
+    #[test]
+    fn debug_leaves_out_the_share() {
+        let key = Rc5Key::new(b"secret key bytes").unwrap();
+        let shares = split_key(&mut CounterRng(0), &key, 2, 2).unwrap();
+        assert_eq!(format!("{:?}", shares[1]), "Share(2, 16 bytes)");
+    }
+
+    #[test]
+    fn rejects_bad_parameters_and_shares() {
+        let mut rng = CounterRng(0);
+        let key = Rc5Key::new(b"key").unwrap();
+        for (n, k) in [(3, 0), (2, 3), (256, 2)] {
+            assert_eq!(
+                split_key(&mut rng, &key, n, k),
+                Err(Error::UnsupportedParameters)
+            );
+        }
+
+        let shares = split_key(&mut rng, &key, 3, 2).unwrap();
+        assert_eq!(recover_key(&[]), Err(Error::InvalidShares));
+        let twice = [shares[0].clone(), shares[0].clone()];
+        assert_eq!(recover_key(&twice), Err(Error::InvalidShares));
+        let mut short = shares[1].clone();
+        short.value.pop();
+        assert_eq!(
+            recover_key(&[shares[0].clone(), short]),
+            Err(Error::InvalidShares)
+        );
+        let empty = Share {
+            index: 1,
+            value: Vec::new(),
+        };
+        assert_eq!(recover_key(&[empty]), Err(Error::InvalidShares));
+    }
+}
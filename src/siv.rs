@@ -0,0 +1,512 @@
+//!
+//! SIV authenticated encryption, deterministic or with a nonce.
+//!
+//! Equal plaintexts with equal associated data encrypt to equal ciphertexts,
+//! so encrypted index keys in a key-value store still support equality
+//! lookups. That equality is all an observer learns, which is also why this
+//! is kept apart from the randomized modes: use it only where the lookup
+//! needs it, never for general data.
+//!
+//! The construction is SIV from RFC 5297 over RC5: S2V over [`Cmac`] turns
+//! the associated data and the plaintext into a synthetic IV, which is both
+//! the tag and the initial counter of [`ctr`](crate::ctr) encryption under a
+//! second key. Field doubling follows the [`lrw`](crate::lrw) conventions and
+//! the counter is not masked, so the output is not byte-compatible with
+//! AES-SIV implementations.
+//!
+//! [`seal_batch`](DeterministicAead::seal_batch) instead adds a per-record
+//! nonce, the base nonce plus the record's index, as the last associated
+//! data entry, as RFC 5297 allows. Equal records then encrypt differently, and
+//! a record only opens at the index it was sealed at. One schedule serves the
+//! whole batch, e.g. all cells of a database column.
+//!
+//! [`Siv`] is the same with a caller-supplied nonce, behind [`AeadAlgo`]: a
+//! nonce-misuse-resistant AEAD. Should a nonce ever repeat, an observer only
+//! learns whether two whole messages were equal, where CTR-based modes such
+//! as [`ccm`](crate::ccm) or [`ocb`](crate::ocb) leak the XOR of the
+//! plaintexts and [`ocb`](crate::ocb) loses authenticity too.
+//!
+
+use crate::algo::AeadAlgo;
+use crate::ctr::apply_keystream_at;
+use crate::error::Error;
+use crate::lrw::double;
+use crate::mac::Cmac;
+use crate::rc5::expand_key;
+use crate::word::Word;
+
+// Largest block, two u128 words.
+const MAX_BLOCK: usize = 32;
+
+///
+/// Deterministic encryption with a MAC key and an encryption key.
+///
+/// The output is the synthetic IV, one block, followed by the ciphertext.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::siv::DeterministicAead;
+///
+/// let siv = DeterministicAead::<u64>::new(b"index mac key 01", b"index enc key 01", 16).unwrap();
+///
+/// let a = siv.seal(&[b"users"], b"alice@example.com");
+/// let b = siv.seal(&[b"users"], b"alice@example.com");
+/// assert_eq!(a, b); // look the row up by its encrypted key
+///
+/// assert_eq!(siv.open(&[b"users"], &a).unwrap(), b"alice@example.com");
+/// assert!(siv.open(&[b"groups"], &a).is_err());
+/// ```
+///
+pub struct DeterministicAead<W: Word> {
+    mac: Cmac<W>,
+    enc_exp: Vec<W>,
+}
+
+impl<W: Word> DeterministicAead<W> {
+    ///
+    /// The two keys must be independent, e.g. derived with different labels.
+    /// Fails with [`Error::UnsupportedParameters`] where [`Cmac::new`] does.
+    ///
+    pub fn new(mac_key: &[u8], enc_key: &[u8], rounds: usize) -> Result<Self, Error> {
+        Ok(DeterministicAead {
+            mac: Cmac::new(mac_key, rounds)?,
+            enc_exp: expand_key(enc_key, rounds),
+        })
+    }
+
+    ///
+    /// Encrypts `plaintext`, authenticating it together with every entry of
+    /// `aad`, in order.
+    ///
+    pub fn seal(&self, aad: &[&[u8]], plaintext: &[u8]) -> Vec<u8> {
+        let bs = 2 * W::BYTES;
+        let mut out = vec![0u8; bs + plaintext.len()];
+        let (iv, ct) = out.split_at_mut(bs);
+        ct.copy_from_slice(plaintext);
+        iv.copy_from_slice(&self.s2v(aad, ct, |_, _| {})[..bs]);
+        // cannot fail, the IV is one block
+        let _ = apply_keystream_at(&self.enc_exp, iv, 0, ct);
+        out
+    }
+
+    ///
+    /// Decrypts the output of [`seal`](Self::seal) made with the same `aad`.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `sealed` is shorter than a
+    /// block and with [`Error::AuthenticationFailed`] if it or `aad` was
+    /// altered.
+    ///
+    pub fn open(&self, aad: &[&[u8]], sealed: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut buf = sealed.to_vec();
+        let len = self.open_in_place(aad, &mut buf)?.len();
+        buf.drain(..buf.len() - len);
+        Ok(buf)
+    }
+
+    ///
+    /// Like [`open`](Self::open), but decrypts `sealed` in place and returns
+    /// the plaintext part of it, using no memory that grows with the message.
+    ///
+    /// SIV can only check the tag against the plaintext, so on failure the
+    /// decrypted bytes are zeroed before returning the error. Each chunk is
+    /// decrypted right before the MAC absorbs it, so the data is read from
+    /// memory once rather than once per pass.
+    ///
+    pub fn open_in_place<'b>(
+        &self,
+        aad: &[&[u8]],
+        sealed: &'b mut [u8],
+    ) -> Result<&'b mut [u8], Error> {
+        let bs = 2 * W::BYTES;
+        if sealed.len() < bs {
+            return Err(Error::InvalidLength);
+        }
+        let (iv, pt) = sealed.split_at_mut(bs);
+        let expected = self.s2v(aad, pt, |offset, chunk| {
+            let _ = apply_keystream_at(&self.enc_exp, iv, offset as u64, chunk);
+        });
+        let diff = expected[..bs]
+            .iter()
+            .zip(iv.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            pt.fill(0);
+            return Err(Error::AuthenticationFailed);
+        }
+        Ok(pt)
+    }
+
+    ///
+    /// Seals every record of `records`, record `i` under the nonce
+    /// `base_nonce + i`, both read as little-endian integers of one block.
+    ///
+    /// Fails with [`Error::InvalidLength`] if `base_nonce` isn't one block
+    /// long. A base nonce must not be reused with the same keys; drawing it at
+    /// random is fine for blocks of 16 bytes or more.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// use rc5_cipher::siv::DeterministicAead;
+    ///
+    /// let siv = DeterministicAead::<u64>::new(b"column mac key 1", b"column enc key 1", 20).unwrap();
+    /// let base_nonce = [0x42; 16];
+    /// let cells: [&[u8]; 3] = [b"alice", b"bob", b"alice"];
+    ///
+    /// let sealed = siv.seal_batch(&base_nonce, &cells).unwrap();
+    /// assert_ne!(sealed[0], sealed[2]);
+    ///
+    /// assert_eq!(siv.open_at(&base_nonce, 1, &sealed[1]).unwrap(), b"bob");
+    /// let opened = siv.open_batch(&base_nonce, &sealed);
+    /// assert_eq!(opened[2].as_deref().unwrap(), b"alice");
+    /// ```
+    ///
+    pub fn seal_batch<R: AsRef<[u8]>>(
+        &self,
+        base_nonce: &[u8],
+        records: &[R],
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let bs = 2 * W::BYTES;
+        if base_nonce.len() != bs {
+            return Err(Error::InvalidLength);
+        }
+        let mut nonce = [0u8; MAX_BLOCK];
+        Ok(records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| {
+                add_le(base_nonce, i as u64, &mut nonce[..bs]);
+                self.seal(&[&nonce[..bs]], record.as_ref())
+            })
+            .collect())
+    }
+
+    ///
+    /// Opens record `index` of a [`seal_batch`](Self::seal_batch).
+    ///
+    /// Fails with [`Error::AuthenticationFailed`] if the record was altered or
+    /// sealed at another index, and with [`Error::InvalidLength`] if
+    /// `base_nonce` isn't one block long or `sealed` is shorter.
+    ///
+    pub fn open_at(&self, base_nonce: &[u8], index: u64, sealed: &[u8]) -> Result<Vec<u8>, Error> {
+        let bs = 2 * W::BYTES;
+        if base_nonce.len() != bs {
+            return Err(Error::InvalidLength);
+        }
+        let mut nonce = [0u8; MAX_BLOCK];
+        add_le(base_nonce, index, &mut nonce[..bs]);
+        self.open(&[&nonce[..bs]], sealed)
+    }
+
+    ///
+    /// Opens every record of a [`seal_batch`](Self::seal_batch), each on its
+    /// own, so one damaged record doesn't hide the others.
+    ///
+    pub fn open_batch<R: AsRef<[u8]>>(
+        &self,
+        base_nonce: &[u8],
+        sealed: &[R],
+    ) -> Vec<Result<Vec<u8>, Error>> {
+        sealed
+            .iter()
+            .enumerate()
+            .map(|(i, record)| self.open_at(base_nonce, i as u64, record.as_ref()))
+            .collect()
+    }
+
+    // S2V: CMAC over the associated data entries and the plaintext, each
+    // folded in with a doubling so their order and boundaries count. Chunks
+    // of `plaintext` go through `prepare` before they are read.
+    fn s2v(
+        &self,
+        aad: &[&[u8]],
+        plaintext: &mut [u8],
+        mut prepare: impl FnMut(usize, &mut [u8]),
+    ) -> [u8; MAX_BLOCK] {
+        let bs = 2 * W::BYTES;
+        let mut d = self.mac.tag_xored(&[0u8; MAX_BLOCK][..bs], &[]);
+        for entry in aad {
+            double(&mut d[..bs]);
+            xor(&mut d[..bs], &self.mac.tag_xored(entry, &[])[..bs]);
+        }
+
+        if plaintext.len() >= bs {
+            self.mac.tag_xored_in_place(plaintext, &d[..bs], prepare)
+        } else {
+            prepare(0, plaintext);
+            double(&mut d[..bs]);
+            let mut t = [0u8; MAX_BLOCK];
+            t[..plaintext.len()].copy_from_slice(plaintext);
+            t[plaintext.len()] = 0x80;
+            xor(&mut t[..bs], &d[..bs]);
+            self.mac.tag_xored(&t[..bs], &[])
+        }
+    }
+}
+
+///
+/// SIV with a nonce of `nonce_len` bytes as the last associated data entry,
+/// the synthetic IV appended to the ciphertext as its tag.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::algo::AeadAlgo;
+/// use rc5_cipher::siv::Siv;
+///
+/// let siv = Siv::<u64>::new(b"backup mac key 1", b"backup enc key 1", 20, 12).unwrap();
+/// let nonce = [7u8; 12];
+///
+/// // a repeated nonce only shows that the messages are equal
+/// let a = siv.seal_with_raw_nonce(&nonce, b"disk 1", b"snapshot A").unwrap();
+/// let b = siv.seal_with_raw_nonce(&nonce, b"disk 1", b"snapshot B").unwrap();
+/// assert_ne!(a[..10], b[..10]);
+///
+/// assert_eq!(siv.open(&nonce, b"disk 1", &a).unwrap(), b"snapshot A");
+/// assert!(siv.open(&[8u8; 12], b"disk 1", &a).is_err());
+/// ```
+///
+pub struct Siv<W: Word> {
+    inner: DeterministicAead<W>,
+    nonce_len: usize,
+}
+
+impl<W: Word> Siv<W> {
+    ///
+    /// The two keys must be independent, as for [`DeterministicAead::new`],
+    /// which also gives the errors.
+    ///
+    pub fn new(
+        mac_key: &[u8],
+        enc_key: &[u8],
+        rounds: usize,
+        nonce_len: usize,
+    ) -> Result<Self, Error> {
+        Ok(Siv {
+            inner: DeterministicAead::new(mac_key, enc_key, rounds)?,
+            nonce_len,
+        })
+    }
+}
+
+///
+/// Fails with [`Error::InvalidLength`] if `nonce` has the wrong length, and
+/// with [`Error::AuthenticationFailed`] if the ciphertext, tag, nonce or
+/// `aad` was altered.
+///
+impl<W: Word> AeadAlgo for Siv<W> {
+    fn nonce_len(&self) -> usize {
+        self.nonce_len
+    }
+
+    fn tag_len(&self) -> usize {
+        2 * W::BYTES
+    }
+
+    fn seal_with_raw_nonce(
+        &self,
+        nonce: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if nonce.len() != self.nonce_len {
+            return Err(Error::InvalidLength);
+        }
+        let mut out = self.inner.seal(&[aad, nonce], plaintext);
+        out.rotate_left(2 * W::BYTES);
+        Ok(out)
+    }
+
+    fn open(&self, nonce: &[u8], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        if nonce.len() != self.nonce_len {
+            return Err(Error::InvalidLength);
+        }
+        if ciphertext.len() < 2 * W::BYTES {
+            return Err(Error::AuthenticationFailed);
+        }
+        let mut buf = ciphertext.to_vec();
+        buf.rotate_right(2 * W::BYTES);
+        let len = self.inner.open_in_place(&[aad, nonce], &mut buf)?.len();
+        buf.drain(..buf.len() - len);
+        Ok(buf)
+    }
+}
+
+fn xor(block: &mut [u8], with: &[u8]) {
+    for (b, w) in block.iter_mut().zip(with) {
+        *b ^= w;
+    }
+}
+
+// Writes `base + index` into `out`, both read as little-endian integers.
+fn add_le(base: &[u8], index: u64, out: &mut [u8]) {
+    let mut carry = u128::from(index);
+    for (o, &b) in out.iter_mut().zip(base) {
+        let sum = b as u128 + (carry & 0xff);
+        *o = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_roundtrip() {
+        let siv = DeterministicAead::<u64>::new(b"mac key", b"enc key", 16).unwrap();
+        for len in [0, 1, 15, 16, 17, 100] {
+            let pt: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let sealed = siv.seal(&[b"table", b"column"], &pt);
+            assert_eq!(sealed.len(), 16 + len);
+            assert_eq!(sealed, siv.seal(&[b"table", b"column"], &pt));
+            assert_eq!(siv.open(&[b"table", b"column"], &sealed).unwrap(), pt);
+        }
+    }
+
+    #[test]
+    fn open_in_place_matches_open() {
+        let siv = DeterministicAead::<u32>::new(b"mac key", b"enc key", 12).unwrap();
+        for len in [0, 5, 8, 200] {
+            let pt: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut sealed = siv.seal(&[b"config"], &pt);
+            assert_eq!(siv.open_in_place(&[b"config"], &mut sealed).unwrap(), pt);
+
+            let mut sealed = siv.seal(&[b"config"], &pt);
+            sealed[0] ^= 1;
+            assert_eq!(
+                siv.open_in_place(&[b"config"], &mut sealed),
+                Err(Error::AuthenticationFailed)
+            );
+            assert!(sealed[8..].iter().all(|&b| b == 0));
+        }
+        assert_eq!(
+            siv.open_in_place(&[], &mut [0; 7]),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn batch_binds_records_to_their_index() {
+        let siv = DeterministicAead::<u32>::new(b"mac key", b"enc key", 12).unwrap();
+        let base = [0xff, 0xff, 0, 0, 0, 0, 0, 1];
+        let records = [&b"x"[..], b"x", b"", b"a longer record than one block"];
+        let sealed = siv.seal_batch(&base, &records).unwrap();
+
+        assert_ne!(sealed[0], sealed[1]);
+        // the nonce of record 2 carries into the third byte
+        let nonce = [0x01, 0, 1, 0, 0, 0, 0, 1];
+        assert_eq!(sealed[2], siv.seal(&[&nonce], b""));
+
+        let opened = siv.open_batch(&base, &sealed);
+        for (record, opened) in records.iter().zip(opened) {
+            assert_eq!(opened.unwrap(), *record);
+        }
+
+        let swapped = [&sealed[1], &sealed[0], &sealed[3][..3]];
+        assert_eq!(
+            siv.open_batch(&base, &swapped),
+            [
+                Err(Error::AuthenticationFailed),
+                Err(Error::AuthenticationFailed),
+                Err(Error::InvalidLength),
+            ]
+        );
+        assert_eq!(
+            siv.seal_batch(&[0; 16], &records),
+            Err(Error::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let siv = DeterministicAead::<u32>::new(b"mac key", b"enc key", 12).unwrap();
+        let sealed = siv.seal(&[b"a", b"b"], b"secret index key");
+
+        for i in [0, 7, 8, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 1;
+            assert_eq!(
+                siv.open(&[b"a", b"b"], &bad),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        // the aad entries are bound in order and by boundary
+        for aad in [&[&b"b"[..], b"a"][..], &[b"ab"], &[b"a"], &[]] {
+            assert_eq!(siv.open(aad, &sealed), Err(Error::AuthenticationFailed));
+        }
+        assert_eq!(siv.open(&[], &sealed[..7]), Err(Error::InvalidLength));
+
+        let other = DeterministicAead::<u32>::new(b"mac key", b"other", 12).unwrap();
+        assert_ne!(other.seal(&[b"a", b"b"], b"secret index key"), sealed);
+    }
+
+    #[test]
+    fn nonce_reuse_only_reveals_equal_messages() {
+        let siv = Siv::<u32>::new(b"mac key", b"enc key", 12, 8).unwrap();
+        let nonce = [3u8; 8];
+        let a = siv
+            .seal_with_raw_nonce(&nonce, b"aad", b"same prefix, then A")
+            .unwrap();
+        let b = siv
+            .seal_with_raw_nonce(&nonce, b"aad", b"same prefix, then B")
+            .unwrap();
+        assert_eq!(a.len(), 19 + 8);
+        // a CTR mode would share every byte before the difference
+        assert_ne!(a[..4], b[..4]);
+        assert_eq!(
+            siv.seal_with_raw_nonce(&nonce, b"aad", b"same prefix, then A")
+                .unwrap(),
+            a
+        );
+        assert_ne!(
+            siv.seal_with_raw_nonce(&[4u8; 8], b"aad", b"same prefix, then A")
+                .unwrap(),
+            a
+        );
+
+        // the nonce is the last associated data entry, the IV is the tag
+        let det = DeterministicAead::<u32>::new(b"mac key", b"enc key", 12).unwrap();
+        let sealed = det.seal(&[b"aad", &nonce], b"same prefix, then A");
+        assert_eq!(a[19..], sealed[..8]);
+        assert_eq!(a[..19], sealed[8..]);
+    }
+
+    #[test]
+    fn siv_roundtrip_and_tampering() {
+        fn check<W: Word>() {
+            let siv = Siv::<W>::new(b"siv mac key", b"siv enc key", 16, 12).unwrap();
+            let bs = 2 * W::BYTES;
+            let nonce = [0x5au8; 12];
+            for len in [0, 1, bs, 3 * bs + 1] {
+                let pt: Vec<u8> = (0..len).map(|i| (i * 13) as u8).collect();
+                let sealed = siv.seal_with_raw_nonce(&nonce, b"aad", &pt).unwrap();
+                assert_eq!(sealed.len(), len + siv.tag_len());
+                assert_eq!(siv.open(&nonce, b"aad", &sealed).unwrap(), pt);
+                for i in [0, sealed.len() - 1] {
+                    let mut bad = sealed.clone();
+                    bad[i] ^= 1;
+                    assert_eq!(
+                        siv.open(&nonce, b"aad", &bad),
+                        Err(Error::AuthenticationFailed)
+                    );
+                }
+                assert!(siv.open(&nonce, b"aae", &sealed).is_err());
+                assert!(siv.open(&[0x5b; 12], b"aad", &sealed).is_err());
+            }
+            assert_eq!(
+                siv.open(&nonce, b"", &vec![0; bs - 1]),
+                Err(Error::AuthenticationFailed)
+            );
+            assert_eq!(
+                siv.seal_with_raw_nonce(&[0; 11], b"", b""),
+                Err(Error::InvalidLength)
+            );
+        }
+        check::<u8>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+}
@@ -0,0 +1,267 @@
+//!
+//! One-byte algorithm IDs for parameter bundles.
+//!
+//! A [`Suite`] is the word size, rounds, mode and MAC a format needs to
+//! decrypt. Instead of spelling these out, a header can carry a single ID
+//! looked up here, which saves bytes on small messages and keeps deployments
+//! to a vetted set of combinations.
+//!
+//! IDs below [`PRIVATE_IDS`] are assigned by this crate and never change
+//! meaning: authenticated suites from 0x01, the lowest IDs going to the
+//! recommended ones, and unauthenticated suites, for formats that are
+//! authenticated some other way, from 0x40. IDs in [`PRIVATE_IDS`] are free for
+//! deployments to [`register`] at startup; every process reading the data must
+//! register the same ones.
+//!
+//! An ID says nothing about the strength of its suite, and a registered one
+//! can be anything. [`lookup_checked`] also checks the suite against a
+//! [`ParameterFloor`], as a reader should before decrypting.
+//!
+
+use std::ops::RangeInclusive;
+use std::sync::RwLock;
+
+use crate::error::Error;
+use crate::header::{Header, MacAlg, Mode};
+use crate::params::{ParameterFloor, Rc5Params};
+
+///
+/// IDs left to deployments.
+///
+pub const PRIVATE_IDS: RangeInclusive<u8> = 0x80..=0xff;
+
+///
+/// The parameters an algorithm ID stands for.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suite {
+    pub word_bytes: u8,
+    pub rounds: u8,
+    pub mode: Mode,
+    pub mac: MacAlg,
+}
+
+impl Suite {
+    const fn new(word_bytes: u8, rounds: u8, mode: Mode, mac: MacAlg) -> Self {
+        Suite {
+            word_bytes,
+            rounds,
+            mode,
+            mac,
+        }
+    }
+
+    ///
+    /// The suite of a header's parameters, ignoring its extensions.
+    ///
+    pub fn of(header: &Header) -> Self {
+        Suite::new(header.word_bytes, header.rounds, header.mode, header.mac)
+    }
+
+    ///
+    /// The RC5 parameters of the suite with a key of `key_bytes` bytes.
+    ///
+    pub fn params(&self, key_bytes: usize) -> Rc5Params {
+        Rc5Params {
+            word_bytes: usize::from(self.word_bytes),
+            rounds: usize::from(self.rounds),
+            key_bytes,
+        }
+    }
+
+    ///
+    /// A header with these parameters and no extensions.
+    ///
+    pub fn header(&self) -> Header {
+        Header {
+            version: Header::VERSION,
+            word_bytes: self.word_bytes,
+            rounds: self.rounds,
+            mode: self.mode,
+            mac: self.mac,
+            extensions: Vec::new(),
+        }
+    }
+}
+
+const BUILTIN: &[(u8, Suite)] = &[
+    (0x01, Suite::new(8, 24, Mode::Ctr, MacAlg::Cmac)),
+    (0x02, Suite::new(16, 28, Mode::Ctr, MacAlg::Cmac)),
+    (0x03, Suite::new(4, 20, Mode::Ctr, MacAlg::Cmac)),
+    (0x04, Suite::new(8, 24, Mode::Ctr, MacAlg::HmacSha256)),
+    (0x05, Suite::new(4, 20, Mode::Cbc, MacAlg::Cmac)),
+    (0x40, Suite::new(8, 24, Mode::Ctr, MacAlg::None)),
+    (0x41, Suite::new(16, 28, Mode::Ctr, MacAlg::None)),
+    (0x42, Suite::new(8, 24, Mode::Cbc, MacAlg::None)),
+    (0x43, Suite::new(4, 20, Mode::Ctr, MacAlg::None)),
+    (0x44, Suite::new(4, 20, Mode::Cbc, MacAlg::None)),
+];
+
+static PRIVATE: RwLock<[Option<Suite>; 128]> = RwLock::new([None; 128]);
+
+///
+/// The suite an ID stands for, built-in or registered.
+///
+/// The parameters aren't checked; before decrypting with them, use
+/// [`lookup_checked`] instead.
+///
+pub fn lookup(id: u8) -> Option<Suite> {
+    if PRIVATE_IDS.contains(&id) {
+        return PRIVATE.read().unwrap()[usize::from(id - 0x80)];
+    }
+    BUILTIN.iter().find(|(i, _)| *i == id).map(|(_, s)| *s)
+}
+
+///
+/// Like [`lookup`], but fails with [`Error::UnsupportedParameters`] for an
+/// unknown ID, and with [`Error::WeakParameters`] if the suite with a key of
+/// `key_bytes` bytes is below `floor`.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::header::{MacAlg, Mode};
+/// use rc5_cipher::params::ParameterFloor;
+/// use rc5_cipher::suite::{self, Suite};
+/// use rc5_cipher::Error;
+///
+/// let floor = ParameterFloor::default();
+/// let suite = suite::lookup_checked(0x01, 16, &floor).unwrap();
+/// assert_eq!((suite.word_bytes, suite.mac), (8, MacAlg::Cmac));
+///
+/// let weak = Suite { word_bytes: 2, rounds: 8, mode: Mode::Ctr, mac: MacAlg::None };
+/// suite::register(0xa8, weak).unwrap();
+/// assert_eq!(suite::lookup_checked(0xa8, 16, &floor), Err(Error::WeakParameters));
+/// ```
+///
+pub fn lookup_checked(id: u8, key_bytes: usize, floor: &ParameterFloor) -> Result<Suite, Error> {
+    let suite = lookup(id).ok_or(Error::UnsupportedParameters)?;
+    floor.check(&suite.params(key_bytes))?;
+    Ok(suite)
+}
+
+///
+/// The ID of `suite`, preferring built-in IDs over registered ones.
+///
+pub fn id_of(suite: &Suite) -> Option<u8> {
+    if let Some((id, _)) = BUILTIN.iter().find(|(_, s)| s == suite) {
+        return Some(*id);
+    }
+    let private = PRIVATE.read().unwrap();
+    (0..128)
+        .find(|&i| private[i].as_ref() == Some(suite))
+        .map(|i| 0x80 + i as u8)
+}
+
+///
+/// Assigns a private ID to `suite`.
+///
+/// Registering the same suite again under the same ID is a no-op. Fails with
+/// [`Error::UnsupportedParameters`] if `id` is outside [`PRIVATE_IDS`] or
+/// already stands for a different suite, or if the word size is not one of
+/// 1, 2, 4, 8 or 16.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::header::{MacAlg, Mode};
+/// use rc5_cipher::suite::{self, Suite};
+///
+/// let legacy = Suite { word_bytes: 2, rounds: 16, mode: Mode::Cfb, mac: MacAlg::None };
+/// suite::register(0xa0, legacy).unwrap();
+///
+/// assert_eq!(suite::lookup(0xa0), Some(legacy));
+/// assert_eq!(suite::id_of(&legacy), Some(0xa0));
+/// ```
+///
+pub fn register(id: u8, suite: Suite) -> Result<(), Error> {
+    if !PRIVATE_IDS.contains(&id) || !matches!(suite.word_bytes, 1 | 2 | 4 | 8 | 16) {
+        return Err(Error::UnsupportedParameters);
+    }
+    let mut private = PRIVATE.write().unwrap();
+    let slot = &mut private[usize::from(id - 0x80)];
+    match slot {
+        Some(existing) if *existing != suite => Err(Error::UnsupportedParameters),
+        _ => {
+            *slot = Some(suite);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_ids_are_unique_and_valid() {
+        for (i, (id, suite)) in BUILTIN.iter().enumerate() {
+            assert!(*id != 0 && !PRIVATE_IDS.contains(id));
+            assert!(BUILTIN[i + 1..]
+                .iter()
+                .all(|(other, s)| other != id && s != suite));
+            assert_eq!(lookup(*id), Some(*suite));
+            assert_eq!(id_of(suite), Some(*id));
+            let bytes = suite.header().to_bytes();
+            assert_eq!(Suite::of(&Header::parse(&bytes).unwrap().0), *suite);
+        }
+        assert_eq!(lookup(0x00), None);
+        assert_eq!(lookup(0x7f), None);
+    }
+
+    #[test]
+    fn authenticated_suites_come_first() {
+        // First ID of the unauthenticated suites.
+        const UNAUTHENTICATED: u8 = 0x40;
+        for (id, suite) in BUILTIN {
+            assert_eq!(
+                *id < UNAUTHENTICATED,
+                suite.mac != MacAlg::None,
+                "{:#x}",
+                id
+            );
+            assert_eq!(
+                lookup_checked(*id, 16, &ParameterFloor::default()),
+                Ok(*suite)
+            );
+        }
+    }
+
+    #[test]
+    fn lookup_checked_applies_the_floor() {
+        let floor = ParameterFloor::default();
+        assert_eq!(
+            lookup_checked(0x7f, 16, &floor),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(lookup_checked(0x01, 8, &floor), Err(Error::WeakParameters));
+
+        let legacy = Suite::new(4, 8, Mode::Cbc, MacAlg::None);
+        register(0xf1, legacy).unwrap();
+        assert_eq!(lookup(0xf1), Some(legacy));
+        assert_eq!(lookup_checked(0xf1, 16, &floor), Err(Error::WeakParameters));
+        assert_eq!(
+            lookup_checked(0xf1, 16, &ParameterFloor::default().allow_weak_parameters()),
+            Ok(legacy)
+        );
+    }
+
+    #[test]
+    fn registration() {
+        let suite = Suite::new(1, 12, Mode::Ecb, MacAlg::CbcMac);
+        let other = Suite::new(1, 16, Mode::Ecb, MacAlg::CbcMac);
+
+        assert_eq!(register(0x42, suite), Err(Error::UnsupportedParameters));
+        assert_eq!(
+            register(0xf0, Suite::new(3, 12, Mode::Ecb, MacAlg::None)),
+            Err(Error::UnsupportedParameters)
+        );
+
+        register(0xf0, suite).unwrap();
+        register(0xf0, suite).unwrap();
+        assert_eq!(register(0xf0, other), Err(Error::UnsupportedParameters));
+        assert_eq!(lookup(0xf0), Some(suite));
+        assert_eq!(id_of(&suite), Some(0xf0));
+        assert_eq!(id_of(&other), None);
+    }
+}
@@ -0,0 +1,198 @@
+//!
+//! Key material with a bounded lifetime in memory.
+//!
+//! A [`TimedKey`] holds a key and its expanded schedule for at most a fixed
+//! duration, for policies such as "keys stay in memory no longer than ten
+//! minutes". Once the duration has passed, the next access wipes both and
+//! fails with [`Error::KeyExpired`], so the caller has to derive the key again,
+//! e.g. from a KMS or a passphrase, and [`reload`](TimedKey::reload) it.
+//!
+//! There is no background timer: a key nobody touches stays in memory until
+//! the next access, [`TimedKey::sweep`] or drop. Services holding keys across
+//! idle periods should call `sweep` from a periodic task.
+//!
+//! Time comes from a [`Clock`], by default [`MonotonicClock`]; see
+//! [`crate::clock`] for testing and for targets without `std::time::Instant`.
+//!
+//! Only available with the `timed` feature.
+//!
+
+use std::time::Duration;
+
+use zeroize::{Zeroize, Zeroizing};
+
+pub use crate::clock::{Clock, ManualClock, MonotonicClock};
+use crate::error::Error;
+use crate::rc5::expand_key;
+use crate::word::Word;
+
+///
+/// A key and its RC5 schedule that wipe themselves after `ttl`.
+///
+/// Example:
+///
+/// ```rust
+/// use std::time::Duration;
+/// use rc5_cipher::timed::TimedKey;
+/// use rc5_cipher::{encrypt_kernel, Error};
+///
+/// let mut key = TimedKey::<u32>::new(&[7; 16], 20, Duration::from_secs(600));
+/// let ct = encrypt_kernel([1, 2], key.schedule().unwrap());
+///
+/// key.expire();
+/// assert_eq!(key.schedule().err(), Some(Error::KeyExpired));
+///
+/// key.reload(&[7; 16]);
+/// assert_eq!(encrypt_kernel([1, 2], key.schedule().unwrap()), ct);
+/// ```
+///
+pub struct TimedKey<W: Word + Zeroize, C: Clock = MonotonicClock> {
+    material: Option<Material<W>>,
+    rounds: usize,
+    ttl: Duration,
+    clock: C,
+    loaded: Duration,
+}
+
+struct Material<W: Zeroize> {
+    key: Zeroizing<Vec<u8>>,
+    schedule: Zeroizing<Vec<W>>,
+}
+
+impl<W: Word + Zeroize> TimedKey<W> {
+    pub fn new(key: &[u8], rounds: usize, ttl: Duration) -> Self {
+        Self::with_clock(key, rounds, ttl, MonotonicClock::new())
+    }
+}
+
+impl<W: Word + Zeroize, C: Clock> TimedKey<W, C> {
+    ///
+    /// Like [`new`](TimedKey::new), measuring the lifetime with `clock`.
+    ///
+    pub fn with_clock(key: &[u8], rounds: usize, ttl: Duration, clock: C) -> Self {
+        let loaded = clock.now();
+        let mut timed = TimedKey {
+            material: None,
+            rounds,
+            ttl,
+            clock,
+            loaded,
+        };
+        timed.reload(key);
+        timed
+    }
+
+    ///
+    /// Replaces the material with a freshly derived `key` and restarts the
+    /// lifetime.
+    ///
+    pub fn reload(&mut self, key: &[u8]) {
+        self.material = Some(Material {
+            key: Zeroizing::new(key.to_vec()),
+            schedule: Zeroizing::new(expand_key(key, self.rounds)),
+        });
+        self.loaded = self.clock.now();
+    }
+
+    ///
+    /// The expanded key, or [`Error::KeyExpired`] once the lifetime is over.
+    ///
+    pub fn schedule(&mut self) -> Result<&[W], Error> {
+        Ok(&self.material()?.schedule)
+    }
+
+    ///
+    /// The raw key, e.g. to derive subkeys, or [`Error::KeyExpired`].
+    ///
+    pub fn key(&mut self) -> Result<&[u8], Error> {
+        Ok(&self.material()?.key)
+    }
+
+    ///
+    /// Time left before the material is wiped, `None` if it already was or
+    /// is due.
+    ///
+    pub fn remaining(&self) -> Option<Duration> {
+        self.material.as_ref()?;
+        let elapsed = self.clock.now().saturating_sub(self.loaded);
+        self.ttl.checked_sub(elapsed).filter(|left| !left.is_zero())
+    }
+
+    ///
+    /// Wipes the material if its lifetime is over.
+    ///
+    pub fn sweep(&mut self) {
+        if self.remaining().is_none() {
+            self.expire();
+        }
+    }
+
+    ///
+    /// Wipes the material now.
+    ///
+    pub fn expire(&mut self) {
+        // dropping the Zeroizing buffers wipes them
+        self.material = None;
+    }
+
+    fn material(&mut self) -> Result<&Material<W>, Error> {
+        self.sweep();
+        self.material.as_ref().ok_or(Error::KeyExpired)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    #[test]
+    fn material_expires_after_ttl() {
+        let mut key = TimedKey::<u16>::new(b"0123456789abcdef", 12, Duration::from_millis(20));
+        assert_eq!(key.key().unwrap(), b"0123456789abcdef");
+        assert_eq!(
+            key.schedule().unwrap(),
+            expand_key::<u16>(b"0123456789abcdef", 12)
+        );
+        assert!(key.remaining().unwrap() <= Duration::from_millis(20));
+
+        sleep(Duration::from_millis(30));
+        assert_eq!(key.remaining(), None);
+        assert_eq!(key.schedule().err(), Some(Error::KeyExpired));
+        assert_eq!(key.key().err(), Some(Error::KeyExpired));
+        assert!(key.material.is_none());
+    }
+
+    #[test]
+    fn reload_restarts_the_lifetime() {
+        let mut key = TimedKey::<u64>::new(b"old", 16, Duration::from_millis(20));
+        sleep(Duration::from_millis(30));
+        key.sweep();
+        assert!(key.material.is_none());
+
+        key.reload(b"new");
+        assert_eq!(key.key().unwrap(), b"new");
+        assert_eq!(key.schedule().unwrap(), expand_key::<u64>(b"new", 16));
+    }
+
+    #[test]
+    fn manual_clock_drives_expiry() {
+        let clock = ManualClock::default();
+        let ttl = Duration::from_secs(60);
+        let mut key = TimedKey::<u32, _>::with_clock(b"key", 12, ttl, clock.clone());
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(key.remaining(), Some(Duration::from_secs(1)));
+        assert_eq!(key.key().unwrap(), b"key");
+
+        key.reload(b"key 2");
+        clock.advance(Duration::from_secs(59));
+        assert!(key.schedule().is_ok());
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(key.remaining(), None);
+        assert_eq!(key.key().err(), Some(Error::KeyExpired));
+
+        clock.advance(Duration::MAX);
+        assert_eq!(clock.now(), Duration::from_nanos(u64::MAX));
+    }
+}
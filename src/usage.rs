@@ -0,0 +1,329 @@
+//!
+//! Per-key usage accounting against the birthday bound.
+//!
+//! With an `n`-bit block, ciphertext blocks start to collide after about
+//! `2^(n/2)` blocks under one key, and in CBC or CFB every collision leaks the
+//! XOR of two plaintext blocks (Sweet32). RC5 with 16- or 32-bit words has 32-
+//! or 64-bit blocks, so the bound is reached after 256 KiB or 32 GiB, and
+//! nothing fails visibly when it is. A [`UsageCounter`] tracks the blocks
+//! processed under one key and reports when the key should be replaced.
+//!
+//! Independently of the block size, a [`UsagePolicy`] expresses operational
+//! limits on a key (volume, message count, age) and a [`KeyUsage`] enforces
+//! them, measuring the age with a [`Clock`]. Given one, [`MeteredRc5Ctr`] and
+//! [`EnvelopeEncryptor`] refuse to encrypt past the limits.
+//!
+//! [`MeteredRc5Ctr`]: crate::ctr::MeteredRc5Ctr
+//! [`EnvelopeEncryptor`]: crate::envelope::EnvelopeEncryptor
+//!
+
+use std::time::Duration;
+
+use crate::audit::{self, Event};
+use crate::clock::{Clock, MonotonicClock};
+use crate::error::Error;
+
+///
+/// How close a key is to its birthday bound.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageStatus {
+    /// Well below the bound.
+    Ok,
+    /// At least 1/16 of the bound has been processed; rekey soon.
+    RekeySoon,
+    /// At least 1/4 of the bound has been processed; collisions are likely
+    /// enough that the key must not be used further.
+    Exhausted,
+}
+
+///
+/// Counts the blocks processed under one key.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::usage::{UsageCounter, UsageStatus};
+/// use rc5_cipher::Error;
+///
+/// // RC5-16: 32-bit blocks, birthday bound of 2^16 blocks
+/// let mut usage = UsageCounter::new(4).with_hard_limit();
+/// assert_eq!(usage.record(4095), Ok(UsageStatus::Ok));
+/// assert_eq!(usage.record(1), Ok(UsageStatus::RekeySoon));
+/// assert_eq!(usage.record(3 * 4096), Err(Error::KeyExhausted));
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageCounter {
+    block_bytes: usize,
+    blocks: u64,
+    hard_limit: bool,
+}
+
+impl UsageCounter {
+    pub fn new(block_bytes: usize) -> Self {
+        UsageCounter {
+            block_bytes,
+            blocks: 0,
+            hard_limit: false,
+        }
+    }
+
+    ///
+    /// Makes [`UsageCounter::record`] fail once the key is exhausted, instead
+    /// of only reporting it.
+    ///
+    pub fn with_hard_limit(mut self) -> Self {
+        self.hard_limit = true;
+        self
+    }
+
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.blocks.saturating_mul(self.block_bytes as u64)
+    }
+
+    ///
+    /// Number of blocks, `2^(n/2)` for `n`-bit blocks, at which collisions are
+    /// expected. `None` when it doesn't fit in a `u64`.
+    ///
+    pub fn birthday_bound(&self) -> Option<u64> {
+        1u64.checked_shl((4 * self.block_bytes) as u32)
+    }
+
+    pub fn status(&self) -> UsageStatus {
+        match self.birthday_bound() {
+            Some(bound) if self.blocks >= bound / 4 => UsageStatus::Exhausted,
+            Some(bound) if self.blocks >= bound / 16 => UsageStatus::RekeySoon,
+            _ => UsageStatus::Ok,
+        }
+    }
+
+    ///
+    /// Accounts for `blocks` more blocks and returns the resulting status.
+    /// With a hard limit, fails with [`Error::KeyExhausted`] instead of
+    /// returning [`UsageStatus::Exhausted`]; the blocks are counted either way.
+    ///
+    pub fn record(&mut self, blocks: u64) -> Result<UsageStatus, Error> {
+        self.blocks = self.blocks.saturating_add(blocks);
+        match self.status() {
+            UsageStatus::Exhausted if self.hard_limit => Err(Error::KeyExhausted),
+            status => Ok(status),
+        }
+    }
+}
+
+///
+/// Limits after which a key must be replaced. `None` means unlimited.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsagePolicy {
+    pub max_bytes: Option<u64>,
+    pub max_messages: Option<u64>,
+    pub max_age: Option<Duration>,
+}
+
+///
+/// Usage of one key, checked against a [`UsagePolicy`].
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::usage::{KeyUsage, UsagePolicy};
+/// use rc5_cipher::Error;
+///
+/// let policy = UsagePolicy {
+///     max_messages: Some(2),
+///     ..UsagePolicy::default()
+/// };
+/// let mut usage = KeyUsage::new(policy);
+///
+/// assert_eq!(usage.record_message(100), Ok(()));
+/// assert_eq!(usage.record_message(100), Ok(()));
+/// assert_eq!(usage.record_message(100), Err(Error::KeyExhausted));
+///
+/// // after rekeying, the counters start over
+/// usage.rekey();
+/// assert_eq!(usage.record_message(100), Ok(()));
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct KeyUsage<C: Clock = MonotonicClock> {
+    policy: UsagePolicy,
+    bytes: u64,
+    messages: u64,
+    clock: C,
+    created: Duration,
+}
+
+impl KeyUsage {
+    pub fn new(policy: UsagePolicy) -> Self {
+        Self::with_clock(policy, MonotonicClock::new())
+    }
+}
+
+impl<C: Clock> KeyUsage<C> {
+    ///
+    /// Like [`new`](KeyUsage::new), measuring the key's age with `clock`.
+    ///
+    pub fn with_clock(policy: UsagePolicy, clock: C) -> Self {
+        let created = clock.now();
+        KeyUsage {
+            policy,
+            bytes: 0,
+            messages: 0,
+            clock,
+            created,
+        }
+    }
+
+    pub fn policy(&self) -> &UsagePolicy {
+        &self.policy
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    ///
+    /// Returns [`Error::KeyExhausted`] if the key is too old or has already
+    /// processed as much as the policy allows.
+    ///
+    pub fn check(&self) -> Result<(), Error> {
+        self.check_totals(self.bytes, self.messages)
+    }
+
+    ///
+    /// Accounts for one message of `bytes` bytes that is about to be
+    /// processed, failing without counting it if that would exceed the policy.
+    ///
+    pub fn record_message(&mut self, bytes: u64) -> Result<(), Error> {
+        let bytes = self.bytes.saturating_add(bytes);
+        let messages = self.messages.saturating_add(1);
+        self.check_totals(bytes, messages)?;
+        self.bytes = bytes;
+        self.messages = messages;
+        Ok(())
+    }
+
+    ///
+    /// Accounts for `bytes` more bytes of the message in progress, e.g. the
+    /// next chunk of a stream, failing without counting them if that would
+    /// exceed the policy.
+    ///
+    pub fn record_bytes(&mut self, bytes: u64) -> Result<(), Error> {
+        let bytes = self.bytes.saturating_add(bytes);
+        self.check_totals(bytes, self.messages)?;
+        self.bytes = bytes;
+        Ok(())
+    }
+
+    ///
+    /// Resets the counters and the age after the key has been replaced.
+    ///
+    pub fn rekey(&mut self) {
+        audit::emit(Event::Rekeyed, None, None, 0);
+        self.bytes = 0;
+        self.messages = 0;
+        self.created = self.clock.now();
+    }
+
+    fn check_totals(&self, bytes: u64, messages: u64) -> Result<(), Error> {
+        let exceeded = |used: u64, max: Option<u64>| max.is_some_and(|max| used > max);
+        let age = self.clock.now().saturating_sub(self.created);
+        if exceeded(bytes, self.policy.max_bytes)
+            || exceeded(messages, self.policy.max_messages)
+            || self.policy.max_age.is_some_and(|max| age >= max)
+        {
+            return Err(Error::KeyExhausted);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn thresholds_follow_block_size() {
+        // RC5-32: 64-bit blocks, 2^32 blocks
+        let mut usage = UsageCounter::new(8);
+        assert_eq!(usage.birthday_bound(), Some(1 << 32));
+        assert_eq!(usage.record((1 << 28) - 1), Ok(UsageStatus::Ok));
+        assert_eq!(usage.record(1), Ok(UsageStatus::RekeySoon));
+        assert_eq!(usage.bytes(), 1 << 31);
+        // without a hard limit exhaustion is only reported
+        assert_eq!(usage.record(3 << 28), Ok(UsageStatus::Exhausted));
+        assert_eq!(usage.blocks(), 1 << 30);
+
+        // RC5-128: 256-bit blocks, the bound doesn't fit in a u64
+        let mut usage = UsageCounter::new(32).with_hard_limit();
+        assert_eq!(usage.birthday_bound(), None);
+        assert_eq!(usage.record(u64::MAX), Ok(UsageStatus::Ok));
+        assert_eq!(usage.record(1), Ok(UsageStatus::Ok));
+    }
+
+    #[test]
+    fn hard_limit_errors() {
+        // RC5-8: 16-bit blocks, 256 blocks
+        let mut usage = UsageCounter::new(2).with_hard_limit();
+        assert_eq!(usage.record(63), Ok(UsageStatus::RekeySoon));
+        assert_eq!(usage.record(1), Err(Error::KeyExhausted));
+        assert_eq!(usage.record(1), Err(Error::KeyExhausted));
+        assert_eq!(usage.blocks(), 65);
+    }
+
+    #[test]
+    fn policy_limits_bytes_and_age() {
+        let policy = UsagePolicy {
+            max_bytes: Some(1000),
+            ..UsagePolicy::default()
+        };
+        let mut usage = KeyUsage::new(policy);
+        assert_eq!(usage.record_message(600), Ok(()));
+        assert_eq!(usage.record_message(401), Err(Error::KeyExhausted));
+        // the rejected message isn't counted
+        assert_eq!(usage.bytes(), 600);
+        assert_eq!(usage.record_message(400), Ok(()));
+        assert_eq!(usage.messages(), 2);
+        assert_eq!(usage.record_bytes(1), Err(Error::KeyExhausted));
+        assert_eq!(usage.bytes(), 1000);
+
+        let expired = KeyUsage::new(UsagePolicy {
+            max_age: Some(Duration::ZERO),
+            ..UsagePolicy::default()
+        });
+        assert_eq!(expired.check(), Err(Error::KeyExhausted));
+
+        let unlimited = KeyUsage::new(UsagePolicy::default());
+        assert_eq!(unlimited.check(), Ok(()));
+    }
+
+    #[test]
+    fn age_follows_the_clock() {
+        let clock = ManualClock::default();
+        let policy = UsagePolicy {
+            max_age: Some(Duration::from_secs(3600)),
+            ..UsagePolicy::default()
+        };
+        let mut usage = KeyUsage::with_clock(policy, clock.clone());
+
+        clock.advance(Duration::from_secs(3599));
+        assert_eq!(usage.record_message(1), Ok(()));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(usage.record_message(1), Err(Error::KeyExhausted));
+
+        usage.rekey();
+        assert_eq!(usage.check(), Ok(()));
+        assert_eq!(usage.messages(), 0);
+    }
+}
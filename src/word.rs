@@ -14,84 +14,902 @@ pub trait Word:
 {
     const ZERO: Self;
     const BYTES: usize;
+    /// Defaults to `8 * BYTES`.
+    const BITS: u32 = (Self::BYTES * 8) as u32;
     const P: Self;
     const Q: Self;
 
     fn from_usize(val: usize) -> Self;
     fn from_u8(val: u8) -> Self;
-}
 
-impl Word for u8 {
-    const ZERO: Self = 0u8;
-    const BYTES: usize = 1;
-    const P: Self = 0xB7u8;
-    const Q: Self = 0x9Fu8;
+    /// Reads a word from the first `BYTES` bytes of `bytes`, little-endian.
+    ///
+    /// The provided methods from here on are built from the operators and
+    /// work for any word, but bit by bit; implementations should override
+    /// them with native operations.
+    fn from_le_slice(bytes: &[u8]) -> Self {
+        bytes[..Self::BYTES]
+            .iter()
+            .rev()
+            .fold(Self::ZERO, |word, &byte| {
+                word.wrapping_shl(8) | Self::from_u8(byte)
+            })
+    }
 
-    fn from_usize(val: usize) -> Self {
-        val as Self
+    /// Writes the word into the first `BYTES` bytes of `out`, little-endian.
+    fn write_le(self, out: &mut [u8]) {
+        for (i, byte) in out[..Self::BYTES].iter_mut().enumerate() {
+            *byte = (0..8).fold(0, |acc, k| acc | (bit(self, 8 * i as u32 + k) as u8) << k);
+        }
     }
 
-    fn from_u8(val: u8) -> Self {
-        val as Self
+    /// Rotates left by `by` modulo the word size, in the word's own width.
+    /// For the power-of-two sizes only the low byte of `by` matters, since
+    /// they divide 256; other sizes, such as 24 bits, must reduce all of it.
+    fn rotl(self, by: Self) -> Self {
+        match reduce(by) {
+            0 => self,
+            n => self.wrapping_shl(n) | self.wrapping_shr(Self::BITS - n),
+        }
     }
+
+    /// Rotates right by `by` modulo the word size, in the word's own width.
+    fn rotr(self, by: Self) -> Self {
+        match reduce(by) {
+            0 => self,
+            n => self.wrapping_shr(n) | self.wrapping_shl(Self::BITS - n),
+        }
+    }
+}
+
+// Bit `i` of `word`.
+fn bit<W: Word>(word: W, i: u32) -> bool {
+    word.wrapping_shr(i) & W::from_u8(1) != W::ZERO
+}
+
+// `by` modulo `W::BITS`, read from the most significant bit down.
+fn reduce<W: Word>(by: W) -> u32 {
+    (0..W::BITS)
+        .rev()
+        .fold(0, |acc, i| (2 * acc + bit(by, i) as u32) % W::BITS)
+}
+
+macro_rules! impl_word {
+    ($($t:ty)*) => {$(
+        impl Word for $t {
+            const ZERO: Self = 0;
+            const BYTES: usize = core::mem::size_of::<$t>();
+            const BITS: u32 = <$t>::BITS;
+            const P: Self = magic_p(<$t>::BITS) as Self;
+            const Q: Self = magic_q(<$t>::BITS) as Self;
+
+            fn from_usize(val: usize) -> Self {
+                val as Self
+            }
+
+            fn from_u8(val: u8) -> Self {
+                val.into()
+            }
+
+            fn from_le_slice(bytes: &[u8]) -> Self {
+                Self::from_le_bytes(bytes[..Self::BYTES].try_into().unwrap())
+            }
+
+            fn write_le(self, out: &mut [u8]) {
+                out[..Self::BYTES].copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn rotl(self, by: Self) -> Self {
+                self.rotate_left(u32::from(by.to_le_bytes()[0]))
+            }
+
+            fn rotr(self, by: Self) -> Self {
+                self.rotate_right(u32::from(by.to_le_bytes()[0]))
+            }
+        }
+    )*};
 }
 
-impl Word for u16 {
-    const ZERO: Self = 0u16;
-    const BYTES: usize = 2;
-    const P: Self = 0xB7E1u16;
-    const Q: Self = 0x9E37u16;
+impl_word! { u8 u16 u32 u64 u128 }
 
-    fn from_usize(val: usize) -> Self {
-        val as Self
+///
+/// `Odd((e - 2) * 2^bits)`, the RC5 constant `P` for a `bits`-bit word.
+///
+/// Derived from the series `e - 2 = 1/2! + 1/3! + ...`, so that word types
+/// outside the standard sizes, such as 24 or 48 bits, get the right value.
+/// `bits` must be between 1 and 128.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::word::magic_p;
+///
+/// assert_eq!(magic_p(32), 0xB7E15163);
+/// assert_eq!(magic_p(24), 0xB7E151);
+/// ```
+///
+pub const fn magic_p(bits: u32) -> u128 {
+    assert!(bits >= 1 && bits <= 128);
+    // Spigot over the factorial-base digits of e - 2, which are all 1: each
+    // doubling carries the next binary digit out of the 1/2! position. 50
+    // terms are exact well beyond 128 bits, as 50! > 2^214.
+    const TERMS: usize = 50;
+    let mut digits = [1u32; TERMS + 1];
+    let mut frac = 0u128;
+    let mut bit = 0;
+    while bit < bits {
+        let mut carry = 0;
+        let mut k = TERMS;
+        while k >= 2 {
+            let x = digits[k] * 2 + carry;
+            digits[k] = x % k as u32;
+            carry = x / k as u32;
+            k -= 1;
+        }
+        frac = (frac << 1) | carry as u128;
+        bit += 1;
     }
+    // the nearest odd integer to x is floor(x) | 1
+    frac | 1
+}
 
-    fn from_u8(val: u8) -> Self {
-        val as Self
+///
+/// `Odd((phi - 1) * 2^bits)`, the RC5 constant `Q` for a `bits`-bit word.
+///
+/// Derived from the ratio of consecutive Fibonacci numbers, which converges
+/// to `phi - 1`. `bits` must be between 1 and 128.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::word::magic_q;
+///
+/// assert_eq!(magic_q(32), 0x9E3779B9);
+/// assert_eq!(magic_q(48), 0x9E3779B97F4B);
+/// ```
+///
+pub const fn magic_q(bits: u32) -> u128 {
+    assert!(bits >= 1 && bits <= 128);
+    // F(n) / F(n + 1) for the first F(n + 1) past 2^125, within about
+    // 2^-250 of phi - 1, divided out one bit at a time.
+    let (mut num, mut den) = (1u128, 1u128);
+    while den < 1 << 125 {
+        let next = num + den;
+        num = den;
+        den = next;
+    }
+    let mut rem = num;
+    let mut frac = 0u128;
+    let mut bit = 0;
+    while bit < bits {
+        rem <<= 1;
+        let one = rem >= den;
+        if one {
+            rem -= den;
+        }
+        frac = (frac << 1) | one as u128;
+        bit += 1;
     }
+    frac | 1
 }
 
-impl Word for u32 {
-    const ZERO: Self = 0u32;
-    const BYTES: usize = 4;
-    const P: Self = 0xB7E15163u32;
-    const Q: Self = 0x9E3779B9u32;
+///
+/// A property a [`Word`] implementation violates, found by [`validate`].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDefect {
+    /// `BITS` isn't `8 * BYTES`, or isn't between 8 and 256.
+    Size,
+    /// `P` or `Q` is even.
+    EvenConstant,
+    /// `P` or `Q` differs from [`magic_p`] or [`magic_q`] for `BITS`.
+    WrongConstant,
+    /// `from_le_slice`, `write_le`, `from_u8` or `from_usize` disagree.
+    Encoding,
+    /// Addition, subtraction or XOR don't wrap modulo `2^BITS`.
+    Arithmetic,
+    /// `rotl` and `rotr` aren't inverse rotations, or don't take the amount
+    /// modulo `BITS`.
+    Rotation,
+    /// The key schedule depends too little or too much on each key bit.
+    WeakSchedule,
+    /// `expand_key` and `expand_key_into` produce different schedules.
+    ScheduleMismatch,
+    /// Decrypting a block doesn't give back what was encrypted, or
+    /// encrypting leaves it unchanged.
+    Roundtrip,
+}
 
-    fn from_usize(val: usize) -> Self {
-        val as Self
+impl std::fmt::Display for WordDefect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WordDefect::Size => write!(f, "word size is inconsistent or unsupported"),
+            WordDefect::EvenConstant => write!(f, "magic constant P or Q is even"),
+            WordDefect::WrongConstant => write!(f, "magic constant P or Q is wrong"),
+            WordDefect::Encoding => write!(f, "byte conversions disagree"),
+            WordDefect::Arithmetic => write!(f, "arithmetic doesn't wrap"),
+            WordDefect::Rotation => write!(f, "rotations are wrong"),
+            WordDefect::WeakSchedule => write!(f, "key schedule doesn't mix the key"),
+            WordDefect::ScheduleMismatch => write!(f, "key schedule implementations disagree"),
+            WordDefect::Roundtrip => write!(f, "encryption doesn't roundtrip"),
+        }
     }
+}
 
-    fn from_u8(val: u8) -> Self {
-        val as Self
+///
+/// The outcome of [`validate`]: every defect found, and the fraction of key
+/// schedule bits a single flipped key bit changes, which is close to one half
+/// for a schedule that mixes well.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordReport {
+    pub defects: Vec<WordDefect>,
+    pub schedule_avalanche: f64,
+}
+
+impl WordReport {
+    ///
+    /// Whether no defect was found.
+    ///
+    pub fn is_ok(&self) -> bool {
+        self.defects.is_empty()
     }
 }
 
-impl Word for u64 {
-    const ZERO: Self = 0u64;
-    const BYTES: usize = 8;
-    const P: Self = 0xB7E151628AED2A6Bu64;
-    const Q: Self = 0x9E3779B97F4A7C15u64;
+///
+/// Runs sanity checks on the [`Word`] implementation of `W`, meant for word
+/// types defined outside this crate: a subtly wrong one still encrypts and
+/// decrypts, but can be far weaker than RC5. Call it once from the tests of
+/// the crate defining the type.
+///
+/// Checks what the kernels rely on: a whole number of bytes per word, the
+/// oddness and values of `P` and `Q`, byte conversions, wrapping arithmetic
+/// and rotations modulo `BITS` on pseudo-random values,
+/// how much the key schedule changes per key bit, and the encryption
+/// roundtrip over pseudo-random keys and blocks. The inputs are fixed, so
+/// the report is reproducible.
+///
+/// Widths that aren't a power of two, e.g. 24 bits, can pass: the block
+/// cipher and the modes without a field multiplication (ECB, CBC, CFB, CTR)
+/// work with them. XTS, LRW, OCB, CMAC and SIV have no field polynomial for
+/// such blocks, and their constructors fail with
+/// [`Error::UnsupportedParameters`](crate::Error::UnsupportedParameters).
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::word::validate;
+///
+/// let report = validate::<u32>();
+/// assert!(report.is_ok(), "{:?}", report.defects);
+/// assert!((report.schedule_avalanche - 0.5).abs() < 0.05);
+/// ```
+///
+pub fn validate<W: Word>() -> WordReport {
+    let mut defects = Vec::new();
+    let mut report = |defect| {
+        if !defects.contains(&defect) {
+            defects.push(defect);
+        }
+    };
+
+    let bits = W::BITS as usize;
+    if W::BYTES * 8 != bits || !(8..=256).contains(&bits) {
+        // nothing else can be checked with sizes that don't add up
+        report(WordDefect::Size);
+        return WordReport {
+            defects,
+            schedule_avalanche: 0.0,
+        };
+    }
+
+    let mut rng = 0x9e37_79b9_7f4a_7c15u64;
+    let mut bytes = vec![0u8; W::BYTES];
+    let mut random = |bytes: &mut [u8]| {
+        for b in bytes.iter_mut() {
+            // xorshift64
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            *b = rng as u8;
+        }
+    };
+    let one = W::from_u8(1);
+
+    // constants
+    let (mut p, mut q) = (vec![0u8; W::BYTES], vec![0u8; W::BYTES]);
+    W::P.write_le(&mut p);
+    W::Q.write_le(&mut q);
+    if p[0] & 1 == 0 || q[0] & 1 == 0 {
+        report(WordDefect::EvenConstant);
+    }
+    if bits <= 128
+        && (p[..] != magic_p(W::BITS).to_le_bytes()[..W::BYTES]
+            || q[..] != magic_q(W::BITS).to_le_bytes()[..W::BYTES])
+    {
+        report(WordDefect::WrongConstant);
+    }
+
+    // encoding
+    let mut out = vec![0u8; W::BYTES];
+    for _ in 0..64 {
+        random(&mut bytes);
+        W::from_le_slice(&bytes).write_le(&mut out);
+        if out != bytes {
+            report(WordDefect::Encoding);
+        }
+    }
+    one.write_le(&mut out);
+    if out[0] != 1
+        || out[1..].iter().any(|&b| b != 0)
+        || W::from_usize(1) != one
+        || W::from_usize(0xa5) != W::from_u8(0xa5)
+    {
+        report(WordDefect::Encoding);
+    }
 
-    fn from_usize(val: usize) -> Self {
-        val as Self
+    // arithmetic
+    let ones = W::from_le_slice(&vec![0xff; W::BYTES]);
+    if ones.wrapping_add(&one) != W::ZERO || W::ZERO.wrapping_sub(&one) != ones {
+        report(WordDefect::Arithmetic);
+    }
+    for _ in 0..64 {
+        random(&mut bytes);
+        let x = W::from_le_slice(&bytes);
+        random(&mut bytes);
+        let y = W::from_le_slice(&bytes);
+        if x.wrapping_add(&y).wrapping_sub(&y) != x
+            || (x ^ y) ^ y != x
+            || x.wrapping_add(&y) != y.wrapping_add(&x)
+        {
+            report(WordDefect::Arithmetic);
+        }
     }
 
-    fn from_u8(val: u8) -> Self {
-        val as Self
+    // rotations
+    for _ in 0..8 {
+        random(&mut bytes);
+        let x = W::from_le_slice(&bytes);
+        let spun = x.wrapping_shl(1) | x.wrapping_shr(W::BITS - 1);
+        if x.rotl(one) != spun || spun.rotr(one) != x || x.rotl(W::from_usize(bits)) != x {
+            report(WordDefect::Rotation);
+        }
+        for n in 0..bits {
+            let n = W::from_usize(n);
+            if x.rotl(n).rotr(n) != x {
+                report(WordDefect::Rotation);
+            }
+        }
+        // the kernels rotate by whole words, reduced modulo BITS
+        for _ in 0..8 {
+            random(&mut bytes);
+            let by = W::from_le_slice(&bytes);
+            let reduced = bytes
+                .iter()
+                .rev()
+                .fold(0, |r, &b| (r * 256 + b as usize) % bits);
+            let reduced = W::from_usize(reduced);
+            if x.rotl(by) != x.rotl(reduced) || x.rotr(by) != x.rotr(reduced) {
+                report(WordDefect::Rotation);
+            }
+        }
+    }
+
+    // key schedule
+    const ROUNDS: usize = 12;
+    const TRIALS: usize = 32;
+    let mut key = [0u8; 16];
+    let (mut flipped, mut total) = (0u64, 0u64);
+    let mut into = vec![W::ZERO; 2 * (ROUNDS + 1)];
+    let mut word = vec![0u8; W::BYTES];
+    for trial in 0..TRIALS {
+        random(&mut key);
+        let base = crate::rc5::expand_key::<W>(&key, ROUNDS);
+        crate::rc5::expand_key_into(&key, &mut into);
+        if into != base {
+            report(WordDefect::ScheduleMismatch);
+        }
+        let bit = trial * 37 % 128;
+        key[bit / 8] ^= 1 << (bit % 8);
+        let other = crate::rc5::expand_key::<W>(&key, ROUNDS);
+        for (a, b) in base.iter().zip(&other) {
+            (*a ^ *b).write_le(&mut word);
+            flipped += word.iter().map(|b| u64::from(b.count_ones())).sum::<u64>();
+            total += bits as u64;
+        }
+    }
+    let schedule_avalanche = flipped as f64 / total as f64;
+    if !(0.4..=0.6).contains(&schedule_avalanche) {
+        report(WordDefect::WeakSchedule);
+    }
+
+    // roundtrip
+    let mut unchanged = 0;
+    for _ in 0..TRIALS {
+        random(&mut key);
+        let key_exp = crate::rc5::expand_key::<W>(&key, ROUNDS);
+        random(&mut bytes);
+        let a = W::from_le_slice(&bytes);
+        random(&mut bytes);
+        let pt = [a, W::from_le_slice(&bytes)];
+        let ct = crate::rc5::encrypt_kernel(pt, &key_exp);
+        if crate::rc5::decrypt_kernel(ct, &key_exp) != pt {
+            report(WordDefect::Roundtrip);
+        }
+        if ct == pt {
+            unchanged += 1;
+        }
+    }
+    if unchanged > 1 {
+        report(WordDefect::Roundtrip);
+    }
+
+    WordReport {
+        defects,
+        schedule_avalanche,
     }
 }
 
-impl Word for u128 {
-    const ZERO: Self = 0u128;
-    const BYTES: usize = 16;
-    const P: Self = 0xB7E151628AED2A6ABF7158809CF4F3C7u128;
-    const Q: Self = 0x9E3779B97F4A7C15F39CC0605CEDC835u128;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check<W: Word>(val: W) {
+        assert_eq!(W::BYTES * 8, W::BITS as usize);
+        let mut buf = [0u8; 16];
+        val.write_le(&mut buf);
+        assert_eq!(W::from_le_slice(&buf), val);
+        assert_eq!(
+            val.rotl(W::from_usize(W::BITS as usize + 1)),
+            val.rotl(W::from_u8(1))
+        );
+    }
+
+    #[test]
+    fn impls_are_consistent() {
+        check(0xA5u8);
+        check(0xA55Au16);
+        check(0xA55A_0FF0u32);
+        check(u64::P);
+        check(u128::Q);
+    }
+
+    #[test]
+    fn magic_constants_match_the_paper() {
+        // the values published for RC5 and its test vectors
+        let known: [(u32, u128, u128); 7] = [
+            (8, 0xB7, 0x9F),
+            (16, 0xB7E1, 0x9E37),
+            (24, 0xB7E151, 0x9E3779),
+            (32, 0xB7E15163, 0x9E3779B9),
+            (48, 0xB7E151628AED, 0x9E3779B97F4B),
+            (64, 0xB7E151628AED2A6B, 0x9E3779B97F4A7C15),
+            (
+                128,
+                0xB7E151628AED2A6ABF7158809CF4F3C7,
+                0x9E3779B97F4A7C15F39CC0605CEDC835,
+            ),
+        ];
+        for (bits, p, q) in known {
+            assert_eq!(magic_p(bits), p, "P{}", bits);
+            assert_eq!(magic_q(bits), q, "Q{}", bits);
+        }
+        assert_eq!(u64::P, 0xB7E151628AED2A6B);
+        assert_eq!(u8::Q, 0x9F);
+    }
+
+    #[test]
+    fn builtin_words_validate() {
+        fn check<W: Word>() {
+            let report = validate::<W>();
+            assert!(report.is_ok(), "{} bits: {:?}", W::BITS, report.defects);
+            assert!((report.schedule_avalanche - 0.5).abs() < 0.05);
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+
+    // A built-in word that keeps every provided method of `Word`.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Bare<W>(W);
+
+    macro_rules! bare_ops {
+        ($($tr:ident $f:ident $rhs:ty => $e:expr),*) => {$(
+            impl<W: Word> std::ops::$tr<$rhs> for Bare<W> {
+                type Output = Self;
+                fn $f(self, o: $rhs) -> Self {
+                    let f: fn(W, $rhs) -> W = $e;
+                    Bare(f(self.0, o))
+                }
+            }
+        )*};
+    }
+    bare_ops!(
+        BitAnd bitand Self => |a, b| a & b.0,
+        BitOr bitor Self => |a, b| a | b.0,
+        BitXor bitxor Self => |a, b| a ^ b.0,
+        Shl shl Self => |a, b| a << b.0,
+        Shr shr Self => |a, b| a >> b.0,
+        Shl shl usize => |a, n| a.wrapping_shl(n as u32),
+        Shr shr usize => |a, n| a.wrapping_shr(n as u32),
+        Add add Self => |a, b| a.wrapping_add(&b.0),
+        Sub sub Self => |a, b| a.wrapping_sub(&b.0)
+    );
+
+    impl<W: Word> num::traits::WrappingAdd for Bare<W> {
+        fn wrapping_add(&self, o: &Self) -> Self {
+            *self + *o
+        }
+    }
+
+    impl<W: Word> num::traits::WrappingSub for Bare<W> {
+        fn wrapping_sub(&self, o: &Self) -> Self {
+            *self - *o
+        }
+    }
+
+    impl<W: Word> num::traits::WrappingShl for Bare<W> {
+        fn wrapping_shl(&self, n: u32) -> Self {
+            Bare(self.0.wrapping_shl(n))
+        }
+    }
+
+    impl<W: Word> num::traits::WrappingShr for Bare<W> {
+        fn wrapping_shr(&self, n: u32) -> Self {
+            Bare(self.0.wrapping_shr(n))
+        }
+    }
+
+    impl<W: Word> Word for Bare<W> {
+        const ZERO: Self = Bare(W::ZERO);
+        const BYTES: usize = W::BYTES;
+        const P: Self = Bare(W::P);
+        const Q: Self = Bare(W::Q);
+
+        fn from_usize(val: usize) -> Self {
+            Bare(W::from_usize(val))
+        }
+
+        fn from_u8(val: u8) -> Self {
+            Bare(W::from_u8(val))
+        }
+    }
+
+    #[test]
+    fn provided_methods_match_the_native_ones() {
+        fn check<W: Word>(val: W) {
+            assert_eq!(Bare::<W>::BITS, W::BITS);
+            let (mut native, mut provided) = ([0u8; 16], [0u8; 16]);
+            val.write_le(&mut native);
+            Bare(val).write_le(&mut provided);
+            assert_eq!(native, provided);
+            assert_eq!(Bare::<W>::from_le_slice(&native), Bare(val));
+            for by in [0, 1, 7, 8, 13, 63, 64, 65, 127, 200, 255] {
+                let by = W::from_u8(by);
+                assert_eq!(Bare(val).rotl(Bare(by)), Bare(val.rotl(by)));
+                assert_eq!(Bare(val).rotr(Bare(by)), Bare(val.rotr(by)));
+            }
+        }
+        check(0xA5u8);
+        check(0xA55Au16);
+        check(0xA55A_0FF0u32);
+        check(u64::P);
+        check(u128::Q);
+
+        let report = validate::<Bare<u32>>();
+        assert!(report.is_ok(), "{:?}", report.defects);
+    }
+
+    // A u32 word with one fault: 1 an even Q, 2 a rotr that ignores the
+    // amount, 3 a key-independent schedule, 4 a mismatched BITS.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Faulty<const F: u8>(u32);
+
+    macro_rules! ops {
+        ($($tr:ident $f:ident $op:tt),*) => {$(
+            impl<const F: u8> std::ops::$tr for Faulty<F> {
+                type Output = Self;
+                fn $f(self, o: Self) -> Self {
+                    Faulty(self.0 $op o.0)
+                }
+            }
+        )*};
+    }
+    ops!(BitAnd bitand &, BitOr bitor |, BitXor bitxor ^);
+
+    impl<const F: u8> std::ops::Shl for Faulty<F> {
+        type Output = Self;
+        fn shl(self, o: Self) -> Self {
+            Faulty(self.0.wrapping_shl(o.0))
+        }
+    }
 
-    fn from_usize(val: usize) -> Self {
-        val as Self
+    impl<const F: u8> std::ops::Shr for Faulty<F> {
+        type Output = Self;
+        fn shr(self, o: Self) -> Self {
+            Faulty(self.0.wrapping_shr(o.0))
+        }
     }
 
-    fn from_u8(val: u8) -> Self {
-        val as Self
+    impl<const F: u8> std::ops::Shl<usize> for Faulty<F> {
+        type Output = Self;
+        fn shl(self, n: usize) -> Self {
+            Faulty(self.0 << n)
+        }
+    }
+
+    impl<const F: u8> std::ops::Shr<usize> for Faulty<F> {
+        type Output = Self;
+        fn shr(self, n: usize) -> Self {
+            Faulty(self.0 >> n)
+        }
+    }
+
+    impl<const F: u8> std::ops::Add for Faulty<F> {
+        type Output = Self;
+        fn add(self, o: Self) -> Self {
+            Faulty(self.0.wrapping_add(o.0))
+        }
+    }
+
+    impl<const F: u8> std::ops::Sub for Faulty<F> {
+        type Output = Self;
+        fn sub(self, o: Self) -> Self {
+            Faulty(self.0.wrapping_sub(o.0))
+        }
+    }
+
+    impl<const F: u8> num::traits::WrappingAdd for Faulty<F> {
+        fn wrapping_add(&self, o: &Self) -> Self {
+            // a schedule that forgets the key: S[i] = S[i-1] + Q stays as is
+            // and the mixing only ever adds zero
+            match F {
+                3 if o.0 != <u32 as Word>::Q => *self,
+                _ => *self + *o,
+            }
+        }
+    }
+
+    impl<const F: u8> num::traits::WrappingSub for Faulty<F> {
+        fn wrapping_sub(&self, o: &Self) -> Self {
+            *self - *o
+        }
+    }
+
+    impl<const F: u8> num::traits::WrappingShl for Faulty<F> {
+        fn wrapping_shl(&self, n: u32) -> Self {
+            Faulty(self.0.wrapping_shl(n))
+        }
+    }
+
+    impl<const F: u8> num::traits::WrappingShr for Faulty<F> {
+        fn wrapping_shr(&self, n: u32) -> Self {
+            Faulty(self.0.wrapping_shr(n))
+        }
+    }
+
+    impl<const F: u8> Word for Faulty<F> {
+        const ZERO: Self = Faulty(0);
+        const BYTES: usize = 4;
+        const BITS: u32 = if F == 4 { 24 } else { 32 };
+        const P: Self = Faulty(<u32 as Word>::P);
+        const Q: Self = Faulty(if F == 1 { 0x9E3779B8 } else { <u32 as Word>::Q });
+
+        fn from_usize(val: usize) -> Self {
+            Faulty(val as u32)
+        }
+
+        fn from_u8(val: u8) -> Self {
+            Faulty(val.into())
+        }
+
+        fn from_le_slice(bytes: &[u8]) -> Self {
+            Faulty(u32::from_le_slice(bytes))
+        }
+
+        fn write_le(self, out: &mut [u8]) {
+            self.0.write_le(out)
+        }
+
+        fn rotl(self, by: Self) -> Self {
+            Faulty(self.0.rotl(by.0))
+        }
+
+        fn rotr(self, by: Self) -> Self {
+            match F {
+                2 => Faulty(self.0.rotate_right(1)),
+                _ => Faulty(self.0.rotr(by.0)),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_faulty_words() {
+        assert!(validate::<Faulty<0>>().is_ok());
+
+        let report = validate::<Faulty<1>>();
+        assert!(report.defects.contains(&WordDefect::EvenConstant));
+        assert!(report.defects.contains(&WordDefect::WrongConstant));
+
+        let report = validate::<Faulty<2>>();
+        assert!(report.defects.contains(&WordDefect::Rotation));
+        assert!(report.defects.contains(&WordDefect::Roundtrip));
+
+        let report = validate::<Faulty<3>>();
+        assert!(report.defects.contains(&WordDefect::WeakSchedule));
+        assert!(report.schedule_avalanche < 0.1);
+
+        assert_eq!(validate::<Faulty<4>>().defects, [WordDefect::Size]);
+    }
+
+    // A 24-bit word in a u32; with `LOW_BYTE` its rotations only look at the
+    // low byte of the amount, which is wrong for a size that doesn't divide 256.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct U24<const LOW_BYTE: bool>(u32);
+
+    const MASK_24: u32 = 0xff_ffff;
+
+    macro_rules! ops_24 {
+        ($($tr:ident $f:ident $op:tt),*) => {$(
+            impl<const L: bool> std::ops::$tr for U24<L> {
+                type Output = Self;
+                fn $f(self, o: Self) -> Self {
+                    U24((self.0 $op o.0) & MASK_24)
+                }
+            }
+        )*};
+    }
+    ops_24!(BitAnd bitand &, BitOr bitor |, BitXor bitxor ^);
+
+    impl<const L: bool> std::ops::Add for U24<L> {
+        type Output = Self;
+        fn add(self, o: Self) -> Self {
+            U24::masked(self.0.wrapping_add(o.0))
+        }
+    }
+
+    impl<const L: bool> std::ops::Sub for U24<L> {
+        type Output = Self;
+        fn sub(self, o: Self) -> Self {
+            U24::masked(self.0.wrapping_sub(o.0))
+        }
+    }
+
+    impl<const L: bool> std::ops::Shl<usize> for U24<L> {
+        type Output = Self;
+        fn shl(self, n: usize) -> Self {
+            U24((self.0 << n) & MASK_24)
+        }
+    }
+
+    impl<const L: bool> std::ops::Shr<usize> for U24<L> {
+        type Output = Self;
+        fn shr(self, n: usize) -> Self {
+            U24(self.0 >> n)
+        }
+    }
+
+    impl<const L: bool> std::ops::Shl for U24<L> {
+        type Output = Self;
+        fn shl(self, o: Self) -> Self {
+            U24::masked(self.0.wrapping_shl(o.0))
+        }
+    }
+
+    impl<const L: bool> std::ops::Shr for U24<L> {
+        type Output = Self;
+        fn shr(self, o: Self) -> Self {
+            U24(self.0.wrapping_shr(o.0))
+        }
+    }
+
+    impl<const L: bool> num::traits::WrappingAdd for U24<L> {
+        fn wrapping_add(&self, o: &Self) -> Self {
+            *self + *o
+        }
+    }
+
+    impl<const L: bool> num::traits::WrappingSub for U24<L> {
+        fn wrapping_sub(&self, o: &Self) -> Self {
+            *self - *o
+        }
+    }
+
+    impl<const L: bool> num::traits::WrappingShl for U24<L> {
+        fn wrapping_shl(&self, n: u32) -> Self {
+            U24(self.0.checked_shl(n).unwrap_or(0) & MASK_24)
+        }
+    }
+
+    impl<const L: bool> num::traits::WrappingShr for U24<L> {
+        fn wrapping_shr(&self, n: u32) -> Self {
+            U24(self.0.checked_shr(n).unwrap_or(0))
+        }
+    }
+
+    impl<const L: bool> U24<L> {
+        fn masked(x: u32) -> Self {
+            U24(x & MASK_24)
+        }
+
+        fn amount(by: Self) -> u32 {
+            if L {
+                (by.0 & 0xff) % 24
+            } else {
+                by.0 % 24
+            }
+        }
+    }
+
+    impl<const L: bool> Word for U24<L> {
+        const ZERO: Self = U24(0);
+        const BYTES: usize = 3;
+        const BITS: u32 = 24;
+        const P: Self = U24(magic_p(24) as u32);
+        const Q: Self = U24(magic_q(24) as u32);
+
+        fn from_usize(val: usize) -> Self {
+            U24(val as u32 & MASK_24)
+        }
+
+        fn from_u8(val: u8) -> Self {
+            U24(val.into())
+        }
+
+        fn from_le_slice(bytes: &[u8]) -> Self {
+            U24(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+        }
+
+        fn write_le(self, out: &mut [u8]) {
+            out[..3].copy_from_slice(&self.0.to_le_bytes()[..3]);
+        }
+
+        fn rotl(self, by: Self) -> Self {
+            let n = Self::amount(by);
+            U24(((self.0 << n) | (self.0 >> (24 - n))) & MASK_24)
+        }
+
+        fn rotr(self, by: Self) -> Self {
+            let n = Self::amount(by);
+            U24(((self.0 >> n) | (self.0 << (24 - n))) & MASK_24)
+        }
+    }
+
+    #[test]
+    fn non_power_of_two_words_validate() {
+        let report = validate::<U24<false>>();
+        assert!(report.is_ok(), "{:?}", report.defects);
+        assert!((report.schedule_avalanche - 0.5).abs() < 0.05);
+
+        assert_eq!(validate::<U24<true>>().defects, [WordDefect::Rotation]);
+    }
+
+    #[test]
+    fn field_modes_reject_non_power_of_two_words() {
+        use crate::error::Error;
+        use crate::lrw::Lrw;
+        use crate::mac::Cmac;
+        use crate::ocb::Ocb;
+        use crate::rc5::expand_key;
+        use crate::siv::{DeterministicAead, Siv};
+        use crate::xts::Xts;
+
+        type W = U24<false>;
+        let unsupported = Some(Error::UnsupportedParameters);
+
+        let key_exp = expand_key::<W>(b"data key", 12);
+        let tweak_exp = expand_key::<W>(b"tweak key", 12);
+        assert_eq!(Xts::new(&key_exp, &tweak_exp).err(), unsupported);
+        assert_eq!(Lrw::new(&key_exp, &[0x5c; 6]).err(), unsupported);
+        assert_eq!(Ocb::<W>::new(b"key", 12, 4, 6).err(), unsupported);
+        assert_eq!(Cmac::<W>::new(b"key", 12).err(), unsupported);
+        assert_eq!(
+            DeterministicAead::<W>::new(b"mac", b"enc", 12).err(),
+            unsupported
+        );
+        assert_eq!(Siv::<W>::new(b"mac", b"enc", 12, 6).err(), unsupported);
     }
 }
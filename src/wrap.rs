@@ -0,0 +1,245 @@
+//!
+//! Key wrapping for small secrets, such as data-encryption keys.
+//!
+//! The construction of RFC 3394 with its semiblocks as RC5 words: a key of
+//! `n` words `R_1..R_n` and a check word `A`, initially `0xa6` in every byte,
+//! go through six passes of `B = E(A || R_i)`, `A = B_hi ^ t`, `R_i = B_lo`
+//! with `t = n * j + i` XORed in as a big-endian integer. Unwrapping runs
+//! the passes backwards and checks that `A` comes back intact, so an altered
+//! or foreign wrapped key is rejected rather than unwrapped to garbage.
+//!
+//! Words are read little-endian as everywhere in this crate, so wrapped keys
+//! are not comparable with AES key wrap. The check is one word: use `u64` or
+//! `u128` words, or accept a `2^-32` forgery chance with `u32`. Narrower
+//! words are rejected. The wrapped key is one word longer than the key.
+//!
+
+use crate::error::{Error, Failure};
+use crate::rc5::{decrypt_kernel, encrypt_kernel};
+use crate::word::Word;
+
+// The initial value of the check word, repeated in each byte.
+const IV_BYTE: u8 = 0xa6;
+
+///
+/// Wraps `key` under the expanded key-encryption key `kek_exp`.
+///
+/// Fails with [`Error::UnsupportedParameters`] for words narrower than
+/// `u32`, and with [`Error::InvalidLength`] unless `key` is a whole number
+/// of words, at least two.
+///
+/// Example:
+///
+/// ```rust
+/// use rc5_cipher::expand_key;
+/// use rc5_cipher::wrap::{unwrap_key, wrap_key};
+///
+/// let kek_exp = expand_key::<u64>(b"key-encryption k", 20);
+/// let dek = [0x5au8; 16];
+///
+/// let wrapped = wrap_key(&kek_exp, &dek).unwrap();
+/// assert_eq!(wrapped.len(), 24);
+/// assert_eq!(unwrap_key(&kek_exp, &wrapped).unwrap(), dek);
+///
+/// let other = expand_key::<u64>(b"another kek 0001", 20);
+/// assert!(unwrap_key(&other, &wrapped).is_err());
+/// ```
+///
+pub fn wrap_key<W: Word>(kek_exp: &[W], key: &[u8]) -> Result<Vec<u8>, Error> {
+    let u = W::BYTES;
+    let n = semiblocks::<W>(key.len())?;
+    let mut out = vec![IV_BYTE; u];
+    out.extend_from_slice(key);
+
+    let mut a = W::from_le_slice(&out);
+    for j in 0..6 {
+        for i in 1..=n {
+            let r = &mut out[i * u..(i + 1) * u];
+            let b = encrypt_kernel([a, W::from_le_slice(r)], kek_exp);
+            b[1].write_le(r);
+            a = xor_counter(b[0], (n * j + i) as u64);
+        }
+    }
+    a.write_le(&mut out);
+    Ok(out)
+}
+
+///
+/// Unwraps the output of [`wrap_key`] made under the same `kek_exp`.
+///
+/// Fails with a [`Failure::Crypto`] that doesn't say why if `wrapped` was
+/// altered, wrapped under another key or can't be the output of
+/// [`wrap_key`], and with a [`Failure::Usage`] of
+/// [`Error::UnsupportedParameters`] for words narrower than `u32`.
+///
+pub fn unwrap_key<W: Word>(kek_exp: &[W], wrapped: &[u8]) -> Result<Vec<u8>, Failure> {
+    if W::BYTES < 4 {
+        return Err(Error::UnsupportedParameters.into());
+    }
+    unwrap_body(kek_exp, wrapped).map_err(Failure::decrypting)
+}
+
+fn unwrap_body<W: Word>(kek_exp: &[W], wrapped: &[u8]) -> Result<Vec<u8>, Error> {
+    let u = W::BYTES;
+    let n = semiblocks::<W>(wrapped.len().saturating_sub(u))?;
+    let mut out = wrapped.to_vec();
+
+    let mut a = W::from_le_slice(&out);
+    for j in (0..6).rev() {
+        for i in (1..=n).rev() {
+            let r = &mut out[i * u..(i + 1) * u];
+            let b = decrypt_kernel(
+                [xor_counter(a, (n * j + i) as u64), W::from_le_slice(r)],
+                kek_exp,
+            );
+            b[1].write_le(r);
+            a = b[0];
+        }
+    }
+
+    a.write_le(&mut out);
+    let diff = out[..u].iter().fold(0u8, |acc, b| acc | (b ^ IV_BYTE));
+    if diff != 0 {
+        out.fill(0);
+        return Err(Error::AuthenticationFailed);
+    }
+    out.drain(..u);
+    Ok(out)
+}
+
+// The number of words in a key of `len` bytes, if it can be wrapped.
+fn semiblocks<W: Word>(len: usize) -> Result<usize, Error> {
+    let u = W::BYTES;
+    if u < 4 {
+        return Err(Error::UnsupportedParameters);
+    }
+    let n = len / u;
+    // the largest counter, 6n, must fit in the check word
+    if !len.is_multiple_of(u) || n < 2 || (u < 8 && 6 * n as u64 > u32::MAX as u64) {
+        return Err(Error::InvalidLength);
+    }
+    Ok(n)
+}
+
+// XORs `t` into the word `a` as a big-endian integer, as RFC 3394 does with
+// its semiblocks.
+fn xor_counter<W: Word>(a: W, t: u64) -> W {
+    let u = W::BYTES;
+    let mut bytes = [0u8; 16];
+    a.write_le(&mut bytes);
+    let t = t.to_be_bytes();
+    let n = u.min(8);
+    for (b, t) in bytes[u - n..u].iter_mut().zip(&t[8 - n..]) {
+        *b ^= t;
+    }
+    W::from_le_slice(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CryptoError;
+    use crate::rc5::expand_key;
+
+    #[test]
+    fn follows_the_rfc_3394_passes() {
+        let kek_exp = expand_key::<u32>(b"kek", 12);
+        let key = [1, 2, 3, 4, 5, 6, 7, 8];
+        let wrapped = wrap_key(&kek_exp, &key).unwrap();
+
+        // n = 2, so the counters run from 1 to 12
+        let mut a = u32::from_le_bytes([IV_BYTE; 4]);
+        let mut r = [u32::from_le_slice(&key), u32::from_le_slice(&key[4..])];
+        for t in 1..=12u32 {
+            let i = ((t - 1) % 2) as usize;
+            let b = encrypt_kernel([a, r[i]], &kek_exp);
+            r[i] = b[1];
+            let mut bytes = b[0].to_le_bytes();
+            for (x, y) in bytes.iter_mut().zip(t.to_be_bytes()) {
+                *x ^= y;
+            }
+            a = u32::from_le_bytes(bytes);
+        }
+        let mut expected = [0u8; 12];
+        a.write_le(&mut expected);
+        r[0].write_le(&mut expected[4..]);
+        r[1].write_le(&mut expected[8..]);
+        assert_eq!(wrapped, expected);
+    }
+
+    #[test]
+    fn roundtrip_for_every_supported_size() {
+        fn check<W: Word>() {
+            let kek_exp = expand_key::<W>(b"key-encryption key", 16);
+            let u = W::BYTES;
+            for n in [2, 3, 8] {
+                let key: Vec<u8> = (0..n * u).map(|i| (i * 7 + 1) as u8).collect();
+                let wrapped = wrap_key(&kek_exp, &key).unwrap();
+                assert_eq!(wrapped.len(), key.len() + u);
+                assert_ne!(wrapped[u..], key[..]);
+                assert_eq!(unwrap_key(&kek_exp, &wrapped).unwrap(), key);
+            }
+        }
+        check::<u32>();
+        check::<u64>();
+        check::<u128>();
+    }
+
+    #[test]
+    fn rejects_tampering_and_other_keys() {
+        let kek_exp = expand_key::<u64>(b"kek one", 20);
+        let wrapped = wrap_key(&kek_exp, &[0x33; 32]).unwrap();
+        for i in [0, 7, 8, 20, wrapped.len() - 1] {
+            let mut bad = wrapped.clone();
+            bad[i] ^= 0x80;
+            assert_eq!(
+                unwrap_body(&kek_exp, &bad),
+                Err(Error::AuthenticationFailed),
+                "byte {}",
+                i
+            );
+        }
+        // swapping two wrapped words breaks the counters
+        let mut swapped = wrapped.clone();
+        let (head, tail) = swapped.split_at_mut(16);
+        head[8..].swap_with_slice(&mut tail[..8]);
+        assert!(unwrap_key(&kek_exp, &swapped).is_err());
+
+        let other = expand_key::<u64>(b"kek two", 20);
+        assert_eq!(
+            unwrap_body(&other, &wrapped),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            unwrap_key(&other, &wrapped),
+            Err(Failure::Crypto(CryptoError))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lengths_and_words() {
+        let kek_exp = expand_key::<u64>(b"kek", 20);
+        for len in [0, 8, 12, 17] {
+            assert_eq!(wrap_key(&kek_exp, &vec![0; len]), Err(Error::InvalidLength));
+        }
+        for len in [0, 8, 16, 23] {
+            assert_eq!(
+                unwrap_body(&kek_exp, &vec![0; len]),
+                Err(Error::InvalidLength)
+            );
+            assert_eq!(
+                unwrap_key(&kek_exp, &vec![0; len]),
+                Err(Failure::Crypto(CryptoError))
+            );
+        }
+        let narrow = expand_key::<u16>(b"kek", 12);
+        assert_eq!(
+            wrap_key(&narrow, &[0; 8]),
+            Err(Error::UnsupportedParameters)
+        );
+        assert_eq!(
+            unwrap_key(&narrow, &[0; 10]),
+            Err(Error::UnsupportedParameters.into())
+        );
+    }
+}
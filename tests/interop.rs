@@ -0,0 +1,105 @@
+//!
+//! Decrypts every entry of the corpora under `tests/data` with the
+//! compatibility profile it names, so that a change breaking interop with an
+//! external implementation fails here rather than in a migration job.
+//!
+//! So far the corpus holds published vectors only; captures from other
+//! libraries are still to be added, see the header of `data/interop.txt`.
+//!
+
+use rc5_cipher::encoding::hex_decode;
+use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+use rc5_cipher::word::Word;
+
+const CORPORA: &[(&str, &str)] = &[("interop.txt", include_str!("data/interop.txt"))];
+
+struct Entry<'a> {
+    file: &'a str,
+    line: usize,
+    source: &'a str,
+    profile: LegacyProfile,
+    word_bits: usize,
+    key: Vec<u8>,
+    pt: Vec<u8>,
+    ct: Vec<u8>,
+}
+
+fn profile(name: &str, rounds: usize) -> LegacyProfile {
+    let (endian, padding, chaining) = match name {
+        "spec-ecb" => (Endian::Little, LegacyPadding::None, Chaining::Ecb),
+        "openssl-cbc" => (
+            Endian::Little,
+            LegacyPadding::Pkcs7,
+            Chaining::CbcPrependedIv,
+        ),
+        _ => panic!("unknown profile {}", name),
+    };
+    LegacyProfile {
+        rounds,
+        endian,
+        key: KeyHandling::Raw,
+        padding,
+        chaining,
+    }
+}
+
+fn entries(file: &'static str, corpus: &'static str) -> Vec<Entry<'static>> {
+    let mut out = Vec::new();
+    for (i, line) in corpus.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(fields.len(), 7, "{}:{}: expected 7 fields", file, i + 1);
+
+        let rounds = fields[3].parse().unwrap();
+        out.push(Entry {
+            file,
+            line: i + 1,
+            source: fields[0],
+            profile: profile(fields[1], rounds),
+            word_bits: fields[2].parse().unwrap(),
+            key: hex_decode(fields[4]).unwrap(),
+            pt: hex_decode(fields[5]).unwrap(),
+            ct: hex_decode(fields[6]).unwrap(),
+        });
+    }
+    out
+}
+
+fn check<W: Word>(entry: &Entry) {
+    let pt = entry
+        .profile
+        .decrypt::<W>(&entry.key, &entry.ct)
+        .unwrap_or_else(|e| panic!("{} ({}): {}", entry, entry.source, e));
+    assert_eq!(pt, entry.pt, "{} ({})", entry, entry.source);
+}
+
+impl std::fmt::Display for Entry<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[test]
+fn golden_corpus() {
+    for (file, corpus) in CORPORA {
+        let entries = entries(file, corpus);
+        assert!(!entries.is_empty(), "{}: no entries", file);
+        check_all(&entries);
+    }
+}
+
+fn check_all(entries: &[Entry]) {
+    for entry in entries {
+        match entry.word_bits {
+            8 => check::<u8>(entry),
+            16 => check::<u16>(entry),
+            32 => check::<u32>(entry),
+            64 => check::<u64>(entry),
+            128 => check::<u128>(entry),
+            w => panic!("{}: unsupported word size {}", entry, w),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//!
+//! The RC5-CBC and RC5-CBC-Pad test vectors of RFC 2040, section 9, run
+//! through [`LegacyProfile::rfc2040_cbc`] and
+//! [`LegacyProfile::rfc2040_cbc_pad`] in both directions.
+//!
+
+use rc5_cipher::encoding::hex_decode;
+use rc5_cipher::legacy::LegacyProfile;
+
+// rounds, key, IV, plaintext, ciphertext
+const CBC: &[(usize, &str, &str, &str, &str)] = &[
+    (
+        0,
+        "00",
+        "0000000000000000",
+        "0000000000000000",
+        "7a7bba4d79111d1e",
+    ),
+    (
+        0,
+        "00",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "797bba4d78111d1e",
+    ),
+    (
+        0,
+        "00",
+        "0000000000000001",
+        "0000000000000000",
+        "7a7bba4d79111d1f",
+    ),
+    (
+        0,
+        "00",
+        "0000000000000000",
+        "0000000000000001",
+        "7a7bba4d79111d1f",
+    ),
+    (
+        0,
+        "00",
+        "0102030405060708",
+        "1020304050607080",
+        "8b9ded91ce7794a6",
+    ),
+    (
+        1,
+        "11",
+        "0000000000000000",
+        "0000000000000000",
+        "2f759fe7ad86a378",
+    ),
+    (
+        2,
+        "00",
+        "0000000000000000",
+        "0000000000000000",
+        "dca2694bf40e0788",
+    ),
+    (
+        2,
+        "00000000",
+        "0000000000000000",
+        "0000000000000000",
+        "dca2694bf40e0788",
+    ),
+    (
+        8,
+        "00",
+        "0000000000000000",
+        "0000000000000000",
+        "dcfe098577eca5ff",
+    ),
+    (
+        8,
+        "00",
+        "0102030405060708",
+        "1020304050607080",
+        "9646fb77638f9ca8",
+    ),
+    (
+        12,
+        "00",
+        "0102030405060708",
+        "1020304050607080",
+        "b2b3209db6594da4",
+    ),
+    (
+        16,
+        "00",
+        "0102030405060708",
+        "1020304050607080",
+        "545f7f32a5fc3836",
+    ),
+    (
+        8,
+        "01020304",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "8285e7c1b5bc7402",
+    ),
+    (
+        12,
+        "01020304",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "fc586f92f7080934",
+    ),
+    (
+        16,
+        "01020304",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "cf270ef9717ff7c4",
+    ),
+    (
+        12,
+        "0102030405060708",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "e493f1c1bb4d6e8c",
+    ),
+    (
+        8,
+        "0102030405060708",
+        "0102030405060708",
+        "1020304050607080",
+        "5c4c041e0f217ac3",
+    ),
+    (
+        12,
+        "0102030405060708",
+        "0102030405060708",
+        "1020304050607080",
+        "921f12485373b4f7",
+    ),
+    (
+        16,
+        "0102030405060708",
+        "0102030405060708",
+        "1020304050607080",
+        "5ba0ca6bbe7f5fad",
+    ),
+    (
+        8,
+        "01020304050607081020304050607080",
+        "0102030405060708",
+        "1020304050607080",
+        "c533771cd0110e63",
+    ),
+    (
+        12,
+        "01020304050607081020304050607080",
+        "0102030405060708",
+        "1020304050607080",
+        "294ddb46b3278d60",
+    ),
+    (
+        16,
+        "01020304050607081020304050607080",
+        "0102030405060708",
+        "1020304050607080",
+        "dad6bda9dfe8f7e8",
+    ),
+    (
+        12,
+        "0102030405",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "97e0787837ed317f",
+    ),
+    (
+        8,
+        "0102030405",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "7875dbf6738c6478",
+    ),
+    (
+        8,
+        "0102030405",
+        "7875dbf6738c6478",
+        "0808080808080808",
+        "8f34c3c681c99695",
+    ),
+];
+
+const CBC_PAD: &[(usize, &str, &str, &str, &str)] = &[
+    (
+        8,
+        "0102030405",
+        "0000000000000000",
+        "ffffffffffffffff",
+        "7875dbf6738c64788f34c3c681c99695",
+    ),
+    (
+        8,
+        "0102030405",
+        "0000000000000000",
+        "ffffffffffffffff7875dbf6738c647811223344556677",
+        "7875dbf6738c64787cb3f1df34f948117fd1a023a5bba217",
+    ),
+];
+
+fn check(profile: fn(usize, &[u8]) -> LegacyProfile, vectors: &[(usize, &str, &str, &str, &str)]) {
+    for (i, &(rounds, key, iv, pt, ct)) in vectors.iter().enumerate() {
+        let profile = profile(rounds, &hex_decode(iv).unwrap());
+        let key = hex_decode(key).unwrap();
+        let (pt, ct) = (hex_decode(pt).unwrap(), hex_decode(ct).unwrap());
+        assert_eq!(
+            profile.encrypt::<u32>(&key, &pt).unwrap(),
+            ct,
+            "vector {}",
+            i
+        );
+        assert_eq!(
+            profile.decrypt::<u32>(&key, &ct).unwrap(),
+            pt,
+            "vector {}",
+            i
+        );
+    }
+}
+
+#[test]
+fn rc5_cbc() {
+    check(LegacyProfile::rfc2040_cbc, CBC);
+}
+
+#[test]
+fn rc5_cbc_pad() {
+    check(LegacyProfile::rfc2040_cbc_pad, CBC_PAD);
+}
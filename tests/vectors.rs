@@ -0,0 +1,122 @@
+//!
+//! Every published RC5 test vector for the word sizes the crate supports, run
+//! through the block functions, the block-cipher trait and the ECB and CBC
+//! layers. The `vectors!` table generates one test per vector, so a failure
+//! names the vector.
+//!
+//! The Krovetz draft also lists RC5-24/4/0 and RC5-80/4/12, whose word sizes
+//! have no native integer type and are not implemented.
+//!
+
+use rc5_cipher::algo::{BlockCipherAlgo, KeySchedule, Rc5Algo};
+use rc5_cipher::encoding::hex_decode;
+use rc5_cipher::legacy::{Chaining, Endian, KeyHandling, LegacyPadding, LegacyProfile};
+use rc5_cipher::word::Word;
+use rc5_cipher::{decrypt, decrypt_kernel, encrypt, encrypt_kernel, expand_key};
+
+macro_rules! vectors {
+    ($($name:ident: $w:ty, $rounds:expr, $key:expr, $pt:expr, $ct:expr;)*) => {
+        $(
+            #[test]
+            fn $name() {
+                check::<$w>($rounds, $key, $pt, $ct);
+            }
+        )*
+    };
+}
+
+vectors! {
+    // R. Rivest, "The RC5 Encryption Algorithm", 1995, appendix.
+    rivest_1: u32, 12, "00000000000000000000000000000000", "0000000000000000", "21a5dbee154b8f6d";
+    rivest_2: u32, 12, "915f4619be41b2516355a50110a9ce91", "21a5dbee154b8f6d", "f7c013ac5b2b8952";
+    rivest_3: u32, 12, "783348e75aeb0f2fd7b169bb8dc16787", "f7c013ac5b2b8952", "2f42b3b70369fc92";
+    rivest_4: u32, 12, "dc49db1375a5584f6485b413b5f12baf", "2f42b3b70369fc92", "65c178b284d197cc";
+    rivest_5: u32, 12, "5269f149d41ba0152497574d7f153125", "65c178b284d197cc", "eb44e415da319824";
+
+    // draft-krovetz-rc6-rc5-vectors-00, section 4.
+    krovetz_8_12_4: u8, 12, "00010203", "0001", "212a";
+    krovetz_16_16_8: u16, 16, "0001020304050607", "00010203", "23a8d72e";
+    krovetz_32_20_16: u32, 20,
+        "000102030405060708090a0b0c0d0e0f",
+        "0001020304050607",
+        "2a0edc0e9431ff73";
+    krovetz_64_24_24: u64, 24,
+        "000102030405060708090a0b0c0d0e0f1011121314151617",
+        "000102030405060708090a0b0c0d0e0f",
+        "a46772820edbce0235abea32ae7178da";
+    krovetz_128_28_32: u128, 28,
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+        "eca5910921a4f4cfdd7ad7ad20a1fcba068ec7a7cd752d68fe914b7fe180b440";
+}
+
+fn check<W: Word>(rounds: usize, key: &str, pt: &str, ct: &str) {
+    let key = hex_decode(key).unwrap();
+    let pt = hex_decode(pt).unwrap();
+    let ct = hex_decode(ct).unwrap();
+    assert_eq!(pt.len(), 2 * W::BYTES);
+
+    check_block_fns::<W>(rounds, &key, &pt, &ct);
+    check_block_algo::<W>(rounds, &key, &pt, &ct);
+    check_modes::<W>(rounds, &key, &pt, &ct);
+}
+
+fn words<W: Word>(block: &[u8]) -> [W; 2] {
+    [
+        W::from_le_slice(block),
+        W::from_le_slice(&block[W::BYTES..]),
+    ]
+}
+
+fn check_block_fns<W: Word>(rounds: usize, key: &[u8], pt: &[u8], ct: &[u8]) {
+    let (pt, ct) = (words::<W>(pt), words::<W>(ct));
+    assert!(encrypt(pt, key, rounds) == ct, "encrypt");
+    assert!(decrypt(ct, key, rounds) == pt, "decrypt");
+
+    let key_exp = expand_key::<W>(key, rounds);
+    assert!(encrypt_kernel(pt, &key_exp) == ct, "encrypt_kernel");
+    assert!(decrypt_kernel(ct, &key_exp) == pt, "decrypt_kernel");
+}
+
+fn check_block_algo<W: Word>(rounds: usize, key: &[u8], pt: &[u8], ct: &[u8]) {
+    for schedule in [KeySchedule::Stored, KeySchedule::OnTheFly] {
+        let algo = Rc5Algo::<W>::with_schedule(key, rounds, schedule);
+        let mut block = pt.to_vec();
+        algo.encrypt_block(&mut block);
+        assert_eq!(block, ct, "{:?} encrypt_block", schedule);
+        algo.decrypt_block(&mut block);
+        assert_eq!(block, pt, "{:?} decrypt_block", schedule);
+    }
+}
+
+fn check_modes<W: Word>(rounds: usize, key: &[u8], pt: &[u8], ct: &[u8]) {
+    let profile = |chaining| LegacyProfile {
+        rounds,
+        endian: Endian::Little,
+        key: KeyHandling::Raw,
+        padding: LegacyPadding::None,
+        chaining,
+    };
+
+    // ECB encrypts repeated blocks identically
+    let ecb = profile(Chaining::Ecb);
+    let data = pt.repeat(3);
+    assert_eq!(
+        ecb.encrypt::<W>(key, &data).unwrap(),
+        ct.repeat(3),
+        "ECB encrypt"
+    );
+    assert_eq!(ecb.decrypt::<W>(key, &ct.repeat(3)).unwrap(), data);
+
+    // with a zero IV the first CBC block is the vector, and a second block of
+    // pt ^ ct chains back to the same input
+    let cbc = profile(Chaining::CbcZeroIv);
+    let mut data = pt.to_vec();
+    data.extend(pt.iter().zip(ct).map(|(p, c)| p ^ c));
+    assert_eq!(
+        cbc.encrypt::<W>(key, &data).unwrap(),
+        ct.repeat(2),
+        "CBC encrypt"
+    );
+    assert_eq!(cbc.decrypt::<W>(key, &ct.repeat(2)).unwrap(), data);
+}